@@ -1,3 +1,7 @@
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(feature = "async")]
+pub(crate) mod asyncread;
 #[cfg(feature = "read")]
 pub(crate) mod bbiread;
 #[cfg(feature = "write")]
@@ -12,6 +16,14 @@ pub(crate) mod bigbedwrite;
 pub(crate) mod bigwigread;
 #[cfg(feature = "write")]
 pub(crate) mod bigwigwrite;
+#[cfg(feature = "read")]
+pub(crate) mod chromalias;
+#[cfg(feature = "read")]
+pub(crate) mod sharedread;
+#[cfg(feature = "read")]
+pub(crate) mod summarize;
+#[cfg(feature = "read")]
+pub mod validate;
 
 #[cfg(feature = "write")]
 use serde::{Deserialize, Serialize};
@@ -22,6 +34,11 @@ pub(crate) const BIGBED_MAGIC: u32 = 0x8789_F2EB;
 pub(crate) const CIR_TREE_MAGIC: u32 = 0x2468_ACE0;
 pub(crate) const CHROM_TREE_MAGIC: u32 = 0x78CA_8C91;
 
+/// The largest `uncompress_buf_size` (the maximum size of a decompressed
+/// block) that is accepted. Files written by bigtools or the UCSC tools use
+/// blocks of at most a few megabytes, so anything larger is corrupt.
+pub(crate) const MAX_UNCOMPRESS_BUF_SIZE: u32 = 64 * 1024 * 1024;
+
 /// Info on a specific zoom level in a bbi file
 #[derive(Copy, Clone, Debug)]
 pub struct ZoomHeader {
@@ -32,7 +49,7 @@ pub struct ZoomHeader {
 }
 
 /// A single zoom item
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct ZoomRecord {
     pub(crate) chrom: u32,
     pub start: u32,
@@ -41,7 +58,7 @@ pub struct ZoomRecord {
 }
 
 /// A summary of a section of data (may be an entire file)
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Summary {
     pub total_items: u64,
     pub bases_covered: u64,
@@ -62,6 +79,7 @@ pub struct Value {
 
 /// Represents a single entry in a bigBed file
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "write", derive(Serialize, Deserialize))]
 pub struct BedEntry {
     pub start: u32,
     pub end: u32,
@@ -75,6 +93,8 @@ pub enum BBIFile {
     BigBed,
 }
 
+#[cfg(feature = "async")]
+pub use asyncread::{AsyncBigBedRead, AsyncBigWigRead};
 #[cfg(feature = "read")]
 pub use bbiread::*;
 #[cfg(feature = "write")]
@@ -87,3 +107,12 @@ pub use bigbedwrite::*;
 pub use bigwigread::*;
 #[cfg(feature = "write")]
 pub use bigwigwrite::*;
+#[cfg(feature = "read")]
+pub use chromalias::{ChromAliases, ChromAliasesError};
+#[cfg(feature = "read")]
+pub use sharedread::{PositionalRead, PositionalReader, SharedBigBedRead, SharedBigWigRead};
+#[cfg(feature = "read")]
+pub use summarize::{
+    summarize_entries, summarize_entry_zoom_records, summarize_values, summarize_zoom_records,
+    summary_zoom_level, SummaryKind, UnknownSummaryKind,
+};
@@ -0,0 +1,437 @@
+//! Conversion of the data of bigWigs and bigBeds to Apache Arrow
+//! `RecordBatch`es.
+//!
+//! `BigWigRead::record_batches` returns a `BigWigBatches`, with `chrom`,
+//! `start`, `end`, and `value` columns. `BigBedRead::record_batches` returns
+//! a `BigBedBatches`, with a column for each field of the bigBed's `schema`:
+//! numbers are integer or float columns of the matching width, arrays and
+//! `set`s are lists, and everything else is a string. Fields that can't be
+//! decoded are null.
+//!
+//! Both read every chromosome in order by default, or only the regions
+//! given by `with_regions`.
+
+use std::sync::Arc;
+
+use arrow_array::builder::{Float32Builder, StringBuilder, UInt32Builder};
+use arrow_array::{
+    ArrayRef, Float32Array, Float64Array, Int16Array, Int32Array, Int64Array, Int8Array, ListArray,
+    RecordBatch, StringArray, UInt16Array, UInt32Array, UInt8Array,
+};
+use arrow_buffer::{NullBuffer, OffsetBuffer};
+use arrow_schema::{DataType, Field as ArrowField, Schema, SchemaRef};
+
+use crate::bed::autosql::parse::{Field, FieldType};
+use crate::bed::autosql::typed::{BedSchema, FieldValue};
+use crate::utils::reopen::Reopen;
+use crate::{BBIFileRead, BBIReadError, BedEntry, BigBedRead, BigWigRead, Value};
+
+/// The default number of rows in each `RecordBatch`
+pub const DEFAULT_BATCH_SIZE: usize = 64 * 1024;
+
+type Intervals<V> = Box<dyn Iterator<Item = Result<V, BBIReadError>>>;
+
+/// Opens the intervals of a region (chromosome, start, end)
+type OpenIntervals<B, V> = fn(B, &str, u32, u32) -> Result<Intervals<V>, BBIReadError>;
+
+/// Reads the values of a list of regions, one after another. The file is
+/// reopened for each region.
+struct Regions<B, V> {
+    read: B,
+    regions: std::vec::IntoIter<(String, u32, u32)>,
+    current: Option<(String, Intervals<V>)>,
+    intervals: OpenIntervals<B, V>,
+}
+
+impl<B: Reopen, V> Regions<B, V> {
+    fn new(read: B, regions: Vec<(String, u32, u32)>, intervals: OpenIntervals<B, V>) -> Self {
+        Regions {
+            read,
+            regions: regions.into_iter(),
+            current: None,
+            intervals,
+        }
+    }
+
+    /// Passes up to `batch_size` values (with their chromosome) to `push`,
+    /// returning the number of values. `0` means that all the regions have
+    /// been read.
+    fn fill(
+        &mut self,
+        batch_size: usize,
+        mut push: impl FnMut(&str, V),
+    ) -> Result<usize, BBIReadError> {
+        let mut rows = 0;
+        while rows < batch_size {
+            let (chrom, values) = match &mut self.current {
+                Some(current) => current,
+                None => match self.regions.next() {
+                    Some((chrom, start, end)) => {
+                        let values = (self.intervals)(self.read.reopen()?, &chrom, start, end)?;
+                        self.current.insert((chrom, values))
+                    }
+                    None => break,
+                },
+            };
+            match values.next() {
+                Some(value) => {
+                    push(chrom, value?);
+                    rows += 1;
+                }
+                None => self.current = None,
+            }
+        }
+        Ok(rows)
+    }
+}
+
+fn all_chroms(chroms: &[crate::ChromInfo]) -> Vec<(String, u32, u32)> {
+    chroms
+        .iter()
+        .map(|c| (c.name.clone(), 0, c.length))
+        .collect()
+}
+
+/// An `Iterator` of `RecordBatch`es of the values of a bigWig. See
+/// `BigWigRead::record_batches`.
+pub struct BigWigBatches<R> {
+    regions: Regions<BigWigRead<R>, Value>,
+    schema: SchemaRef,
+    batch_size: usize,
+}
+
+impl<R: BBIFileRead + Reopen + 'static> BigWigRead<R> {
+    /// Returns an `Iterator` of `RecordBatch`es with up to `batch_size` rows
+    /// of the values of this bigWig, with `chrom`, `start`, `end`, and
+    /// `value` columns.
+    pub fn record_batches(self, batch_size: usize) -> BigWigBatches<R> {
+        let regions = all_chroms(self.chroms());
+        let schema = Arc::new(Schema::new(vec![
+            ArrowField::new("chrom", DataType::Utf8, false),
+            ArrowField::new("start", DataType::UInt32, false),
+            ArrowField::new("end", DataType::UInt32, false),
+            ArrowField::new("value", DataType::Float32, false),
+        ]));
+        BigWigBatches {
+            regions: Regions::new(self, regions, |bigwig, chrom, start, end| {
+                Ok(Box::new(bigwig.get_interval_move(chrom, start, end)?))
+            }),
+            schema,
+            batch_size: batch_size.max(1),
+        }
+    }
+}
+
+impl<R: BBIFileRead + Reopen + 'static> BigWigBatches<R> {
+    /// Only reads the values overlapping the given regions (chromosome,
+    /// start, end), in order.
+    pub fn with_regions(self, regions: Vec<(String, u32, u32)>) -> Self {
+        BigWigBatches {
+            regions: Regions::new(self.regions.read, regions, self.regions.intervals),
+            ..self
+        }
+    }
+
+    /// The schema of each `RecordBatch`
+    pub fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+}
+
+impl<R: BBIFileRead + Reopen + 'static> Iterator for BigWigBatches<R> {
+    type Item = Result<RecordBatch, BBIReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chroms = StringBuilder::new();
+        let mut starts = UInt32Builder::with_capacity(self.batch_size);
+        let mut ends = UInt32Builder::with_capacity(self.batch_size);
+        let mut values = Float32Builder::with_capacity(self.batch_size);
+        let rows = self.regions.fill(self.batch_size, |chrom, value| {
+            chroms.append_value(chrom);
+            starts.append_value(value.start);
+            ends.append_value(value.end);
+            values.append_value(value.value);
+        });
+        match rows {
+            Err(e) => Some(Err(e)),
+            Ok(0) => None,
+            Ok(_) => {
+                let columns: Vec<ArrayRef> = vec![
+                    Arc::new(chroms.finish()),
+                    Arc::new(starts.finish()),
+                    Arc::new(ends.finish()),
+                    Arc::new(values.finish()),
+                ];
+                let batch = RecordBatch::try_new(self.schema.clone(), columns)
+                    .expect("Columns match the schema");
+                Some(Ok(batch))
+            }
+        }
+    }
+}
+
+/// An `Iterator` of `RecordBatch`es of the entries of a bigBed. See
+/// `BigBedRead::record_batches`.
+pub struct BigBedBatches<R> {
+    regions: Regions<BigBedRead<R>, BedEntry>,
+    bed_schema: BedSchema,
+    schema: SchemaRef,
+    batch_size: usize,
+}
+
+impl<R: BBIFileRead + Reopen + 'static> BigBedRead<R> {
+    /// Returns an `Iterator` of `RecordBatch`es with up to `batch_size` rows
+    /// of the entries of this bigBed, with a column for each field of its
+    /// `schema`.
+    pub fn record_batches(mut self, batch_size: usize) -> Result<BigBedBatches<R>, BBIReadError> {
+        let bed_schema = self.schema()?;
+        let regions = all_chroms(self.chroms());
+        let schema = Arc::new(bed_arrow_schema(&bed_schema));
+        Ok(BigBedBatches {
+            regions: Regions::new(self, regions, |bigbed, chrom, start, end| {
+                Ok(Box::new(bigbed.get_interval_move(chrom, start, end)?))
+            }),
+            bed_schema,
+            schema,
+            batch_size: batch_size.max(1),
+        })
+    }
+}
+
+impl<R: BBIFileRead + Reopen + 'static> BigBedBatches<R> {
+    /// Only reads the entries overlapping the given regions (chromosome,
+    /// start, end), in order.
+    pub fn with_regions(self, regions: Vec<(String, u32, u32)>) -> Self {
+        BigBedBatches {
+            regions: Regions::new(self.regions.read, regions, self.regions.intervals),
+            ..self
+        }
+    }
+
+    /// The schema of each `RecordBatch`
+    pub fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+}
+
+impl<R: BBIFileRead + Reopen + 'static> Iterator for BigBedBatches<R> {
+    type Item = Result<RecordBatch, BBIReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chroms = StringBuilder::new();
+        let mut starts = UInt32Builder::with_capacity(self.batch_size);
+        let mut ends = UInt32Builder::with_capacity(self.batch_size);
+        let mut fields: Vec<Vec<Option<FieldValue>>> =
+            vec![Vec::with_capacity(self.batch_size); self.bed_schema.rest_fields().len()];
+        let bed_schema = &self.bed_schema;
+        let rows = self.regions.fill(self.batch_size, |chrom, entry| {
+            chroms.append_value(chrom);
+            starts.append_value(entry.start);
+            ends.append_value(entry.end);
+            for (column, value) in fields.iter_mut().zip(bed_schema.decode(&entry.rest)) {
+                column.push(value.ok());
+            }
+        });
+        match rows {
+            Err(e) => Some(Err(e)),
+            Ok(0) => None,
+            Ok(_) => {
+                let mut columns: Vec<ArrayRef> = vec![
+                    Arc::new(chroms.finish()),
+                    Arc::new(starts.finish()),
+                    Arc::new(ends.finish()),
+                ];
+                let data_types = self.schema.fields()[3..].iter().map(|f| f.data_type());
+                columns.extend(
+                    data_types
+                        .zip(fields)
+                        .map(|(data_type, values)| array(data_type, values)),
+                );
+                let batch = RecordBatch::try_new(self.schema.clone(), columns)
+                    .expect("Columns match the schema");
+                Some(Ok(batch))
+            }
+        }
+    }
+}
+
+/// The Arrow schema of the entries of a bigBed with the given `BedSchema`
+pub fn bed_arrow_schema(bed_schema: &BedSchema) -> Schema {
+    let fields = bed_schema.fields();
+    let mut columns = vec![
+        ArrowField::new(&fields[0].name, DataType::Utf8, false),
+        ArrowField::new(&fields[1].name, DataType::UInt32, false),
+        ArrowField::new(&fields[2].name, DataType::UInt32, false),
+    ];
+    columns.extend(
+        bed_schema
+            .rest_fields()
+            .iter()
+            .map(|field| ArrowField::new(&field.name, data_type(field), true)),
+    );
+    Schema::new(columns)
+}
+
+/// The Arrow type of the decoded values of a field
+fn data_type(field: &Field) -> DataType {
+    match (&field.field_size, &field.field_type) {
+        (None, field_type) | (Some(_), field_type @ FieldType::Char) => scalar_type(field_type),
+        (Some(_), field_type) => DataType::List(Arc::new(ArrowField::new_list_field(
+            scalar_type(field_type),
+            true,
+        ))),
+    }
+}
+
+fn scalar_type(field_type: &FieldType) -> DataType {
+    match field_type {
+        FieldType::Byte => DataType::Int8,
+        FieldType::Short => DataType::Int16,
+        FieldType::Int => DataType::Int32,
+        FieldType::Bigint => DataType::Int64,
+        FieldType::Ubyte => DataType::UInt8,
+        FieldType::Ushort => DataType::UInt16,
+        FieldType::Uint => DataType::UInt32,
+        FieldType::Float => DataType::Float32,
+        FieldType::Double => DataType::Float64,
+        FieldType::Set(_) => {
+            DataType::List(Arc::new(ArrowField::new_list_field(DataType::Utf8, true)))
+        }
+        FieldType::Char
+        | FieldType::String
+        | FieldType::Lstring
+        | FieldType::Enum(_)
+        | FieldType::Declaration(..) => DataType::Utf8,
+    }
+}
+
+fn int(value: Option<FieldValue>) -> Option<i64> {
+    match value {
+        Some(FieldValue::Int(v)) => Some(v),
+        Some(FieldValue::Uint(v)) => Some(v as i64),
+        _ => None,
+    }
+}
+
+fn uint(value: Option<FieldValue>) -> Option<u64> {
+    match value {
+        Some(FieldValue::Uint(v)) => Some(v),
+        _ => None,
+    }
+}
+
+fn float(value: Option<FieldValue>) -> Option<f64> {
+    match value {
+        Some(FieldValue::Float(v)) => Some(v),
+        _ => None,
+    }
+}
+
+/// Builds an array of `data_type` (from `data_type()`) from decoded values.
+/// Values are always in the range of their type, since they were parsed as
+/// that type.
+fn array(data_type: &DataType, values: Vec<Option<FieldValue>>) -> ArrayRef {
+    macro_rules! primitive {
+        ($array:ty, $get:ident, $t:ty) => {
+            Arc::new(
+                values
+                    .into_iter()
+                    .map(|v| $get(v).map(|v| v as $t))
+                    .collect::<$array>(),
+            )
+        };
+    }
+    match data_type {
+        DataType::Int8 => primitive!(Int8Array, int, i8),
+        DataType::Int16 => primitive!(Int16Array, int, i16),
+        DataType::Int32 => primitive!(Int32Array, int, i32),
+        DataType::Int64 => primitive!(Int64Array, int, i64),
+        DataType::UInt8 => primitive!(UInt8Array, uint, u8),
+        DataType::UInt16 => primitive!(UInt16Array, uint, u16),
+        DataType::UInt32 => primitive!(UInt32Array, uint, u32),
+        DataType::Float32 => primitive!(Float32Array, float, f32),
+        DataType::Float64 => primitive!(Float64Array, float, f64),
+        DataType::List(item) => {
+            let mut offsets = Vec::with_capacity(values.len() + 1);
+            offsets.push(0i32);
+            let mut valid = Vec::with_capacity(values.len());
+            let mut items = vec![];
+            for value in values {
+                match value {
+                    Some(FieldValue::Array(array)) => {
+                        items.extend(array.into_iter().map(Some));
+                        valid.push(true);
+                    }
+                    Some(FieldValue::Set(members)) => {
+                        items.extend(members.into_iter().map(|m| Some(FieldValue::String(m))));
+                        valid.push(true);
+                    }
+                    _ => valid.push(false),
+                }
+                offsets.push(items.len() as i32);
+            }
+            Arc::new(ListArray::new(
+                item.clone(),
+                OffsetBuffer::new(offsets.into()),
+                array(item.data_type(), items),
+                Some(NullBuffer::from(valid)),
+            ))
+        }
+        _ => Arc::new(
+            values
+                .into_iter()
+                .map(|v| match v {
+                    Some(FieldValue::String(s) | FieldValue::Enum(s)) => Some(s),
+                    _ => None,
+                })
+                .collect::<StringArray>(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::{Int32Type, UInt32Type};
+    use arrow_array::Array;
+
+    #[test]
+    fn test_array() {
+        let schema = BedSchema::from_autosql(crate::bed::autosql::presets::BIG_GENE_PRED).unwrap();
+        let arrow = bed_arrow_schema(&schema);
+        assert_eq!(arrow.field(0).name(), "chrom");
+        assert_eq!(arrow.field(4).data_type(), &DataType::UInt32);
+        assert!(matches!(arrow.field(10).data_type(), DataType::List(_)));
+
+        let values = [
+            "tx1\t0\t+\t110\t380\t0\t2\t100,100,\t0,200,\tG\tcmpl\tincmpl\t0,2,\tt\tg\tg\tc",
+            "tx2\tx\t+\t110\t380\t0\t1\t50,\t0,\tG\tcmpl\tbad\t1,\tt\tg\tg\tc",
+        ]
+        .map(|rest| schema.decode(rest));
+        let column = |i: usize| -> Vec<Option<FieldValue>> {
+            values.iter().map(|v| v[i].clone().ok()).collect()
+        };
+
+        let scores = array(arrow.field(4).data_type(), column(1));
+        let scores = scores.as_primitive::<UInt32Type>();
+        assert_eq!(scores.value(0), 0);
+        assert!(scores.is_null(1));
+
+        let block_sizes = array(arrow.field(10).data_type(), column(7));
+        let block_sizes = block_sizes.as_list::<i32>();
+        assert_eq!(
+            block_sizes.value(0).as_primitive::<Int32Type>().values(),
+            &[100, 100]
+        );
+        assert_eq!(
+            block_sizes.value(1).as_primitive::<Int32Type>().values(),
+            &[50]
+        );
+
+        let cds_end_stat = array(arrow.field(14).data_type(), column(11));
+        let cds_end_stat = cds_end_stat.as_string::<i32>();
+        assert_eq!(cds_end_stat.value(0), "incmpl");
+        assert!(cds_end_stat.is_null(1));
+    }
+}
@@ -0,0 +1,499 @@
+/*!
+Provides an async interface for reading bigWig and bigBed files.
+
+[`AsyncBigWigRead`] and [`AsyncBigBedRead`] mirror [`BigWigRead`][crate::BigWigRead]
+and [`BigBedRead`][crate::BigBedRead], but read from any type that implements
+tokio's [`AsyncRead`] and [`AsyncSeek`], and return [`Stream`]s instead of
+`Iterator`s. Only the io is async: index parsing and block decoding are shared
+with the synchronous readers.
+
+## Example
+```rust, no_run
+# use std::error::Error;
+# use std::path::PathBuf;
+# use futures::TryStreamExt;
+# use bigtools::AsyncBigWigRead;
+# async fn example() -> Result<(), Box<dyn Error>> {
+# let mut dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+# dir.push("resources/test");
+# let mut bigwig = dir.clone();
+# bigwig.push("valid.bigWig");
+let mut bwread = AsyncBigWigRead::open_file(bigwig).await?;
+
+let values: Vec<_> = bwread
+    .get_interval("chr17", 0, 59899)
+    .await?
+    .try_collect()
+    .await?;
+assert_eq!(values[0].start, 59898);
+# Ok(())
+# }
+```
+*/
+use std::collections::{HashSet, VecDeque};
+use std::io::{self, Cursor, SeekFrom};
+use std::path::Path;
+use std::sync::Arc;
+
+use byteordered::Endianness;
+use bytes::Bytes;
+use futures::future::{self, Either};
+use futures::stream::{self, Stream, StreamExt};
+use itertools::Either as ItEither;
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, BufReader};
+
+use crate::bbi::chromalias::{resolve_chrom, ChromAliases};
+use crate::bbi::{BBIFile, BedEntry, Value, ZoomRecord};
+use crate::bbiread::{
+    cir_tree_cycle_error, cir_tree_node_blocks, decode_zoom_block, decompress_block_data,
+    read_cir_tree_header, read_info, BBIFileInfo, BBIFileReadInfoError, BBIReadError, Block,
+    BlockError, ChromInfo, CirTreeIndex, CirTreeIndexType, CirTreeSearchError, ZoomIntervalError,
+};
+use crate::bigbedread::decode_block_entries;
+use crate::bigwigread::decode_block_values;
+use crate::{BigBedReadOpenError, BigWigReadOpenError};
+
+/// How many bytes from the start of the file are initially read to parse the
+/// header, zoom headers, and chrom tree. This is grown if needed.
+const INFO_READ_SIZE: usize = 64 * 1024;
+
+/// The struct used to asynchronously read a bigWig file
+pub struct AsyncBigWigRead<R> {
+    info: BBIFileInfo,
+    read: R,
+    chrom_aliases: Option<Arc<ChromAliases>>,
+}
+
+/// The struct used to asynchronously read a bigBed file
+pub struct AsyncBigBedRead<R> {
+    info: BBIFileInfo,
+    read: R,
+    chrom_aliases: Option<Arc<ChromAliases>>,
+}
+
+impl<R> AsyncBigWigRead<R> {
+    /// Get basic info about this bigWig
+    pub fn info(&self) -> &BBIFileInfo {
+        &self.info
+    }
+
+    /// Gets the chromosomes present in this bigWig
+    pub fn chroms(&self) -> &[ChromInfo] {
+        &self.info.chrom_info
+    }
+
+    /// Attaches chromosome aliases, so that queries for a chromosome can use
+    /// any of its names.
+    pub fn with_chrom_aliases(self, chrom_aliases: Arc<ChromAliases>) -> Self {
+        AsyncBigWigRead {
+            chrom_aliases: Some(chrom_aliases),
+            ..self
+        }
+    }
+
+    /// Gets the chromosome aliases attached to this bigWig, if any
+    pub fn chrom_aliases(&self) -> Option<&ChromAliases> {
+        self.chrom_aliases.as_deref()
+    }
+
+    /// Gets a reference to the inner `R` type, in order to access any info
+    pub fn inner_read(&self) -> &R {
+        &self.read
+    }
+}
+
+impl AsyncBigWigRead<BufReader<File>> {
+    /// Opens a new `AsyncBigWigRead` from a given path as a file.
+    pub async fn open_file(path: impl AsRef<Path>) -> Result<Self, BigWigReadOpenError> {
+        let file = File::open(path).await?;
+        AsyncBigWigRead::open(BufReader::new(file)).await
+    }
+}
+
+impl<R: AsyncRead + AsyncSeek + Unpin + Send> AsyncBigWigRead<R> {
+    /// Opens a new `AsyncBigWigRead` for a given type that implements both
+    /// `AsyncRead` and `AsyncSeek`
+    pub async fn open(mut read: R) -> Result<Self, BigWigReadOpenError> {
+        let info = read_info_async(&mut read).await?;
+        match info.filetype {
+            BBIFile::BigWig => {}
+            _ => return Err(BigWigReadOpenError::NotABigWig),
+        }
+
+        Ok(AsyncBigWigRead {
+            info,
+            read,
+            chrom_aliases: None,
+        })
+    }
+
+    /// For a given chromosome, start, and end, returns a `Stream` of the
+    /// intersecting `Value`s. The resulting stream takes a mutable reference
+    /// of this `AsyncBigWigRead`.
+    pub async fn get_interval<'a>(
+        &'a mut self,
+        chrom_name: &str,
+        start: u32,
+        end: u32,
+    ) -> Result<impl Stream<Item = Result<Value, BBIReadError>> + Send + 'a, BBIReadError> {
+        let cir_tree = full_data_cir_tree(&mut self.read, &mut self.info).await?;
+        let chrom_name = resolve_chrom(
+            self.chrom_aliases.as_deref(),
+            &self.info.chrom_info,
+            chrom_name,
+        );
+        let chrom = self.info.chrom_id(chrom_name)?;
+        let blocks =
+            search_cir_tree(&self.info, &mut self.read, cir_tree, chrom_name, start, end).await?;
+        let endianness = self.info.header.endianness;
+        Ok(block_stream(
+            &mut self.read,
+            &self.info,
+            blocks,
+            move |data| {
+                decode_block_values(endianness, data, chrom, start, end)
+                    .map(Option::unwrap_or_default)
+            },
+        ))
+    }
+
+    /// For a given chromosome, start, and end, returns a `Stream` of the
+    /// intersecting `ZoomRecord`s.
+    pub async fn get_zoom_interval<'a>(
+        &'a mut self,
+        chrom_name: &str,
+        start: u32,
+        end: u32,
+        reduction_level: u32,
+    ) -> Result<impl Stream<Item = Result<ZoomRecord, BBIReadError>> + Send + 'a, ZoomIntervalError>
+    {
+        get_zoom_interval(
+            &mut self.read,
+            &mut self.info,
+            self.chrom_aliases.as_deref(),
+            chrom_name,
+            start,
+            end,
+            reduction_level,
+        )
+        .await
+    }
+
+    /// Returns the values between `start` and `end` as a `Vec<f32>`. Any
+    /// positions with no data in the bigWig will be `std::f32::NAN`.
+    pub async fn values(
+        &mut self,
+        chrom_name: &str,
+        start: u32,
+        end: u32,
+    ) -> Result<Vec<f32>, BBIReadError> {
+        let mut values = vec![f32::NAN; (end - start) as usize];
+        let mut intervals = Box::pin(self.get_interval(chrom_name, start, end).await?);
+        while let Some(value) = intervals.next().await {
+            let value = value?;
+            let value_start = (value.start - start) as usize;
+            let value_end = (value.end - start) as usize;
+            for i in &mut values[value_start..value_end] {
+                *i = value.value
+            }
+        }
+        Ok(values)
+    }
+}
+
+impl<R> AsyncBigBedRead<R> {
+    /// Get basic info about this bigBed
+    pub fn info(&self) -> &BBIFileInfo {
+        &self.info
+    }
+
+    /// Gets the chromosomes present in this bigBed
+    pub fn chroms(&self) -> &[ChromInfo] {
+        &self.info.chrom_info
+    }
+
+    /// Attaches chromosome aliases, so that queries for a chromosome can use
+    /// any of its names.
+    pub fn with_chrom_aliases(self, chrom_aliases: Arc<ChromAliases>) -> Self {
+        AsyncBigBedRead {
+            chrom_aliases: Some(chrom_aliases),
+            ..self
+        }
+    }
+
+    /// Gets the chromosome aliases attached to this bigBed, if any
+    pub fn chrom_aliases(&self) -> Option<&ChromAliases> {
+        self.chrom_aliases.as_deref()
+    }
+
+    /// Gets a reference to the inner `R` type, in order to access any info
+    pub fn inner_read(&self) -> &R {
+        &self.read
+    }
+}
+
+impl AsyncBigBedRead<BufReader<File>> {
+    /// Opens a new `AsyncBigBedRead` from a given path as a file.
+    pub async fn open_file(path: impl AsRef<Path>) -> Result<Self, BigBedReadOpenError> {
+        let file = File::open(path).await?;
+        AsyncBigBedRead::open(BufReader::new(file)).await
+    }
+}
+
+impl<R: AsyncRead + AsyncSeek + Unpin + Send> AsyncBigBedRead<R> {
+    /// Opens a new `AsyncBigBedRead` for a given type that implements both
+    /// `AsyncRead` and `AsyncSeek`
+    pub async fn open(mut read: R) -> Result<Self, BigBedReadOpenError> {
+        let info = read_info_async(&mut read).await?;
+        match info.filetype {
+            BBIFile::BigBed => {}
+            _ => return Err(BigBedReadOpenError::NotABigBed),
+        }
+
+        Ok(AsyncBigBedRead {
+            info,
+            read,
+            chrom_aliases: None,
+        })
+    }
+
+    /// For a given chromosome, start, and end, returns a `Stream` of the
+    /// intersecting `BedEntry`s. The resulting stream takes a mutable
+    /// reference of this `AsyncBigBedRead`.
+    pub async fn get_interval<'a>(
+        &'a mut self,
+        chrom_name: &str,
+        start: u32,
+        end: u32,
+    ) -> Result<impl Stream<Item = Result<BedEntry, BBIReadError>> + Send + 'a, BBIReadError> {
+        let cir_tree = full_data_cir_tree(&mut self.read, &mut self.info).await?;
+        let chrom_name = resolve_chrom(
+            self.chrom_aliases.as_deref(),
+            &self.info.chrom_info,
+            chrom_name,
+        );
+        let chrom = self.info.chrom_id(chrom_name)?;
+        let blocks =
+            search_cir_tree(&self.info, &mut self.read, cir_tree, chrom_name, start, end).await?;
+        let endianness = self.info.header.endianness;
+        Ok(block_stream(
+            &mut self.read,
+            &self.info,
+            blocks,
+            move |data| decode_block_entries(endianness, data, chrom, start, end),
+        ))
+    }
+
+    /// For a given chromosome, start, and end, returns a `Stream` of the
+    /// intersecting `ZoomRecord`s.
+    pub async fn get_zoom_interval<'a>(
+        &'a mut self,
+        chrom_name: &str,
+        start: u32,
+        end: u32,
+        reduction_level: u32,
+    ) -> Result<impl Stream<Item = Result<ZoomRecord, BBIReadError>> + Send + 'a, ZoomIntervalError>
+    {
+        get_zoom_interval(
+            &mut self.read,
+            &mut self.info,
+            self.chrom_aliases.as_deref(),
+            chrom_name,
+            start,
+            end,
+            reduction_level,
+        )
+        .await
+    }
+}
+
+/// Reads exactly `len` bytes at `offset`. Like `read_vec`, this only
+/// allocates as much as can actually be read.
+async fn read_at<R: AsyncRead + AsyncSeek + Unpin>(
+    read: &mut R,
+    offset: u64,
+    len: u64,
+) -> io::Result<Vec<u8>> {
+    read.seek(SeekFrom::Start(offset)).await?;
+    let mut data = vec![];
+    (&mut *read).take(len).read_to_end(&mut data).await?;
+    if (data.len() as u64) < len {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "failed to fill whole buffer",
+        ));
+    }
+    Ok(data)
+}
+
+/// Reads the info of a bbi file by reading the start of the file into memory
+/// and parsing it as with the synchronous readers. The header, zoom headers,
+/// and chrom tree are generally at the start of the file, but if the parse
+/// runs past what was read, more is read and the parse is retried.
+async fn read_info_async<R: AsyncRead + AsyncSeek + Unpin>(
+    read: &mut R,
+) -> Result<BBIFileInfo, BBIFileReadInfoError> {
+    let mut len = INFO_READ_SIZE;
+    loop {
+        read.seek(SeekFrom::Start(0)).await?;
+        let mut data = Vec::with_capacity(len);
+        (&mut *read).take(len as u64).read_to_end(&mut data).await?;
+        let whole_file = data.len() < len;
+        match read_info(&mut Cursor::new(data)) {
+            Err(BBIFileReadInfoError::IoError(e))
+                if e.kind() == io::ErrorKind::UnexpectedEof && !whole_file =>
+            {
+                len *= 4;
+            }
+            res => return res,
+        }
+    }
+}
+
+async fn check_cir_tree_header<R: AsyncRead + AsyncSeek + Unpin>(
+    read: &mut R,
+    endianness: Endianness,
+    index_offset: u64,
+) -> Result<(), BBIReadError> {
+    let data = read_at(read, index_offset, 48).await?;
+    read_cir_tree_header(endianness, &mut Cursor::new(data)).map_err(|e| match e {
+        ItEither::Left(_) => BBIReadError::UnknownMagic,
+        ItEither::Right(e) => e.into(),
+    })
+}
+
+async fn full_data_cir_tree<R: AsyncRead + AsyncSeek + Unpin>(
+    read: &mut R,
+    info: &mut BBIFileInfo,
+) -> Result<CirTreeIndex, BBIReadError> {
+    let index_offset = info.header.full_index_offset;
+    if info.header.full_index_tree_offset.is_none() {
+        check_cir_tree_header(read, info.header.endianness, index_offset).await?;
+        info.header.full_index_tree_offset = Some(index_offset + 48);
+    }
+    Ok(CirTreeIndex(CirTreeIndexType::FullData, index_offset + 48))
+}
+
+async fn zoom_cir_tree<R: AsyncRead + AsyncSeek + Unpin>(
+    read: &mut R,
+    info: &mut BBIFileInfo,
+    reduction_level: u32,
+) -> Result<CirTreeIndex, ZoomIntervalError> {
+    let endianness = info.header.endianness;
+    let zoom_header = info
+        .zoom_headers
+        .iter_mut()
+        .find(|h| h.reduction_level == reduction_level)
+        .ok_or(ZoomIntervalError::ReductionLevelNotFound)?;
+    if zoom_header.index_tree_offset.is_none() {
+        check_cir_tree_header(read, endianness, zoom_header.index_offset)
+            .await
+            .map_err(ZoomIntervalError::BBIReadError)?;
+        zoom_header.index_tree_offset = Some(zoom_header.index_offset + 48);
+    }
+    Ok(CirTreeIndex(
+        CirTreeIndexType::Zoom(reduction_level),
+        zoom_header.index_offset + 48,
+    ))
+}
+
+/// Finds the blocks overlapping the given region, reading each cir tree node
+/// as a whole and parsing it as with the synchronous readers.
+async fn search_cir_tree<R: AsyncRead + AsyncSeek + Unpin>(
+    info: &BBIFileInfo,
+    read: &mut R,
+    at: CirTreeIndex,
+    chrom_name: &str,
+    start: u32,
+    end: u32,
+) -> Result<Vec<Block>, CirTreeSearchError> {
+    let chrom_ix = info
+        .chrom_id(chrom_name)
+        .map_err(|e| CirTreeSearchError::InvalidChromosome(e.0))?;
+    let endianness = info.header.endianness;
+
+    let mut blocks = vec![];
+    let mut visited = HashSet::new();
+    let mut remaining_childblocks = VecDeque::from([at.1]);
+    while let Some(node_offset) = remaining_childblocks.pop_front() {
+        if !visited.insert(node_offset) {
+            return Err(cir_tree_cycle_error(node_offset).into());
+        }
+        let mut node = read_at(read, node_offset, 4).await?;
+        let count = match endianness {
+            Endianness::Big => u16::from_be_bytes([node[2], node[3]]),
+            Endianness::Little => u16::from_le_bytes([node[2], node[3]]),
+        };
+        let item_size = if node[0] == 1 { 32 } else { 24 };
+        node.resize(4 + count as usize * item_size, 0);
+        read.read_exact(&mut node[4..]).await?;
+
+        let (new_childblocks, node_blocks) =
+            cir_tree_node_blocks(node.into(), endianness, chrom_ix, start, end)?;
+        for child in new_childblocks.into_iter().rev() {
+            remaining_childblocks.push_front(child);
+        }
+        blocks.extend(node_blocks);
+    }
+
+    Ok(blocks)
+}
+
+async fn get_block_data<R: AsyncRead + AsyncSeek + Unpin>(
+    read: &mut R,
+    info: &BBIFileInfo,
+    block: &Block,
+) -> io::Result<Bytes> {
+    let raw_data = read_at(read, block.offset, block.size).await?.into();
+    decompress_block_data(&info.header, raw_data).map_err(|e| e.into_io_error(block.offset))
+}
+
+async fn get_zoom_interval<'a, R: AsyncRead + AsyncSeek + Unpin + Send>(
+    read: &'a mut R,
+    info: &'a mut BBIFileInfo,
+    chrom_aliases: Option<&ChromAliases>,
+    chrom_name: &str,
+    start: u32,
+    end: u32,
+    reduction_level: u32,
+) -> Result<impl Stream<Item = Result<ZoomRecord, BBIReadError>> + Send + 'a, ZoomIntervalError> {
+    let cir_tree = zoom_cir_tree(read, info, reduction_level).await?;
+    let chrom_name = resolve_chrom(chrom_aliases, &info.chrom_info, chrom_name);
+    let chrom = info.chrom_id(chrom_name)?;
+    let blocks = search_cir_tree(info, read, cir_tree, chrom_name, start, end).await?;
+    let endianness = info.header.endianness;
+    Ok(block_stream(read, info, blocks, move |data| {
+        decode_zoom_block(endianness, data, chrom, start, end)
+    }))
+}
+
+/// Returns a `Stream` that reads each block in turn and yields the items
+/// decoded from it.
+fn block_stream<'a, R, T, F>(
+    read: &'a mut R,
+    info: &'a BBIFileInfo,
+    blocks: Vec<Block>,
+    decode: F,
+) -> impl Stream<Item = Result<T, BBIReadError>> + Send + 'a
+where
+    R: AsyncRead + AsyncSeek + Unpin + Send,
+    T: Send + 'a,
+    F: FnMut(&[u8]) -> Result<Vec<T>, BlockError> + Send + 'a,
+{
+    stream::unfold(
+        (read, blocks.into_iter(), decode),
+        move |(read, mut blocks, mut decode)| async move {
+            let block = blocks.next()?;
+            let items = match get_block_data(read, info, &block).await {
+                Ok(data) => decode(&data).map_err(|e| BBIReadError::InvalidBlock(block.offset, e)),
+                Err(e) => Err(e.into()),
+            };
+            Some((items, (read, blocks, decode)))
+        },
+    )
+    .flat_map(|items| match items {
+        Ok(items) => Either::Left(stream::iter(items.into_iter().map(Ok))),
+        Err(e) => Either::Right(stream::once(future::ready(Err(e)))),
+    })
+}
@@ -1,22 +1,27 @@
 use std::borrow::BorrowMut;
-use std::collections::hash_map::Entry;
-use std::collections::{HashMap, VecDeque};
+use std::cmp::Ordering;
+use std::collections::{HashSet, VecDeque};
 use std::fs::File;
-use std::io::{self, Read, Seek, SeekFrom};
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::sync::{mpsc, Arc, Mutex, MutexGuard, PoisonError};
 use std::vec::Vec;
 
-use byteordered::Endianness;
-use bytes::{Buf, BytesMut};
+use byteordered::{ByteOrdered, Endianness};
+use bytes::{Buf, Bytes, BytesMut};
 use itertools::Either;
 use libdeflater::Decompressor;
 use smallvec::{smallvec, SmallVec};
 use thiserror::Error;
+use tokio::runtime::Handle;
 
+use crate::bbi::chromalias::{resolve_chrom, ChromAliases};
 use crate::bbi::{
     BBIFile, Summary, ZoomHeader, ZoomRecord, BIGBED_MAGIC, BIGWIG_MAGIC, CHROM_TREE_MAGIC,
-    CIR_TREE_MAGIC,
+    CIR_TREE_MAGIC, MAX_UNCOMPRESS_BUF_SIZE,
 };
 use crate::bed::bedparser::BedValueError;
+use crate::utils::lru::Lru;
 use crate::utils::reopen::{Reopen, ReopenableFile, SeekableRead};
 use crate::{BigBedRead, BigWigRead};
 
@@ -53,6 +58,9 @@ pub struct BBIHeader {
     pub(crate) auto_sql_offset: u64,
     pub(crate) total_summary_offset: u64,
     pub(crate) uncompress_buf_size: u32,
+    /// Offset of the extension header (currently only used for bigBed extra
+    /// indices), or `0` if there isn't one
+    pub(crate) extension_offset: u64,
 }
 
 impl BBIHeader {
@@ -129,6 +137,44 @@ pub enum CirTreeSearchError {
     IoError(#[from] io::Error),
 }
 
+/// Possible problems found in the data of a block
+#[derive(Error, Debug)]
+pub enum BlockError {
+    #[error("The block could not be decompressed ({}).", .0)]
+    Decompression(String),
+    #[error(
+        "The uncompress buffer size ({}) is larger than the maximum of {}.",
+        .0,
+        MAX_UNCOMPRESS_BUF_SIZE
+    )]
+    UncompressBufTooLarge(u32),
+    #[error("The block ended in the middle of an item.")]
+    Truncated,
+    #[error("Unknown bigWig section type: {}", .0)]
+    UnknownSectionType(u8),
+    #[error("The zoom block length ({}) is not a multiple of the record size.", .0)]
+    InvalidZoomBlockLength(usize),
+    #[error("Found an item on chromosome {}, but expected chromosome {}.", .1, .0)]
+    UnexpectedChrom(u32, u32),
+    #[error("An item contained an invalid utf-8 string.")]
+    InvalidUtf8,
+    #[error("{}", .0)]
+    InvalidItem(&'static str),
+}
+
+impl BlockError {
+    /// Converts this into an `io::Error`, for passing through functions that
+    /// only return `io::Error`s. Converting back to a `BBIReadError` recovers
+    /// the `BBIReadError::InvalidBlock`.
+    pub(crate) fn into_io_error(self, offset: u64) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, InvalidBlock(offset, self))
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("Invalid block at offset {}: {}", .0, .1)]
+struct InvalidBlock(u64, BlockError);
+
 /// Possible errors encountered when reading a bbi file
 #[derive(Error, Debug)]
 pub enum BBIReadError {
@@ -138,17 +184,30 @@ pub enum BBIReadError {
     UnknownMagic,
     #[error("The file was invalid: {}", .0)]
     InvalidFile(String),
+    /// The data of the block at the given offset in the file was invalid
+    #[error("Invalid block at offset {}: {}", .0, .1)]
+    InvalidBlock(u64, BlockError),
     #[error("Error parsing bed-like data.")]
     BedValueError(#[from] BedValueError),
     #[error("Error occurred: {}", .0)]
-    IoError(#[from] io::Error),
+    IoError(io::Error),
+}
+
+impl From<io::Error> for BBIReadError {
+    fn from(e: io::Error) -> Self {
+        if !e.get_ref().is_some_and(|inner| inner.is::<InvalidBlock>()) {
+            return BBIReadError::IoError(e);
+        }
+        let InvalidBlock(offset, error) = *e.into_inner().unwrap().downcast().unwrap();
+        BBIReadError::InvalidBlock(offset, error)
+    }
 }
 
 impl From<CirTreeSearchError> for BBIReadError {
     fn from(value: CirTreeSearchError) -> Self {
         match value {
             CirTreeSearchError::InvalidChromosome(chrom) => BBIReadError::InvalidChromosome(chrom),
-            CirTreeSearchError::IoError(e) => BBIReadError::IoError(e),
+            CirTreeSearchError::IoError(e) => e.into(),
         }
     }
 }
@@ -157,7 +216,7 @@ impl From<internal::FullDataCirTreeError> for BBIReadError {
     fn from(value: internal::FullDataCirTreeError) -> Self {
         match value {
             internal::FullDataCirTreeError::UnknownMagic => BBIReadError::UnknownMagic,
-            internal::FullDataCirTreeError::IoError(e) => BBIReadError::IoError(e),
+            internal::FullDataCirTreeError::IoError(e) => e.into(),
         }
     }
 }
@@ -192,9 +251,7 @@ impl From<internal::ZoomDataCirTreeError> for ZoomIntervalError {
             internal::ZoomDataCirTreeError::ReductionLevelNotFound => {
                 ZoomIntervalError::ReductionLevelNotFound
             }
-            internal::ZoomDataCirTreeError::IoError(e) => {
-                ZoomIntervalError::BBIReadError(BBIReadError::IoError(e))
-            }
+            internal::ZoomDataCirTreeError::IoError(e) => ZoomIntervalError::BBIReadError(e.into()),
         }
     }
 }
@@ -396,6 +453,7 @@ pub(crate) fn search_cir_tree_inner<R: BBIFileRead>(
     remaining_childblocks.push_front(at);
     let iter = CirTreeBlockSearchIter {
         remaining_childblocks,
+        visited: HashSet::new(),
         file,
         endianness,
         chrom_ix,
@@ -491,8 +549,18 @@ impl<R: BBIFileRead> GenericBBIRead<R> {
     pub fn open(mut read: R) -> Result<Self, GenericBBIFileOpenError> {
         let info = read_info(&mut read)?;
         match info.filetype {
-            BBIFile::BigWig => Ok(GenericBBIRead::BigWig(BigWigRead { info, read })),
-            BBIFile::BigBed => Ok(GenericBBIRead::BigBed(BigBedRead { info, read })),
+            BBIFile::BigWig => Ok(GenericBBIRead::BigWig(BigWigRead {
+                info,
+                read,
+                chrom_aliases: None,
+                max_block_gap: DEFAULT_MAX_BLOCK_GAP,
+            })),
+            BBIFile::BigBed => Ok(GenericBBIRead::BigBed(BigBedRead {
+                info,
+                read,
+                chrom_aliases: None,
+                max_block_gap: DEFAULT_MAX_BLOCK_GAP,
+            })),
         }
     }
 }
@@ -512,10 +580,289 @@ impl GenericBBIRead<ReopenableFile> {
     }
 }
 
+/// The default maximum gap (in bytes) between two blocks for them to be read
+/// together. See `BBIFileRead::get_blocks_data`.
+pub const DEFAULT_MAX_BLOCK_GAP: u64 = 8 * 1024;
+
+/// Blocks are not merged into reads larger than this
+const MAX_COALESCED_READ_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Whether `block` can be read together with the run of blocks from
+/// `run_start` to `run_end`
+fn can_coalesce(run_start: u64, run_end: u64, block: &Block, max_gap: u64) -> bool {
+    block.offset >= run_end
+        && block.offset - run_end <= max_gap
+        && block.offset.saturating_add(block.size) - run_start <= MAX_COALESCED_READ_SIZE
+}
+
+/// Splits `blocks` into runs of consecutive blocks that can each be read
+/// with a single seek and read.
+fn coalesced_runs(blocks: &[Block], max_gap: u64) -> Vec<&[Block]> {
+    let mut runs = vec![];
+    let mut run_start = 0;
+    for i in 1..blocks.len() {
+        let prev = &blocks[i - 1];
+        if !can_coalesce(
+            blocks[run_start].offset,
+            prev.offset.saturating_add(prev.size),
+            &blocks[i],
+            max_gap,
+        ) {
+            runs.push(&blocks[run_start..i]);
+            run_start = i;
+        }
+    }
+    if run_start < blocks.len() {
+        runs.push(&blocks[run_start..]);
+    }
+    runs
+}
+
+/// Gets the data (uncompressed, if applicable) for each of `blocks`, reading
+/// runs of nearby blocks together.
+fn read_blocks_data<R: SeekableRead>(
+    info: &BBIFileInfo,
+    read: &mut R,
+    blocks: &[Block],
+    max_gap: u64,
+) -> io::Result<Vec<Bytes>> {
+    let mut data = Vec::with_capacity(blocks.len());
+    for run in coalesced_runs(blocks, max_gap) {
+        for (block, block_data) in run.iter().zip(read_raw_run(read, run)?) {
+            let block_data = decompress_block_data(&info.header, block_data)
+                .map_err(|e| e.into_io_error(block.offset))?;
+            data.push(block_data);
+        }
+    }
+    Ok(data)
+}
+
+/// Reads exactly `len` bytes. Unlike `read_exact` into a buffer of `len`
+/// bytes, this only allocates as much as can actually be read, since `len`
+/// may come from a corrupted file.
+pub(crate) fn read_vec<R: Read>(read: &mut R, len: u64) -> io::Result<Vec<u8>> {
+    let mut data = vec![];
+    read.take(len).read_to_end(&mut data)?;
+    if (data.len() as u64) < len {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "failed to fill whole buffer",
+        ));
+    }
+    Ok(data)
+}
+
+/// Reads the raw (still compressed, if applicable) data of a run of blocks
+/// with a single seek and read.
+fn read_raw_run<R: Read + Seek>(read: &mut R, run: &[Block]) -> io::Result<Vec<Bytes>> {
+    let run_start = run[0].offset;
+    let last = &run[run.len() - 1];
+    let run_end = last.offset.checked_add(last.size).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid block size at offset {}", last.offset),
+        )
+    })?;
+    read.seek(SeekFrom::Start(run_start))?;
+    let raw_data = read_vec(read, run_end - run_start)?;
+    Ok(run
+        .iter()
+        .map(|block| {
+            let block_start = (block.offset - run_start) as usize;
+            Bytes::copy_from_slice(&raw_data[block_start..block_start + block.size as usize])
+        })
+        .collect())
+}
+
+/// Takes the next run of blocks from `blocks` that can be read together.
+fn next_block_run<I: Iterator<Item = Block>>(
+    blocks: &mut std::iter::Peekable<I>,
+    max_gap: u64,
+) -> Option<Vec<Block>> {
+    let first = blocks.next()?;
+    let mut run = vec![first];
+    let mut run_end = first.offset.saturating_add(first.size);
+    while let Some(block) = blocks.next_if(|b| can_coalesce(first.offset, run_end, b, max_gap)) {
+        run_end = block.offset.saturating_add(block.size);
+        run.push(block);
+    }
+    Some(run)
+}
+
+/// Reads the data of blocks as they are needed, a run of nearby blocks at a
+/// time (see `BBIFileRead::get_blocks_data`).
+pub(crate) struct BlockDataIter<I: Iterator<Item = Block>> {
+    blocks: std::iter::Peekable<I>,
+    max_gap: u64,
+    pending: VecDeque<(Block, Bytes)>,
+}
+
+impl<I: Iterator<Item = Block>> BlockDataIter<I> {
+    pub(crate) fn new(blocks: I, max_gap: u64) -> Self {
+        BlockDataIter {
+            blocks: blocks.peekable(),
+            max_gap,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Gets the next block and its data, reading the next run of blocks if
+    /// needed.
+    pub(crate) fn next<R: BBIFileRead>(
+        &mut self,
+        read: &mut R,
+        info: &BBIFileInfo,
+    ) -> Option<io::Result<(Block, Bytes)>> {
+        if self.pending.is_empty() {
+            let run = next_block_run(&mut self.blocks, self.max_gap)?;
+            let data = match read.get_blocks_data(info, &run, self.max_gap) {
+                Ok(data) => data,
+                Err(e) => return Some(Err(e)),
+            };
+            self.pending.extend(run.into_iter().zip(data));
+        }
+        self.pending.pop_front().map(Ok)
+    }
+}
+
+/// How many blocks are decompressed and decoded concurrently by a
+/// `ParallelBlockIter`
+const MAX_BLOCKS_IN_FLIGHT: usize = 64;
+
+type BlockDecodeResult<T> = Result<Vec<T>, BlockError>;
+
+/// An iterator over the items of blocks, where the raw data of blocks is read
+/// on the calling thread, but decompressed and decoded on a tokio runtime.
+/// Items are returned in the same order as the blocks.
+pub(crate) struct ParallelBlockIter<'a, R, T, F> {
+    read: &'a mut R,
+    header: BBIHeader,
+    blocks: std::iter::Peekable<std::vec::IntoIter<Block>>,
+    max_gap: u64,
+    runtime: Handle,
+    decode: Arc<F>,
+    in_flight: VecDeque<mpsc::Receiver<Result<Vec<T>, BBIReadError>>>,
+    vals: std::vec::IntoIter<T>,
+}
+
+impl<'a, R, T, F> ParallelBlockIter<'a, R, T, F>
+where
+    R: BBIFileRead,
+    T: Send + 'static,
+    F: Fn(&[u8]) -> BlockDecodeResult<T> + Send + Sync + 'static,
+{
+    pub(crate) fn new(
+        read: &'a mut R,
+        info: &BBIFileInfo,
+        blocks: Vec<Block>,
+        max_gap: u64,
+        runtime: Handle,
+        decode: F,
+    ) -> Self {
+        ParallelBlockIter {
+            read,
+            header: info.header,
+            blocks: blocks.into_iter().peekable(),
+            max_gap,
+            runtime,
+            decode: Arc::new(decode),
+            in_flight: VecDeque::new(),
+            vals: vec![].into_iter(),
+        }
+    }
+
+    /// Reads runs of blocks and spawns their decoding, until enough blocks
+    /// are in flight
+    fn fill(&mut self) -> io::Result<()> {
+        while self.in_flight.len() < MAX_BLOCKS_IN_FLIGHT {
+            let Some(run) = next_block_run(&mut self.blocks, self.max_gap) else {
+                break;
+            };
+            let raw_data = self.read.get_raw_blocks_data(&run, self.max_gap)?;
+            for (block, raw_data) in run.iter().zip(raw_data) {
+                let (snd, rcv) = mpsc::sync_channel(1);
+                let header = self.header;
+                let decode = self.decode.clone();
+                let offset = block.offset;
+                self.runtime.spawn(async move {
+                    let vals = decompress_block_data(&header, raw_data)
+                        .and_then(|data| decode(&data))
+                        .map_err(|e| BBIReadError::InvalidBlock(offset, e));
+                    let _ = snd.send(vals);
+                });
+                self.in_flight.push_back(rcv);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a, R, T, F> Iterator for ParallelBlockIter<'a, R, T, F>
+where
+    R: BBIFileRead,
+    T: Send + 'static,
+    F: Fn(&[u8]) -> BlockDecodeResult<T> + Send + Sync + 'static,
+{
+    type Item = Result<T, BBIReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(v) = self.vals.next() {
+                return Some(Ok(v));
+            }
+            if let Err(e) = self.fill() {
+                return Some(Err(e.into()));
+            }
+            let rcv = self.in_flight.pop_front()?;
+            let vals = match rcv.recv() {
+                Ok(vals) => vals,
+                Err(_) => Err(BBIReadError::InvalidFile(
+                    "Failed to decode block.".to_owned(),
+                )),
+            };
+            match vals {
+                Ok(vals) => self.vals = vals.into_iter(),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
 pub trait BBIFileRead {
     type Reader: Read + Seek;
 
-    fn get_block_data(&mut self, info: &BBIFileInfo, block: &Block) -> io::Result<Vec<u8>>;
+    /// Gets the data (uncompressed, if applicable) of `block`. This may share
+    /// memory with the file's bytes (see `InMemoryBBIFileRead`) or a cache.
+    fn get_block_data(&mut self, info: &BBIFileInfo, block: &Block) -> io::Result<Bytes>;
+
+    /// Gets the data (uncompressed, if applicable) for each of `blocks`.
+    /// Consecutive blocks that are at most `max_gap` bytes apart in the file
+    /// may be fetched together with a single read. By default, each block is
+    /// read separately with `get_block_data`.
+    fn get_blocks_data(
+        &mut self,
+        info: &BBIFileInfo,
+        blocks: &[Block],
+        max_gap: u64,
+    ) -> io::Result<Vec<Bytes>> {
+        let _ = max_gap;
+        blocks
+            .iter()
+            .map(|block| self.get_block_data(info, block))
+            .collect()
+    }
+
+    /// Gets the raw (still compressed, if applicable) data for each of
+    /// `blocks`, to be decompressed elsewhere. By default, runs of blocks at
+    /// most `max_gap` bytes apart are read from `raw_reader` with a single
+    /// seek and read.
+    fn get_raw_blocks_data(&mut self, blocks: &[Block], max_gap: u64) -> io::Result<Vec<Bytes>> {
+        let mut data = Vec::with_capacity(blocks.len());
+        for run in coalesced_runs(blocks, max_gap) {
+            data.extend(read_raw_run(self.raw_reader(), run)?);
+        }
+        Ok(data)
+    }
 
     fn blocks_for_cir_tree_node(
         &mut self,
@@ -526,16 +873,57 @@ pub trait BBIFileRead {
         end: u32,
     ) -> io::Result<(SmallVec<[u64; 4]>, SmallVec<[Block; 4]>)>;
 
+    /// Like `blocks_for_cir_tree_node`, but for many sorted, non-overlapping
+    /// `ranges` on a chromosome at once. Each child node and block is
+    /// returned with the (contiguous) indices of the ranges it overlaps. By
+    /// default, `blocks_for_cir_tree_node` is called for each range.
+    fn blocks_for_cir_tree_node_ranges(
+        &mut self,
+        endianness: Endianness,
+        node_offset: u64,
+        chrom_ix: u32,
+        ranges: &[(u32, u32)],
+    ) -> io::Result<CirTreeNodeRangeBlocks> {
+        let mut nodes: SmallVec<[(u64, Range<usize>); 4]> = smallvec![];
+        let mut blocks: SmallVec<[(Block, Range<usize>); 4]> = smallvec![];
+        for (i, &(start, end)) in ranges.iter().enumerate() {
+            let (range_nodes, range_blocks) =
+                self.blocks_for_cir_tree_node(endianness, node_offset, chrom_ix, start, end)?;
+            for node in range_nodes {
+                match nodes.iter_mut().find(|(n, _)| *n == node) {
+                    Some((_, r)) => r.end = i + 1,
+                    None => nodes.push((node, i..i + 1)),
+                }
+            }
+            for block in range_blocks {
+                match blocks.iter_mut().find(|(b, _)| *b == block) {
+                    Some((_, r)) => r.end = i + 1,
+                    None => blocks.push((block, i..i + 1)),
+                }
+            }
+        }
+        Ok((nodes, blocks))
+    }
+
     fn raw_reader(&mut self) -> &mut Self::Reader;
 }
 
 impl<S: SeekableRead> BBIFileRead for S {
     type Reader = Self;
 
-    fn get_block_data(&mut self, info: &BBIFileInfo, block: &Block) -> io::Result<Vec<u8>> {
+    fn get_block_data(&mut self, info: &BBIFileInfo, block: &Block) -> io::Result<Bytes> {
         read_block_data(info, self, block)
     }
 
+    fn get_blocks_data(
+        &mut self,
+        info: &BBIFileInfo,
+        blocks: &[Block],
+        max_gap: u64,
+    ) -> io::Result<Vec<Bytes>> {
+        read_blocks_data(info, self, blocks, max_gap)
+    }
+
     fn blocks_for_cir_tree_node(
         &mut self,
         endianness: Endianness,
@@ -552,48 +940,257 @@ impl<S: SeekableRead> BBIFileRead for S {
         Ok(nodes_overlapping(iter, chrom_ix, start, end))
     }
 
+    fn blocks_for_cir_tree_node_ranges(
+        &mut self,
+        endianness: Endianness,
+        node_offset: u64,
+        chrom_ix: u32,
+        ranges: &[(u32, u32)],
+    ) -> io::Result<CirTreeNodeRangeBlocks> {
+        let iter = read_node(self, node_offset, endianness)?;
+        Ok(nodes_overlapping_ranges(iter, chrom_ix, ranges))
+    }
+
     fn raw_reader(&mut self) -> &mut Self::Reader {
         self
     }
 }
 
+/// The child nodes and blocks of a cir tree node that overlap a set of
+/// ranges, each with the indices of the ranges they overlap
+pub type CirTreeNodeRangeBlocks = (
+    SmallVec<[(u64, Range<usize>); 4]>,
+    SmallVec<[(Block, Range<usize>); 4]>,
+);
+
+/// The default maximum size (in bytes) of the data cached by a
+/// `CachedBBIFileRead`
+pub const DEFAULT_CACHE_CAPACITY: usize = 64 * 1024 * 1024;
+
+/// Statistics about the cache of a `CachedBBIFileRead`
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Number of cir tree nodes or blocks that were found in the cache
+    pub hits: u64,
+    /// Number of cir tree nodes or blocks that had to be read from the file
+    pub misses: u64,
+    /// Number of cir tree nodes and blocks currently cached
+    pub entries: usize,
+    /// Approximate size (in bytes) of the currently cached data
+    pub size: usize,
+    /// Maximum size (in bytes) of the cached data
+    pub capacity: usize,
+}
+
+type CirTreeNode = Either<Vec<CirTreeNodeLeaf>, Vec<CirTreeNodeNonLeaf>>;
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum CacheKey {
+    Node(u64),
+    Block(Block),
+}
+
+enum CacheValue {
+    Node(CirTreeNode),
+    Block(Bytes),
+}
+
+struct BBIFileCache {
+    lru: Lru<CacheKey, CacheValue>,
+    hits: u64,
+    misses: u64,
+}
+
+impl BBIFileCache {
+    fn get_node(&mut self, node_offset: u64) -> Option<CirTreeNode> {
+        let node = match self.lru.get(&CacheKey::Node(node_offset)) {
+            Some(CacheValue::Node(node)) => Some(node.clone()),
+            _ => None,
+        };
+        self.count(node.is_some());
+        node
+    }
+
+    fn insert_node(&mut self, node_offset: u64, node: CirTreeNode) {
+        let size = match &node {
+            Either::Left(v) => v.len() * std::mem::size_of::<CirTreeNodeLeaf>(),
+            Either::Right(v) => v.len() * std::mem::size_of::<CirTreeNodeNonLeaf>(),
+        };
+        self.lru
+            .insert(CacheKey::Node(node_offset), CacheValue::Node(node), size);
+    }
+
+    fn get_block(&mut self, block: &Block) -> Option<Bytes> {
+        let data = match self.lru.get(&CacheKey::Block(*block)) {
+            Some(CacheValue::Block(data)) => Some(data.clone()),
+            _ => None,
+        };
+        self.count(data.is_some());
+        data
+    }
+
+    fn insert_block(&mut self, block: Block, data: Bytes) {
+        let size = data.len();
+        self.lru
+            .insert(CacheKey::Block(block), CacheValue::Block(data), size);
+    }
+
+    fn count(&mut self, hit: bool) {
+        if hit {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+        }
+    }
+}
+
+/// A `BBIFileRead` that caches cir tree nodes and decompressed block data in
+/// a least-recently-used cache bounded by size. The cache is shared between
+/// this and any `reopen`ed copies.
 pub struct CachedBBIFileRead<S> {
     read: S,
-    cir_tree_node_map: HashMap<u64, Either<Vec<CirTreeNodeLeaf>, Vec<CirTreeNodeNonLeaf>>>,
-    block_data: HashMap<Block, Vec<u8>>,
+    cache: Arc<Mutex<BBIFileCache>>,
 }
 
 impl<S> CachedBBIFileRead<S> {
+    /// Creates a `CachedBBIFileRead` that caches up to
+    /// `DEFAULT_CACHE_CAPACITY` bytes.
+    pub fn new(read: S) -> Self {
+        CachedBBIFileRead::with_capacity(read, DEFAULT_CACHE_CAPACITY)
+    }
+
+    /// Creates a `CachedBBIFileRead` that caches up to `capacity` bytes. Block
+    /// data counts against this with its decompressed size.
+    pub fn with_capacity(read: S, capacity: usize) -> Self {
+        CachedBBIFileRead {
+            read,
+            cache: Arc::new(Mutex::new(BBIFileCache {
+                lru: Lru::new(capacity),
+                hits: 0,
+                misses: 0,
+            })),
+        }
+    }
+
+    /// Creates a `CachedBBIFileRead` that reads from `read`, but shares the
+    /// cache of this one.
+    pub(crate) fn with_read<T>(&self, read: T) -> CachedBBIFileRead<T> {
+        CachedBBIFileRead {
+            read,
+            cache: self.cache.clone(),
+        }
+    }
+
     pub fn inner_read(&self) -> &S {
         &self.read
     }
+
+    /// Returns the hit and miss counts and current size of the cache. Since
+    /// the cache is shared between `reopen`ed copies, these include accesses
+    /// from all of them.
+    pub fn cache_stats(&self) -> CacheStats {
+        let cache = self.cache();
+        CacheStats {
+            hits: cache.hits,
+            misses: cache.misses,
+            entries: cache.lru.len(),
+            size: cache.lru.size(),
+            capacity: cache.lru.capacity(),
+        }
+    }
+
+    fn cache(&self) -> MutexGuard<'_, BBIFileCache> {
+        // The cache is never left in an inconsistent state, so it's fine to
+        // keep using it if another thread panicked while holding it.
+        self.cache.lock().unwrap_or_else(PoisonError::into_inner)
+    }
 }
 
 impl<S: SeekableRead> CachedBBIFileRead<S> {
-    pub fn new(read: S) -> Self {
-        CachedBBIFileRead {
-            read,
-            cir_tree_node_map: HashMap::new(),
-            block_data: HashMap::new(),
+    /// Gets the items of the cir tree node at `node_offset`, from the cache if
+    /// possible.
+    fn node(
+        &mut self,
+        endianness: Endianness,
+        node_offset: u64,
+    ) -> io::Result<
+        CirTreeNodeIterator<
+            std::vec::IntoIter<CirTreeNodeLeaf>,
+            std::vec::IntoIter<CirTreeNodeNonLeaf>,
+        >,
+    > {
+        if let Some(node) = self.cache().get_node(node_offset) {
+            return Ok(match node {
+                Either::Left(v) => CirTreeNodeIterator::Leaf(v.into_iter()),
+                Either::Right(v) => CirTreeNodeIterator::NonLeaf(v.into_iter()),
+            });
         }
+        let (node, iter) = match read_node(&mut self.read, node_offset, endianness)? {
+            CirTreeNodeIterator::Leaf(v) => {
+                let v: Vec<_> = v.collect();
+                (
+                    Either::Left(v.clone()),
+                    CirTreeNodeIterator::Leaf(v.into_iter()),
+                )
+            }
+            CirTreeNodeIterator::NonLeaf(v) => {
+                let v: Vec<_> = v.collect();
+                (
+                    Either::Right(v.clone()),
+                    CirTreeNodeIterator::NonLeaf(v.into_iter()),
+                )
+            }
+        };
+        self.cache().insert_node(node_offset, node);
+        Ok(iter)
     }
 }
 
 impl<S: SeekableRead> BBIFileRead for CachedBBIFileRead<S> {
     type Reader = S;
 
-    fn get_block_data(&mut self, info: &BBIFileInfo, block: &Block) -> io::Result<Vec<u8>> {
-        if let Some(data) = self.block_data.get(block) {
-            return Ok(data.clone());
-        }
-        if self.block_data.len() >= 5000 {
-            self.block_data.clear();
+    fn get_block_data(&mut self, info: &BBIFileInfo, block: &Block) -> io::Result<Bytes> {
+        if let Some(data) = self.cache().get_block(block) {
+            return Ok(data);
         }
         let data = read_block_data(info, &mut self.read, block)?;
-        self.block_data.insert(*block, data.clone());
+        self.cache().insert_block(*block, data.clone());
         Ok(data)
     }
 
+    fn get_blocks_data(
+        &mut self,
+        info: &BBIFileInfo,
+        blocks: &[Block],
+        max_gap: u64,
+    ) -> io::Result<Vec<Bytes>> {
+        let cached: Vec<Option<Bytes>> = {
+            let mut cache = self.cache();
+            blocks.iter().map(|block| cache.get_block(block)).collect()
+        };
+        let missing: Vec<Block> = blocks
+            .iter()
+            .zip(cached.iter())
+            .filter(|(_, data)| data.is_none())
+            .map(|(block, _)| *block)
+            .collect();
+        let mut missing_data = read_blocks_data(info, &mut self.read, &missing, max_gap)?
+            .into_iter()
+            .zip(missing);
+        let mut cache = self.cache();
+        Ok(cached
+            .into_iter()
+            .map(|data| match data {
+                Some(data) => data,
+                None => {
+                    let (data, block) = missing_data.next().unwrap();
+                    cache.insert_block(block, data.clone());
+                    data
+                }
+            })
+            .collect())
+    }
+
     fn blocks_for_cir_tree_node(
         &mut self,
         endianness: Endianness,
@@ -602,35 +1199,19 @@ impl<S: SeekableRead> BBIFileRead for CachedBBIFileRead<S> {
         start: u32,
         end: u32,
     ) -> io::Result<(SmallVec<[u64; 4]>, SmallVec<[Block; 4]>)> {
-        match self.cir_tree_node_map.entry(node_offset) {
-            Entry::Occupied(node) => {
-                let iter = match node.get() {
-                    Either::Left(v) => CirTreeNodeIterator::Leaf(v.clone().into_iter()),
-                    Either::Right(v) => CirTreeNodeIterator::NonLeaf(v.clone().into_iter()),
-                };
-                Ok(nodes_overlapping(iter, chrom_ix, start, end))
-            }
-            Entry::Vacant(e) => {
-                let iter = match read_node(&mut self.read, node_offset, endianness) {
-                    Ok(d) => d,
-                    Err(e) => return Err(e),
-                };
-                let iter = match iter {
-                    CirTreeNodeIterator::Leaf(v) => {
-                        let v: Vec<_> = v.collect();
-                        e.insert(Either::Left(v.clone()));
-                        CirTreeNodeIterator::Leaf(v.into_iter())
-                    }
-                    CirTreeNodeIterator::NonLeaf(v) => {
-                        let v: Vec<_> = v.collect();
-                        e.insert(Either::Right(v.clone()));
-                        CirTreeNodeIterator::NonLeaf(v.into_iter())
-                    }
-                };
+        let iter = self.node(endianness, node_offset)?;
+        Ok(nodes_overlapping(iter, chrom_ix, start, end))
+    }
 
-                Ok(nodes_overlapping(iter, chrom_ix, start, end))
-            }
-        }
+    fn blocks_for_cir_tree_node_ranges(
+        &mut self,
+        endianness: Endianness,
+        node_offset: u64,
+        chrom_ix: u32,
+        ranges: &[(u32, u32)],
+    ) -> io::Result<CirTreeNodeRangeBlocks> {
+        let iter = self.node(endianness, node_offset)?;
+        Ok(nodes_overlapping_ranges(iter, chrom_ix, ranges))
     }
 
     fn raw_reader(&mut self) -> &mut Self::Reader {
@@ -642,12 +1223,124 @@ impl<R: Reopen + SeekableRead> Reopen for CachedBBIFileRead<R> {
     fn reopen(&self) -> io::Result<Self> {
         Ok(Self {
             read: self.read.reopen()?,
-            cir_tree_node_map: self.cir_tree_node_map.clone(),
-            block_data: self.block_data.clone(),
+            cache: self.cache.clone(),
         })
     }
 }
 
+/// Memory-maps the file at `path`, as `Bytes` that share the mapping.
+///
+/// # Safety
+///
+/// The file must not be modified or truncated while it is mapped (that is,
+/// until the returned `Bytes` and all its clones and slices are dropped).
+#[cfg(feature = "mmap")]
+pub(crate) unsafe fn mmap_bytes(path: impl AsRef<std::path::Path>) -> io::Result<Bytes> {
+    let file = File::open(path)?;
+    let mmap = memmap2::Mmap::map(&file)?;
+    Ok(Bytes::from_owner(mmap))
+}
+
+/// A `BBIFileRead` for a bbi file that is entirely in memory, such as
+/// `Bytes` (see `BigWigRead::from_bytes`) or a memory-mapped file (see
+/// `BigWigRead::open_mmap`). Cir tree nodes and blocks are decoded directly
+/// from the underlying bytes, without any seeks or read calls. The data of
+/// uncompressed blocks is returned as a slice of the file's `Bytes`, so only
+/// compressed blocks are copied (into their decompressed data).
+pub struct InMemoryBBIFileRead {
+    read: Cursor<Bytes>,
+}
+
+impl InMemoryBBIFileRead {
+    /// Creates an `InMemoryBBIFileRead` over the bytes of an entire bbi file
+    pub fn new(bytes: Bytes) -> Self {
+        InMemoryBBIFileRead {
+            read: Cursor::new(bytes),
+        }
+    }
+
+    /// Gets the underlying bytes of the file
+    pub fn bytes(&self) -> &Bytes {
+        self.read.get_ref()
+    }
+
+    /// Gets the raw (still compressed, if applicable) data of a block
+    fn raw_block_data(&self, block: &Block) -> io::Result<Bytes> {
+        let bytes = self.bytes();
+        block
+            .offset
+            .checked_add(block.size)
+            .and_then(|end| {
+                let start = usize::try_from(block.offset).ok()?;
+                let end = usize::try_from(end).ok()?;
+                (end <= bytes.len()).then(|| bytes.slice(start..end))
+            })
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("Block at offset {} extends past end of file", block.offset),
+                )
+            })
+    }
+
+    /// Gets the items of the cir tree node at `node_offset`
+    fn node(&self, endianness: Endianness, node_offset: u64) -> io::Result<CirTreeNodeIterator> {
+        let bytes = self.bytes();
+        let start = usize::try_from(node_offset).map_or(bytes.len(), |o| o.min(bytes.len()));
+        parse_node(bytes.slice(start..), node_offset, endianness)
+    }
+}
+
+impl BBIFileRead for InMemoryBBIFileRead {
+    type Reader = Cursor<Bytes>;
+
+    fn get_block_data(&mut self, info: &BBIFileInfo, block: &Block) -> io::Result<Bytes> {
+        let raw_data = self.raw_block_data(block)?;
+        decompress_block_data(&info.header, raw_data).map_err(|e| e.into_io_error(block.offset))
+    }
+
+    fn get_raw_blocks_data(&mut self, blocks: &[Block], max_gap: u64) -> io::Result<Vec<Bytes>> {
+        let _ = max_gap;
+        blocks
+            .iter()
+            .map(|block| self.raw_block_data(block))
+            .collect()
+    }
+
+    fn blocks_for_cir_tree_node(
+        &mut self,
+        endianness: Endianness,
+        node_offset: u64,
+        chrom_ix: u32,
+        start: u32,
+        end: u32,
+    ) -> io::Result<(SmallVec<[u64; 4]>, SmallVec<[Block; 4]>)> {
+        let iter = self.node(endianness, node_offset)?;
+        Ok(nodes_overlapping(iter, chrom_ix, start, end))
+    }
+
+    fn blocks_for_cir_tree_node_ranges(
+        &mut self,
+        endianness: Endianness,
+        node_offset: u64,
+        chrom_ix: u32,
+        ranges: &[(u32, u32)],
+    ) -> io::Result<CirTreeNodeRangeBlocks> {
+        let iter = self.node(endianness, node_offset)?;
+        Ok(nodes_overlapping_ranges(iter, chrom_ix, ranges))
+    }
+
+    fn raw_reader(&mut self) -> &mut Self::Reader {
+        &mut self.read
+    }
+}
+
+impl Reopen for InMemoryBBIFileRead {
+    fn reopen(&self) -> io::Result<Self> {
+        Ok(InMemoryBBIFileRead::new(self.bytes().clone()))
+    }
+}
+
 pub(crate) fn read_info<R: BBIFileRead>(file: &mut R) -> Result<BBIFileInfo, BBIFileReadInfoError> {
     let mut file = file.raw_reader();
 
@@ -674,6 +1367,7 @@ pub(crate) fn read_info<R: BBIFileRead>(file: &mut R) -> Result<BBIFileInfo, BBI
         auto_sql_offset,
         total_summary_offset,
         uncompress_buf_size,
+        extension_offset,
     ) = match endianness {
         Endianness::Big => {
             let version = header_data.get_u16();
@@ -686,7 +1380,7 @@ pub(crate) fn read_info<R: BBIFileRead>(file: &mut R) -> Result<BBIFileInfo, BBI
             let auto_sql_offset = header_data.get_u64();
             let total_summary_offset = header_data.get_u64();
             let uncompress_buf_size = header_data.get_u32();
-            let _reserved = header_data.get_u64();
+            let extension_offset = header_data.get_u64();
 
             (
                 version,
@@ -699,6 +1393,7 @@ pub(crate) fn read_info<R: BBIFileRead>(file: &mut R) -> Result<BBIFileInfo, BBI
                 auto_sql_offset,
                 total_summary_offset,
                 uncompress_buf_size,
+                extension_offset,
             )
         }
         Endianness::Little => {
@@ -712,7 +1407,7 @@ pub(crate) fn read_info<R: BBIFileRead>(file: &mut R) -> Result<BBIFileInfo, BBI
             let auto_sql_offset = header_data.get_u64_le();
             let total_summary_offset = header_data.get_u64_le();
             let uncompress_buf_size = header_data.get_u32_le();
-            let _reserved = header_data.get_u64_le();
+            let extension_offset = header_data.get_u64_le();
 
             (
                 version,
@@ -725,6 +1420,7 @@ pub(crate) fn read_info<R: BBIFileRead>(file: &mut R) -> Result<BBIFileInfo, BBI
                 auto_sql_offset,
                 total_summary_offset,
                 uncompress_buf_size,
+                extension_offset,
             )
         }
     };
@@ -742,6 +1438,7 @@ pub(crate) fn read_info<R: BBIFileRead>(file: &mut R) -> Result<BBIFileInfo, BBI
         auto_sql_offset,
         total_summary_offset,
         uncompress_buf_size,
+        extension_offset,
     };
 
     let zoom_headers = read_zoom_headers(file, &header)?;
@@ -783,11 +1480,23 @@ pub(crate) fn read_info<R: BBIFileRead>(file: &mut R) -> Result<BBIFileInfo, BBI
         }
     };
 
-    assert_eq!(val_size, 8u32);
+    if val_size != 8 {
+        return Err(BBIFileReadInfoError::InvalidChroms);
+    }
 
-    let mut chrom_info = Vec::with_capacity(item_count as usize);
-    read_chrom_tree_block(&mut file, endianness, &mut chrom_info, key_size)
-        .map_err(|_| BBIFileReadInfoError::InvalidChroms)?;
+    let mut chrom_info = Vec::with_capacity(item_count.min(1024) as usize);
+    let mut visited = HashSet::new();
+    read_chrom_tree_block(
+        &mut file,
+        endianness,
+        &mut chrom_info,
+        key_size,
+        &mut visited,
+    )
+    .map_err(|e| match e {
+        ChromTreeBlockReadError::IoError(e) => BBIFileReadInfoError::IoError(e),
+        ChromTreeBlockReadError::InvalidFile(_) => BBIFileReadInfoError::InvalidChroms,
+    })?;
 
     let info = BBIFileInfo {
         filetype,
@@ -857,7 +1566,14 @@ fn read_chrom_tree_block<R: SeekableRead>(
     endianness: Endianness,
     chroms: &mut Vec<ChromInfo>,
     key_size: u32,
+    visited: &mut HashSet<u64>,
 ) -> Result<(), ChromTreeBlockReadError> {
+    // A node that is reachable more than once would otherwise be read forever
+    if !visited.insert(f.stream_position()?) {
+        return Err(ChromTreeBlockReadError::InvalidFile(
+            "Invalid file format: Cycle in chromosome tree.".to_owned(),
+        ));
+    }
     let mut header_data = BytesMut::zeroed(4);
     f.read_exact(&mut header_data)?;
 
@@ -869,8 +1585,8 @@ fn read_chrom_tree_block<R: SeekableRead>(
     };
 
     if isleaf == 1 {
-        let mut bytes = BytesMut::zeroed((key_size as usize + 8) * (count as usize));
-        f.read_exact(&mut bytes)?;
+        let len = (u64::from(key_size) + 8) * u64::from(count);
+        let mut bytes = BytesMut::from(&read_vec(f, len)?[..]);
 
         for _ in 0..count {
             let key_string = match std::str::from_utf8(&bytes.as_ref()[0..(key_size as usize)]) {
@@ -898,8 +1614,8 @@ fn read_chrom_tree_block<R: SeekableRead>(
         let mut children: Vec<u64> = vec![];
         children.reserve_exact(count as usize);
 
-        let mut bytes = BytesMut::zeroed((key_size as usize + 8) * (count as usize));
-        f.read_exact(&mut bytes)?;
+        let len = (u64::from(key_size) + 8) * u64::from(count);
+        let mut bytes = BytesMut::from(&read_vec(f, len)?[..]);
 
         for _ in 0..count {
             // We don't need this, but have to read it
@@ -915,12 +1631,89 @@ fn read_chrom_tree_block<R: SeekableRead>(
         // Then go through each child block
         for child in children {
             f.seek(SeekFrom::Start(child))?;
-            read_chrom_tree_block(f, endianness, chroms, key_size)?;
+            read_chrom_tree_block(f, endianness, chroms, key_size, visited)?;
         }
     }
     Ok(())
 }
 
+/// Searches a B+ tree (in the same format as the chrom tree) starting at
+/// `tree_offset` for all items with the given key. Returns the raw bytes of
+/// the value for each matching item.
+pub(crate) fn search_bplustree<R: Read + Seek>(
+    file: &mut R,
+    endianness: Endianness,
+    tree_offset: u64,
+    key: &[u8],
+) -> Result<Vec<Vec<u8>>, BBIReadError> {
+    let mut file = ByteOrdered::runtime(file, endianness);
+    file.seek(SeekFrom::Start(tree_offset))?;
+    let magic = file.read_u32()?;
+    if magic != CHROM_TREE_MAGIC {
+        return Err(BBIReadError::UnknownMagic);
+    }
+    let _block_size = file.read_u32()?;
+    let key_size = file.read_u32()? as usize;
+    let val_size = file.read_u32()? as usize;
+    let _item_count = file.read_u64()?;
+    let _reserved = file.read_u64()?;
+
+    if key.len() > key_size {
+        return Ok(vec![]);
+    }
+    // Compares a key from the tree to `key` padded with zeros to `key_size`
+    let cmp_key = |item_key: &[u8]| {
+        let (head, tail) = item_key.split_at(key.len());
+        head.cmp(key)
+            .then_with(|| match tail.iter().all(|b| *b == 0) {
+                true => Ordering::Equal,
+                false => Ordering::Greater,
+            })
+    };
+
+    let mut values = vec![];
+    let mut visited = HashSet::new();
+    let mut nodes = vec![tree_offset + 32];
+    while let Some(node_offset) = nodes.pop() {
+        if !visited.insert(node_offset) {
+            return Err(BBIReadError::InvalidFile(format!(
+                "B+ tree node at offset {} is reachable more than once.",
+                node_offset
+            )));
+        }
+        file.seek(SeekFrom::Start(node_offset))?;
+        let isleaf = file.read_u8()?;
+        let _reserved = file.read_u8()?;
+        let count = file.read_u16()? as usize;
+        if isleaf == 1 {
+            for _ in 0..count {
+                let item_key = read_vec(&mut file, key_size as u64)?;
+                let value = read_vec(&mut file, val_size as u64)?;
+                if cmp_key(&item_key).is_eq() {
+                    values.push(value);
+                }
+            }
+        } else {
+            let mut children = Vec::with_capacity(count);
+            for _ in 0..count {
+                let child_key = read_vec(&mut file, key_size as u64)?;
+                children.push((child_key, file.read_u64()?));
+            }
+            // Keys can be duplicated, so a run of matching items may span
+            // multiple children. A child can only contain the key if the key
+            // falls between its first key and the first key of the next child.
+            for i in (0..count).rev() {
+                let after_start = i == 0 || cmp_key(&children[i].0).is_le();
+                let before_end = i + 1 == count || cmp_key(&children[i + 1].0).is_ge();
+                if after_start && before_end {
+                    nodes.push(children[i].1);
+                }
+            }
+        }
+    }
+    Ok(values)
+}
+
 #[inline]
 fn compare_position(chrom1: u32, chrom1_base: u32, chrom2: u32, chrom2_base: u32) -> i8 {
     if chrom1 < chrom2 {
@@ -973,7 +1766,7 @@ pub(crate) struct CirTreeLeafItemIterator {
     endianness: Endianness,
     i: usize,
     count: usize,
-    bytes: Vec<u8>,
+    bytes: Bytes,
 }
 
 impl Iterator for CirTreeLeafItemIterator {
@@ -1053,27 +1846,12 @@ impl Iterator for CirTreeLeafItemIterator {
         })
     }
 }
-fn cir_tree_leaf_items<R: SeekableRead>(
-    file: &mut R,
-    endianness: Endianness,
-    count: usize,
-) -> io::Result<CirTreeLeafItemIterator> {
-    let mut bytes = vec![0u8; count * 32];
-    file.read_exact(&mut bytes)?;
-
-    Ok(CirTreeLeafItemIterator {
-        endianness,
-        i: 0,
-        count,
-        bytes,
-    })
-}
 
 pub(crate) struct CirTreeNonLeafItemsIterator {
     endianness: Endianness,
     i: usize,
     count: usize,
-    bytes: Vec<u8>,
+    bytes: Bytes,
 }
 
 impl Iterator for CirTreeNonLeafItemsIterator {
@@ -1139,24 +1917,10 @@ impl Iterator for CirTreeNonLeafItemsIterator {
         })
     }
 }
-fn cir_tree_non_leaf_items<R: SeekableRead>(
-    file: &mut R,
-    endianness: Endianness,
-    count: usize,
-) -> io::Result<CirTreeNonLeafItemsIterator> {
-    let mut bytes = vec![0u8; (count as usize) * 32];
-    file.read_exact(&mut bytes)?;
-
-    Ok(CirTreeNonLeafItemsIterator {
-        endianness,
-        i: 0,
-        count,
-        bytes,
-    })
-}
 
 pub(crate) struct CirTreeBlockSearchIter<'a, R: BBIFileRead> {
     remaining_childblocks: VecDeque<u64>,
+    visited: HashSet<u64>,
 
     file: &'a mut R,
     endianness: Endianness,
@@ -1175,6 +1939,9 @@ impl<'a, R: BBIFileRead> Iterator for CirTreeBlockSearchIter<'a, R> {
         let end = self.end;
 
         let node_offset = self.remaining_childblocks.pop_front()?;
+        if !self.visited.insert(node_offset) {
+            return Some(Err(cir_tree_cycle_error(node_offset)));
+        }
 
         let (new_childblocks, blocks) =
             match file.blocks_for_cir_tree_node(endianness, node_offset, chrom_ix, start, end) {
@@ -1190,6 +1957,18 @@ impl<'a, R: BBIFileRead> Iterator for CirTreeBlockSearchIter<'a, R> {
     }
 }
 
+/// The error when a cir tree node is reached more than once while searching
+/// a (corrupted) cir tree, which would otherwise loop forever.
+pub(crate) fn cir_tree_cycle_error(node_offset: u64) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "Cir tree node at offset {} is reachable more than once.",
+            node_offset
+        ),
+    )
+}
+
 pub(crate) enum CirTreeNodeIterator<
     L: Iterator<Item = CirTreeNodeLeaf> = CirTreeLeafItemIterator,
     N: Iterator<Item = CirTreeNodeNonLeaf> = CirTreeNonLeafItemsIterator,
@@ -1198,24 +1977,23 @@ pub(crate) enum CirTreeNodeIterator<
     NonLeaf(N),
 }
 
-pub(crate) fn read_node<R: SeekableRead>(
-    file: &mut R,
+/// Parses the 4 byte header of the cir tree node at `node_offset`, returning
+/// whether it is a leaf and the total size of its items
+fn parse_node_header(
+    mut header_data: &[u8],
     node_offset: u64,
     endianness: Endianness,
-) -> io::Result<CirTreeNodeIterator> {
-    match file.seek(SeekFrom::Start(node_offset)) {
-        Err(e) => return Err(e),
-        Ok(_) => {}
-    };
-
-    let mut header_data = BytesMut::zeroed(4);
-    match file.read_exact(&mut header_data) {
-        Err(e) => return Err(e),
-        Ok(_) => {}
-    }
-
+) -> io::Result<(bool, usize)> {
     let isleaf: u8 = header_data.get_u8();
-    assert!(isleaf == 1 || isleaf == 0, "Unexpected isleaf: {}", isleaf);
+    if isleaf > 1 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Unexpected isleaf ({}) for cir tree node at offset {}.",
+                isleaf, node_offset
+            ),
+        ));
+    }
     let _reserved = header_data.get_u8();
 
     let count = match endianness {
@@ -1223,20 +2001,87 @@ pub(crate) fn read_node<R: SeekableRead>(
         Endianness::Little => header_data.get_u16_le(),
     };
 
-    let iter = if isleaf == 1 {
-        let iter = match cir_tree_leaf_items(file, endianness, count as usize) {
-            Ok(v) => v,
-            Err(e) => return Err(e),
-        };
-        CirTreeNodeIterator::Leaf(iter)
+    // Each leaf item is 32 bytes: the start and end chrom and base, and the
+    // block offset and size. Each non-leaf item is 24 bytes: the start and
+    // end chrom and base, and the child node offset.
+    let item_size = if isleaf == 1 { 32 } else { 24 };
+    Ok((isleaf == 1, count as usize * item_size))
+}
+
+fn node_items(isleaf: bool, endianness: Endianness, bytes: Bytes) -> CirTreeNodeIterator {
+    if isleaf {
+        CirTreeNodeIterator::Leaf(CirTreeLeafItemIterator {
+            endianness,
+            i: 0,
+            count: bytes.len() / 32,
+            bytes,
+        })
     } else {
-        let iter = match cir_tree_non_leaf_items(file, endianness, count as usize) {
-            Ok(v) => v,
-            Err(e) => return Err(e),
-        };
-        CirTreeNodeIterator::NonLeaf(iter)
+        CirTreeNodeIterator::NonLeaf(CirTreeNonLeafItemsIterator {
+            endianness,
+            i: 0,
+            count: bytes.len() / 24,
+            bytes,
+        })
+    }
+}
+
+pub(crate) fn read_node<R: SeekableRead>(
+    file: &mut R,
+    node_offset: u64,
+    endianness: Endianness,
+) -> io::Result<CirTreeNodeIterator> {
+    file.seek(SeekFrom::Start(node_offset))?;
+
+    let mut header_data = [0u8; 4];
+    file.read_exact(&mut header_data)?;
+    let (isleaf, items_size) = parse_node_header(&header_data, node_offset, endianness)?;
+
+    let mut bytes = vec![0u8; items_size];
+    file.read_exact(&mut bytes)?;
+    Ok(node_items(isleaf, endianness, bytes.into()))
+}
+
+/// Parses the cir tree node at the start of `node` (which may extend past
+/// the end of the node). The items are decoded directly from `node`.
+pub(crate) fn parse_node(
+    node: Bytes,
+    node_offset: u64,
+    endianness: Endianness,
+) -> io::Result<CirTreeNodeIterator> {
+    let eof = || {
+        io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!(
+                "Cir tree node at offset {} extends past end of file",
+                node_offset
+            ),
+        )
     };
-    Ok(iter)
+    let header_data = node.get(..4).ok_or_else(eof)?;
+    let (isleaf, items_size) = parse_node_header(header_data, node_offset, endianness)?;
+    if node.len() - 4 < items_size {
+        return Err(eof());
+    }
+    Ok(node_items(isleaf, endianness, node.slice(4..4 + items_size)))
+}
+
+/// The child node offsets and blocks of a cir tree node
+#[cfg(feature = "async")]
+pub(crate) type CirTreeNodeBlocks = (SmallVec<[u64; 4]>, SmallVec<[Block; 4]>);
+
+/// Reads the cir tree node in `node` (the node header and all its items) and
+/// returns the child nodes and blocks overlapping the given region.
+#[cfg(feature = "async")]
+pub(crate) fn cir_tree_node_blocks(
+    node: Bytes,
+    endianness: Endianness,
+    chrom_ix: u32,
+    start: u32,
+    end: u32,
+) -> io::Result<CirTreeNodeBlocks> {
+    let iter = parse_node(node, 0, endianness)?;
+    Ok(nodes_overlapping(iter, chrom_ix, start, end))
 }
 
 fn nodes_overlapping<
@@ -1291,53 +2136,273 @@ fn nodes_overlapping<
     }
 }
 
+/// Returns the indices of the sorted, non-overlapping `ranges` on chromosome
+/// `chrom_ix` that overlap the given span (with the same inclusive semantics
+/// as `overlaps`).
+fn overlapping_ranges(
+    chrom_ix: u32,
+    ranges: &[(u32, u32)],
+    start_chrom_ix: u32,
+    start_base: u32,
+    end_chrom_ix: u32,
+    end_base: u32,
+) -> Range<usize> {
+    if start_chrom_ix > chrom_ix || end_chrom_ix < chrom_ix {
+        return 0..0;
+    }
+    let start = if start_chrom_ix == chrom_ix {
+        start_base
+    } else {
+        0
+    };
+    let end = if end_chrom_ix == chrom_ix {
+        end_base
+    } else {
+        u32::MAX
+    };
+    let lo = ranges.partition_point(|r| r.1 < start);
+    let hi = ranges.partition_point(|r| r.0 <= end);
+    lo..hi.max(lo)
+}
+
+fn nodes_overlapping_ranges<
+    L: Iterator<Item = CirTreeNodeLeaf>,
+    N: Iterator<Item = CirTreeNodeNonLeaf>,
+>(
+    iter: CirTreeNodeIterator<L, N>,
+    chrom_ix: u32,
+    ranges: &[(u32, u32)],
+) -> CirTreeNodeRangeBlocks {
+    match iter {
+        CirTreeNodeIterator::Leaf(iter) => {
+            let mut blocks: SmallVec<[_; 4]> = smallvec![];
+            for child in iter {
+                let overlapping = overlapping_ranges(
+                    chrom_ix,
+                    ranges,
+                    child.start_chrom_ix,
+                    child.start_base,
+                    child.end_chrom_ix,
+                    child.end_base,
+                );
+                if !overlapping.is_empty() {
+                    let block = Block {
+                        offset: child.data_offset,
+                        size: child.data_size,
+                    };
+                    blocks.push((block, overlapping));
+                }
+            }
+            (smallvec![], blocks)
+        }
+        CirTreeNodeIterator::NonLeaf(iter) => {
+            let mut new_childblocks: SmallVec<[_; 4]> = smallvec![];
+            for child in iter {
+                let overlapping = overlapping_ranges(
+                    chrom_ix,
+                    ranges,
+                    child.start_chrom_ix,
+                    child.start_base,
+                    child.end_chrom_ix,
+                    child.end_base,
+                );
+                if !overlapping.is_empty() {
+                    new_childblocks.push((child.node_offset, overlapping));
+                }
+            }
+            (new_childblocks, smallvec![])
+        }
+    }
+}
+
+/// Searches the cir tree at `at` for the blocks overlapping any of the
+/// sorted, non-overlapping `ranges` on chromosome `chrom_ix`, visiting each
+/// node at most once. The blocks are returned sorted by offset, each with the
+/// indices of the ranges it overlaps.
+pub(crate) fn search_cir_tree_ranges<R: BBIFileRead>(
+    endianness: Endianness,
+    file: &mut R,
+    at: u64,
+    chrom_ix: u32,
+    ranges: &[(u32, u32)],
+) -> io::Result<Vec<(Block, Range<usize>)>> {
+    let mut blocks: Vec<(Block, Range<usize>)> = vec![];
+    if ranges.is_empty() {
+        return Ok(blocks);
+    }
+
+    let mut visited = HashSet::new();
+    let mut remaining_childblocks = VecDeque::new();
+    remaining_childblocks.push_back((at, 0..ranges.len()));
+    while let Some((node_offset, node_ranges)) = remaining_childblocks.pop_front() {
+        if !visited.insert(node_offset) {
+            return Err(cir_tree_cycle_error(node_offset));
+        }
+        let offset = node_ranges.start;
+        let (nodes, node_blocks) = file.blocks_for_cir_tree_node_ranges(
+            endianness,
+            node_offset,
+            chrom_ix,
+            &ranges[node_ranges],
+        )?;
+        let shift = |r: Range<usize>| r.start + offset..r.end + offset;
+        remaining_childblocks.extend(nodes.into_iter().map(|(n, r)| (n, shift(r))));
+        blocks.extend(node_blocks.into_iter().map(|(b, r)| (b, shift(r))));
+    }
+
+    // A block could be reachable through multiple nodes
+    blocks.sort_by_key(|(b, r)| (b.offset, r.start));
+    blocks.dedup_by(|next, prev| {
+        if next.0 != prev.0 {
+            return false;
+        }
+        prev.1.end = prev.1.end.max(next.1.end);
+        true
+    });
+    Ok(blocks)
+}
+
+/// Resolves the chromosome names of `regions` (using `aliases`, if there are
+/// any) to their ids in the file.
+pub(crate) fn region_chrom_ids<S: AsRef<str>>(
+    info: &BBIFileInfo,
+    aliases: Option<&ChromAliases>,
+    regions: impl IntoIterator<Item = (S, u32, u32)>,
+) -> Result<Vec<(u32, u32, u32)>, ChromIdNotFound> {
+    regions
+        .into_iter()
+        .map(|(chrom, start, end)| {
+            let chrom = resolve_chrom(aliases, &info.chrom_info, chrom.as_ref());
+            Ok((info.chrom_id(chrom)?, start, end))
+        })
+        .collect()
+}
+
+/// Gets the items in each of `regions` (chrom id, start, end). Regions are
+/// grouped by chromosome, and the cir tree at `at` is searched once for each
+/// chromosome. Every overlapping block is read once and then decoded with
+/// `decode` for each region it overlaps. The results are in the same order as
+/// `regions`.
+pub(crate) fn get_intervals_batched<R: BBIFileRead, T>(
+    read: &mut R,
+    info: &BBIFileInfo,
+    at: u64,
+    regions: &[(u32, u32, u32)],
+    max_gap: u64,
+    mut decode: impl FnMut(&[u8], u32, u32, u32) -> Result<Vec<T>, BlockError>,
+) -> Result<Vec<Vec<T>>, BBIReadError> {
+    let endianness = info.header.endianness;
+    let mut results: Vec<Vec<T>> = regions.iter().map(|_| vec![]).collect();
+
+    let mut order: Vec<usize> = (0..regions.len()).collect();
+    order.sort_by_key(|&i| regions[i]);
+    let mut order = &order[..];
+    while let Some(&first) = order.first() {
+        let chrom = regions[first].0;
+        let chrom_len = order.partition_point(|&i| regions[i].0 == chrom);
+        let (chrom_regions, rest) = order.split_at(chrom_len);
+        order = rest;
+
+        // Merge overlapping regions, keeping track of the regions in each
+        let mut ranges: Vec<(u32, u32)> = vec![];
+        let mut range_regions: Vec<Vec<usize>> = vec![];
+        for &i in chrom_regions {
+            let (_, start, end) = regions[i];
+            match ranges.last_mut() {
+                Some(last) if start <= last.1 => {
+                    last.1 = last.1.max(end);
+                    range_regions.last_mut().unwrap().push(i);
+                }
+                _ => {
+                    ranges.push((start, end));
+                    range_regions.push(vec![i]);
+                }
+            }
+        }
+
+        let blocks = search_cir_tree_ranges(endianness, read, at, chrom, &ranges)?;
+        let mut block_data = BlockDataIter::new(blocks.iter().map(|(b, _)| *b), max_gap);
+        for (_, block_ranges) in blocks.iter() {
+            let (block, data) = match block_data.next(read, info) {
+                Some(data) => data?,
+                None => break,
+            };
+            for range in block_ranges.clone() {
+                for &i in &range_regions[range] {
+                    let (_, start, end) = regions[i];
+                    let items = decode(&data, chrom, start, end)
+                        .map_err(|e| BBIReadError::InvalidBlock(block.offset, e))?;
+                    results[i].extend(items);
+                }
+            }
+        }
+    }
+
+    Ok(results)
+}
+
 /// Gets the data (uncompressed, if applicable) from a given block
 fn read_block_data<R: SeekableRead>(
     info: &BBIFileInfo,
     read: &mut R,
     block: &Block,
-) -> io::Result<Vec<u8>> {
-    let uncompress_buf_size = info.header.uncompress_buf_size as usize;
-
+) -> io::Result<Bytes> {
     read.seek(SeekFrom::Start(block.offset))?;
 
-    let mut raw_data = vec![0u8; block.size as usize];
-    read.read_exact(&mut raw_data)?;
-    let block_data: Vec<u8> = if uncompress_buf_size > 0 {
-        let mut decompressor = Decompressor::new();
-        let mut outbuf = vec![0; uncompress_buf_size];
-        let decompressed = decompressor
-            .zlib_decompress(&raw_data, &mut outbuf)
-            .unwrap();
-        outbuf.truncate(decompressed);
-        outbuf
+    let raw_data = read_vec(read, block.size)?.into();
+    decompress_block_data(&info.header, raw_data).map_err(|e| e.into_io_error(block.offset))
+}
+
+/// Decompresses (if applicable) the raw data of a block. The data of an
+/// uncompressed block is returned as is, without copying.
+pub(crate) fn decompress_block_data(
+    header: &BBIHeader,
+    raw_data: Bytes,
+) -> Result<Bytes, BlockError> {
+    if header.uncompress_buf_size > 0 {
+        decompress(header, &raw_data).map(Bytes::from)
     } else {
-        raw_data
-    };
+        Ok(raw_data)
+    }
+}
 
-    Ok(block_data)
+/// Decompresses the raw data of a block in a compressed file. The buffer for
+/// the decompressed data is allocated up front, so a corrupt (huge)
+/// `uncompress_buf_size` is an error rather than an allocation failure.
+fn decompress(header: &BBIHeader, raw_data: &[u8]) -> Result<Vec<u8>, BlockError> {
+    if header.uncompress_buf_size > MAX_UNCOMPRESS_BUF_SIZE {
+        return Err(BlockError::UncompressBufTooLarge(
+            header.uncompress_buf_size,
+        ));
+    }
+    let mut decompressor = Decompressor::new();
+    let mut outbuf = vec![0; header.uncompress_buf_size as usize];
+    let decompressed = decompressor
+        .zlib_decompress(raw_data, &mut outbuf)
+        .map_err(|e| BlockError::Decompression(e.to_string()))?;
+    outbuf.truncate(decompressed);
+    Ok(outbuf)
 }
 
-pub(crate) fn get_zoom_block_values<B: BBIRead>(
-    bbifile: &mut B,
-    block: Block,
-    known_offset: &mut u64,
+/// Decodes the zoom records of the given chromosome overlapping `start` and
+/// `end` from the (decompressed) data of a zoom block
+pub(crate) fn decode_zoom_block(
+    endianness: Endianness,
+    data: &[u8],
     chrom: u32,
     start: u32,
     end: u32,
-) -> Result<std::vec::IntoIter<ZoomRecord>, BBIReadError> {
-    let (read, info) = bbifile.reader_and_info();
-    let data = read.get_block_data(info, &block)?;
+) -> Result<Vec<ZoomRecord>, BlockError> {
     let mut bytes = BytesMut::with_capacity(data.len());
-    bytes.extend_from_slice(&data);
+    bytes.extend_from_slice(data);
 
     let len = bytes.len();
-    assert_eq!(len % (4 * 8), 0);
+    if !len.is_multiple_of(4 * 8) {
+        return Err(BlockError::InvalidZoomBlockLength(len));
+    }
     let itemcount = len / (4 * 8);
     let mut records = Vec::with_capacity(itemcount);
 
-    let endianness = bbifile.info().header.endianness;
-
     match endianness {
         Endianness::Big => {
             for _ in 0..itemcount {
@@ -1395,8 +2460,7 @@ pub(crate) fn get_zoom_block_values<B: BBIRead>(
         }
     }
 
-    *known_offset = block.offset + block.size;
-    Ok(records.into_iter())
+    Ok(records)
 }
 
 pub(crate) struct ZoomIntervalIter<I, R, B>
@@ -1407,8 +2471,7 @@ where
 {
     _r: std::marker::PhantomData<R>,
     bbifile: B,
-    known_offset: u64,
-    blocks: I,
+    blocks: BlockDataIter<I>,
     vals: Option<std::vec::IntoIter<ZoomRecord>>,
     chrom: u32,
     start: u32,
@@ -1421,12 +2484,18 @@ where
     R: BBIRead,
     B: BorrowMut<R>,
 {
-    pub fn new(bbifile: B, blocks: I, chrom: u32, start: u32, end: u32) -> Self {
+    pub fn new(
+        bbifile: B,
+        blocks: I,
+        max_block_gap: u64,
+        chrom: u32,
+        start: u32,
+        end: u32,
+    ) -> Self {
         ZoomIntervalIter {
             _r: std::marker::PhantomData,
             bbifile,
-            known_offset: 0,
-            blocks,
+            blocks: BlockDataIter::new(blocks, max_block_gap),
             vals: None,
             chrom,
             start,
@@ -1455,25 +2524,158 @@ where
                     }
                 },
                 None => {
-                    let current_block = self.blocks.next()?;
-                    let file = self.bbifile.borrow_mut();
-                    match get_zoom_block_values(
-                        file,
-                        current_block,
-                        &mut self.known_offset,
-                        self.chrom,
-                        self.start,
-                        self.end,
-                    ) {
-                        Ok(vals) => {
-                            self.vals = Some(vals);
-                        }
+                    let (read, info) = self.bbifile.borrow_mut().reader_and_info();
+                    let (block, data) = match self.blocks.next(read, info)? {
+                        Ok(data) => data,
                         Err(e) => {
-                            return Some(Err(e));
+                            return Some(Err(e.into()));
                         }
+                    };
+                    let endianness = info.header.endianness;
+                    match decode_zoom_block(endianness, &data, self.chrom, self.start, self.end) {
+                        Ok(vals) => self.vals = Some(vals.into_iter()),
+                        Err(e) => return Some(Err(BBIReadError::InvalidBlock(block.offset, e))),
                     }
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coalesced_runs() {
+        let block = |offset, size| Block { offset, size };
+        let blocks = [
+            block(0, 100),
+            block(100, 50),
+            block(200, 10),
+            block(5000, 10),
+            block(5000, 10),
+            block(10, 10),
+        ];
+        let runs = coalesced_runs(&blocks, 50);
+        assert_eq!(
+            runs,
+            [&blocks[0..3], &blocks[3..4], &blocks[4..5], &blocks[5..6]]
+        );
+        let runs = coalesced_runs(&blocks, 0);
+        assert_eq!(
+            runs,
+            [
+                &blocks[0..2],
+                &blocks[2..3],
+                &blocks[3..4],
+                &blocks[4..5],
+                &blocks[5..6]
+            ]
+        );
+        let runs = coalesced_runs(&blocks, DEFAULT_MAX_BLOCK_GAP);
+        assert_eq!(runs, [&blocks[0..4], &blocks[4..5], &blocks[5..6]]);
+
+        let large = [
+            block(0, MAX_COALESCED_READ_SIZE),
+            block(MAX_COALESCED_READ_SIZE, 1),
+        ];
+        assert_eq!(coalesced_runs(&large, 0).len(), 2);
+        assert!(coalesced_runs(&[], 0).is_empty());
+    }
+
+    #[test]
+    fn test_non_leaf_node_at_eof() -> io::Result<()> {
+        // A non-leaf node with two items, at the very end of the file
+        let mut node = vec![0u8, 0, 2, 0];
+        for (start, end, offset) in [(0u32, 10u32, 100u64), (10, 20, 200)] {
+            node.extend_from_slice(&0u32.to_le_bytes());
+            node.extend_from_slice(&start.to_le_bytes());
+            node.extend_from_slice(&0u32.to_le_bytes());
+            node.extend_from_slice(&end.to_le_bytes());
+            node.extend_from_slice(&offset.to_le_bytes());
+        }
+        let iter = read_node(&mut Cursor::new(node), 0, Endianness::Little)?;
+        let CirTreeNodeIterator::NonLeaf(items) = iter else {
+            panic!("Expected a non-leaf node");
+        };
+        let items: Vec<_> = items
+            .map(|item| (item.start_base, item.end_base, item.node_offset))
+            .collect();
+        assert_eq!(items, [(0, 10, 100), (10, 20, 200)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_in_memory_read_is_zero_copy() -> io::Result<()> {
+        // A leaf node with a single item, followed by its (uncompressed) block
+        let mut file = vec![1u8, 0, 1, 0];
+        for v in [0u32, 0, 0, 10] {
+            file.extend_from_slice(&v.to_le_bytes());
+        }
+        file.extend_from_slice(&36u64.to_le_bytes());
+        file.extend_from_slice(&8u64.to_le_bytes());
+        file.extend_from_slice(b"blockdat");
+        let bytes = Bytes::from(file);
+
+        let info = BBIFileInfo {
+            filetype: BBIFile::BigWig,
+            header: BBIHeader {
+                endianness: Endianness::Little,
+                version: 4,
+                field_count: 0,
+                defined_field_count: 0,
+                zoom_levels: 0,
+                chromosome_tree_offset: 0,
+                full_data_offset: 0,
+                full_index_offset: 0,
+                full_index_tree_offset: None,
+                auto_sql_offset: 0,
+                total_summary_offset: 0,
+                uncompress_buf_size: 0,
+                extension_offset: 0,
+            },
+            zoom_headers: vec![],
+            chrom_info: vec![],
+        };
+        let mut read = InMemoryBBIFileRead::new(bytes.clone());
+        let (nodes, blocks) = read.blocks_for_cir_tree_node(Endianness::Little, 0, 0, 0, 10)?;
+        assert!(nodes.is_empty());
+        assert_eq!(blocks.as_slice(), [Block { offset: 36, size: 8 }]);
+
+        let data = read.get_block_data(&info, &blocks[0])?;
+        assert_eq!(&data[..], b"blockdat");
+        assert_eq!(data.as_ptr(), bytes[36..].as_ptr());
+        let raw_data = read.get_raw_blocks_data(&blocks, DEFAULT_MAX_BLOCK_GAP)?;
+        assert_eq!(raw_data[0].as_ptr(), bytes[36..].as_ptr());
+
+        // Nodes and blocks past the end of the file are errors
+        let mut read = InMemoryBBIFileRead::new(bytes.slice(..40));
+        assert!(read.get_block_data(&info, &blocks[0]).is_err());
+        let mut read = InMemoryBBIFileRead::new(bytes.slice(..20));
+        assert!(read
+            .blocks_for_cir_tree_node(Endianness::Little, 0, 0, 0, 10)
+            .is_err());
+        assert!(read
+            .blocks_for_cir_tree_node(Endianness::Little, 100, 0, 0, 10)
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_overlapping_ranges() {
+        let ranges = [(10, 20), (30, 40), (50, 60)];
+        assert_eq!(overlapping_ranges(1, &ranges, 1, 0, 1, 5), 0..0);
+        assert_eq!(overlapping_ranges(1, &ranges, 1, 0, 1, 10), 0..1);
+        assert_eq!(overlapping_ranges(1, &ranges, 1, 20, 1, 30), 0..2);
+        assert_eq!(overlapping_ranges(1, &ranges, 1, 21, 1, 29), 1..1);
+        assert_eq!(overlapping_ranges(1, &ranges, 1, 35, 2, 0), 1..3);
+        assert_eq!(overlapping_ranges(1, &ranges, 0, 100, 1, 45), 0..2);
+        assert_eq!(overlapping_ranges(1, &ranges, 0, 0, 2, 0), 0..3);
+        assert_eq!(overlapping_ranges(1, &ranges, 2, 0, 2, 100), 0..0);
+        assert_eq!(overlapping_ranges(1, &ranges, 0, 0, 0, 100), 0..0);
+        assert_eq!(overlapping_ranges(1, &[], 1, 0, 1, 100), 0..0);
+    }
+}
@@ -68,8 +68,26 @@ pub enum InputSortType {
     ALL,
     /// Start values within a chromosome must be sorted, but chromosomes may be out of order
     START,
-    // TODO
-    //NONE,
+    /// Neither chromosomes nor start values are sorted. The data source must
+    /// sort the values before they are written (see
+    /// [`ExternalSortStream`][crate::beddata::ExternalSortStream]).
+    NONE,
+}
+
+/// The encoding used for the data sections of a bigWig
+#[derive(Copy, Clone, Debug)]
+pub enum SectionEncoding {
+    /// Use the most compact of the encodings below that can represent each section
+    Auto,
+    /// Store the start, end, and value of every item (the default)
+    BedGraph,
+    /// Store only the value of every item. Only used for sections where all
+    /// items have the same span and are evenly spaced; others are written as
+    /// bedGraph.
+    FixedStep,
+    /// Store the start and value of every item. Only used for sections where
+    /// all items have the same span; others are written as bedGraph.
+    VarStep,
 }
 
 /// The default block size used when writing a bbi file
@@ -91,6 +109,8 @@ pub struct BBIWriteOptions {
     pub input_sort_type: InputSortType,
     pub channel_size: usize,
     pub inmemory: bool,
+    /// The encoding of data sections. Only applies to bigWigs.
+    pub section_encoding: SectionEncoding,
 }
 
 impl Default for BBIWriteOptions {
@@ -105,6 +125,7 @@ impl Default for BBIWriteOptions {
             input_sort_type: InputSortType::ALL,
             channel_size: 100,
             inmemory: false,
+            section_encoding: SectionEncoding::BedGraph,
         }
     }
 }
@@ -255,6 +276,89 @@ pub(crate) fn write_chrom_tree<W: Write + Seek + Send + 'static>(
     Ok(())
 }
 
+/// Writes a complete (possibly multi-level) B+ tree with the same layout as
+/// the chrom tree. `items` must be sorted by key, each key must be at most
+/// `key_size` bytes, and `write_val` must write exactly `val_size` bytes.
+pub(crate) fn write_bplustree<W: Write + Seek, T>(
+    file: &mut W,
+    block_size: u32,
+    key_size: u32,
+    val_size: u32,
+    items: &[T],
+    key: impl Fn(&T) -> &[u8],
+    write_val: impl Fn(&mut W, &T) -> io::Result<()>,
+) -> io::Result<()> {
+    let item_count = items.len() as u64;
+    let block_size = u64::from(block_size).min(item_count).max(1);
+
+    file.write_u32::<NativeEndian>(CHROM_TREE_MAGIC)?;
+    file.write_u32::<NativeEndian>(block_size as u32)?;
+    file.write_u32::<NativeEndian>(key_size)?;
+    file.write_u32::<NativeEndian>(val_size)?;
+    file.write_u64::<NativeEndian>(item_count)?;
+    file.write_u64::<NativeEndian>(0)?; // Reserved
+
+    let mut levels = 1;
+    let mut level_items = item_count;
+    while level_items > block_size {
+        level_items = level_items.div_ceil(block_size);
+        levels += 1;
+    }
+
+    let write_key = |file: &mut W, k: &[u8]| -> io::Result<()> {
+        file.write_all(k)?;
+        file.write_all(&vec![0u8; key_size as usize - k.len()])
+    };
+
+    let index_block_size = 4 + block_size * (u64::from(key_size) + 8);
+    let leaf_block_size = 4 + block_size * u64::from(key_size + val_size);
+
+    // Every block is padded to `block_size` slots, so the offset of each
+    // child can be calculated up front from the number of blocks per level.
+    let mut level_start = file.tell()?;
+    for level in (1..levels).rev() {
+        let slot_size = block_size.pow(level);
+        let node_size = slot_size * block_size;
+        let node_count = item_count.div_ceil(node_size);
+        let next_level_block_size = if level == 1 {
+            leaf_block_size
+        } else {
+            index_block_size
+        };
+        level_start += node_count * index_block_size;
+        let mut next_child = level_start;
+        for node_start in (0..item_count).step_by(node_size as usize) {
+            let count = block_size.min((item_count - node_start).div_ceil(slot_size));
+            file.write_u8(0)?;
+            file.write_u8(0)?;
+            file.write_u16::<NativeEndian>(count as u16)?;
+            for i in 0..count {
+                write_key(file, key(&items[(node_start + i * slot_size) as usize]))?;
+                file.write_u64::<NativeEndian>(next_child)?;
+                next_child += next_level_block_size;
+            }
+            for _ in count..block_size {
+                file.write_all(&vec![0u8; key_size as usize + 8])?;
+            }
+        }
+    }
+
+    for chunk in items.chunks(block_size as usize) {
+        file.write_u8(1)?;
+        file.write_u8(0)?;
+        file.write_u16::<NativeEndian>(chunk.len() as u16)?;
+        for item in chunk {
+            write_key(file, key(item))?;
+            write_val(file, item)?;
+        }
+        for _ in chunk.len() as u64..block_size {
+            file.write_all(&vec![0u8; (key_size + val_size) as usize])?;
+        }
+    }
+
+    Ok(())
+}
+
 pub(crate) async fn encode_zoom_section(
     compress: bool,
     items_in_section: Vec<ZoomRecord>,
@@ -5,20 +5,28 @@
 //! of order chromosomes. `BedParserParallelStreamingIterator`, on the other
 //! hand, is more complicated wrapper and will queue up to 4 extra chromosomes
 //! to be processed concurrently.
+//!
+//! Both require their input to be sorted by start within each chromosome.
+//! `ExternalSortStream` can be used to sort unsorted input before it is
+//! passed to `BedParserStreamingIterator`.
 
-use std::collections::VecDeque;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
 use std::fs::File;
-use std::io::{BufReader, Read};
+use std::io::{self, BufReader, BufWriter, Read};
 use std::path::PathBuf;
 
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use tokio::runtime::Runtime;
 
 use crate::bed::bedparser::{
     parse_bed, parse_bedgraph, BedFileStream, BedInfallibleIteratorStream, BedIteratorStream,
-    BedValueError, Parser, StreamingBedValues,
+    BedValueError, Parser, StreamingBedValues, WigFileStream,
 };
 use crate::utils::file_view::FileView;
 use crate::utils::streaming_linereader::StreamingLineReader;
+use crate::utils::tempfilebuffer::TempFileBuffer;
 use crate::{BBIDataProcessor, BBIDataSource, BBIProcessError, BedEntry, ProcessDataError, Value};
 
 pub struct BedParserStreamingIterator<S: StreamingBedValues> {
@@ -59,6 +67,15 @@ impl<R: Read> BedParserStreamingIterator<BedFileStream<Value, BufReader<R>>> {
     }
 }
 
+impl<R: Read> BedParserStreamingIterator<WigFileStream<BufReader<R>>> {
+    pub fn from_wig_file(file: R, allow_out_of_order_chroms: bool) -> Self {
+        BedParserStreamingIterator::new(
+            WigFileStream::from_wig_file(file),
+            allow_out_of_order_chroms,
+        )
+    }
+}
+
 impl<
         V: Clone,
         E: Into<BedValueError>,
@@ -85,6 +102,20 @@ impl<V: Clone, C: Into<String> + for<'a> PartialEq<&'a str>, I: Iterator<Item =
     }
 }
 
+impl<S: StreamingBedValues> BedParserStreamingIterator<ExternalSortStream<S>>
+where
+    S::Value: Positioned + Serialize + DeserializeOwned,
+{
+    /// Sorts `bed_data` before processing, using an `ExternalSortStream` that
+    /// holds up to `DEFAULT_SORT_CHUNK_ITEMS` values in memory at once.
+    pub fn from_unsorted(bed_data: S, inmemory: bool) -> Self {
+        BedParserStreamingIterator::new(
+            ExternalSortStream::new(bed_data, DEFAULT_SORT_CHUNK_ITEMS, inmemory),
+            false,
+        )
+    }
+}
+
 impl<S: StreamingBedValues> BBIDataSource for BedParserStreamingIterator<S> {
     type Value = S::Value;
     type Error = BedValueError;
@@ -178,6 +209,196 @@ impl<S: StreamingBedValues> BBIDataSource for BedParserStreamingIterator<S> {
     }
 }
 
+/// A value that has a position on a chromosome, used to sort values.
+pub trait Positioned {
+    fn start(&self) -> u32;
+    fn end(&self) -> u32;
+}
+
+impl Positioned for Value {
+    fn start(&self) -> u32 {
+        self.start
+    }
+    fn end(&self) -> u32 {
+        self.end
+    }
+}
+
+impl Positioned for BedEntry {
+    fn start(&self) -> u32 {
+        self.start
+    }
+    fn end(&self) -> u32 {
+        self.end
+    }
+}
+
+/// The default number of values an `ExternalSortStream` sorts in memory before
+/// spilling them to a temporary file.
+pub const DEFAULT_SORT_CHUNK_ITEMS: usize = 1_000_000;
+
+enum SortedRun<V> {
+    InMemory(std::vec::IntoIter<(String, V)>),
+    Spilled {
+        reader: BufReader<Box<dyn Read + Send>>,
+        remaining: usize,
+    },
+}
+
+impl<V: DeserializeOwned> SortedRun<V> {
+    fn next(&mut self) -> Result<Option<(String, V)>, BedValueError> {
+        match self {
+            SortedRun::InMemory(iter) => Ok(iter.next()),
+            SortedRun::Spilled { remaining: 0, .. } => Ok(None),
+            SortedRun::Spilled { reader, remaining } => {
+                *remaining -= 1;
+                bincode::deserialize_from(reader)
+                    .map(Some)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e).into())
+            }
+        }
+    }
+}
+
+type RunKey = Reverse<(String, u32, u32, usize)>;
+
+enum SortState<S, V> {
+    Unsorted(S),
+    Merging {
+        runs: Vec<SortedRun<V>>,
+        /// The position of the next value of each run
+        heap: BinaryHeap<RunKey>,
+        /// The next value of each run
+        heads: Vec<Option<V>>,
+    },
+}
+
+/// Sorts the values of a `StreamingBedValues` by chromosome, then start, then
+/// end (the same order as `sort -k1,1 -k2,2n`), so that unsorted input can be
+/// written. Values are sorted in chunks of at most `chunk_items`, which (other
+/// than the last) are written to temporary files (or kept in memory, if
+/// `inmemory` is set) and then merged. Values that compare equal are kept in
+/// input order.
+///
+/// All input is read on the first call to `next`.
+pub struct ExternalSortStream<S: StreamingBedValues> {
+    state: SortState<S, S::Value>,
+    chunk_items: usize,
+    inmemory: bool,
+    chrom: String,
+}
+
+impl<S: StreamingBedValues> ExternalSortStream<S>
+where
+    S::Value: Positioned + Serialize + DeserializeOwned,
+{
+    pub fn new(bed_data: S, chunk_items: usize, inmemory: bool) -> Self {
+        ExternalSortStream {
+            state: SortState::Unsorted(bed_data),
+            chunk_items: chunk_items.max(1),
+            inmemory,
+            chrom: String::new(),
+        }
+    }
+}
+
+fn sort_chunk<V: Positioned>(chunk: &mut [(String, V)]) {
+    chunk.sort_by(|a, b| {
+        a.0.cmp(&b.0)
+            .then(a.1.start().cmp(&b.1.start()))
+            .then(a.1.end().cmp(&b.1.end()))
+    });
+}
+
+fn spill_chunk<V: Serialize>(
+    chunk: Vec<(String, V)>,
+    inmemory: bool,
+) -> Result<SortedRun<V>, BedValueError> {
+    let (buf, writer) = TempFileBuffer::<File>::new(inmemory);
+    let mut writer = BufWriter::new(writer);
+    for item in chunk.iter() {
+        bincode::serialize_into(&mut writer, item).map_err(io::Error::other)?;
+    }
+    drop(writer.into_inner().map_err(|e| e.into_error())?);
+    Ok(SortedRun::Spilled {
+        reader: BufReader::new(buf.into_reader()?),
+        remaining: chunk.len(),
+    })
+}
+
+fn sort_runs<S: StreamingBedValues>(
+    bed_data: &mut S,
+    chunk_items: usize,
+    inmemory: bool,
+) -> Result<Vec<SortedRun<S::Value>>, BedValueError>
+where
+    S::Value: Positioned + Serialize,
+{
+    let mut runs = vec![];
+    let mut chunk = Vec::with_capacity(chunk_items.min(DEFAULT_SORT_CHUNK_ITEMS));
+    while let Some(next) = bed_data.next() {
+        let (chrom, value) = next?;
+        chunk.push((chrom.to_string(), value));
+        if chunk.len() == chunk_items {
+            sort_chunk(&mut chunk);
+            runs.push(spill_chunk(std::mem::take(&mut chunk), inmemory)?);
+        }
+    }
+    sort_chunk(&mut chunk);
+    runs.push(SortedRun::InMemory(chunk.into_iter()));
+    Ok(runs)
+}
+
+impl<S: StreamingBedValues> StreamingBedValues for ExternalSortStream<S>
+where
+    S::Value: Positioned + Serialize + DeserializeOwned,
+{
+    type Value = S::Value;
+
+    fn next(&mut self) -> Option<Result<(&str, Self::Value), BedValueError>> {
+        if let SortState::Unsorted(bed_data) = &mut self.state {
+            let mut runs = match sort_runs(bed_data, self.chunk_items, self.inmemory) {
+                Ok(runs) => runs,
+                Err(e) => return Some(Err(e)),
+            };
+            let mut heap = BinaryHeap::with_capacity(runs.len());
+            let mut heads = Vec::with_capacity(runs.len());
+            for (i, run) in runs.iter_mut().enumerate() {
+                match run.next() {
+                    Ok(Some((chrom, value))) => {
+                        heap.push(Reverse((chrom, value.start(), value.end(), i)));
+                        heads.push(Some(value));
+                    }
+                    Ok(None) => heads.push(None),
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+            self.state = SortState::Merging { runs, heap, heads };
+        }
+        let SortState::Merging { runs, heap, heads } = &mut self.state else {
+            unreachable!();
+        };
+
+        let Reverse((chrom, _, _, i)) = heap.pop()?;
+        let value = heads[i].take().unwrap();
+        match runs[i].next() {
+            Ok(Some((next_chrom, next_value))) => {
+                heap.push(Reverse((
+                    next_chrom,
+                    next_value.start(),
+                    next_value.end(),
+                    i,
+                )));
+                heads[i] = Some(next_value);
+            }
+            Ok(None) => {}
+            Err(e) => return Some(Err(e)),
+        }
+        self.chrom = chrom;
+        Some(Ok((&self.chrom, value)))
+    }
+}
+
 pub struct BedParserParallelStreamingIterator<V> {
     allow_out_of_order_chroms: bool,
 
@@ -232,7 +453,14 @@ impl<V: Send + 'static> BBIDataSource for BedParserParallelStreamingIterator<V>
                         break;
                     }
                 };
-                next.map(|n| assert!(curr.1 != n.1));
+                if next.is_some_and(|n| curr.1 == n.1) {
+                    return Err(BBIProcessError::SourceError(BedValueError::InvalidInput(
+                        format!(
+                            "Input bedGraph has multiple separate runs of chromosome {}. Sort with `sort -k1,1 -k2,2n`.",
+                            curr.1
+                        ),
+                    )));
+                }
                 // TODO: test this correctly fails
                 if !self.allow_out_of_order_chroms && next.map(|n| curr.1 > n.1).unwrap_or(false) {
                     return Err(BBIProcessError::SourceError(BedValueError::InvalidInput(
@@ -357,4 +585,45 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_external_sort() -> Result<(), BedValueError> {
+        let chroms = ["chr2", "chr1", "chr10", "chr1"];
+        let entries: Vec<_> = (0..1000u32)
+            .map(|i| {
+                let chrom = chroms[(i % 4) as usize];
+                let start = (i * 7919) % 503;
+                let entry = BedEntry {
+                    start,
+                    end: start + 1 + i % 3,
+                    rest: format!("{}", i),
+                };
+                (chrom, entry)
+            })
+            .collect();
+        let mut expected = entries.clone();
+        expected.sort_by_key(|(chrom, entry)| (*chrom, entry.start, entry.end));
+
+        // Sort in one chunk, and in many chunks spilled to files or memory
+        for (chunk_items, inmemory) in [(2000, false), (64, false), (64, true), (1, false)] {
+            let iter = entries.clone().into_iter();
+            let mut stream = ExternalSortStream::new(
+                BedInfallibleIteratorStream { iter, curr: None },
+                chunk_items,
+                inmemory,
+            );
+            let mut sorted = vec![];
+            while let Some(next) = stream.next() {
+                let (chrom, entry) = next?;
+                sorted.push((chrom.to_string(), entry));
+            }
+            let sorted: Vec<_> = sorted
+                .iter()
+                .map(|(c, e)| (c.as_str(), e.clone()))
+                .collect();
+            assert_eq!(sorted, expected);
+        }
+
+        Ok(())
+    }
 }
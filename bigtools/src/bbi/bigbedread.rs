@@ -2,22 +2,35 @@ use std::borrow::BorrowMut;
 use std::fs::File;
 use std::io::{self, BufRead, BufReader, Seek, SeekFrom};
 use std::path::Path;
+use std::sync::Arc;
 use std::vec::Vec;
 
 use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
 use byteordered::ByteOrdered;
-use bytes::{Buf, BytesMut};
+use bytes::{Buf, Bytes, BytesMut};
 use itertools::Itertools;
 use thiserror::Error;
+use tokio::runtime::Runtime;
 
+use crate::bbi::chromalias::{resolve_chrom, ChromAliases};
+use crate::bbi::summarize::{
+    summarize_entries, summarize_entry_zoom_records, summary_zoom_level, zoom_read_error,
+    SummaryKind,
+};
 use crate::bbi::{BBIFile, BedEntry, ZoomRecord};
 use crate::bbiread::{
-    read_info, BBIFileInfo, BBIFileReadInfoError, BBIRead, BBIReadError, Block, ChromInfo,
-    ZoomIntervalIter,
+    decode_zoom_block, get_intervals_batched, read_info, region_chrom_ids, search_bplustree,
+    BBIFileInfo, BBIFileReadInfoError, BBIRead, BBIReadError, Block, BlockDataIter, BlockError,
+    ChromInfo, InMemoryBBIFileRead, ParallelBlockIter, ZoomIntervalIter, DEFAULT_MAX_BLOCK_GAP,
 };
+use crate::bed::autosql::parse::parse_autosql;
+use crate::bed::autosql::typed::{BedSchema, TypedBedEntry};
 use crate::internal::BBIReadInternal;
 use crate::utils::reopen::{Reopen, ReopenableFile, SeekableRead};
-use crate::{search_cir_tree, BBIFileRead, CachedBBIFileRead, Summary, ZoomIntervalError};
+use crate::{
+    search_cir_tree, BBIFileRead, CachedBBIFileRead, Summary, ZoomIntervalError,
+    DEFAULT_CACHE_CAPACITY,
+};
 
 struct IntervalIter<I, R, B>
 where
@@ -27,8 +40,7 @@ where
 {
     r: std::marker::PhantomData<R>,
     bigbed: B,
-    known_offset: u64,
-    blocks: I,
+    blocks: BlockDataIter<I>,
     vals: Option<std::vec::IntoIter<BedEntry>>,
     expected_chrom: u32,
     start: u32,
@@ -55,21 +67,25 @@ where
                     }
                 },
                 None => {
-                    // TODO: Could minimize this by chunking block reads
-                    let current_block = self.blocks.next()?;
-                    match get_block_entries(
-                        self.bigbed.borrow_mut(),
-                        current_block,
-                        &mut self.known_offset,
+                    let bigbed = self.bigbed.borrow_mut();
+                    let (block, data) = match self.blocks.next(&mut bigbed.read, &bigbed.info)? {
+                        Ok(data) => data,
+                        Err(e) => {
+                            return Some(Err(e.into()));
+                        }
+                    };
+                    match decode_block_entries(
+                        bigbed.info.header.endianness,
+                        &data,
                         self.expected_chrom,
                         self.start,
                         self.end,
                     ) {
                         Ok(vals) => {
-                            self.vals = Some(vals);
+                            self.vals = Some(vals.into_iter());
                         }
                         Err(e) => {
-                            return Some(Err(e));
+                            return Some(Err(BBIReadError::InvalidBlock(block.offset, e)));
                         }
                     }
                 }
@@ -99,10 +115,27 @@ impl From<BBIFileReadInfoError> for BigBedReadOpenError {
     }
 }
 
+/// Possible errors encountered when searching an extra index of a bigBed
+#[derive(Error, Debug)]
+pub enum ExtraIndexSearchError {
+    #[error("There is no extra index for the field: {}", .0)]
+    IndexNotFound(String),
+    #[error("{}", .0)]
+    BBIReadError(#[from] BBIReadError),
+}
+
+impl From<io::Error> for ExtraIndexSearchError {
+    fn from(e: io::Error) -> Self {
+        ExtraIndexSearchError::BBIReadError(e.into())
+    }
+}
+
 /// The struct used to read a bigBed file
 pub struct BigBedRead<R> {
     pub(super) info: BBIFileInfo,
     pub(super) read: R,
+    pub(super) chrom_aliases: Option<Arc<ChromAliases>>,
+    pub(super) max_block_gap: u64,
 }
 
 impl<R: Reopen> Reopen for BigBedRead<R> {
@@ -110,6 +143,8 @@ impl<R: Reopen> Reopen for BigBedRead<R> {
         Ok(BigBedRead {
             info: self.info.clone(),
             read: self.read.reopen()?,
+            chrom_aliases: self.chrom_aliases.clone(),
+            max_block_gap: self.max_block_gap,
         })
     }
 }
@@ -146,6 +181,32 @@ impl<R> BigBedRead<R> {
     pub fn chroms(&self) -> &[ChromInfo] {
         &self.info.chrom_info
     }
+
+    /// Sets the aliases used to resolve chromosome names in queries. With
+    /// aliases set, any alias of a chromosome can be used in place of the
+    /// name stored in the file.
+    pub fn with_chrom_aliases(self, chrom_aliases: Arc<ChromAliases>) -> Self {
+        BigBedRead {
+            chrom_aliases: Some(chrom_aliases),
+            ..self
+        }
+    }
+
+    /// Gets the aliases used to resolve chromosome names, if any
+    pub fn chrom_aliases(&self) -> Option<&ChromAliases> {
+        self.chrom_aliases.as_deref()
+    }
+
+    /// Sets the maximum gap (in bytes) between blocks for them to be read
+    /// together when querying data. Larger gaps mean fewer reads (or, for
+    /// remote files, fewer requests), at the cost of reading unneeded data.
+    /// Defaults to `DEFAULT_MAX_BLOCK_GAP`.
+    pub fn with_max_block_gap(self, max_block_gap: u64) -> Self {
+        BigBedRead {
+            max_block_gap,
+            ..self
+        }
+    }
 }
 
 impl BigBedRead<ReopenableFile> {
@@ -163,6 +224,27 @@ impl BigBedRead<ReopenableFile> {
     }
 }
 
+impl BigBedRead<InMemoryBBIFileRead> {
+    /// Opens a new `BigBedRead` from a bigBed that is entirely in memory.
+    pub fn from_bytes(bytes: Bytes) -> Result<Self, BigBedReadOpenError> {
+        BigBedRead::open(InMemoryBBIFileRead::new(bytes))
+    }
+
+    /// Opens a new `BigBedRead` from a given path by memory-mapping the file.
+    /// Blocks and cir tree nodes are read directly from the mapped memory
+    /// (see `InMemoryBBIFileRead`).
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while this `BigBedRead`
+    /// (or any `reopen`ed copy) is alive.
+    #[cfg(feature = "mmap")]
+    pub unsafe fn open_mmap(path: impl AsRef<Path>) -> Result<Self, BigBedReadOpenError> {
+        let bytes = crate::bbiread::mmap_bytes(path)?;
+        BigBedRead::open(InMemoryBBIFileRead::new(bytes))
+    }
+}
+
 impl<R> BigBedRead<R>
 where
     R: SeekableRead,
@@ -170,10 +252,18 @@ where
     /// Converts this `BigBedRead`` to where the `BBIFileRead` caches index
     /// access and block data
     pub fn cached(self) -> BigBedRead<CachedBBIFileRead<R>> {
-        let read = CachedBBIFileRead::new(self.read);
+        self.cached_with_capacity(DEFAULT_CACHE_CAPACITY)
+    }
+
+    /// Like `cached`, but caches at most `capacity` bytes of index and block
+    /// data, evicting the least-recently used data first.
+    pub fn cached_with_capacity(self, capacity: usize) -> BigBedRead<CachedBBIFileRead<R>> {
+        let read = CachedBBIFileRead::with_capacity(self.read, capacity);
         BigBedRead {
             read,
             info: self.info,
+            chrom_aliases: self.chrom_aliases,
+            max_block_gap: self.max_block_gap,
         }
     }
 }
@@ -187,7 +277,12 @@ impl<R: BBIFileRead> BigBedRead<R> {
             _ => return Err(BigBedReadOpenError::NotABigBed),
         }
 
-        Ok(BigBedRead { info, read })
+        Ok(BigBedRead {
+            info,
+            read,
+            chrom_aliases: None,
+            max_block_gap: DEFAULT_MAX_BLOCK_GAP,
+        })
     }
 
     /// Reads the autosql from this bigBed
@@ -223,6 +318,146 @@ impl<R: BBIFileRead> BigBedRead<R> {
         &self.read
     }
 
+    /// Reads the extra index list, returning the field id (column number)
+    /// and B+ tree offset of each extra index.
+    fn extra_index_list(&mut self) -> Result<Vec<(u16, u64)>, BBIReadError> {
+        let header = self.info.header;
+        if header.extension_offset == 0 {
+            return Ok(vec![]);
+        }
+        let reader = self.reader().raw_reader();
+        let mut reader = ByteOrdered::runtime(BufReader::new(reader), header.endianness);
+        reader.seek(SeekFrom::Start(header.extension_offset))?;
+        let _extension_size = reader.read_u16()?;
+        let count = reader.read_u16()?;
+        let index_list_offset = reader.read_u64()?;
+
+        reader.seek(SeekFrom::Start(index_list_offset))?;
+        let mut indices = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let _index_type = reader.read_u16()?;
+            let field_count = reader.read_u16()?;
+            let tree_offset = reader.read_u64()?;
+            let _reserved = reader.read_u32()?;
+            let mut field_ids = Vec::with_capacity(field_count as usize);
+            for _ in 0..field_count {
+                field_ids.push(reader.read_u16()?);
+                let _reserved = reader.read_u16()?;
+            }
+            // Only single-field indices are written (or supported by kent tools)
+            if let [field_id] = field_ids[..] {
+                indices.push((field_id, tree_offset));
+            }
+        }
+        Ok(indices)
+    }
+
+    fn autosql_field_names(&mut self) -> Result<Vec<String>, BBIReadError> {
+        let autosql = self
+            .autosql()?
+            .unwrap_or_else(|| crate::bed::autosql::BED3.to_string());
+        let mut declarations = parse_autosql(&autosql)
+            .map_err(|_| BBIReadError::InvalidFile("Invalid autosql".to_owned()))?;
+        Ok(declarations
+            .pop()
+            .map(|decl| decl.fields.into_iter().map(|f| f.name).collect())
+            .unwrap_or_default())
+    }
+
+    /// Returns the names of the fields that have an extra index, which can
+    /// be searched with `search_extra_index`.
+    pub fn extra_indices(&mut self) -> Result<Vec<String>, BBIReadError> {
+        let indices = self.extra_index_list()?;
+        if indices.is_empty() {
+            return Ok(vec![]);
+        }
+        let field_names = self.autosql_field_names()?;
+        indices
+            .into_iter()
+            .map(|(field_id, _)| {
+                field_names.get(field_id as usize).cloned().ok_or_else(|| {
+                    BBIReadError::InvalidFile(format!(
+                        "Extra index field ({}) is not in the autosql.",
+                        field_id
+                    ))
+                })
+            })
+            .collect()
+    }
+
+    /// Searches the extra index for `field` for all items where that field
+    /// equals `key`. Returns the matching entries along with their chromosome.
+    pub fn search_extra_index(
+        &mut self,
+        field: &str,
+        key: &str,
+    ) -> Result<Vec<(String, BedEntry)>, ExtraIndexSearchError> {
+        let indices = self.extra_index_list()?;
+        let field_id = self
+            .autosql_field_names()?
+            .iter()
+            .position(|name| name == field);
+        let tree_offset = field_id.and_then(|field_id| {
+            indices
+                .iter()
+                .find(|(id, _)| *id as usize == field_id)
+                .map(|(_, offset)| *offset)
+        });
+        let (Some(field_id), Some(tree_offset)) = (field_id, tree_offset) else {
+            return Err(ExtraIndexSearchError::IndexNotFound(field.to_owned()));
+        };
+
+        let endianness = self.info.header.endianness;
+        let values = search_bplustree(
+            self.read.raw_reader(),
+            endianness,
+            tree_offset,
+            key.as_bytes(),
+        )?;
+        let mut blocks: Vec<Block> = values
+            .into_iter()
+            .map(|value| {
+                let mut value = ByteOrdered::runtime(&value[..], endianness);
+                Ok(Block {
+                    offset: value.read_u64()?,
+                    size: value.read_u64()?,
+                })
+            })
+            .collect::<io::Result<_>>()?;
+        blocks.sort_by_key(|b| b.offset);
+        blocks.dedup();
+
+        let mut entries = vec![];
+        let blocks_data = self
+            .read
+            .get_blocks_data(&self.info, &blocks, self.max_block_gap)?;
+        for (block, data) in blocks.iter().zip(blocks_data) {
+            let mut bytes = BytesMut::from(&data[..]);
+            while let Some((chrom_id, entry)) = read_bed_entry(&mut bytes, endianness)
+                .map_err(|e| BBIReadError::InvalidBlock(block.offset, e))?
+            {
+                let chrom = self
+                    .info
+                    .chrom_info
+                    .iter()
+                    .find(|c| c.id == chrom_id)
+                    .ok_or_else(|| {
+                        BBIReadError::InvalidFile(format!("Unknown chrom id: {}", chrom_id))
+                    })?;
+                let matches = match field_id {
+                    0 => chrom.name == key,
+                    1 => entry.start.to_string() == key,
+                    2 => entry.end.to_string() == key,
+                    _ => entry.rest.split('\t').nth(field_id - 3) == Some(key),
+                };
+                if matches {
+                    entries.push((chrom.name.clone(), entry));
+                }
+            }
+        }
+        Ok(entries)
+    }
+
     /// Returns the summary data from bigBed
     ///
     /// Note: For version 1 of bigBeds, there is no total summary. In that
@@ -269,6 +504,11 @@ impl<R: BBIFileRead> BigBedRead<R> {
         end: u32,
     ) -> Result<impl Iterator<Item = Result<BedEntry, BBIReadError>> + 'a, BBIReadError> {
         let cir_tree = self.full_data_cir_tree()?;
+        let chrom_name = resolve_chrom(
+            self.chrom_aliases.as_deref(),
+            &self.info.chrom_info,
+            chrom_name,
+        );
         let blocks = search_cir_tree(&self.info, &mut self.read, cir_tree, chrom_name, start, end)?;
         // TODO: this is only for asserting that the chrom is what we expect
         let chrom_ix = self
@@ -278,11 +518,11 @@ impl<R: BBIFileRead> BigBedRead<R> {
             .find(|&x| x.name == chrom_name)
             .unwrap()
             .id;
+        let max_block_gap = self.max_block_gap;
         Ok(IntervalIter {
             r: std::marker::PhantomData,
             bigbed: self,
-            known_offset: 0,
-            blocks: blocks.into_iter(),
+            blocks: BlockDataIter::new(blocks.into_iter(), max_block_gap),
             vals: None,
             expected_chrom: chrom_ix,
             start,
@@ -290,6 +530,88 @@ impl<R: BBIFileRead> BigBedRead<R> {
         })
     }
 
+    /// Gets the schema of the fields of this bigBed from its autosql. If it
+    /// has no autosql, the standard bed fields are used.
+    pub fn schema(&mut self) -> Result<BedSchema, BBIReadError> {
+        match self.autosql()? {
+            Some(autosql) if !autosql.trim().is_empty() => BedSchema::from_autosql(&autosql)
+                .map_err(|e| BBIReadError::InvalidFile(e.to_string())),
+            _ => {
+                let field_count = usize::from(self.info.header.field_count);
+                Ok(BedSchema::bed(field_count.saturating_sub(3)))
+            }
+        }
+    }
+
+    /// For a given chromosome, start, and end, returns an `Iterator` of the
+    /// intersecting entries, with their fields decoded according to the
+    /// bigBed's `schema`. Fields that can't be decoded are errors in
+    /// `TypedBedEntry::values`, rather than errors of the whole entry.
+    pub fn get_interval_typed<'a>(
+        &'a mut self,
+        chrom_name: &str,
+        start: u32,
+        end: u32,
+    ) -> Result<impl Iterator<Item = Result<TypedBedEntry, BBIReadError>> + 'a, BBIReadError> {
+        let schema = Arc::new(self.schema()?);
+        let intervals = self.get_interval(chrom_name, start, end)?;
+        Ok(intervals.map(move |entry| entry.map(|e| TypedBedEntry::new(&e, schema.clone()))))
+    }
+
+    /// For a given chromosome, start, and end, returns an `Iterator` of the
+    /// intersecting `BedEntry`s, like `get_interval`. Blocks are still read
+    /// on the calling thread, but are decompressed and decoded on `runtime`,
+    /// which can substantially speed up reading large regions. The
+    /// `runtime` should be multi-threaded, and the iterator should not be
+    /// used from within it.
+    pub fn get_interval_parallel<'a>(
+        &'a mut self,
+        chrom_name: &str,
+        start: u32,
+        end: u32,
+        runtime: &Runtime,
+    ) -> Result<impl Iterator<Item = Result<BedEntry, BBIReadError>> + 'a, BBIReadError> {
+        let cir_tree = self.full_data_cir_tree()?;
+        let chrom_name = resolve_chrom(
+            self.chrom_aliases.as_deref(),
+            &self.info.chrom_info,
+            chrom_name,
+        );
+        let chrom = self.info.chrom_id(chrom_name)?;
+        let blocks = search_cir_tree(&self.info, &mut self.read, cir_tree, chrom_name, start, end)?;
+        let endianness = self.info.header.endianness;
+        Ok(ParallelBlockIter::new(
+            &mut self.read,
+            &self.info,
+            blocks,
+            self.max_block_gap,
+            runtime.handle().clone(),
+            move |data: &[u8]| decode_block_entries(endianness, data, chrom, start, end),
+        ))
+    }
+
+    /// For each of the given regions (chromosome, start, end), gets the
+    /// intersecting `BedEntry`s. Unlike calling `get_interval` for each
+    /// region, the index is searched only once per chromosome, and each block
+    /// is read only once, even if it overlaps multiple regions. The results
+    /// are in the same order as `regions`.
+    pub fn get_intervals<S: AsRef<str>>(
+        &mut self,
+        regions: impl IntoIterator<Item = (S, u32, u32)>,
+    ) -> Result<Vec<Vec<BedEntry>>, BBIReadError> {
+        let cir_tree = self.full_data_cir_tree()?;
+        let regions = region_chrom_ids(&self.info, self.chrom_aliases.as_deref(), regions)?;
+        let endianness = self.info.header.endianness;
+        get_intervals_batched(
+            &mut self.read,
+            &self.info,
+            cir_tree.1,
+            &regions,
+            self.max_block_gap,
+            |data, chrom, start, end| decode_block_entries(endianness, data, chrom, start, end),
+        )
+    }
+
     /// For a given chromosome, start, and end, returns an `Iterator` of the
     /// intersecting `BedEntry`s. The resulting iterator takes this `BigBedRead`
     /// by value.
@@ -300,6 +622,11 @@ impl<R: BBIFileRead> BigBedRead<R> {
         end: u32,
     ) -> Result<impl Iterator<Item = Result<BedEntry, BBIReadError>>, BBIReadError> {
         let cir_tree = self.full_data_cir_tree()?;
+        let chrom_name = resolve_chrom(
+            self.chrom_aliases.as_deref(),
+            &self.info.chrom_info,
+            chrom_name,
+        );
         let blocks = search_cir_tree(&self.info, &mut self.read, cir_tree, chrom_name, start, end)?;
         // TODO: this is only for asserting that the chrom is what we expect
         let chrom_ix = self
@@ -309,11 +636,11 @@ impl<R: BBIFileRead> BigBedRead<R> {
             .find(|&x| x.name == chrom_name)
             .unwrap()
             .id;
+        let max_block_gap = self.max_block_gap;
         Ok(IntervalIter {
             r: std::marker::PhantomData,
             bigbed: self,
-            known_offset: 0,
-            blocks: blocks.into_iter(),
+            blocks: BlockDataIter::new(blocks.into_iter(), max_block_gap),
             vals: None,
             expected_chrom: chrom_ix,
             start,
@@ -334,7 +661,13 @@ impl<R: BBIFileRead> BigBedRead<R> {
         let cir_tree = self
             .zoom_cir_tree(reduction_level)
             .map_err(|_| ZoomIntervalError::ReductionLevelNotFound)?;
+        let max_block_gap = self.max_block_gap;
 
+        let chrom_name = resolve_chrom(
+            self.chrom_aliases.as_deref(),
+            &self.info.chrom_info,
+            chrom_name,
+        );
         let chrom = self.info.chrom_id(chrom_name)?;
 
         let blocks = search_cir_tree(&self.info, &mut self.read, cir_tree, chrom_name, start, end)?;
@@ -342,7 +675,39 @@ impl<R: BBIFileRead> BigBedRead<R> {
             std::vec::IntoIter<Block>,
             BigBedRead<R>,
             &mut BigBedRead<R>,
-        >::new(self, blocks.into_iter(), chrom, start, end))
+        >::new(
+            self,
+            blocks.into_iter(),
+            max_block_gap,
+            chrom,
+            start,
+            end,
+        ))
+    }
+
+    /// For each of the given regions (chromosome, start, end), gets the
+    /// intersecting `ZoomRecord`s of the given reduction level. Like
+    /// `get_intervals`, the index is searched only once per chromosome. The
+    /// results are in the same order as `regions`.
+    pub fn get_zoom_intervals<S: AsRef<str>>(
+        &mut self,
+        regions: impl IntoIterator<Item = (S, u32, u32)>,
+        reduction_level: u32,
+    ) -> Result<Vec<Vec<ZoomRecord>>, ZoomIntervalError> {
+        let cir_tree = self
+            .zoom_cir_tree(reduction_level)
+            .map_err(|_| ZoomIntervalError::ReductionLevelNotFound)?;
+        let regions = region_chrom_ids(&self.info, self.chrom_aliases.as_deref(), regions)?;
+        let endianness = self.info.header.endianness;
+        get_intervals_batched(
+            &mut self.read,
+            &self.info,
+            cir_tree.1,
+            &regions,
+            self.max_block_gap,
+            |data, chrom, start, end| decode_zoom_block(endianness, data, chrom, start, end),
+        )
+        .map_err(ZoomIntervalError::BBIReadError)
     }
 
     /// For a given chromosome, start, and end, returns an `Iterator` of the
@@ -357,69 +722,84 @@ impl<R: BBIFileRead> BigBedRead<R> {
         let cir_tree = self
             .zoom_cir_tree(reduction_level)
             .map_err(|_| ZoomIntervalError::ReductionLevelNotFound)?;
+        let max_block_gap = self.max_block_gap;
 
+        let chrom_name = resolve_chrom(
+            self.chrom_aliases.as_deref(),
+            &self.info.chrom_info,
+            chrom_name,
+        );
         let chrom = self.info.chrom_id(chrom_name)?;
 
         let blocks = search_cir_tree(&self.info, &mut self.read, cir_tree, chrom_name, start, end)?;
         Ok(ZoomIntervalIter::new(
             self,
             blocks.into_iter(),
+            max_block_gap,
             chrom,
             start,
             end,
         ))
     }
+
+    /// Summarizes the item density (the number of entries
+    /// overlapping each base) from `start` to `end` on a chromosome
+    /// into `bins` equal bins, using the given `SummaryKind`. Bins without
+    /// data are `None`. If `exact` is `false`, the summaries are estimated
+    /// from a zoom level when there is one with a small enough reduction
+    /// level (see `summary_zoom_level`).
+    pub fn summarize(
+        &mut self,
+        chrom_name: &str,
+        start: u32,
+        end: u32,
+        bins: usize,
+        kind: SummaryKind,
+        exact: bool,
+    ) -> Result<Vec<Option<f64>>, BBIReadError> {
+        let (region_start, region_end) = (start as i64, end as i64);
+        let zoom = match exact {
+            true => None,
+            false => summary_zoom_level(&self.info, region_start, region_end, bins),
+        };
+        match zoom {
+            Some(reduction_level) => {
+                let records = self
+                    .get_zoom_interval(chrom_name, start, end, reduction_level)
+                    .map_err(zoom_read_error)?;
+                summarize_entry_zoom_records(records, region_start, region_end, bins, kind)
+            }
+            None => {
+                let entries = self.get_interval(chrom_name, start, end)?;
+                summarize_entries(entries, region_start, region_end, bins, kind)
+            }
+        }
+    }
 }
 
 // TODO: remove expected_chrom
-fn get_block_entries<R: BBIFileRead>(
-    bigbed: &mut BigBedRead<R>,
-    block: Block,
-    known_offset: &mut u64,
+/// Decodes the entries overlapping `start` and `end` from the (decompressed)
+/// data of a bigBed block.
+pub(crate) fn decode_block_entries(
+    endianness: byteordered::Endianness,
+    data: &[u8],
     expected_chrom: u32,
     start: u32,
     end: u32,
-) -> Result<std::vec::IntoIter<BedEntry>, BBIReadError> {
-    let data = bigbed.read.get_block_data(&bigbed.info, &block)?;
+) -> Result<Vec<BedEntry>, BlockError> {
     let mut bytes = BytesMut::with_capacity(data.len());
-    bytes.extend_from_slice(&data);
+    bytes.extend_from_slice(data);
     let mut entries: Vec<BedEntry> = Vec::new();
 
-    let mut read_entry = || -> Result<Option<BedEntry>, BBIReadError> {
-        if bytes.len() < 12 {
+    let mut read_entry = || -> Result<Option<BedEntry>, BlockError> {
+        let Some((chrom_id, entry)) = read_bed_entry(&mut bytes, endianness)? else {
             return Ok(None);
-        }
-        let (chrom_id, chrom_start, chrom_end) = match bigbed.info.header.endianness {
-            byteordered::Endianness::Big => (bytes.get_u32(), bytes.get_u32(), bytes.get_u32()),
-            byteordered::Endianness::Little => {
-                (bytes.get_u32_le(), bytes.get_u32_le(), bytes.get_u32_le())
-            }
         };
-        if chrom_start == 0 && chrom_end == 0 {
-            return Err(BBIReadError::InvalidFile(
-                "Chrom start and end both equal 0.".to_owned(),
-            ));
-        }
         // FIXME: should this just return empty?
-        assert_eq!(
-            chrom_id, expected_chrom,
-            "BUG: bigBed had multiple chroms in a section"
-        );
-        let nul = bytes.iter().find_position(|b| **b == b'\0');
-        let s = match nul {
-            Some((pos, _)) => {
-                let b = bytes.split_to(pos);
-                bytes.get_u8();
-                b.to_vec()
-            }
-            None => bytes.to_vec(),
-        };
-        let rest = String::from_utf8(s).unwrap();
-        Ok(Some(BedEntry {
-            start: chrom_start,
-            end: chrom_end,
-            rest,
-        }))
+        if chrom_id != expected_chrom {
+            return Err(BlockError::UnexpectedChrom(expected_chrom, chrom_id));
+        }
+        Ok(Some(entry))
     };
     while let Some(entry) = read_entry()? {
         if entry.end >= start && entry.start <= end {
@@ -427,6 +807,46 @@ fn get_block_entries<R: BBIFileRead>(
         }
     }
 
-    *known_offset = block.offset + block.size;
-    Ok(entries.into_iter())
+    Ok(entries)
+}
+
+/// Reads a single item from the (decompressed) data of a bigBed block,
+/// returning the chrom id along with the entry.
+fn read_bed_entry(
+    bytes: &mut BytesMut,
+    endianness: byteordered::Endianness,
+) -> Result<Option<(u32, BedEntry)>, BlockError> {
+    if bytes.len() < 12 {
+        return Ok(None);
+    }
+    let (chrom_id, chrom_start, chrom_end) = match endianness {
+        byteordered::Endianness::Big => (bytes.get_u32(), bytes.get_u32(), bytes.get_u32()),
+        byteordered::Endianness::Little => {
+            (bytes.get_u32_le(), bytes.get_u32_le(), bytes.get_u32_le())
+        }
+    };
+    if chrom_start == 0 && chrom_end == 0 {
+        return Err(BlockError::InvalidItem("Chrom start and end both equal 0."));
+    }
+    if chrom_end < chrom_start {
+        return Err(BlockError::InvalidItem("Item end is before its start."));
+    }
+    let nul = bytes.iter().find_position(|b| **b == b'\0');
+    let s = match nul {
+        Some((pos, _)) => {
+            let b = bytes.split_to(pos);
+            bytes.get_u8();
+            b.to_vec()
+        }
+        None => return Err(BlockError::Truncated),
+    };
+    let rest = String::from_utf8(s).map_err(|_| BlockError::InvalidUtf8)?;
+    Ok(Some((
+        chrom_id,
+        BedEntry {
+            start: chrom_start,
+            end: chrom_end,
+            rest,
+        },
+    )))
 }
@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 use std::ffi::CString;
 use std::fs::File;
-use std::io::{self, BufWriter, Seek, Write};
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
 use std::path::Path;
 
 use futures::sink::SinkExt;
@@ -21,10 +21,11 @@ use index_list::IndexList;
 
 use crate::bbi::{BedEntry, Summary, Value, ZoomRecord, BIGBED_MAGIC};
 use crate::bbiwrite::{
-    self, encode_zoom_section, write_blank_headers, write_zooms, BBIProcessError, BBIWriteOptions,
-    SectionData,
+    self, encode_zoom_section, write_blank_headers, write_bplustree, write_zooms, BBIProcessError,
+    BBIWriteOptions, Section, SectionData,
 };
 use crate::bed::autosql::parse::parse_autosql;
+use crate::bed::autosql::validate::BedValidator;
 
 /// The struct used to write a bigBed file
 pub struct BigBedWrite<W: Write + Seek + Send + 'static> {
@@ -32,6 +33,14 @@ pub struct BigBedWrite<W: Write + Seek + Send + 'static> {
     chrom_sizes: HashMap<String, u32>,
     pub options: BBIWriteOptions,
     pub autosql: Option<String>,
+    /// The names of the autosql fields to write extra (name) indices for. A
+    /// field can be looked up later with `BigBedRead::search_extra_index`.
+    pub extra_indices: Vec<String>,
+    /// If set, each item is checked against this before it is written, and
+    /// writing fails at the first invalid item. This works for any source of
+    /// values (including parallel ones), but unlike wrapping a stream in a
+    /// `ValidatingBedStream`, errors don't include a line number.
+    pub validator: Option<BedValidator>,
 }
 
 impl BigBedWrite<File> {
@@ -51,27 +60,43 @@ impl<W: Write + Seek + Send + 'static> BigBedWrite<W> {
             chrom_sizes,
             options: BBIWriteOptions::default(),
             autosql: None,
+            extra_indices: vec![],
+            validator: None,
         }
     }
 
     fn write_pre(
         file: &mut BufWriter<W>,
         autosql: Option<String>,
-    ) -> Result<(u64, u64, u64, u64, u16), ProcessDataError> {
+        extra_indices: &[String],
+    ) -> Result<(u64, u64, ExtraIndexHeader, u64, u64, u16), ProcessDataError> {
         write_blank_headers(file)?;
 
         let autosql = autosql.unwrap_or_else(|| crate::bed::autosql::BED3.to_string());
 
-        let field_count = 'field_count: {
-            let Ok(mut declarations) = parse_autosql(&autosql) else {
-                break 'field_count None;
-            };
-            let Some(decl) = declarations.pop() else {
-                break 'field_count None;
-            };
-            Some(decl.fields.len())
-        };
-        let field_count = field_count.unwrap_or(3) as u16;
+        let declaration = parse_autosql(&autosql)
+            .ok()
+            .and_then(|mut declarations| declarations.pop());
+        let field_count = declaration
+            .as_ref()
+            .map(|decl| decl.fields.len())
+            .unwrap_or(3) as u16;
+
+        let extra_index_fields = extra_indices
+            .iter()
+            .map(|name| {
+                declaration
+                    .as_ref()
+                    .and_then(|decl| decl.fields.iter().position(|f| &f.name == name))
+                    .map(|id| id as u16)
+                    .ok_or_else(|| {
+                        ProcessDataError::InvalidInput(format!(
+                            "Invalid extra index: `{}` is not a field in the autosql",
+                            name
+                        ))
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
 
         let autosql = CString::new(autosql.into_bytes()).map_err(|_| {
             ProcessDataError::InvalidInput("Invalid autosql: null byte in string".to_owned())
@@ -83,7 +108,30 @@ impl<W: Write + Seek + Send + 'static> BigBedWrite<W> {
         let total_summary_offset = file.tell()?;
         file.write_all(&[0; 40])?;
 
-        // TODO: extra indices
+        let extra_index = if extra_index_fields.is_empty() {
+            ExtraIndexHeader {
+                extension_offset: 0,
+                index_list_offset: 0,
+                fields: vec![],
+            }
+        } else {
+            let extension_offset = file.tell()?;
+            let index_list_offset = extension_offset + EXTENSION_HEADER_SIZE;
+            file.write_u16::<NativeEndian>(EXTENSION_HEADER_SIZE as u16)?;
+            file.write_u16::<NativeEndian>(extra_index_fields.len() as u16)?;
+            file.write_u64::<NativeEndian>(index_list_offset)?;
+            file.write_all(&[0; EXTENSION_HEADER_SIZE as usize - 12])?;
+            // The index list is filled in once the indices themselves are written
+            file.write_all(&vec![
+                0;
+                extra_index_fields.len() * EXTRA_INDEX_LIST_ITEM_SIZE
+            ])?;
+            ExtraIndexHeader {
+                extension_offset,
+                index_list_offset,
+                fields: extra_index_fields,
+            }
+        };
 
         let full_data_offset = file.tell()?;
 
@@ -99,6 +147,7 @@ impl<W: Write + Seek + Send + 'static> BigBedWrite<W> {
         Ok((
             autosql_offset,
             total_summary_offset,
+            extra_index,
             full_data_offset,
             pre_data,
             field_count,
@@ -113,9 +162,23 @@ impl<W: Write + Seek + Send + 'static> BigBedWrite<W> {
     ) -> Result<(), BBIProcessError<V::Error>> {
         let mut file = BufWriter::new(self.out);
 
-        let (autosql_offset, total_summary_offset, full_data_offset, pre_data, field_count) =
-            BigBedWrite::write_pre(&mut file, self.autosql)?;
+        let (
+            autosql_offset,
+            total_summary_offset,
+            extra_index,
+            full_data_offset,
+            pre_data,
+            field_count,
+        ) = BigBedWrite::write_pre(&mut file, self.autosql, &self.extra_indices)?;
 
+        let mut extra_index_keys = vec![];
+        let vals = ExtraIndexSource {
+            vals,
+            fields: &extra_index.fields,
+            validator: self.validator.as_ref(),
+            items_per_slot: self.options.items_per_slot,
+            keys: &mut extra_index_keys,
+        };
         let output = bbiwrite::write_vals::<_, _, BigBedFullProcess>(
             vals,
             file,
@@ -127,10 +190,11 @@ impl<W: Write + Seek + Send + 'static> BigBedWrite<W> {
             output?;
 
         let chrom_ids = chrom_ids.get_map();
+        let sections = contiguous_sections(raw_sections_iter, pre_data);
         let (data_size, chrom_index_start, index_start, _total_sections) = bbiwrite::write_mid(
             &mut file,
             pre_data,
-            raw_sections_iter,
+            sections.iter().copied(),
             self.chrom_sizes,
             &chrom_ids,
             &self.options,
@@ -139,6 +203,15 @@ impl<W: Write + Seek + Send + 'static> BigBedWrite<W> {
         let zoom_entries = write_zooms(&mut file, zoom_infos, data_size, &self.options)?;
         let num_zooms = zoom_entries.len() as u16;
 
+        let extra_index_offsets = write_extra_indices(
+            &mut file,
+            &extra_index,
+            extra_index_keys,
+            &sections,
+            &chrom_ids,
+            &self.options,
+        )?;
+
         write_info(
             &mut file,
             BIGBED_MAGIC,
@@ -159,6 +232,8 @@ impl<W: Write + Seek + Send + 'static> BigBedWrite<W> {
             summary.total_items,
         )?;
 
+        write_extra_index_header(&mut file, &extra_index, &extra_index_offsets)?;
+
         Ok(())
     }
 
@@ -172,10 +247,23 @@ impl<W: Write + Seek + Send + 'static> BigBedWrite<W> {
     ) -> Result<(), BBIProcessError<V::Error>> {
         let mut file = BufWriter::new(self.out);
 
-        let (autosql_offset, total_summary_offset, full_data_offset, pre_data, field_count) =
-            BigBedWrite::write_pre(&mut file, self.autosql)?;
-
-        let vals = make_vals()?;
+        let (
+            autosql_offset,
+            total_summary_offset,
+            extra_index,
+            full_data_offset,
+            pre_data,
+            field_count,
+        ) = BigBedWrite::write_pre(&mut file, self.autosql, &self.extra_indices)?;
+
+        let mut extra_index_keys = vec![];
+        let vals = ExtraIndexSource {
+            vals: make_vals()?,
+            fields: &extra_index.fields,
+            validator: self.validator.as_ref(),
+            items_per_slot: self.options.items_per_slot,
+            keys: &mut extra_index_keys,
+        };
 
         let output = bbiwrite::write_vals_no_zoom::<_, _, BigBedNoZoomsProcess>(
             vals,
@@ -188,10 +276,11 @@ impl<W: Write + Seek + Send + 'static> BigBedWrite<W> {
             output?;
 
         let chrom_ids = chrom_ids.get_map();
+        let sections = contiguous_sections(raw_sections_iter, pre_data);
         let (data_size, chrom_index_start, index_start, _total_sections) = bbiwrite::write_mid(
             &mut file,
             pre_data,
-            raw_sections_iter,
+            sections.iter().copied(),
             self.chrom_sizes,
             &chrom_ids,
             &self.options,
@@ -201,7 +290,7 @@ impl<W: Write + Seek + Send + 'static> BigBedWrite<W> {
 
         let output = bbiwrite::write_zoom_vals::<_, _, BigBedZoomsProcess<W>>(
             vals,
-            self.options,
+            self.options.clone(),
             &runtime,
             &chrom_ids,
             (summary.bases_covered as f64 / summary.total_items as f64) as u32,
@@ -213,6 +302,15 @@ impl<W: Write + Seek + Send + 'static> BigBedWrite<W> {
         uncompress_buf_size = uncompress_buf_size.max(zoom_uncompress_buf_size);
         let num_zooms = zoom_entries.len() as u16;
 
+        let extra_index_offsets = write_extra_indices(
+            &mut file,
+            &extra_index,
+            extra_index_keys,
+            &sections,
+            &chrom_ids,
+            &self.options,
+        )?;
+
         write_info(
             &mut file,
             BIGBED_MAGIC,
@@ -232,10 +330,264 @@ impl<W: Write + Seek + Send + 'static> BigBedWrite<W> {
             summary.total_items,
         )?;
 
+        write_extra_index_header(&mut file, &extra_index, &extra_index_offsets)?;
+
         Ok(())
     }
 }
 
+/// Size of the bigBed extension header. The first two fields are the size
+/// itself and the number of extra indices, followed by the offset of the extra
+/// index list.
+const EXTENSION_HEADER_SIZE: u64 = 64;
+/// Size of a single (single-field) entry in the extra index list.
+const EXTRA_INDEX_LIST_ITEM_SIZE: usize = 20;
+/// Byte offset in the common header that points to the extension header.
+const EXTENSION_OFFSET_POSITION: u64 = 56;
+
+/// The location of the extension header and extra index list. If there are
+/// no extra indices, neither is written.
+struct ExtraIndexHeader {
+    extension_offset: u64,
+    index_list_offset: u64,
+    /// The autosql field ids (column numbers, with `chrom` being `0`)
+    fields: Vec<u16>,
+}
+
+/// The keys for each extra index for a single chromosome. Each key is stored
+/// along with the index of the section (within this chromosome) containing
+/// the item.
+struct ChromExtraIndexKeys {
+    chrom: String,
+    keys: Vec<Vec<(String, u32)>>,
+}
+
+/// Wraps the values being written so that, in addition to being processed as
+/// normal, each item is checked with the validator (if any) and the extra
+/// index key for each item is collected.
+struct ExtraIndexSource<'a, V> {
+    vals: V,
+    fields: &'a [u16],
+    validator: Option<&'a BedValidator>,
+    items_per_slot: u32,
+    keys: &'a mut Vec<ChromExtraIndexKeys>,
+}
+
+impl<'a, V: BBIDataSource<Value = BedEntry>> BBIDataSource for ExtraIndexSource<'a, V> {
+    type Value = BedEntry;
+    type Error = V::Error;
+
+    fn process_to_bbi<
+        P: BBIDataProcessor<Value = Self::Value> + Send + 'static,
+        StartProcessing: FnMut(String) -> Result<P, ProcessDataError>,
+        Advance: FnMut(P),
+    >(
+        &mut self,
+        runtime: &Runtime,
+        start_processing: &mut StartProcessing,
+        advance: &mut Advance,
+    ) -> Result<(), BBIProcessError<Self::Error>> {
+        let ExtraIndexSource {
+            vals,
+            fields,
+            validator,
+            items_per_slot,
+            keys,
+        } = self;
+
+        let mut start_processing = |chrom: String| {
+            let processor = start_processing(chrom.clone())?;
+            Ok(ExtraIndexProcessor {
+                processor,
+                fields: fields.to_vec(),
+                validator: validator.cloned(),
+                items_per_slot: *items_per_slot,
+                item_count: 0,
+                keys: ChromExtraIndexKeys {
+                    chrom,
+                    keys: vec![vec![]; fields.len()],
+                },
+            })
+        };
+        let mut advance = |p: ExtraIndexProcessor<P>| {
+            keys.push(p.keys);
+            advance(p.processor);
+        };
+        vals.process_to_bbi(runtime, &mut start_processing, &mut advance)
+    }
+}
+
+struct ExtraIndexProcessor<P> {
+    processor: P,
+    fields: Vec<u16>,
+    validator: Option<BedValidator>,
+    items_per_slot: u32,
+    item_count: u32,
+    keys: ChromExtraIndexKeys,
+}
+
+impl<P: BBIDataProcessorCreate> BBIDataProcessorCreate for ExtraIndexProcessor<P> {
+    type I = P::I;
+    type Out = P::Out;
+    // Only the inner processor is created and destroyed by the bbi writing
+    // code; the wrapper is constructed by `ExtraIndexSource` instead.
+    fn create(internal_data: Self::I) -> Self {
+        ExtraIndexProcessor {
+            processor: P::create(internal_data),
+            fields: vec![],
+            validator: None,
+            items_per_slot: 1,
+            item_count: 0,
+            keys: ChromExtraIndexKeys {
+                chrom: String::new(),
+                keys: vec![],
+            },
+        }
+    }
+    fn destroy(self) -> Self::Out {
+        self.processor.destroy()
+    }
+}
+
+impl<P: BBIDataProcessor<Value = BedEntry> + Send> BBIDataProcessor for ExtraIndexProcessor<P> {
+    type Value = BedEntry;
+    async fn do_process(
+        &mut self,
+        current_val: Self::Value,
+        next_val: Option<&Self::Value>,
+    ) -> Result<(), ProcessDataError> {
+        if let Some(validator) = &self.validator {
+            if let Err(e) = validator.validate(&current_val) {
+                return Err(ProcessDataError::InvalidInput(format!(
+                    "Invalid bed ({}:{}-{}): {}",
+                    self.keys.chrom, current_val.start, current_val.end, e
+                )));
+            }
+        }
+
+        // Items are split into sections of `items_per_slot` in order (see `process_val`)
+        let section = self.item_count / self.items_per_slot;
+        self.item_count += 1;
+        for (field, keys) in self.fields.iter().zip(self.keys.keys.iter_mut()) {
+            let key = match field {
+                0 => self.keys.chrom.clone(),
+                1 => current_val.start.to_string(),
+                2 => current_val.end.to_string(),
+                _ => match current_val.rest.split('\t').nth(*field as usize - 3) {
+                    Some(key) => key.to_string(),
+                    None => continue,
+                },
+            };
+            if key.is_empty() {
+                continue;
+            }
+            if keys
+                .last()
+                .is_some_and(|last| last.0 == key && last.1 == section)
+            {
+                continue;
+            }
+            keys.push((key, section));
+        }
+
+        self.processor.do_process(current_val, next_val).await
+    }
+}
+
+/// Sets the offset of each section, assuming they are written contiguously
+/// starting at `pre_data` (the same assumption as `write_mid`).
+fn contiguous_sections(sections: impl Iterator<Item = Section>, pre_data: u64) -> Vec<Section> {
+    let mut current_offset = pre_data;
+    sections
+        .map(|mut section| {
+            section.offset = current_offset;
+            current_offset += section.size;
+            section
+        })
+        .collect()
+}
+
+/// Writes a B+ tree for each extra index, mapping each key to the offset and
+/// size of the data blocks that contain it. Returns the offset of each tree.
+fn write_extra_indices<W: Write + Seek + Send + 'static>(
+    file: &mut BufWriter<W>,
+    extra_index: &ExtraIndexHeader,
+    keys: Vec<ChromExtraIndexKeys>,
+    sections: &[Section],
+    chrom_ids: &HashMap<String, u32>,
+    options: &BBIWriteOptions,
+) -> io::Result<Vec<u64>> {
+    if extra_index.fields.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut chrom_sections: HashMap<u32, Vec<(u64, u64)>> = HashMap::new();
+    for section in sections {
+        chrom_sections
+            .entry(section.chrom)
+            .or_default()
+            .push((section.offset, section.size));
+    }
+
+    let mut offsets = Vec::with_capacity(extra_index.fields.len());
+    for field_idx in 0..extra_index.fields.len() {
+        let mut items: Vec<(&[u8], u64, u64)> = vec![];
+        for chrom_keys in keys.iter() {
+            let chrom_sections = &chrom_sections[&chrom_ids[&chrom_keys.chrom]];
+            for (key, section) in chrom_keys.keys[field_idx].iter() {
+                let (offset, size) = chrom_sections[*section as usize];
+                items.push((key.as_bytes(), offset, size));
+            }
+        }
+        items.sort();
+        items.dedup();
+
+        let key_size = items.iter().map(|i| i.0.len()).max().unwrap_or(0).max(1) as u32;
+        offsets.push(file.tell()?);
+        write_bplustree(
+            file,
+            options.block_size,
+            key_size,
+            16,
+            &items,
+            |item| item.0,
+            |file, item| {
+                file.write_u64::<NativeEndian>(item.1)?;
+                file.write_u64::<NativeEndian>(item.2)
+            },
+        )?;
+    }
+    Ok(offsets)
+}
+
+/// Points the common header to the extension header and fills in the extra
+/// index list.
+fn write_extra_index_header<W: Write + Seek + Send + 'static>(
+    file: &mut BufWriter<W>,
+    extra_index: &ExtraIndexHeader,
+    tree_offsets: &[u64],
+) -> io::Result<()> {
+    if extra_index.fields.is_empty() {
+        return Ok(());
+    }
+
+    file.seek(SeekFrom::Start(EXTENSION_OFFSET_POSITION))?;
+    file.write_u64::<NativeEndian>(extra_index.extension_offset)?;
+
+    file.seek(SeekFrom::Start(extra_index.index_list_offset))?;
+    for (field, tree_offset) in extra_index.fields.iter().zip(tree_offsets) {
+        file.write_u16::<NativeEndian>(0)?; // type (B+ tree)
+        file.write_u16::<NativeEndian>(1)?; // field count
+        file.write_u64::<NativeEndian>(*tree_offset)?;
+        file.write_all(&[0; 4])?; // reserved
+        file.write_u16::<NativeEndian>(*field)?;
+        file.write_u16::<NativeEndian>(0)?; // reserved
+    }
+
+    file.seek(SeekFrom::End(0))?;
+    Ok(())
+}
+
 async fn process_val(
     current_val: BedEntry,
     next_val: Option<&BedEntry>,
@@ -330,9 +682,12 @@ async fn process_val(
                 .map(|o| o.end >= item_start)
                 .unwrap_or(true));
 
-            if overlap.get_last().map(|o| o.end).unwrap_or(item_start) == item_start {
+            // Any part of the current item past the end of existing overlaps
+            // is only covered by the current item
+            let last_end = overlap.get_last().map(|o| o.end).unwrap_or(item_start);
+            if last_end == item_start || last_end < item_end {
                 overlap.insert_last(Value {
-                    start: item_start,
+                    start: last_end,
                     end: item_end,
                     value: 1.0,
                 });
@@ -939,7 +1294,8 @@ async fn encode_section(
     let mut bytes = Vec::with_capacity(items_in_section.len() * 30);
 
     let start = items_in_section[0].start;
-    let end = items_in_section[items_in_section.len() - 1].end;
+    // Items can overlap, so the last item doesn't necessarily end last
+    let end = items_in_section.iter().map(|item| item.end).max().unwrap();
 
     // FIXME: Each of these calls end up calling `Vec::reserve`
     // We could instead use a `Cursor<&mut [u8]>`, but we would need to be a bit
@@ -42,20 +42,29 @@ use std::borrow::BorrowMut;
 use std::fs::File;
 use std::io::{self, Seek, SeekFrom};
 use std::path::Path;
+use std::sync::Arc;
 use std::vec::Vec;
 
 use byteordered::{ByteOrdered, Endianness};
-use bytes::{Buf, BytesMut};
+use bytes::{Buf, Bytes, BytesMut};
 use thiserror::Error;
+use tokio::runtime::Runtime;
 
+use crate::bbi::chromalias::{resolve_chrom, ChromAliases};
+use crate::bbi::summarize::{
+    summarize_values, summarize_zoom_records, summary_zoom_level, zoom_read_error, SummaryKind,
+};
 use crate::bbi::{BBIFile, Summary, Value, ZoomRecord};
 use crate::bbiread::{
-    read_info, BBIFileInfo, BBIFileReadInfoError, BBIRead, BBIReadError, Block, ChromInfo,
-    ZoomIntervalIter,
+    decode_zoom_block, get_intervals_batched, read_info, region_chrom_ids, BBIFileInfo,
+    BBIFileReadInfoError, BBIRead, BBIReadError, Block, BlockDataIter, BlockError, ChromInfo,
+    InMemoryBBIFileRead, ParallelBlockIter, ZoomIntervalIter, DEFAULT_MAX_BLOCK_GAP,
 };
 use crate::internal::BBIReadInternal;
 use crate::utils::reopen::{Reopen, ReopenableFile, SeekableRead};
-use crate::{search_cir_tree, BBIFileRead, CachedBBIFileRead, ZoomIntervalError};
+use crate::{
+    search_cir_tree, BBIFileRead, CachedBBIFileRead, ZoomIntervalError, DEFAULT_CACHE_CAPACITY,
+};
 
 struct IntervalIter<I, R, B>
 where
@@ -65,8 +74,7 @@ where
 {
     r: std::marker::PhantomData<R>,
     bigwig: B,
-    known_offset: u64,
-    blocks: I,
+    blocks: BlockDataIter<I>,
     vals: Option<std::vec::IntoIter<Value>>,
     chrom: u32,
     start: u32,
@@ -93,22 +101,26 @@ where
                     }
                 },
                 None => {
-                    // TODO: Could minimize this by chunking block reads
-                    let current_block = self.blocks.next()?;
-                    match get_block_values(
-                        self.bigwig.borrow_mut(),
-                        current_block,
-                        &mut self.known_offset,
+                    let bigwig = self.bigwig.borrow_mut();
+                    let (block, data) = match self.blocks.next(&mut bigwig.read, &bigwig.info)? {
+                        Ok(data) => data,
+                        Err(e) => {
+                            return Some(Err(e.into()));
+                        }
+                    };
+                    match decode_block_values(
+                        bigwig.info.header.endianness,
+                        &data,
                         self.chrom,
                         self.start,
                         self.end,
                     ) {
                         Ok(Some(vals)) => {
-                            self.vals = Some(vals);
+                            self.vals = Some(vals.into_iter());
                         }
                         Ok(None) => {}
                         Err(e) => {
-                            return Some(Err(e));
+                            return Some(Err(BBIReadError::InvalidBlock(block.offset, e)));
                         }
                     }
                 }
@@ -148,6 +160,8 @@ impl From<BBIFileReadInfoError> for BigWigReadOpenError {
 pub struct BigWigRead<R> {
     pub(super) info: BBIFileInfo,
     pub(super) read: R,
+    pub(super) chrom_aliases: Option<Arc<ChromAliases>>,
+    pub(super) max_block_gap: u64,
 }
 
 impl<R: Reopen> Reopen for BigWigRead<R> {
@@ -155,6 +169,8 @@ impl<R: Reopen> Reopen for BigWigRead<R> {
         Ok(BigWigRead {
             info: self.info.clone(),
             read: self.read.reopen()?,
+            chrom_aliases: self.chrom_aliases.clone(),
+            max_block_gap: self.max_block_gap,
         })
     }
 }
@@ -191,6 +207,32 @@ impl<R> BigWigRead<R> {
     pub fn chroms(&self) -> &[ChromInfo] {
         &self.info.chrom_info
     }
+
+    /// Sets the aliases used to resolve chromosome names in queries. With
+    /// aliases set, any alias of a chromosome can be used in place of the
+    /// name stored in the file.
+    pub fn with_chrom_aliases(self, chrom_aliases: Arc<ChromAliases>) -> Self {
+        BigWigRead {
+            chrom_aliases: Some(chrom_aliases),
+            ..self
+        }
+    }
+
+    /// Gets the aliases used to resolve chromosome names, if any
+    pub fn chrom_aliases(&self) -> Option<&ChromAliases> {
+        self.chrom_aliases.as_deref()
+    }
+
+    /// Sets the maximum gap (in bytes) between blocks for them to be read
+    /// together when querying data. Larger gaps mean fewer reads (or, for
+    /// remote files, fewer requests), at the cost of reading unneeded data.
+    /// Defaults to `DEFAULT_MAX_BLOCK_GAP`.
+    pub fn with_max_block_gap(self, max_block_gap: u64) -> Self {
+        BigWigRead {
+            max_block_gap,
+            ..self
+        }
+    }
 }
 
 impl BigWigRead<ReopenableFile> {
@@ -208,6 +250,27 @@ impl BigWigRead<ReopenableFile> {
     }
 }
 
+impl BigWigRead<InMemoryBBIFileRead> {
+    /// Opens a new `BigWigRead` from a bigWig that is entirely in memory.
+    pub fn from_bytes(bytes: Bytes) -> Result<Self, BigWigReadOpenError> {
+        BigWigRead::open(InMemoryBBIFileRead::new(bytes))
+    }
+
+    /// Opens a new `BigWigRead` from a given path by memory-mapping the file.
+    /// Blocks and cir tree nodes are read directly from the mapped memory
+    /// (see `InMemoryBBIFileRead`).
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while this `BigWigRead`
+    /// (or any `reopen`ed copy) is alive.
+    #[cfg(feature = "mmap")]
+    pub unsafe fn open_mmap(path: impl AsRef<Path>) -> Result<Self, BigWigReadOpenError> {
+        let bytes = crate::bbiread::mmap_bytes(path)?;
+        BigWigRead::open(InMemoryBBIFileRead::new(bytes))
+    }
+}
+
 impl<R> BigWigRead<R>
 where
     R: SeekableRead,
@@ -215,10 +278,18 @@ where
     /// Converts this `BigWigRead`` to where the `BBIFileRead` caches index
     /// access and block data
     pub fn cached(self) -> BigWigRead<CachedBBIFileRead<R>> {
-        let read = CachedBBIFileRead::new(self.read);
+        self.cached_with_capacity(DEFAULT_CACHE_CAPACITY)
+    }
+
+    /// Like `cached`, but caches at most `capacity` bytes of index and block
+    /// data, evicting the least-recently used data first.
+    pub fn cached_with_capacity(self, capacity: usize) -> BigWigRead<CachedBBIFileRead<R>> {
+        let read = CachedBBIFileRead::with_capacity(self.read, capacity);
         BigWigRead {
             read,
             info: self.info,
+            chrom_aliases: self.chrom_aliases,
+            max_block_gap: self.max_block_gap,
         }
     }
 }
@@ -235,12 +306,22 @@ where
             _ => return Err(BigWigReadOpenError::NotABigWig),
         }
 
-        Ok(BigWigRead { info, read })
+        Ok(BigWigRead {
+            info,
+            read,
+            chrom_aliases: None,
+            max_block_gap: DEFAULT_MAX_BLOCK_GAP,
+        })
     }
 
     /// Does *not* check if the passed `R` matches the provided info (including if the `R` is a bigWig at all!)
     pub fn with_info(info: BBIFileInfo, read: R) -> Self {
-        BigWigRead { info, read }
+        BigWigRead {
+            info,
+            read,
+            chrom_aliases: None,
+            max_block_gap: DEFAULT_MAX_BLOCK_GAP,
+        }
     }
 
     /// Gets a reference to the inner `R` type, in order to access any info
@@ -293,14 +374,19 @@ where
         start: u32,
         end: u32,
     ) -> Result<impl Iterator<Item = Result<Value, BBIReadError>> + 'a, BBIReadError> {
-        let chrom = self.info.chrom_id(chrom_name)?;
         let cir_tree = self.full_data_cir_tree()?;
+        let chrom_name = resolve_chrom(
+            self.chrom_aliases.as_deref(),
+            &self.info.chrom_info,
+            chrom_name,
+        );
+        let chrom = self.info.chrom_id(chrom_name)?;
         let blocks = search_cir_tree(&self.info, &mut self.read, cir_tree, chrom_name, start, end)?;
+        let max_block_gap = self.max_block_gap;
         Ok(IntervalIter {
             r: std::marker::PhantomData,
             bigwig: self,
-            known_offset: 0,
-            blocks: blocks.into_iter(),
+            blocks: BlockDataIter::new(blocks.into_iter(), max_block_gap),
             vals: None,
             chrom,
             start,
@@ -308,6 +394,66 @@ where
         })
     }
 
+    /// For a given chromosome, start, and end, returns an `Iterator` of the
+    /// intersecting `Value`s, like `get_interval`. Blocks are still read on
+    /// the calling thread, but are decompressed and decoded on `runtime`,
+    /// which can substantially speed up reading large regions. The
+    /// `runtime` should be multi-threaded, and the iterator should not be
+    /// used from within it.
+    pub fn get_interval_parallel<'a>(
+        &'a mut self,
+        chrom_name: &str,
+        start: u32,
+        end: u32,
+        runtime: &Runtime,
+    ) -> Result<impl Iterator<Item = Result<Value, BBIReadError>> + 'a, BBIReadError> {
+        let cir_tree = self.full_data_cir_tree()?;
+        let chrom_name = resolve_chrom(
+            self.chrom_aliases.as_deref(),
+            &self.info.chrom_info,
+            chrom_name,
+        );
+        let chrom = self.info.chrom_id(chrom_name)?;
+        let blocks = search_cir_tree(&self.info, &mut self.read, cir_tree, chrom_name, start, end)?;
+        let endianness = self.info.header.endianness;
+        Ok(ParallelBlockIter::new(
+            &mut self.read,
+            &self.info,
+            blocks,
+            self.max_block_gap,
+            runtime.handle().clone(),
+            move |data: &[u8]| {
+                decode_block_values(endianness, data, chrom, start, end)
+                    .map(Option::unwrap_or_default)
+            },
+        ))
+    }
+
+    /// For each of the given regions (chromosome, start, end), gets the
+    /// intersecting `Value`s. Unlike calling `get_interval` for each region,
+    /// the index is searched only once per chromosome, and each block is read
+    /// only once, even if it overlaps multiple regions. The results are in
+    /// the same order as `regions`.
+    pub fn get_intervals<S: AsRef<str>>(
+        &mut self,
+        regions: impl IntoIterator<Item = (S, u32, u32)>,
+    ) -> Result<Vec<Vec<Value>>, BBIReadError> {
+        let cir_tree = self.full_data_cir_tree()?;
+        let regions = region_chrom_ids(&self.info, self.chrom_aliases.as_deref(), regions)?;
+        let endianness = self.info.header.endianness;
+        get_intervals_batched(
+            &mut self.read,
+            &self.info,
+            cir_tree.1,
+            &regions,
+            self.max_block_gap,
+            |data, chrom, start, end| {
+                decode_block_values(endianness, data, chrom, start, end)
+                    .map(Option::unwrap_or_default)
+            },
+        )
+    }
+
     /// For a given chromosome, start, and end, returns an `Iterator` of the
     /// intersecting `Value`s. The resulting iterator takes this `BigWigRead`
     /// by value.
@@ -317,14 +463,19 @@ where
         start: u32,
         end: u32,
     ) -> Result<impl Iterator<Item = Result<Value, BBIReadError>>, BBIReadError> {
-        let chrom = self.info.chrom_id(chrom_name)?;
         let cir_tree = self.full_data_cir_tree()?;
+        let chrom_name = resolve_chrom(
+            self.chrom_aliases.as_deref(),
+            &self.info.chrom_info,
+            chrom_name,
+        );
+        let chrom = self.info.chrom_id(chrom_name)?;
         let blocks = search_cir_tree(&self.info, &mut self.read, cir_tree, chrom_name, start, end)?;
+        let max_block_gap = self.max_block_gap;
         Ok(IntervalIter {
             r: std::marker::PhantomData,
             bigwig: self,
-            known_offset: 0,
-            blocks: blocks.into_iter(),
+            blocks: BlockDataIter::new(blocks.into_iter(), max_block_gap),
             vals: None,
             chrom,
             start,
@@ -343,7 +494,13 @@ where
     ) -> Result<impl Iterator<Item = Result<ZoomRecord, BBIReadError>> + 'a, ZoomIntervalError>
     {
         let cir_tree = self.zoom_cir_tree(reduction_level)?;
+        let max_block_gap = self.max_block_gap;
 
+        let chrom_name = resolve_chrom(
+            self.chrom_aliases.as_deref(),
+            &self.info.chrom_info,
+            chrom_name,
+        );
         let chrom = self.info.chrom_id(chrom_name)?;
 
         let blocks = search_cir_tree(&self.info, &mut self.read, cir_tree, chrom_name, start, end)?;
@@ -352,7 +509,37 @@ where
             std::vec::IntoIter<Block>,
             BigWigRead<R>,
             &mut BigWigRead<R>,
-        >::new(self, blocks.into_iter(), chrom, start, end))
+        >::new(
+            self,
+            blocks.into_iter(),
+            max_block_gap,
+            chrom,
+            start,
+            end,
+        ))
+    }
+
+    /// For each of the given regions (chromosome, start, end), gets the
+    /// intersecting `ZoomRecord`s of the given reduction level. Like
+    /// `get_intervals`, the index is searched only once per chromosome. The
+    /// results are in the same order as `regions`.
+    pub fn get_zoom_intervals<S: AsRef<str>>(
+        &mut self,
+        regions: impl IntoIterator<Item = (S, u32, u32)>,
+        reduction_level: u32,
+    ) -> Result<Vec<Vec<ZoomRecord>>, ZoomIntervalError> {
+        let cir_tree = self.zoom_cir_tree(reduction_level)?;
+        let regions = region_chrom_ids(&self.info, self.chrom_aliases.as_deref(), regions)?;
+        let endianness = self.info.header.endianness;
+        get_intervals_batched(
+            &mut self.read,
+            &self.info,
+            cir_tree.1,
+            &regions,
+            self.max_block_gap,
+            |data, chrom, start, end| decode_zoom_block(endianness, data, chrom, start, end),
+        )
+        .map_err(ZoomIntervalError::BBIReadError)
     }
 
     /// For a given chromosome, start, and end, returns an `Iterator` of the
@@ -365,7 +552,13 @@ where
         reduction_level: u32,
     ) -> Result<impl Iterator<Item = Result<ZoomRecord, BBIReadError>>, ZoomIntervalError> {
         let cir_tree = self.zoom_cir_tree(reduction_level)?;
+        let max_block_gap = self.max_block_gap;
 
+        let chrom_name = resolve_chrom(
+            self.chrom_aliases.as_deref(),
+            &self.info.chrom_info,
+            chrom_name,
+        );
         let chrom = self.info.chrom_id(chrom_name)?;
 
         let blocks = search_cir_tree(&self.info, &mut self.read, cir_tree, chrom_name, start, end)?;
@@ -373,6 +566,7 @@ where
         Ok(ZoomIntervalIter::new(
             self,
             blocks.into_iter(),
+            max_block_gap,
             chrom,
             start,
             end,
@@ -387,14 +581,22 @@ where
         start: u32,
         end: u32,
     ) -> Result<Vec<f32>, BBIReadError> {
-        let chrom = self.info.chrom_id(chrom_name)?;
         let cir_tree = self.full_data_cir_tree()?;
+        let chrom_name = resolve_chrom(
+            self.chrom_aliases.as_deref(),
+            &self.info.chrom_info,
+            chrom_name,
+        );
+        let chrom = self.info.chrom_id(chrom_name)?;
         let blocks = search_cir_tree(&self.info, &mut self.read, cir_tree, chrom_name, start, end)?;
         let mut values = vec![std::f32::NAN; (end - start) as usize];
-        use crate::utils::tell::Tell;
-        let mut known_offset = self.reader().raw_reader().tell()?;
-        for block in blocks {
-            let block_values = get_block_values(self, block, &mut known_offset, chrom, start, end)?;
+        let endianness = self.info.header.endianness;
+        let blocks_data = self
+            .read
+            .get_blocks_data(&self.info, &blocks, self.max_block_gap)?;
+        for (block, data) in blocks.iter().zip(blocks_data) {
+            let block_values = decode_block_values(endianness, &data, chrom, start, end)
+                .map_err(|e| BBIReadError::InvalidBlock(block.offset, e))?;
             let block_values = match block_values {
                 Some(v) => v,
                 None => continue,
@@ -409,61 +611,97 @@ where
         }
         Ok(values)
     }
+
+    /// Summarizes the values from `start` to `end` on a chromosome
+    /// into `bins` equal bins, using the given `SummaryKind`. Bins without
+    /// data are `None`. If `exact` is `false`, the summaries are estimated
+    /// from a zoom level when there is one with a small enough reduction
+    /// level (see `summary_zoom_level`).
+    pub fn summarize(
+        &mut self,
+        chrom_name: &str,
+        start: u32,
+        end: u32,
+        bins: usize,
+        kind: SummaryKind,
+        exact: bool,
+    ) -> Result<Vec<Option<f64>>, BBIReadError> {
+        let (region_start, region_end) = (start as i64, end as i64);
+        let zoom = match exact {
+            true => None,
+            false => summary_zoom_level(&self.info, region_start, region_end, bins),
+        };
+        match zoom {
+            Some(reduction_level) => {
+                let records = self
+                    .get_zoom_interval(chrom_name, start, end, reduction_level)
+                    .map_err(zoom_read_error)?;
+                summarize_zoom_records(records, region_start, region_end, bins, kind)
+            }
+            None => {
+                let values = self.get_interval(chrom_name, start, end)?;
+                summarize_values(values, region_start, region_end, bins, kind)
+            }
+        }
+    }
 }
 
-fn get_block_values<R: BBIFileRead>(
-    bigwig: &mut BigWigRead<R>,
-    block: Block,
-    known_offset: &mut u64,
+/// Decodes the values overlapping `start` and `end` from the (decompressed)
+/// data of a bigWig block. Returns `None` if the block is for a different
+/// chromosome.
+pub(crate) fn decode_block_values(
+    endianness: Endianness,
+    data: &[u8],
     chrom: u32,
     start: u32,
     end: u32,
-) -> Result<Option<std::vec::IntoIter<Value>>, BBIReadError> {
-    let data = bigwig.read.get_block_data(&bigwig.info, &block)?;
+) -> Result<Option<Vec<Value>>, BlockError> {
     let mut bytes = BytesMut::with_capacity(data.len());
-    bytes.extend_from_slice(&data);
+    bytes.extend_from_slice(data);
 
+    if bytes.len() < 24 {
+        return Err(BlockError::Truncated);
+    }
     let mut bytes_header = bytes.split_to(24);
 
-    let (chrom_id, chrom_start, item_step, item_span, section_type, item_count) =
-        match bigwig.info.header.endianness {
-            Endianness::Big => {
-                let chrom_id = bytes_header.get_u32();
-                let chrom_start = bytes_header.get_u32();
-                let _chrom_end = bytes_header.get_u32();
-                let item_step = bytes_header.get_u32();
-                let item_span = bytes_header.get_u32();
-                let section_type = bytes_header.get_u8();
-                let _reserved = bytes_header.get_u8();
-                let item_count = bytes_header.get_u16();
-                (
-                    chrom_id,
-                    chrom_start,
-                    item_step,
-                    item_span,
-                    section_type,
-                    item_count,
-                )
-            }
-            Endianness::Little => {
-                let chrom_id = bytes_header.get_u32_le();
-                let chrom_start = bytes_header.get_u32_le();
-                let _chrom_end = bytes_header.get_u32_le();
-                let item_step = bytes_header.get_u32_le();
-                let item_span = bytes_header.get_u32_le();
-                let section_type = bytes_header.get_u8();
-                let _reserved = bytes_header.get_u8();
-                let item_count = bytes_header.get_u16_le();
-                (
-                    chrom_id,
-                    chrom_start,
-                    item_step,
-                    item_span,
-                    section_type,
-                    item_count,
-                )
-            }
-        };
+    let (chrom_id, chrom_start, item_step, item_span, section_type, item_count) = match endianness {
+        Endianness::Big => {
+            let chrom_id = bytes_header.get_u32();
+            let chrom_start = bytes_header.get_u32();
+            let _chrom_end = bytes_header.get_u32();
+            let item_step = bytes_header.get_u32();
+            let item_span = bytes_header.get_u32();
+            let section_type = bytes_header.get_u8();
+            let _reserved = bytes_header.get_u8();
+            let item_count = bytes_header.get_u16();
+            (
+                chrom_id,
+                chrom_start,
+                item_step,
+                item_span,
+                section_type,
+                item_count,
+            )
+        }
+        Endianness::Little => {
+            let chrom_id = bytes_header.get_u32_le();
+            let chrom_start = bytes_header.get_u32_le();
+            let _chrom_end = bytes_header.get_u32_le();
+            let item_step = bytes_header.get_u32_le();
+            let item_span = bytes_header.get_u32_le();
+            let section_type = bytes_header.get_u8();
+            let _reserved = bytes_header.get_u8();
+            let item_count = bytes_header.get_u16_le();
+            (
+                chrom_id,
+                chrom_start,
+                item_step,
+                item_span,
+                section_type,
+                item_count,
+            )
+        }
+    };
 
     let mut values: Vec<Value> = Vec::with_capacity(item_count as usize);
 
@@ -471,14 +709,23 @@ fn get_block_values<R: BBIFileRead>(
         return Ok(None);
     }
 
+    let item_size = match section_type {
+        1 => 12,
+        2 => 8,
+        3 => 4,
+        _ => return Err(BlockError::UnknownSectionType(section_type)),
+    };
+    if bytes.len() < (item_count as usize) * item_size {
+        return Err(BlockError::Truncated);
+    }
+
     match section_type {
         1 => {
-            assert!(bytes.len() >= (item_count as usize) * 12);
             for i in 0..(item_count as usize) {
                 let istart = i * 12;
                 let block_item_data: &[u8; 12] = bytes[istart..istart + 12].try_into().unwrap();
                 // bedgraph
-                let (chrom_start, chrom_end, value) = match bigwig.info.header.endianness {
+                let (chrom_start, chrom_end, value) = match endianness {
                     Endianness::Big => {
                         let chrom_start = u32::from_be_bytes([
                             block_item_data[0],
@@ -522,6 +769,9 @@ fn get_block_values<R: BBIFileRead>(
                         (chrom_start, chrom_end, value)
                     }
                 };
+                if chrom_end < chrom_start {
+                    return Err(BlockError::InvalidItem("Item end is before its start."));
+                }
                 let mut value = Value {
                     start: chrom_start,
                     end: chrom_end,
@@ -537,7 +787,7 @@ fn get_block_values<R: BBIFileRead>(
         2 => {
             for _ in 0..item_count {
                 // variable step
-                let (chrom_start, value) = match bigwig.info.header.endianness {
+                let (chrom_start, value) = match endianness {
                     Endianness::Big => {
                         let chrom_start = bytes.get_u32();
                         let value = bytes.get_f32();
@@ -549,7 +799,9 @@ fn get_block_values<R: BBIFileRead>(
                         (chrom_start, value)
                     }
                 };
-                let chrom_end = chrom_start + item_span;
+                let chrom_end = chrom_start
+                    .checked_add(item_span)
+                    .ok_or(BlockError::InvalidItem("Item end is out of range."))?;
                 let mut value = Value {
                     start: chrom_start,
                     end: chrom_end,
@@ -566,7 +818,7 @@ fn get_block_values<R: BBIFileRead>(
             let mut curr_start = chrom_start;
             for _ in 0..item_count {
                 // fixed step
-                let value = match bigwig.info.header.endianness {
+                let value = match endianness {
                     Endianness::Big => {
                         let value = bytes.get_f32();
                         value
@@ -577,8 +829,10 @@ fn get_block_values<R: BBIFileRead>(
                     }
                 };
                 let chrom_start = curr_start;
-                curr_start += item_step;
-                let chrom_end = chrom_start + item_span;
+                curr_start = curr_start.saturating_add(item_step);
+                let chrom_end = chrom_start
+                    .checked_add(item_span)
+                    .ok_or(BlockError::InvalidItem("Item end is out of range."))?;
                 let mut value = Value {
                     start: chrom_start,
                     end: chrom_end,
@@ -591,14 +845,8 @@ fn get_block_values<R: BBIFileRead>(
                 }
             }
         }
-        _ => {
-            return Err(BBIReadError::InvalidFile(format!(
-                "Unknown bigwig section type: {}",
-                section_type
-            )))
-        }
+        _ => unreachable!("section type is checked above"),
     }
 
-    *known_offset = block.offset + block.size;
-    Ok(Some(values.into_iter()))
+    Ok(Some(values))
 }
@@ -61,7 +61,7 @@ use crate::{
 use crate::bbi::{Summary, Value, ZoomRecord, BIGWIG_MAGIC};
 use crate::bbiwrite::{
     self, encode_zoom_section, write_blank_headers, write_zooms, BBIProcessError, BBIWriteOptions,
-    SectionData,
+    SectionData, SectionEncoding,
 };
 
 struct ZoomItem {
@@ -308,8 +308,9 @@ async fn process_val(
     items.push(current_val);
     if next_val.is_none() || items.len() >= options.items_per_slot as usize {
         let items = std::mem::replace(items, Vec::with_capacity(options.items_per_slot as usize));
-        let handle: tokio::task::JoinHandle<io::Result<(SectionData, usize)>> =
-            runtime.spawn(encode_section(options.compress, items, chrom_id));
+        let handle: tokio::task::JoinHandle<io::Result<(SectionData, usize)>> = runtime.spawn(
+            encode_section(options.compress, options.section_encoding, items, chrom_id),
+        );
         ftx.send(handle).await.expect("Couldn't send");
     }
 
@@ -730,8 +731,34 @@ impl<W: Write + Seek + Send + 'static> BBIDataProcessor for BigWigZoomsProcess<W
     }
 }
 
+/// Picks the encoding to use for a section. Returns the section type (as
+/// written in the section header), item step, and item span.
+fn section_type(encoding: SectionEncoding, items: &[Value]) -> (u8, u32, u32) {
+    const BEDGRAPH: (u8, u32, u32) = (1, 0, 0);
+    let span = items[0].end - items[0].start;
+    let same_span = items.iter().all(|item| item.end - item.start == span);
+    if !same_span {
+        return BEDGRAPH;
+    }
+    let step = match items {
+        [_] => span,
+        [first, second, ..] => second.start - first.start,
+        [] => unreachable!(),
+    };
+    let evenly_spaced = items.windows(2).all(|w| w[1].start - w[0].start == step);
+
+    match encoding {
+        SectionEncoding::BedGraph => BEDGRAPH,
+        SectionEncoding::VarStep => (2, 0, span),
+        SectionEncoding::FixedStep | SectionEncoding::Auto if evenly_spaced => (3, step, span),
+        SectionEncoding::FixedStep => BEDGRAPH,
+        SectionEncoding::Auto => (2, 0, span),
+    }
+}
+
 async fn encode_section(
     compress: bool,
+    encoding: SectionEncoding,
     items_in_section: Vec<Value>,
     chrom_id: u32,
 ) -> io::Result<(SectionData, usize)> {
@@ -741,18 +768,27 @@ async fn encode_section(
 
     let start = items_in_section[0].start;
     let end = items_in_section[items_in_section.len() - 1].end;
+    let (section_type, item_step, item_span) = section_type(encoding, &items_in_section);
     bytes.write_u32::<NativeEndian>(chrom_id)?;
     bytes.write_u32::<NativeEndian>(start)?;
     bytes.write_u32::<NativeEndian>(end)?;
-    bytes.write_u32::<NativeEndian>(0)?;
-    bytes.write_u32::<NativeEndian>(0)?;
-    bytes.write_u8(1)?;
+    bytes.write_u32::<NativeEndian>(item_step)?;
+    bytes.write_u32::<NativeEndian>(item_span)?;
+    bytes.write_u8(section_type)?;
     bytes.write_u8(0)?;
     bytes.write_u16::<NativeEndian>(items_in_section.len() as u16)?;
 
     for item in items_in_section.iter() {
-        bytes.write_u32::<NativeEndian>(item.start)?;
-        bytes.write_u32::<NativeEndian>(item.end)?;
+        match section_type {
+            1 => {
+                bytes.write_u32::<NativeEndian>(item.start)?;
+                bytes.write_u32::<NativeEndian>(item.end)?;
+            }
+            2 => {
+                bytes.write_u32::<NativeEndian>(item.start)?;
+            }
+            _ => {}
+        }
         bytes.write_f32::<NativeEndian>(item.value)?;
     }
 
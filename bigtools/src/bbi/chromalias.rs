@@ -0,0 +1,205 @@
+//! Chromosome aliases, so that a chromosome can be queried by any of its names
+//! (e.g. `chr1`, `1`, or `NC_000001.11`), regardless of the naming used in a
+//! bbi file.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek};
+use std::path::Path;
+
+use thiserror::Error;
+
+use crate::bbi::BIGBED_MAGIC;
+use crate::{BBIFileRead, BBIReadError, BigBedRead, BigBedReadOpenError, ChromInfo};
+
+/// Possible errors encountered when loading chromosome aliases
+#[derive(Error, Debug)]
+pub enum ChromAliasesError {
+    #[error("Unable to open chromAlias bigBed: {}", .0)]
+    BigBedOpenError(BigBedReadOpenError),
+    #[error("Unable to read chromAlias bigBed: {}", .0)]
+    BBIReadError(#[from] BBIReadError),
+    #[error("Error occurred: {}", .0)]
+    IoError(#[from] io::Error),
+}
+
+/// Groups of names that each refer to the same chromosome.
+///
+/// Aliases can be attached to a `BigWigRead` or `BigBedRead` (see
+/// `BigWigRead::with_chrom_aliases`), so that queries for any name of a
+/// chromosome are resolved to the name used in the file.
+#[derive(Clone, Debug, Default)]
+pub struct ChromAliases {
+    groups: Vec<Vec<String>>,
+    index: HashMap<String, usize>,
+}
+
+impl ChromAliases {
+    pub fn new() -> Self {
+        ChromAliases::default()
+    }
+
+    /// Adds a group of names that all refer to the same chromosome. If any of
+    /// the names already has aliases, the groups are merged.
+    pub fn add_aliases<S: Into<String>>(&mut self, names: impl IntoIterator<Item = S>) {
+        let names: Vec<String> = names.into_iter().map(Into::into).collect();
+        let group = names.iter().find_map(|name| self.index.get(name).copied());
+        let group = match group {
+            Some(group) => group,
+            None => {
+                self.groups.push(vec![]);
+                self.groups.len() - 1
+            }
+        };
+        for name in names {
+            match self.index.get(&name).copied() {
+                Some(existing) if existing == group => {}
+                Some(existing) => {
+                    // Merge the other group into this one
+                    let other = std::mem::take(&mut self.groups[existing]);
+                    for other_name in other {
+                        self.index.insert(other_name.clone(), group);
+                        self.groups[group].push(other_name);
+                    }
+                }
+                None => {
+                    self.index.insert(name.clone(), group);
+                    self.groups[group].push(name);
+                }
+            }
+        }
+    }
+
+    /// Reads aliases from a UCSC chromAlias.txt file. Each line is a
+    /// tab-separated list of names for one chromosome. Lines starting with `#`
+    /// (like the header) are skipped.
+    pub fn from_txt<R: BufRead>(txt: R) -> io::Result<Self> {
+        let mut aliases = ChromAliases::new();
+        for line in txt.lines() {
+            let line = line?;
+            if line.starts_with('#') {
+                continue;
+            }
+            let names = line.split('\t').map(str::trim).filter(|n| !n.is_empty());
+            aliases.add_aliases(names);
+        }
+        Ok(aliases)
+    }
+
+    /// Reads aliases from a UCSC chromAlias bigBed, where each entry spans a
+    /// chromosome and the remaining fields are its aliases.
+    pub fn from_bigbed<R: BBIFileRead>(bigbed: &mut BigBedRead<R>) -> Result<Self, BBIReadError> {
+        let chroms = bigbed.chroms().to_vec();
+        let mut aliases = ChromAliases::new();
+        for chrom in chroms {
+            let mut names = vec![chrom.name.clone()];
+            for entry in bigbed.get_interval(&chrom.name, 0, chrom.length)? {
+                let entry = entry?;
+                let rest = entry.rest.split('\t').filter(|n| !n.is_empty());
+                names.extend(rest.map(str::to_owned));
+            }
+            aliases.add_aliases(names);
+        }
+        Ok(aliases)
+    }
+
+    /// Reads aliases from either a chromAlias bigBed or a chromAlias.txt file.
+    pub fn open_file(path: impl AsRef<Path>) -> Result<Self, ChromAliasesError> {
+        let mut file = File::open(&path)?;
+        let mut magic = [0u8; 4];
+        let is_bigbed = match file.read_exact(&mut magic) {
+            Ok(()) => {
+                u32::from_le_bytes(magic) == BIGBED_MAGIC
+                    || u32::from_be_bytes(magic) == BIGBED_MAGIC
+            }
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => false,
+            Err(e) => return Err(e.into()),
+        };
+        file.rewind()?;
+        if is_bigbed {
+            let mut bigbed = BigBedRead::open(file).map_err(ChromAliasesError::BigBedOpenError)?;
+            Ok(ChromAliases::from_bigbed(&mut bigbed)?)
+        } else {
+            Ok(ChromAliases::from_txt(BufReader::new(file))?)
+        }
+    }
+
+    /// Gets all the names of the chromosome with the given name (including
+    /// `name` itself), if there are any.
+    pub fn aliases(&self, name: &str) -> Option<&[String]> {
+        self.index.get(name).map(|&group| &*self.groups[group])
+    }
+
+    /// Finds the name in `chroms` that refers to the same chromosome as
+    /// `name`. If `name` is in `chroms`, it is returned as-is.
+    pub fn resolve<'a>(&'a self, chroms: &'a [ChromInfo], name: &'a str) -> Option<&'a str> {
+        if chroms.iter().any(|c| c.name == name) {
+            return Some(name);
+        }
+        self.aliases(name)?
+            .iter()
+            .find(|alias| chroms.iter().any(|c| &c.name == *alias))
+            .map(String::as_str)
+    }
+}
+
+/// Resolves `name` against the chromosomes of a file, falling back to `name`
+/// itself if there are no aliases or no match.
+pub(crate) fn resolve_chrom<'a>(
+    aliases: Option<&'a ChromAliases>,
+    chroms: &'a [ChromInfo],
+    name: &'a str,
+) -> &'a str {
+    aliases
+        .and_then(|aliases| aliases.resolve(chroms, name))
+        .unwrap_or(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aliases() -> io::Result<()> {
+        let txt = "\
+# ucsc\tassembly\tgenbank\trefseq
+chr1\t1\tCM000663.2\tNC_000001.11
+chrM\tMT\t\tNC_012920.1
+";
+        let mut aliases = ChromAliases::from_txt(txt.as_bytes())?;
+        assert_eq!(
+            aliases.aliases("1").unwrap(),
+            ["chr1", "1", "CM000663.2", "NC_000001.11"]
+        );
+        assert_eq!(aliases.aliases("MT").unwrap().len(), 3);
+        assert!(aliases.aliases("chr2").is_none());
+
+        let chroms = [
+            ChromInfo {
+                name: "1".to_string(),
+                length: 100,
+                id: 0,
+            },
+            ChromInfo {
+                name: "chrM".to_string(),
+                length: 100,
+                id: 1,
+            },
+        ];
+        assert_eq!(aliases.resolve(&chroms, "chr1"), Some("1"));
+        assert_eq!(aliases.resolve(&chroms, "NC_000001.11"), Some("1"));
+        assert_eq!(aliases.resolve(&chroms, "1"), Some("1"));
+        assert_eq!(aliases.resolve(&chroms, "MT"), Some("chrM"));
+        assert_eq!(aliases.resolve(&chroms, "chr2"), None);
+        assert_eq!(resolve_chrom(None, &chroms, "chr1"), "chr1");
+        assert_eq!(resolve_chrom(Some(&aliases), &chroms, "chr2"), "chr2");
+
+        // Overlapping groups are merged
+        aliases.add_aliases(["chrM", "M"]);
+        aliases.add_aliases(["M", "chrMT"]);
+        assert_eq!(aliases.aliases("chrMT").unwrap().len(), 5);
+        assert_eq!(aliases.resolve(&chroms, "chrMT"), Some("chrM"));
+
+        Ok(())
+    }
+}
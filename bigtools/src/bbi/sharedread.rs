@@ -0,0 +1,436 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use itertools::Either;
+
+use crate::bbi::chromalias::{resolve_chrom, ChromAliases};
+use crate::bbi::{BBIFile, BedEntry, Value, ZoomRecord};
+use crate::bbiread::{
+    decode_zoom_block, read_cir_tree_header, read_info, search_cir_tree_inner, BBIFileInfo,
+    BBIFileReadInfoError, BBIReadError, Block, BlockDataIter, BlockError, CacheStats,
+    CachedBBIFileRead, ChromInfo, ZoomIntervalError, DEFAULT_CACHE_CAPACITY, DEFAULT_MAX_BLOCK_GAP,
+};
+use crate::bigbedread::decode_block_entries;
+use crate::bigwigread::decode_block_values;
+use crate::{BigBedReadOpenError, BigWigReadOpenError};
+
+/// A source of bytes that can be read at any offset through a shared
+/// reference, without seeking.
+pub trait PositionalRead {
+    /// Reads bytes starting at `offset` into `buf`, returning the number of
+    /// bytes read. Like `Read::read`, this may read fewer bytes than
+    /// `buf.len()`, and returns 0 at the end of the file.
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize>;
+}
+
+#[cfg(unix)]
+impl PositionalRead for File {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        std::os::unix::fs::FileExt::read_at(self, buf, offset)
+    }
+}
+
+#[cfg(windows)]
+impl PositionalRead for File {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        std::os::windows::fs::FileExt::seek_read(self, buf, offset)
+    }
+}
+
+fn read_slice_at(bytes: &[u8], buf: &mut [u8], offset: u64) -> usize {
+    let start = usize::try_from(offset).map_or(bytes.len(), |o| o.min(bytes.len()));
+    let len = buf.len().min(bytes.len() - start);
+    buf[..len].copy_from_slice(&bytes[start..start + len]);
+    len
+}
+
+impl PositionalRead for Bytes {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        Ok(read_slice_at(self, buf, offset))
+    }
+}
+
+impl PositionalRead for Vec<u8> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        Ok(read_slice_at(self, buf, offset))
+    }
+}
+
+/// A `Read` and `Seek` over a shared `PositionalRead`, with its own position.
+/// Seeking from the end is not supported.
+pub struct PositionalReader<'a, R> {
+    read: &'a R,
+    pos: u64,
+}
+
+impl<'a, R: PositionalRead> PositionalReader<'a, R> {
+    pub fn new(read: &'a R, pos: u64) -> Self {
+        PositionalReader { read, pos }
+    }
+}
+
+impl<R: PositionalRead> Read for PositionalReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.read.read_at(buf, self.pos)?;
+        self.pos += read as u64;
+        Ok(read)
+    }
+}
+
+impl<R: PositionalRead> Seek for PositionalReader<'_, R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+            SeekFrom::End(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "Seeking from the end is not supported",
+                ))
+            }
+        };
+        self.pos = pos.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.pos)
+    }
+}
+
+/// The state shared by `SharedBigWigRead` and `SharedBigBedRead`
+struct SharedBBIRead<R> {
+    info: BBIFileInfo,
+    read: CachedBBIFileRead<R>,
+    chrom_aliases: Option<Arc<ChromAliases>>,
+    max_block_gap: u64,
+}
+
+impl<R: PositionalRead> SharedBBIRead<R> {
+    fn open(read: R, capacity: usize) -> Result<(BBIFile, Self), BBIFileReadInfoError> {
+        let info = read_info(&mut PositionalReader::new(&read, 0))?;
+        let shared = SharedBBIRead {
+            info,
+            read: CachedBBIFileRead::with_capacity(read, capacity),
+            chrom_aliases: None,
+            max_block_gap: DEFAULT_MAX_BLOCK_GAP,
+        };
+        Ok((shared.info.filetype, shared))
+    }
+
+    /// A reader for a single query, which shares the cache
+    fn reader(&self) -> CachedBBIFileRead<PositionalReader<'_, R>> {
+        self.read
+            .with_read(PositionalReader::new(self.read.inner_read(), 0))
+    }
+
+    /// Checks the header of the cir tree at `index_offset`, returning the
+    /// offset of its root node
+    fn cir_tree(&self, index_offset: u64) -> Result<u64, BBIReadError> {
+        let mut reader = PositionalReader::new(self.read.inner_read(), index_offset);
+        read_cir_tree_header(self.info.header.endianness, &mut reader).map_err(|e| match e {
+            Either::Left(_) => BBIReadError::UnknownMagic,
+            Either::Right(e) => e.into(),
+        })?;
+        Ok(index_offset + 48)
+    }
+
+    fn zoom_cir_tree(&self, reduction_level: u32) -> Result<u64, ZoomIntervalError> {
+        let zoom_header = self
+            .info
+            .zoom_headers
+            .iter()
+            .find(|h| h.reduction_level == reduction_level)
+            .ok_or(ZoomIntervalError::ReductionLevelNotFound)?;
+        self.cir_tree(zoom_header.index_offset)
+            .map_err(ZoomIntervalError::BBIReadError)
+    }
+
+    /// Finds the blocks in the cir tree with the root node `root` overlapping
+    /// a region, returning them with the id of the chromosome
+    fn search(
+        &self,
+        read: &mut CachedBBIFileRead<PositionalReader<'_, R>>,
+        root: u64,
+        chrom_name: &str,
+        start: u32,
+        end: u32,
+    ) -> Result<(u32, Vec<Block>), BBIReadError> {
+        let chrom_name = resolve_chrom(
+            self.chrom_aliases.as_deref(),
+            &self.info.chrom_info,
+            chrom_name,
+        );
+        let chrom = self.info.chrom_id(chrom_name)?;
+        let endianness = self.info.header.endianness;
+        let blocks = search_cir_tree_inner(endianness, read, root, chrom, start, end)?;
+        Ok((chrom, blocks))
+    }
+
+    /// Lazily reads and decodes the items of `blocks`
+    fn decode_blocks<'a, T: 'a>(
+        &'a self,
+        mut read: CachedBBIFileRead<PositionalReader<'a, R>>,
+        blocks: Vec<Block>,
+        decode: impl Fn(&[u8]) -> Result<Vec<T>, BlockError> + 'a,
+    ) -> impl Iterator<Item = Result<T, BBIReadError>> + 'a {
+        let mut blocks = BlockDataIter::new(blocks.into_iter(), self.max_block_gap);
+        std::iter::from_fn(move || blocks.next(&mut read, &self.info)).flat_map(move |data| {
+            let items = data.map_err(BBIReadError::from).and_then(|(block, data)| {
+                decode(&data).map_err(|e| BBIReadError::InvalidBlock(block.offset, e))
+            });
+            match items {
+                Ok(items) => Either::Left(items.into_iter().map(Ok)),
+                Err(e) => Either::Right(std::iter::once(Err(e))),
+            }
+        })
+    }
+
+    fn get_zoom_interval<'a>(
+        &'a self,
+        chrom_name: &str,
+        start: u32,
+        end: u32,
+        reduction_level: u32,
+    ) -> Result<impl Iterator<Item = Result<ZoomRecord, BBIReadError>> + 'a, ZoomIntervalError>
+    {
+        let root = self.zoom_cir_tree(reduction_level)?;
+        let mut read = self.reader();
+        let (chrom, blocks) = self
+            .search(&mut read, root, chrom_name, start, end)
+            .map_err(ZoomIntervalError::BBIReadError)?;
+        let endianness = self.info.header.endianness;
+        Ok(self.decode_blocks(read, blocks, move |data| {
+            decode_zoom_block(endianness, data, chrom, start, end)
+        }))
+    }
+}
+
+/// A bigWig reader that can be queried through a shared reference, and so
+/// from many threads at once.
+///
+/// `BigWigRead` takes `&mut self` for every query, since the underlying
+/// reader seeks. Instead, this reads with positional reads (see
+/// `PositionalRead`), and all queries share the parsed `BBIFileInfo` and a
+/// cache of index nodes and block data.
+///
+/// ```rust,no_run
+/// # use std::error::Error;
+/// # use std::sync::Arc;
+/// # use bigtools::SharedBigWigRead;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// let bwread = Arc::new(SharedBigWigRead::open_file("test.bigWig")?);
+/// let handles: Vec<_> = (0..4)
+///     .map(|i| {
+///         let bwread = bwread.clone();
+///         std::thread::spawn(move || {
+///             let start = i * 1_000_000;
+///             let values = bwread.get_interval("chr1", start, start + 1_000_000)?;
+///             Ok::<_, bigtools::BBIReadError>(values.count())
+///         })
+///     })
+///     .collect();
+/// for handle in handles {
+///     handle.join().unwrap()?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct SharedBigWigRead<R> {
+    inner: SharedBBIRead<R>,
+}
+
+impl SharedBigWigRead<File> {
+    /// Opens a new `SharedBigWigRead` from a given path as a file.
+    pub fn open_file(path: impl AsRef<Path>) -> Result<Self, BigWigReadOpenError> {
+        SharedBigWigRead::open(File::open(path)?)
+    }
+}
+
+impl<R: PositionalRead> SharedBigWigRead<R> {
+    /// Opens a new `SharedBigWigRead`, with a cache of up to
+    /// `DEFAULT_CACHE_CAPACITY` bytes.
+    pub fn open(read: R) -> Result<Self, BigWigReadOpenError> {
+        SharedBigWigRead::open_with_capacity(read, DEFAULT_CACHE_CAPACITY)
+    }
+
+    /// Like `open`, but caches at most `capacity` bytes of index and block
+    /// data.
+    pub fn open_with_capacity(read: R, capacity: usize) -> Result<Self, BigWigReadOpenError> {
+        let (filetype, inner) = SharedBBIRead::open(read, capacity)?;
+        match filetype {
+            BBIFile::BigWig => Ok(SharedBigWigRead { inner }),
+            _ => Err(BigWigReadOpenError::NotABigWig),
+        }
+    }
+
+    /// Get basic info about this bigWig
+    pub fn info(&self) -> &BBIFileInfo {
+        &self.inner.info
+    }
+
+    /// Gets the chromosomes present in this bigWig
+    pub fn chroms(&self) -> &[ChromInfo] {
+        &self.inner.info.chrom_info
+    }
+
+    /// Sets the aliases used to resolve chromosome names in queries. See
+    /// `BigWigRead::with_chrom_aliases`.
+    pub fn with_chrom_aliases(mut self, chrom_aliases: Arc<ChromAliases>) -> Self {
+        self.inner.chrom_aliases = Some(chrom_aliases);
+        self
+    }
+
+    /// Sets the maximum gap (in bytes) between blocks for them to be read
+    /// together. See `BigWigRead::with_max_block_gap`.
+    pub fn with_max_block_gap(mut self, max_block_gap: u64) -> Self {
+        self.inner.max_block_gap = max_block_gap;
+        self
+    }
+
+    /// Returns the hit and miss counts and current size of the shared cache
+    pub fn cache_stats(&self) -> CacheStats {
+        self.inner.read.cache_stats()
+    }
+
+    /// For a given chromosome, start, and end, returns an `Iterator` of the
+    /// intersecting `Value`s.
+    pub fn get_interval<'a>(
+        &'a self,
+        chrom_name: &str,
+        start: u32,
+        end: u32,
+    ) -> Result<impl Iterator<Item = Result<Value, BBIReadError>> + 'a, BBIReadError> {
+        let inner = &self.inner;
+        let root = inner.cir_tree(inner.info.header.full_index_offset)?;
+        let mut read = inner.reader();
+        let (chrom, blocks) = inner.search(&mut read, root, chrom_name, start, end)?;
+        let endianness = inner.info.header.endianness;
+        Ok(inner.decode_blocks(read, blocks, move |data| {
+            decode_block_values(endianness, data, chrom, start, end).map(Option::unwrap_or_default)
+        }))
+    }
+
+    /// For a given chromosome, start, and end, returns an `Iterator` of the
+    /// intersecting `ZoomRecord`s.
+    pub fn get_zoom_interval<'a>(
+        &'a self,
+        chrom_name: &str,
+        start: u32,
+        end: u32,
+        reduction_level: u32,
+    ) -> Result<impl Iterator<Item = Result<ZoomRecord, BBIReadError>> + 'a, ZoomIntervalError>
+    {
+        self.inner
+            .get_zoom_interval(chrom_name, start, end, reduction_level)
+    }
+
+    /// Returns the values between `start` and `end` as a `Vec<f32>`. Any
+    /// positions with no data in the bigWig will be `std::f32::NAN`.
+    pub fn values(&self, chrom_name: &str, start: u32, end: u32) -> Result<Vec<f32>, BBIReadError> {
+        let mut values = vec![f32::NAN; (end - start) as usize];
+        for value in self.get_interval(chrom_name, start, end)? {
+            let value = value?;
+            let value_start = (value.start.max(start) - start) as usize;
+            let value_end = (value.end.min(end) - start) as usize;
+            values[value_start..value_end].fill(value.value);
+        }
+        Ok(values)
+    }
+}
+
+/// A bigBed reader that can be queried through a shared reference, and so
+/// from many threads at once. See `SharedBigWigRead`.
+pub struct SharedBigBedRead<R> {
+    inner: SharedBBIRead<R>,
+}
+
+impl SharedBigBedRead<File> {
+    /// Opens a new `SharedBigBedRead` from a given path as a file.
+    pub fn open_file(path: impl AsRef<Path>) -> Result<Self, BigBedReadOpenError> {
+        SharedBigBedRead::open(File::open(path)?)
+    }
+}
+
+impl<R: PositionalRead> SharedBigBedRead<R> {
+    /// Opens a new `SharedBigBedRead`, with a cache of up to
+    /// `DEFAULT_CACHE_CAPACITY` bytes.
+    pub fn open(read: R) -> Result<Self, BigBedReadOpenError> {
+        SharedBigBedRead::open_with_capacity(read, DEFAULT_CACHE_CAPACITY)
+    }
+
+    /// Like `open`, but caches at most `capacity` bytes of index and block
+    /// data.
+    pub fn open_with_capacity(read: R, capacity: usize) -> Result<Self, BigBedReadOpenError> {
+        let (filetype, inner) = SharedBBIRead::open(read, capacity)?;
+        match filetype {
+            BBIFile::BigBed => Ok(SharedBigBedRead { inner }),
+            _ => Err(BigBedReadOpenError::NotABigBed),
+        }
+    }
+
+    /// Get basic info about this bigBed
+    pub fn info(&self) -> &BBIFileInfo {
+        &self.inner.info
+    }
+
+    /// Gets the chromosomes present in this bigBed
+    pub fn chroms(&self) -> &[ChromInfo] {
+        &self.inner.info.chrom_info
+    }
+
+    /// Sets the aliases used to resolve chromosome names in queries. See
+    /// `BigBedRead::with_chrom_aliases`.
+    pub fn with_chrom_aliases(mut self, chrom_aliases: Arc<ChromAliases>) -> Self {
+        self.inner.chrom_aliases = Some(chrom_aliases);
+        self
+    }
+
+    /// Sets the maximum gap (in bytes) between blocks for them to be read
+    /// together. See `BigBedRead::with_max_block_gap`.
+    pub fn with_max_block_gap(mut self, max_block_gap: u64) -> Self {
+        self.inner.max_block_gap = max_block_gap;
+        self
+    }
+
+    /// Returns the hit and miss counts and current size of the shared cache
+    pub fn cache_stats(&self) -> CacheStats {
+        self.inner.read.cache_stats()
+    }
+
+    /// For a given chromosome, start, and end, returns an `Iterator` of the
+    /// intersecting `BedEntry`s.
+    pub fn get_interval<'a>(
+        &'a self,
+        chrom_name: &str,
+        start: u32,
+        end: u32,
+    ) -> Result<impl Iterator<Item = Result<BedEntry, BBIReadError>> + 'a, BBIReadError> {
+        let inner = &self.inner;
+        let root = inner.cir_tree(inner.info.header.full_index_offset)?;
+        let mut read = inner.reader();
+        let (chrom, blocks) = inner.search(&mut read, root, chrom_name, start, end)?;
+        let endianness = inner.info.header.endianness;
+        Ok(inner.decode_blocks(read, blocks, move |data| {
+            decode_block_entries(endianness, data, chrom, start, end)
+        }))
+    }
+
+    /// For a given chromosome, start, and end, returns an `Iterator` of the
+    /// intersecting `ZoomRecord`s.
+    pub fn get_zoom_interval<'a>(
+        &'a self,
+        chrom_name: &str,
+        start: u32,
+        end: u32,
+        reduction_level: u32,
+    ) -> Result<impl Iterator<Item = Result<ZoomRecord, BBIReadError>> + 'a, ZoomIntervalError>
+    {
+        self.inner
+            .get_zoom_interval(chrom_name, start, end, reduction_level)
+    }
+}
@@ -0,0 +1,559 @@
+//! Binned summaries (mean, min, max, sum, coverage, and standard deviation)
+//! of a region of a bigWig or bigBed.
+//!
+//! The summaries are either computed exactly from the full data, or estimated
+//! from the zoom level with the largest reduction level that is still at most
+//! half the bin size (see `summary_zoom_level`).
+//!
+//! For bigBeds, the "value" at a base is the number of entries overlapping
+//! that base (the item density), not any particular score.
+//!
+//! In addition to `BigWigRead::summarize` and `BigBedRead::summarize`, the
+//! binning functions (`summarize_values`, etc.) are exposed for regions that
+//! extend past the ends of a chromosome (e.g. start before 0). Bins (or parts
+//! of bins) outside of the chromosome are considered to have no data.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::bbi::{BedEntry, Value, ZoomRecord};
+use crate::bbiread::{BBIFileInfo, BBIReadError, ZoomIntervalError};
+
+/// The statistic to calculate for each bin
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SummaryKind {
+    /// The mean over the covered bases
+    Mean,
+    /// The minimum value. For bigBeds, this is 0 if any base is uncovered.
+    Min,
+    /// The maximum value
+    Max,
+    /// The sum of the value at each covered base
+    Sum,
+    /// The fraction of bases that are covered
+    Coverage,
+    /// The (sample) standard deviation over the covered bases
+    Std,
+}
+
+#[derive(Error, Debug)]
+#[error("Unknown summary: `{}`. Expected one of `mean`, `min`, `max`, `sum`, `coverage`, or `std`.", .0)]
+pub struct UnknownSummaryKind(pub String);
+
+impl FromStr for SummaryKind {
+    type Err = UnknownSummaryKind;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "mean" => SummaryKind::Mean,
+            "min" => SummaryKind::Min,
+            "max" => SummaryKind::Max,
+            "sum" => SummaryKind::Sum,
+            "coverage" => SummaryKind::Coverage,
+            "std" => SummaryKind::Std,
+            _ => return Err(UnknownSummaryKind(s.to_string())),
+        })
+    }
+}
+
+/// Picks the zoom level to estimate `bins` bins over `start` to `end` from:
+/// the one with the largest reduction level that is at most half of the bin
+/// size. Returns `None` if no zoom level is small enough.
+pub fn summary_zoom_level(info: &BBIFileInfo, start: i64, end: i64, bins: usize) -> Option<u32> {
+    let max_zoom_size = ((end - start) as f32 / (bins * 2) as f32) as u32;
+    info.zoom_headers
+        .iter()
+        .map(|z| z.reduction_level)
+        .filter(|&r| r <= max_zoom_size)
+        .max()
+}
+
+/// Converts the error from reading a zoom level that is known to exist
+pub(crate) fn zoom_read_error(e: ZoomIntervalError) -> BBIReadError {
+    match e {
+        ZoomIntervalError::BBIReadError(e) => e,
+        ZoomIntervalError::ReductionLevelNotFound => {
+            BBIReadError::InvalidFile("Zoom level not found.".to_owned())
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct BinStats {
+    // The total overlap and the overlap-weighted values, for the mean
+    weight: f64,
+    weighted_sum: f64,
+    min: f64,
+    max: f64,
+    // The (estimated, for zoom data) covered bases, sum, and sum of squares
+    covered: f64,
+    sum: f64,
+    sum_squares: f64,
+}
+
+impl Default for BinStats {
+    fn default() -> Self {
+        BinStats {
+            weight: 0.0,
+            weighted_sum: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            covered: 0.0,
+            sum: 0.0,
+            sum_squares: 0.0,
+        }
+    }
+}
+
+struct Bins {
+    start: i64,
+    bin_size: f64,
+    stats: Vec<BinStats>,
+}
+
+impl Bins {
+    fn new(start: i64, end: i64, bins: usize) -> Self {
+        Bins {
+            start,
+            bin_size: (end - start) as f64 / bins as f64,
+            stats: vec![BinStats::default(); bins],
+        }
+    }
+
+    fn bin_bounds(&self, bin: usize) -> (i64, i64) {
+        let bin_start = (bin as f64 * self.bin_size) as i64;
+        let bin_end = ((bin + 1) as f64 * self.bin_size) as i64;
+        (self.start + bin_start, self.start + bin_end)
+    }
+
+    /// The first bin that may overlap `start`
+    fn first_bin(&self, start: i64) -> usize {
+        let offset = (start - self.start).max(0);
+        // Rounding can make this off by one, so start a bin early
+        ((offset as f64 / self.bin_size) as usize).saturating_sub(1)
+    }
+
+    /// Calls `add` with the stats and overlap size of each bin overlapping
+    /// `start` to `end`.
+    fn add(&mut self, start: i64, end: i64, mut add: impl FnMut(&mut BinStats, f64)) {
+        for bin in self.first_bin(start)..self.stats.len() {
+            let (bin_start, bin_end) = self.bin_bounds(bin);
+            if bin_start >= end {
+                break;
+            }
+            let overlap = bin_end.min(end) - bin_start.max(start);
+            if overlap > 0 {
+                add(&mut self.stats[bin], overlap as f64);
+            }
+        }
+    }
+
+    fn add_value(&mut self, start: i64, end: i64, value: f64) {
+        self.add(start, end, |stats, overlap| {
+            stats.weight += overlap;
+            stats.weighted_sum += overlap * value;
+            stats.min = stats.min.min(value);
+            stats.max = stats.max.max(value);
+            stats.covered += overlap;
+            stats.sum += overlap * value;
+            stats.sum_squares += overlap * value * value;
+        });
+    }
+
+    fn add_zoom_record(&mut self, record: &ZoomRecord) {
+        let summary = &record.summary;
+        if summary.bases_covered == 0 || record.end <= record.start {
+            return;
+        }
+        let span = (record.end - record.start) as f64;
+        let mean = summary.sum / summary.bases_covered as f64;
+        self.add(record.start as i64, record.end as i64, |stats, overlap| {
+            // The mean is weighted by the overlap with the record, while the
+            // other stats are scaled by the fraction of the record overlapped
+            let fraction = overlap / span;
+            stats.weight += overlap;
+            stats.weighted_sum += overlap * mean;
+            stats.min = stats.min.min(summary.min_val);
+            stats.max = stats.max.max(summary.max_val);
+            stats.covered += summary.bases_covered as f64 * fraction;
+            stats.sum += summary.sum * fraction;
+            stats.sum_squares += summary.sum_squares * fraction;
+        });
+    }
+
+    /// Gets the summary of each bin, or `None` for bins without data. If
+    /// `uncovered_is_zero`, then uncovered bases have a value of 0 (for the
+    /// minimum).
+    fn finish(self, kind: SummaryKind, uncovered_is_zero: bool) -> Vec<Option<f64>> {
+        (0..self.stats.len())
+            .map(|bin| {
+                let stats = &self.stats[bin];
+                if stats.weight == 0.0 {
+                    return None;
+                }
+                let (bin_start, bin_end) = self.bin_bounds(bin);
+                let bin_size = (bin_end - bin_start) as f64;
+                Some(match kind {
+                    SummaryKind::Mean => stats.weighted_sum / stats.weight,
+                    SummaryKind::Min if uncovered_is_zero && stats.covered < bin_size => 0.0,
+                    SummaryKind::Min => stats.min,
+                    SummaryKind::Max => stats.max,
+                    SummaryKind::Sum => stats.sum,
+                    SummaryKind::Coverage => (stats.covered / bin_size).min(1.0),
+                    SummaryKind::Std => {
+                        let n = stats.covered;
+                        if n > 1.0 {
+                            let var = (stats.sum_squares - stats.sum * stats.sum / n) / (n - 1.0);
+                            var.max(0.0).sqrt()
+                        } else {
+                            0.0
+                        }
+                    }
+                })
+            })
+            .collect()
+    }
+}
+
+/// The sums of the means of the zoom records overlapping each base, for the
+/// mean item density of bigBed zoom data. Rather than weighting the mean of
+/// each record by its overlap (like `Bins`), the means are summed base by base
+/// and then over the bases of each bin, which keeps the rounding of the
+/// estimates the same as earlier versions.
+struct BaseSums {
+    bins: Bins,
+    // The bins that records may still be added to, with the sum at each base
+    // (or NaN if no record overlaps it)
+    active: VecDeque<(usize, Vec<f64>)>,
+    means: Vec<Option<f64>>,
+}
+
+impl BaseSums {
+    fn new(start: i64, end: i64, bins: usize) -> Self {
+        BaseSums {
+            bins: Bins::new(start, end, bins),
+            active: VecDeque::new(),
+            means: vec![None; bins],
+        }
+    }
+
+    /// Adds a zoom record. Records must be sorted by start.
+    fn add_zoom_record(&mut self, record: &ZoomRecord) {
+        let summary = &record.summary;
+        if summary.bases_covered == 0 || record.end <= record.start {
+            return;
+        }
+        let mean = summary.sum / summary.bases_covered as f64;
+        let (start, end) = (record.start as i64, record.end as i64);
+        // Bins that end before this record can't overlap any later records
+        while let Some(&(bin, _)) = self.active.front() {
+            if self.bins.bin_bounds(bin).1 > start {
+                break;
+            }
+            let (bin, sums) = self.active.pop_front().unwrap();
+            self.finish_bin(bin, sums);
+        }
+        for bin in self.bins.first_bin(start)..self.bins.stats.len() {
+            let (bin_start, bin_end) = self.bins.bin_bounds(bin);
+            if bin_start >= end {
+                break;
+            }
+            let (overlap_start, overlap_end) = (bin_start.max(start), bin_end.min(end));
+            if overlap_end <= overlap_start {
+                continue;
+            }
+            let index = match self.active.iter().position(|&(b, _)| b == bin) {
+                Some(index) => index,
+                None => {
+                    let sums = vec![f64::NAN; (bin_end - bin_start) as usize];
+                    self.active.push_back((bin, sums));
+                    self.active.len() - 1
+                }
+            };
+            let range = (overlap_start - bin_start) as usize..(overlap_end - bin_start) as usize;
+            for sum in &mut self.active[index].1[range] {
+                *sum = sum.max(0.0) + mean;
+            }
+        }
+    }
+
+    fn finish_bin(&mut self, bin: usize, sums: Vec<f64>) {
+        let covered = sums.iter().filter(|sum| !sum.is_nan()).count();
+        if covered > 0 {
+            let sum = sums.into_iter().map(|sum| sum.max(0.0)).sum::<f64>();
+            self.means[bin] = Some(sum / covered as f64);
+        }
+    }
+
+    /// Gets the mean of each bin, or `None` for bins without data
+    fn finish(mut self) -> Vec<Option<f64>> {
+        while let Some((bin, sums)) = self.active.pop_front() {
+            self.finish_bin(bin, sums);
+        }
+        self.means
+    }
+}
+
+/// Summarizes bigWig `values` into `bins` equal bins over `start` to `end`.
+/// Values outside of the region are ignored.
+pub fn summarize_values<I: Iterator<Item = Result<Value, BBIReadError>>>(
+    values: I,
+    start: i64,
+    end: i64,
+    bins: usize,
+    kind: SummaryKind,
+) -> Result<Vec<Option<f64>>, BBIReadError> {
+    let mut binned = Bins::new(start, end, bins);
+    for value in values {
+        let value = value?;
+        binned.add_value(value.start as i64, value.end as i64, value.value as f64);
+    }
+    Ok(binned.finish(kind, false))
+}
+
+/// Summarizes bigWig zoom `records` into `bins` equal bins over `start` to
+/// `end`. The summary of a part of a zoom record is estimated by assuming
+/// values are evenly distributed within the record.
+pub fn summarize_zoom_records<I: Iterator<Item = Result<ZoomRecord, BBIReadError>>>(
+    records: I,
+    start: i64,
+    end: i64,
+    bins: usize,
+    kind: SummaryKind,
+) -> Result<Vec<Option<f64>>, BBIReadError> {
+    let mut binned = Bins::new(start, end, bins);
+    for record in records {
+        binned.add_zoom_record(&record?);
+    }
+    Ok(binned.finish(kind, false))
+}
+
+/// Summarizes the item density of (sorted) bigBed `entries` into `bins` equal
+/// bins over `start` to `end`.
+pub fn summarize_entries<I: Iterator<Item = Result<BedEntry, BBIReadError>>>(
+    entries: I,
+    start: i64,
+    end: i64,
+    bins: usize,
+    kind: SummaryKind,
+) -> Result<Vec<Option<f64>>, BBIReadError> {
+    let mut binned = Bins::new(start, end, bins);
+    // Sweep over the entries, keeping the ends of the ones overlapping `pos`,
+    // to find the segments with a constant number of entries.
+    let mut ends: BinaryHeap<Reverse<i64>> = BinaryHeap::new();
+    let mut pos = i64::MIN;
+    let mut flush_until = |ends: &mut BinaryHeap<Reverse<i64>>, pos: &mut i64, until: i64| {
+        while let Some(&Reverse(next_end)) = ends.peek() {
+            if next_end > until {
+                break;
+            }
+            if next_end > *pos {
+                binned.add_value(*pos, next_end, ends.len() as f64);
+                *pos = next_end;
+            }
+            ends.pop();
+        }
+        if !ends.is_empty() && until > *pos {
+            binned.add_value(*pos, until, ends.len() as f64);
+        }
+        *pos = (*pos).max(until);
+    };
+    for entry in entries {
+        let entry = entry?;
+        let (entry_start, entry_end) = (entry.start as i64, entry.end as i64);
+        flush_until(&mut ends, &mut pos, entry_start);
+        ends.push(Reverse(entry_end));
+    }
+    flush_until(&mut ends, &mut pos, i64::MAX);
+    Ok(binned.finish(kind, true))
+}
+
+/// Summarizes the item density from bigBed zoom `records` into `bins` equal
+/// bins over `start` to `end`. Like `summarize_zoom_records`, values are
+/// assumed to be evenly distributed within each record. The records must be
+/// sorted by start.
+pub fn summarize_entry_zoom_records<I: Iterator<Item = Result<ZoomRecord, BBIReadError>>>(
+    records: I,
+    start: i64,
+    end: i64,
+    bins: usize,
+    kind: SummaryKind,
+) -> Result<Vec<Option<f64>>, BBIReadError> {
+    if kind == SummaryKind::Mean {
+        let mut sums = BaseSums::new(start, end, bins);
+        for record in records {
+            sums.add_zoom_record(&record?);
+        }
+        return Ok(sums.finish());
+    }
+    let mut binned = Bins::new(start, end, bins);
+    for record in records {
+        binned.add_zoom_record(&record?);
+    }
+    Ok(binned.finish(kind, true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(entries: &[(u32, u32)]) -> impl Iterator<Item = Result<BedEntry, BBIReadError>> {
+        entries
+            .iter()
+            .map(|&(start, end)| {
+                Ok(BedEntry {
+                    start,
+                    end,
+                    rest: "".to_string(),
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn test_summarize_entries() -> Result<(), BBIReadError> {
+        use SummaryKind::*;
+
+        assert_eq!(summarize_entries(entries(&[]), 10, 20, 1, Mean)?, [None]);
+        assert_eq!(
+            summarize_entries(entries(&[(10, 20)]), 10, 20, 1, Mean)?,
+            [Some(1.0)]
+        );
+        assert_eq!(
+            summarize_entries(entries(&[(10, 20)]), 10, 20, 2, Mean)?,
+            [Some(1.0), Some(1.0)]
+        );
+
+        let two = [(10, 20), (15, 20)];
+        assert_eq!(
+            summarize_entries(entries(&two), 10, 20, 2, Mean)?,
+            [Some(1.0), Some(2.0)]
+        );
+        assert_eq!(
+            summarize_entries(entries(&two), 10, 20, 1, Min)?,
+            [Some(1.0)]
+        );
+        assert_eq!(
+            summarize_entries(entries(&two), 10, 20, 1, Max)?,
+            [Some(2.0)]
+        );
+        assert_eq!(
+            summarize_entries(entries(&two), 10, 20, 1, Sum)?,
+            [Some(15.0)]
+        );
+        assert_eq!(
+            summarize_entries(entries(&two), 10, 20, 1, Coverage)?,
+            [Some(1.0)]
+        );
+
+        let three = [(10, 20), (15, 20), (15, 25)];
+        assert_eq!(
+            summarize_entries(entries(&three), 10, 20, 2, Mean)?,
+            [Some(1.0), Some(3.0)]
+        );
+        assert_eq!(
+            summarize_entries(entries(&three), 10, 20, 1, Min)?,
+            [Some(1.0)]
+        );
+        assert_eq!(
+            summarize_entries(entries(&three), 10, 20, 1, Max)?,
+            [Some(3.0)]
+        );
+
+        // Uncovered bases count as 0 for the minimum, but not the mean
+        let partial = [(12, 14), (16, 18)];
+        assert_eq!(
+            summarize_entries(entries(&partial), 10, 20, 1, Mean)?,
+            [Some(1.0)]
+        );
+        assert_eq!(
+            summarize_entries(entries(&partial), 10, 20, 1, Min)?,
+            [Some(0.0)]
+        );
+        assert_eq!(
+            summarize_entries(entries(&partial), 10, 20, 1, Coverage)?,
+            [Some(0.4)]
+        );
+        assert_eq!(
+            summarize_entries(entries(&partial), 10, 20, 5, Mean)?,
+            [None, Some(1.0), None, Some(1.0), None]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_summarize_entry_zoom_records() -> Result<(), BBIReadError> {
+        let record = |start, end, bases_covered, sum| {
+            Ok(ZoomRecord {
+                chrom: 0,
+                start,
+                end,
+                summary: crate::Summary {
+                    total_items: 0,
+                    bases_covered,
+                    min_val: 1.0,
+                    max_val: 1.0,
+                    sum,
+                    sum_squares: sum,
+                },
+            })
+        };
+        let records = || {
+            [
+                record(100, 4011, 3911, 3911.0),
+                record(4011, 6100, 490, 852.0),
+                record(6200, 6300, 100, 200.0),
+            ]
+            .into_iter()
+        };
+        // The means are summed base by base, so this isn't quite the exact
+        // (3911 * 1.0 + 2089 * 852 / 490) / 6000
+        assert_eq!(
+            summarize_entry_zoom_records(records().take(2), 0, 40000, 1, SummaryKind::Mean)?,
+            [Some(1.2572170068028603)]
+        );
+        assert_eq!(
+            summarize_entry_zoom_records(records().skip(2), 0, 12000, 3, SummaryKind::Mean)?,
+            [None, Some(2.0), None]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_summarize_values() -> Result<(), BBIReadError> {
+        use SummaryKind::*;
+
+        let values = || {
+            [(0, 5, 1.0), (5, 10, 3.0), (15, 20, 2.0)]
+                .into_iter()
+                .map(|(start, end, value)| Ok(Value { start, end, value }))
+        };
+        assert_eq!(summarize_values(values(), 0, 20, 1, Mean)?, [Some(2.0)]);
+        assert_eq!(summarize_values(values(), 0, 20, 1, Min)?, [Some(1.0)]);
+        assert_eq!(summarize_values(values(), 0, 20, 1, Max)?, [Some(3.0)]);
+        assert_eq!(summarize_values(values(), 0, 20, 1, Sum)?, [Some(30.0)]);
+        assert_eq!(
+            summarize_values(values(), 0, 20, 1, Coverage)?,
+            [Some(0.75)]
+        );
+        let std = summarize_values(values(), 0, 20, 1, Std)?[0].unwrap();
+        assert!((std - (10.0f64 / 14.0).sqrt()).abs() < 1e-9);
+        assert_eq!(
+            summarize_values(values(), 0, 20, 4, Mean)?,
+            [Some(1.0), Some(3.0), None, Some(2.0)]
+        );
+        // Regions can extend past the start of the chromosome
+        assert_eq!(
+            summarize_values(values(), -10, 10, 4, Mean)?,
+            [None, None, Some(1.0), Some(3.0)]
+        );
+
+        Ok(())
+    }
+}
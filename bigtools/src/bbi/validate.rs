@@ -0,0 +1,1449 @@
+//! Deep integrity checks for bigWig and bigBed files.
+//!
+//! Unlike opening a file with `BigWigRead` or `BigBedRead`, which only reads
+//! the parts of a file needed for a given query (and assumes they are
+//! well-formed), `validate` walks the entire file: the header, the chromosome
+//! B+ tree, the full data R-tree and every zoom level's R-tree. Every block is
+//! decompressed and decoded, and the total summary is recomputed from the
+//! full data and compared to the one stored in the file.
+//!
+//! Rather than stopping at the first problem, every problem found is recorded
+//! along with the byte offset in the file it was found at. For problems inside
+//! of a (compressed) data block, this is the offset of the block.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fmt;
+use std::io::{self, Read, Seek, SeekFrom};
+
+use byteordered::Endianness;
+use bytes::Buf;
+use libdeflater::Decompressor;
+
+use crate::bbi::{
+    BBIFile, Summary, BIGBED_MAGIC, BIGWIG_MAGIC, CHROM_TREE_MAGIC, CIR_TREE_MAGIC,
+    MAX_UNCOMPRESS_BUF_SIZE,
+};
+
+/// The maximum depth of a B+ tree or R-tree before it is assumed to be cyclic
+const MAX_TREE_DEPTH: usize = 64;
+
+/// A single problem found in a bbi file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationProblem {
+    /// The byte offset in the file where the problem was found
+    pub offset: u64,
+    /// A description of the problem
+    pub message: String,
+}
+
+impl fmt::Display for ValidationProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "offset {}: {}", self.offset, self.message)
+    }
+}
+
+/// The result of validating a bbi file
+#[derive(Clone, Debug)]
+pub struct ValidationReport {
+    /// The type of the file, or `None` if the magic was not recognized
+    pub filetype: Option<BBIFile>,
+    /// Every problem found, in the order they were found
+    pub problems: Vec<ValidationProblem>,
+    /// The number of full data and zoom blocks that were decompressed and decoded
+    pub blocks_checked: u64,
+    /// The total summary, recomputed from the full data. `None` if the file
+    /// has no data (or none that could be read).
+    pub summary: Option<Summary>,
+}
+
+impl ValidationReport {
+    /// Returns `true` if no problems were found
+    pub fn is_valid(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Validates the bigWig or bigBed in `read`, returning every problem found.
+///
+/// An `Err` is only returned if the length of the file can't be determined;
+/// all other errors (including unexpected ends of the file) are reported as
+/// problems.
+pub fn validate<R: Read + Seek>(mut read: R) -> io::Result<ValidationReport> {
+    let file_len = read.seek(SeekFrom::End(0))?;
+    let mut validator = Validator {
+        read,
+        file_len,
+        endianness: Endianness::Big,
+        problems: vec![],
+        blocks_checked: 0,
+        decompressor: Decompressor::new(),
+        uncompress_buf: vec![],
+    };
+    let (filetype, summary) = match validator.validate() {
+        Some((filetype, summary)) => (Some(filetype), summary),
+        None => (None, None),
+    };
+    Ok(ValidationReport {
+        filetype,
+        problems: validator.problems,
+        blocks_checked: validator.blocks_checked,
+        summary,
+    })
+}
+
+/// Reads fixed-size fields from a byte slice. Callers must make sure the
+/// slice is long enough.
+struct Fields<'a> {
+    bytes: &'a [u8],
+    endianness: Endianness,
+}
+
+impl<'a> Fields<'a> {
+    fn new(bytes: &'a [u8], endianness: Endianness) -> Self {
+        Fields { bytes, endianness }
+    }
+
+    fn skip(&mut self, len: usize) {
+        self.bytes.advance(len);
+    }
+
+    fn u8(&mut self) -> u8 {
+        self.bytes.get_u8()
+    }
+
+    fn u16(&mut self) -> u16 {
+        match self.endianness {
+            Endianness::Big => self.bytes.get_u16(),
+            Endianness::Little => self.bytes.get_u16_le(),
+        }
+    }
+
+    fn u32(&mut self) -> u32 {
+        match self.endianness {
+            Endianness::Big => self.bytes.get_u32(),
+            Endianness::Little => self.bytes.get_u32_le(),
+        }
+    }
+
+    fn u64(&mut self) -> u64 {
+        match self.endianness {
+            Endianness::Big => self.bytes.get_u64(),
+            Endianness::Little => self.bytes.get_u64_le(),
+        }
+    }
+
+    fn f32(&mut self) -> f32 {
+        match self.endianness {
+            Endianness::Big => self.bytes.get_f32(),
+            Endianness::Little => self.bytes.get_f32_le(),
+        }
+    }
+
+    fn f64(&mut self) -> f64 {
+        match self.endianness {
+            Endianness::Big => self.bytes.get_f64(),
+            Endianness::Little => self.bytes.get_f64_le(),
+        }
+    }
+}
+
+struct Header {
+    filetype: BBIFile,
+    magic: u32,
+    version: u16,
+    zoom_levels: u16,
+    chromosome_tree_offset: u64,
+    full_data_offset: u64,
+    full_index_offset: u64,
+    field_count: u16,
+    defined_field_count: u16,
+    auto_sql_offset: u64,
+    total_summary_offset: u64,
+    uncompress_buf_size: u32,
+    extension_offset: u64,
+}
+
+struct Zoom {
+    reduction_level: u32,
+    data_offset: u64,
+    index_offset: u64,
+}
+
+/// A position in the file's coordinate space (chromosome id and base)
+type Position = (u32, u32);
+
+/// A leaf item of an R-tree: the range of data covered by a block, and where
+/// that block is.
+#[derive(Copy, Clone, Debug)]
+struct LeafBlock {
+    start: Position,
+    end: Position,
+    offset: u64,
+    size: u64,
+}
+
+/// Where the blocks of an R-tree must be, and what the tree is called in messages
+struct CirTree<'a> {
+    name: &'a str,
+    index_offset: u64,
+    data_start: u64,
+    data_end: u64,
+}
+
+/// The blocks of an R-tree (in order), along with the item count from its header
+/// and the number of leaf items (including those for invalid blocks)
+struct CirTreeBlocks {
+    blocks: Vec<LeafBlock>,
+    item_count: u64,
+    leaf_count: u64,
+}
+
+struct Validator<R> {
+    read: R,
+    file_len: u64,
+    endianness: Endianness,
+    problems: Vec<ValidationProblem>,
+    blocks_checked: u64,
+    /// Reused to decompress every block
+    decompressor: Decompressor,
+    uncompress_buf: Vec<u8>,
+}
+
+impl<R: Read + Seek> Validator<R> {
+    fn problem(&mut self, offset: u64, message: impl Into<String>) {
+        self.problems.push(ValidationProblem {
+            offset,
+            message: message.into(),
+        });
+    }
+
+    /// Reads `len` bytes at `offset`, reporting a problem (and returning
+    /// `None`) if they are not all in the file.
+    fn read_at(&mut self, offset: u64, len: u64, what: &str) -> Option<Vec<u8>> {
+        let in_file = matches!(offset.checked_add(len), Some(end) if end <= self.file_len);
+        if !in_file {
+            self.problem(
+                offset,
+                format!(
+                    "{} ({} bytes) extends past the end of the file ({} bytes)",
+                    what, len, self.file_len
+                ),
+            );
+            return None;
+        }
+        let mut bytes = vec![0u8; len as usize];
+        let res = self
+            .read
+            .seek(SeekFrom::Start(offset))
+            .and_then(|_| self.read.read_exact(&mut bytes));
+        match res {
+            Ok(()) => Some(bytes),
+            Err(e) => {
+                self.problem(offset, format!("Could not read {}: {}", what, e));
+                None
+            }
+        }
+    }
+
+    /// Checks that an offset read from the header at `field_offset` points
+    /// inside of the file
+    fn check_offset(&mut self, field_offset: u64, name: &str, offset: u64) -> bool {
+        if offset >= self.file_len {
+            self.problem(
+                field_offset,
+                format!(
+                    "{} ({}) is past the end of the file ({} bytes)",
+                    name, offset, self.file_len
+                ),
+            );
+            return false;
+        }
+        true
+    }
+
+    fn validate(&mut self) -> Option<(BBIFile, Option<Summary>)> {
+        let header = self.validate_header()?;
+        let zooms = self.validate_zoom_headers(&header);
+        self.validate_trailing_magic(&header);
+        self.validate_auto_sql(&header);
+        self.validate_extension(&header);
+        let chroms = self.validate_chrom_tree(&header);
+
+        let summary = self.validate_full_data(&header, &chroms);
+        for zoom in zooms {
+            self.validate_zoom(&header, &zoom, &chroms);
+        }
+
+        Some((header.filetype, summary))
+    }
+
+    fn validate_header(&mut self) -> Option<Header> {
+        let bytes = self.read_at(0, 64, "Header")?;
+        let magic = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let (filetype, endianness) = match magic {
+            _ if magic == BIGWIG_MAGIC => (BBIFile::BigWig, Endianness::Little),
+            _ if magic == BIGWIG_MAGIC.swap_bytes() => (BBIFile::BigWig, Endianness::Big),
+            _ if magic == BIGBED_MAGIC => (BBIFile::BigBed, Endianness::Little),
+            _ if magic == BIGBED_MAGIC.swap_bytes() => (BBIFile::BigBed, Endianness::Big),
+            _ => {
+                self.problem(
+                    0,
+                    format!(
+                        "Unknown magic ({:#010x}); not a bigWig or bigBed",
+                        u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+                    ),
+                );
+                return None;
+            }
+        };
+        self.endianness = endianness;
+
+        let mut fields = Fields::new(&bytes, endianness);
+        let magic = fields.u32();
+        let header = Header {
+            filetype,
+            magic,
+            version: fields.u16(),
+            zoom_levels: fields.u16(),
+            chromosome_tree_offset: fields.u64(),
+            full_data_offset: fields.u64(),
+            full_index_offset: fields.u64(),
+            field_count: fields.u16(),
+            defined_field_count: fields.u16(),
+            auto_sql_offset: fields.u64(),
+            total_summary_offset: fields.u64(),
+            uncompress_buf_size: fields.u32(),
+            extension_offset: fields.u64(),
+        };
+
+        if header.version == 0 || header.version > 4 {
+            self.problem(4, format!("Unknown version ({})", header.version));
+        }
+
+        self.check_offset(8, "Chromosome tree offset", header.chromosome_tree_offset);
+        let full_data_ok = self.check_offset(16, "Full data offset", header.full_data_offset);
+        let full_index_ok = self.check_offset(24, "Full index offset", header.full_index_offset);
+        if full_data_ok && full_index_ok && header.full_data_offset >= header.full_index_offset {
+            self.problem(
+                16,
+                format!(
+                    "Full data offset ({}) is not before the full index offset ({})",
+                    header.full_data_offset, header.full_index_offset
+                ),
+            );
+        }
+
+        if header.uncompress_buf_size > MAX_UNCOMPRESS_BUF_SIZE {
+            self.problem(
+                52,
+                format!(
+                    "Uncompress buffer size ({}) is larger than the maximum of {}",
+                    header.uncompress_buf_size, MAX_UNCOMPRESS_BUF_SIZE
+                ),
+            );
+        }
+
+        if let BBIFile::BigBed = filetype {
+            if header.field_count < 3 {
+                self.problem(
+                    32,
+                    format!("Field count ({}) is less than 3", header.field_count),
+                );
+            }
+            if header.defined_field_count > header.field_count {
+                self.problem(
+                    34,
+                    format!(
+                        "Defined field count ({}) is greater than the field count ({})",
+                        header.defined_field_count, header.field_count
+                    ),
+                );
+            }
+        }
+
+        Some(header)
+    }
+
+    fn validate_zoom_headers(&mut self, header: &Header) -> Vec<Zoom> {
+        let len = u64::from(header.zoom_levels) * 24;
+        let Some(bytes) = self.read_at(64, len, "Zoom headers") else {
+            return vec![];
+        };
+        let mut fields = Fields::new(&bytes, self.endianness);
+        let mut zooms: Vec<Zoom> = vec![];
+        for i in 0..u64::from(header.zoom_levels) {
+            let header_offset = 64 + i * 24;
+            let reduction_level = fields.u32();
+            fields.skip(4);
+            let data_offset = fields.u64();
+            let index_offset = fields.u64();
+
+            let mut valid = true;
+            if reduction_level == 0 {
+                self.problem(header_offset, "Zoom reduction level is 0");
+            }
+            if let Some(last) = zooms.last() {
+                if reduction_level <= last.reduction_level {
+                    self.problem(
+                        header_offset,
+                        format!(
+                            "Zoom reduction level ({}) is not greater than the previous level ({})",
+                            reduction_level, last.reduction_level
+                        ),
+                    );
+                }
+            }
+            valid &= self.check_offset(header_offset + 8, "Zoom data offset", data_offset);
+            valid &= self.check_offset(header_offset + 16, "Zoom index offset", index_offset);
+            if data_offset < header.full_index_offset {
+                self.problem(
+                    header_offset + 8,
+                    format!(
+                        "Zoom data offset ({}) is before the full index offset ({})",
+                        data_offset, header.full_index_offset
+                    ),
+                );
+                valid = false;
+            }
+            if data_offset >= index_offset {
+                self.problem(
+                    header_offset + 8,
+                    format!(
+                        "Zoom data offset ({}) is not before the zoom index offset ({})",
+                        data_offset, index_offset
+                    ),
+                );
+                valid = false;
+            }
+            if valid {
+                zooms.push(Zoom {
+                    reduction_level,
+                    data_offset,
+                    index_offset,
+                });
+            }
+        }
+        zooms
+    }
+
+    fn validate_trailing_magic(&mut self, header: &Header) {
+        // Older files don't end with the magic
+        if header.version < 4 || self.file_len < 68 {
+            return;
+        }
+        let offset = self.file_len - 4;
+        let Some(bytes) = self.read_at(offset, 4, "Trailing magic") else {
+            return;
+        };
+        let magic = Fields::new(&bytes, self.endianness).u32();
+        if magic != header.magic {
+            self.problem(
+                offset,
+                "File does not end with the magic number (the file may be truncated)",
+            );
+        }
+    }
+
+    fn validate_auto_sql(&mut self, header: &Header) {
+        if header.auto_sql_offset == 0 {
+            return;
+        }
+        if !self.check_offset(36, "AutoSql offset", header.auto_sql_offset) {
+            return;
+        }
+        // The autoSql is a NUL-terminated string, usually right before the
+        // chromosome tree.
+        let end = if header.auto_sql_offset < header.chromosome_tree_offset {
+            header.chromosome_tree_offset.min(self.file_len)
+        } else {
+            self.file_len
+        };
+        let len = (end - header.auto_sql_offset).min(1024 * 1024);
+        let Some(bytes) = self.read_at(header.auto_sql_offset, len, "AutoSql") else {
+            return;
+        };
+        match bytes.iter().position(|b| *b == 0) {
+            Some(nul) => {
+                if std::str::from_utf8(&bytes[..nul]).is_err() {
+                    self.problem(header.auto_sql_offset, "AutoSql is not valid utf-8");
+                }
+            }
+            None => self.problem(header.auto_sql_offset, "AutoSql is not NUL-terminated"),
+        }
+    }
+
+    fn validate_extension(&mut self, header: &Header) {
+        if header.extension_offset == 0 {
+            return;
+        }
+        if !self.check_offset(56, "Extension offset", header.extension_offset) {
+            return;
+        }
+        let Some(bytes) = self.read_at(header.extension_offset, 64, "Extension header") else {
+            return;
+        };
+        let mut fields = Fields::new(&bytes, self.endianness);
+        let _extension_size = fields.u16();
+        let extra_index_count = fields.u16();
+        let extra_index_list_offset = fields.u64();
+        if extra_index_count > 0 {
+            self.check_offset(
+                header.extension_offset + 4,
+                "Extra index list offset",
+                extra_index_list_offset,
+            );
+        }
+    }
+
+    /// Walks the chromosome B+ tree, returning the length of each chromosome
+    /// by its id
+    fn validate_chrom_tree(&mut self, header: &Header) -> HashMap<u32, u32> {
+        let mut chroms = HashMap::new();
+        let tree_offset = header.chromosome_tree_offset;
+        let Some(bytes) = self.read_at(tree_offset, 32, "Chromosome tree header") else {
+            return chroms;
+        };
+        let mut fields = Fields::new(&bytes, self.endianness);
+        let magic = fields.u32();
+        let block_size = fields.u32();
+        let key_size = fields.u32();
+        let val_size = fields.u32();
+        let item_count = fields.u64();
+        if magic != CHROM_TREE_MAGIC {
+            self.problem(tree_offset, "Invalid chromosome tree magic");
+            return chroms;
+        }
+        if val_size != 8 {
+            self.problem(
+                tree_offset + 12,
+                format!("Chromosome tree value size ({}) is not 8", val_size),
+            );
+            return chroms;
+        }
+
+        let mut visited = HashSet::new();
+        let mut leaves = vec![];
+        self.validate_chrom_tree_node(
+            tree_offset + 32,
+            block_size,
+            key_size,
+            None,
+            0,
+            &mut visited,
+            &mut leaves,
+        );
+
+        if leaves.len() as u64 != item_count {
+            self.problem(
+                tree_offset + 16,
+                format!(
+                    "Chromosome tree item count ({}) does not match the number of chromosomes ({})",
+                    item_count,
+                    leaves.len()
+                ),
+            );
+        }
+        let mut last_key: Option<Vec<u8>> = None;
+        for (offset, key, id, length) in leaves {
+            if let Some(last_key) = &last_key {
+                if key <= *last_key {
+                    self.problem(
+                        offset,
+                        format!(
+                            "Chromosome `{}` is not sorted after `{}`",
+                            String::from_utf8_lossy(&key),
+                            String::from_utf8_lossy(last_key)
+                        ),
+                    );
+                }
+            }
+            if std::str::from_utf8(&key).is_err() {
+                self.problem(offset, "Chromosome name is not valid utf-8");
+            }
+            if chroms.insert(id, length).is_some() {
+                self.problem(offset, format!("Chromosome id {} is duplicated", id));
+            }
+            last_key = Some(key);
+        }
+        chroms
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn validate_chrom_tree_node(
+        &mut self,
+        node_offset: u64,
+        block_size: u32,
+        key_size: u32,
+        expected_first_key: Option<&[u8]>,
+        depth: usize,
+        visited: &mut HashSet<u64>,
+        leaves: &mut Vec<(u64, Vec<u8>, u32, u32)>,
+    ) {
+        if depth > MAX_TREE_DEPTH || !visited.insert(node_offset) {
+            self.problem(node_offset, "Chromosome tree is cyclic");
+            return;
+        }
+        let Some(bytes) = self.read_at(node_offset, 4, "Chromosome tree node") else {
+            return;
+        };
+        let mut fields = Fields::new(&bytes, self.endianness);
+        let is_leaf = fields.u8();
+        fields.skip(1);
+        let count = fields.u16();
+        if is_leaf > 1 {
+            self.problem(
+                node_offset,
+                format!("Invalid chromosome tree node type ({})", is_leaf),
+            );
+            return;
+        }
+        if u32::from(count) > block_size {
+            self.problem(
+                node_offset,
+                format!(
+                    "Chromosome tree node has more items ({}) than the block size ({})",
+                    count, block_size
+                ),
+            );
+        }
+
+        let item_size = u64::from(key_size) + 8;
+        let items_offset = node_offset + 4;
+        let Some(bytes) = self.read_at(
+            items_offset,
+            item_size * u64::from(count),
+            "Chromosome tree node items",
+        ) else {
+            return;
+        };
+        let mut fields = Fields::new(&bytes, self.endianness);
+        let mut children = vec![];
+        for i in 0..u64::from(count) {
+            let item_offset = items_offset + i * item_size;
+            let key = fields.bytes[..key_size as usize].to_vec();
+            fields.skip(key_size as usize);
+            let key = key.into_iter().take_while(|b| *b != 0).collect::<Vec<_>>();
+            if i == 0 {
+                if let Some(expected) = expected_first_key {
+                    if expected != key {
+                        self.problem(
+                            item_offset,
+                            "First key of a chromosome tree node does not match its parent's key",
+                        );
+                    }
+                }
+            }
+            if is_leaf == 1 {
+                let id = fields.u32();
+                let length = fields.u32();
+                leaves.push((item_offset, key, id, length));
+            } else {
+                let child_offset = fields.u64();
+                if self.check_offset(
+                    item_offset + u64::from(key_size),
+                    "Child node",
+                    child_offset,
+                ) {
+                    children.push((key, child_offset));
+                }
+            }
+        }
+        for (key, child_offset) in children {
+            self.validate_chrom_tree_node(
+                child_offset,
+                block_size,
+                key_size,
+                Some(&key),
+                depth + 1,
+                visited,
+                leaves,
+            );
+        }
+    }
+
+    /// Walks an R-tree, returning the blocks in its leaves (in order)
+    fn validate_cir_tree(&mut self, tree: &CirTree, chroms: &HashMap<u32, u32>) -> CirTreeBlocks {
+        let mut blocks = CirTreeBlocks {
+            blocks: vec![],
+            item_count: 0,
+            leaf_count: 0,
+        };
+        let Some(bytes) = self.read_at(tree.index_offset, 48, "R-tree header") else {
+            return blocks;
+        };
+        let mut fields = Fields::new(&bytes, self.endianness);
+        let magic = fields.u32();
+        let block_size = fields.u32();
+        blocks.item_count = fields.u64();
+        let start = (fields.u32(), fields.u32());
+        let end = (fields.u32(), fields.u32());
+        let end_file_offset = fields.u64();
+        if magic != CIR_TREE_MAGIC {
+            self.problem(
+                tree.index_offset,
+                format!("Invalid {} R-tree magic", tree.name),
+            );
+            return blocks;
+        }
+        if end_file_offset != tree.data_end {
+            self.problem(
+                tree.index_offset + 32,
+                format!(
+                    "{} R-tree end of data ({}) does not match the start of the index ({})",
+                    tree.name, end_file_offset, tree.data_end
+                ),
+            );
+        }
+
+        let mut visited = HashSet::new();
+        self.validate_cir_tree_node(
+            tree,
+            tree.index_offset + 48,
+            block_size,
+            (start, end),
+            0,
+            &mut visited,
+            &mut blocks,
+        );
+
+        let mut last: Option<LeafBlock> = None;
+        for block in blocks.blocks.iter() {
+            if !chroms.contains_key(&block.start.0) || !chroms.contains_key(&block.end.0) {
+                self.problem(
+                    block.offset,
+                    format!(
+                        "{} block refers to an unknown chromosome id ({} or {})",
+                        tree.name, block.start.0, block.end.0
+                    ),
+                );
+            }
+            if let Some(last) = last {
+                if block.start < last.start {
+                    self.problem(
+                        block.offset,
+                        format!("{} blocks are not sorted by position", tree.name),
+                    );
+                }
+                if block.offset < last.offset + last.size {
+                    self.problem(
+                        block.offset,
+                        format!(
+                            "{} block overlaps or is before the previous block (at {})",
+                            tree.name, last.offset
+                        ),
+                    );
+                }
+            }
+            last = Some(*block);
+        }
+
+        blocks
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn validate_cir_tree_node(
+        &mut self,
+        tree: &CirTree,
+        node_offset: u64,
+        block_size: u32,
+        bounds: (Position, Position),
+        depth: usize,
+        visited: &mut HashSet<u64>,
+        blocks: &mut CirTreeBlocks,
+    ) {
+        if depth > MAX_TREE_DEPTH || !visited.insert(node_offset) {
+            self.problem(node_offset, format!("{} R-tree is cyclic", tree.name));
+            return;
+        }
+        let Some(bytes) = self.read_at(node_offset, 4, "R-tree node") else {
+            return;
+        };
+        let mut fields = Fields::new(&bytes, self.endianness);
+        let is_leaf = fields.u8();
+        fields.skip(1);
+        let count = fields.u16();
+        if is_leaf > 1 {
+            self.problem(
+                node_offset,
+                format!("Invalid {} R-tree node type ({})", tree.name, is_leaf),
+            );
+            return;
+        }
+        if count == 0 {
+            self.problem(node_offset, format!("{} R-tree node is empty", tree.name));
+        }
+        if u32::from(count) > block_size {
+            self.problem(
+                node_offset,
+                format!(
+                    "{} R-tree node has more items ({}) than the block size ({})",
+                    tree.name, count, block_size
+                ),
+            );
+        }
+
+        let item_size = if is_leaf == 1 { 32 } else { 24 };
+        let items_offset = node_offset + 4;
+        let Some(bytes) = self.read_at(
+            items_offset,
+            item_size * u64::from(count),
+            "R-tree node items",
+        ) else {
+            return;
+        };
+        let mut fields = Fields::new(&bytes, self.endianness);
+        let mut last_start = None;
+        let mut children = vec![];
+        for i in 0..u64::from(count) {
+            let item_offset = items_offset + i * item_size;
+            let start = (fields.u32(), fields.u32());
+            let end = (fields.u32(), fields.u32());
+            if start > end {
+                self.problem(
+                    item_offset,
+                    format!("{} R-tree item starts after it ends", tree.name),
+                );
+            }
+            if start < bounds.0 || end > bounds.1 {
+                self.problem(
+                    item_offset,
+                    format!(
+                        "{} R-tree item ({}:{}-{}:{}) is outside of its parent ({}:{}-{}:{})",
+                        tree.name,
+                        start.0,
+                        start.1,
+                        end.0,
+                        end.1,
+                        bounds.0 .0,
+                        bounds.0 .1,
+                        bounds.1 .0,
+                        bounds.1 .1,
+                    ),
+                );
+            }
+            if last_start.is_some_and(|last_start| start < last_start) {
+                self.problem(
+                    item_offset,
+                    format!("{} R-tree node items are not sorted", tree.name),
+                );
+            }
+            last_start = Some(start);
+
+            if is_leaf == 1 {
+                blocks.leaf_count += 1;
+                let offset = fields.u64();
+                let size = fields.u64();
+                let in_data = offset >= tree.data_start
+                    && offset
+                        .checked_add(size)
+                        .is_some_and(|end| end <= tree.data_end);
+                if !in_data {
+                    self.problem(
+                        item_offset + 16,
+                        format!(
+                            "{} block ({} bytes at {}) is outside of the data section ({}-{})",
+                            tree.name, size, offset, tree.data_start, tree.data_end
+                        ),
+                    );
+                } else if size == 0 {
+                    self.problem(item_offset + 24, format!("{} block is empty", tree.name));
+                } else {
+                    blocks.blocks.push(LeafBlock {
+                        start,
+                        end,
+                        offset,
+                        size,
+                    });
+                }
+            } else {
+                let child_offset = fields.u64();
+                if self.check_offset(item_offset + 16, "Child node", child_offset) {
+                    children.push((child_offset, (start, end)));
+                }
+            }
+        }
+        for (child_offset, child_bounds) in children {
+            self.validate_cir_tree_node(
+                tree,
+                child_offset,
+                block_size,
+                child_bounds,
+                depth + 1,
+                visited,
+                blocks,
+            );
+        }
+    }
+
+    /// Checks the item count in the header of an R-tree. The UCSC tools write
+    /// the number of items in the data (for the full data of bigBeds and for
+    /// zooms), while bigtools writes the number of blocks.
+    fn check_cir_tree_item_count(&mut self, tree: &CirTree, blocks: &CirTreeBlocks, items: u64) {
+        if blocks.item_count != blocks.leaf_count && blocks.item_count != items {
+            self.problem(
+                tree.index_offset + 8,
+                format!(
+                    "{} R-tree item count ({}) does not match the number of blocks ({}) or items ({})",
+                    tree.name, blocks.item_count, blocks.leaf_count, items
+                ),
+            );
+        }
+    }
+
+    /// Reads and decompresses (if needed) a block. An oversized
+    /// `uncompress_buf_size` (already reported) is capped, so blocks larger
+    /// than the cap fail to decompress.
+    fn read_block(&mut self, header: &Header, block: &LeafBlock) -> Option<Vec<u8>> {
+        let raw = self.read_at(block.offset, block.size, "Block")?;
+        self.blocks_checked += 1;
+        if header.uncompress_buf_size == 0 {
+            return Some(raw);
+        }
+        let buf_size = header.uncompress_buf_size.min(MAX_UNCOMPRESS_BUF_SIZE) as usize;
+        self.uncompress_buf.resize(buf_size, 0);
+        match self
+            .decompressor
+            .zlib_decompress(&raw, &mut self.uncompress_buf)
+        {
+            Ok(len) => Some(self.uncompress_buf[..len].to_vec()),
+            Err(e) => {
+                self.problem(
+                    block.offset,
+                    format!(
+                        "Could not decompress block (with a maximum size of {}): {}",
+                        header.uncompress_buf_size, e
+                    ),
+                );
+                None
+            }
+        }
+    }
+
+    fn validate_full_data(
+        &mut self,
+        header: &Header,
+        chroms: &HashMap<u32, u32>,
+    ) -> Option<Summary> {
+        // The UCSC tools write a 32-bit section count for bigWigs, while
+        // bigtools (and the UCSC tools for bigBeds) write a 64-bit item count
+        let bytes = self.read_at(header.full_data_offset, 8, "Data count")?;
+        let section_count = Fields::new(&bytes, self.endianness).u32();
+        let item_count = Fields::new(&bytes, self.endianness).u64();
+
+        let tree = CirTree {
+            name: "Full data",
+            index_offset: header.full_index_offset,
+            data_start: header.full_data_offset + 4,
+            data_end: header.full_index_offset,
+        };
+        let cir_tree = self.validate_cir_tree(&tree, chroms);
+        let blocks = &cir_tree.blocks;
+
+        let mut summary = SummaryBuilder::default();
+        let mut depth = DepthSweep::default();
+        let mut last_item: Option<(u32, u32, u32)> = None;
+        for block in blocks.iter() {
+            let Some(data) = self.read_block(header, block) else {
+                continue;
+            };
+            let items = match header.filetype {
+                BBIFile::BigWig => self.decode_bigwig_block(block, &data),
+                BBIFile::BigBed => self.decode_bigbed_block(block, &data),
+            };
+            for (i, (chrom, start, end, value)) in items.into_iter().enumerate() {
+                let item = format!("Item {} in block", i);
+                self.check_item(block, &item, (chrom, start, end), chroms);
+                if let Some((last_chrom, last_start, last_end)) = last_item {
+                    let unsorted = match header.filetype {
+                        // bigWig values can't overlap
+                        BBIFile::BigWig => (chrom, start) < (last_chrom, last_end),
+                        BBIFile::BigBed => (chrom, start) < (last_chrom, last_start),
+                    };
+                    if unsorted {
+                        self.problem(
+                            block.offset,
+                            format!(
+                                "{} ({}:{}-{}) is not sorted after the previous item ({}:{}-{})",
+                                item, chrom, start, end, last_chrom, last_start, last_end
+                            ),
+                        );
+                    }
+                }
+                last_item = Some((chrom, start, end));
+
+                summary.total_items += 1;
+                match header.filetype {
+                    BBIFile::BigWig => summary.add(end.saturating_sub(start), f64::from(value)),
+                    BBIFile::BigBed => depth.add(chrom, start, end, &mut summary),
+                }
+            }
+        }
+        depth.finish(&mut summary);
+        self.check_cir_tree_item_count(&tree, &cir_tree, summary.total_items);
+
+        let count_ok = match header.filetype {
+            BBIFile::BigWig => {
+                item_count == summary.total_items || u64::from(section_count) == cir_tree.leaf_count
+            }
+            BBIFile::BigBed => item_count == summary.total_items,
+        };
+        if !count_ok {
+            self.problem(
+                header.full_data_offset,
+                format!(
+                    "Data count ({}) does not match the number of items ({}) or blocks ({})",
+                    item_count, summary.total_items, cir_tree.leaf_count
+                ),
+            );
+        }
+
+        let summary = summary.finish();
+        self.validate_total_summary(header, summary);
+        summary
+    }
+
+    /// Compares the recomputed summary to the one stored in the file
+    fn validate_total_summary(&mut self, header: &Header, summary: Option<Summary>) {
+        let offset = header.total_summary_offset;
+        if offset == 0 {
+            // Version 1 files don't have a total summary
+            return;
+        }
+        if !self.check_offset(44, "Total summary offset", offset) {
+            return;
+        }
+        let Some(bytes) = self.read_at(offset, 40, "Total summary") else {
+            return;
+        };
+        let mut fields = Fields::new(&bytes, self.endianness);
+        let bases_covered = fields.u64();
+        let min_val = fields.f64();
+        let max_val = fields.f64();
+        let sum = fields.f64();
+        let sum_squares = fields.f64();
+
+        let Some(summary) = summary else {
+            if bases_covered != 0 {
+                self.problem(
+                    offset,
+                    format!(
+                        "Total summary has {} bases covered, but there is no data",
+                        bases_covered
+                    ),
+                );
+            }
+            return;
+        };
+        let mut mismatch = |name: &str, stored: String, computed: String| {
+            self.problems.push(ValidationProblem {
+                offset,
+                message: format!(
+                    "Total summary {} ({}) does not match the data ({})",
+                    name, stored, computed
+                ),
+            });
+        };
+        if bases_covered != summary.bases_covered {
+            mismatch(
+                "bases covered",
+                bases_covered.to_string(),
+                summary.bases_covered.to_string(),
+            );
+        }
+        let values = [
+            ("min", min_val, summary.min_val),
+            ("max", max_val, summary.max_val),
+            ("sum", sum, summary.sum),
+            ("sum of squares", sum_squares, summary.sum_squares),
+        ];
+        for (name, stored, computed) in values {
+            if !approx_eq(stored, computed) {
+                mismatch(name, stored.to_string(), computed.to_string());
+            }
+        }
+    }
+
+    fn validate_zoom(&mut self, header: &Header, zoom: &Zoom, chroms: &HashMap<u32, u32>) {
+        let name = format!("Zoom level {}", zoom.reduction_level);
+        let tree = CirTree {
+            name: &name,
+            index_offset: zoom.index_offset,
+            data_start: zoom.data_offset,
+            data_end: zoom.index_offset,
+        };
+        let cir_tree = self.validate_cir_tree(&tree, chroms);
+
+        let mut records = 0;
+        let mut last_record: Option<(u32, u32, u32)> = None;
+        for block in cir_tree.blocks.iter() {
+            let Some(data) = self.read_block(header, block) else {
+                continue;
+            };
+            if data.len() % 32 != 0 {
+                self.problem(
+                    block.offset,
+                    format!(
+                        "{} block size ({}) is not a multiple of the record size (32)",
+                        name,
+                        data.len()
+                    ),
+                );
+            }
+            let mut fields = Fields::new(&data, self.endianness);
+            for i in 0..data.len() / 32 {
+                records += 1;
+                let chrom = fields.u32();
+                let start = fields.u32();
+                let end = fields.u32();
+                let bases_covered = fields.u32();
+                let min_val = fields.f32();
+                let max_val = fields.f32();
+                let _sum = fields.f32();
+                let _sum_squares = fields.f32();
+
+                let record = format!("{} record {} in block", name, i);
+                if start >= end {
+                    self.problem(
+                        block.offset,
+                        format!("{} ({}-{}) is empty", record, start, end),
+                    );
+                }
+                if !chroms.contains_key(&chrom) {
+                    self.problem(
+                        block.offset,
+                        format!("{} has an unknown chromosome id ({})", record, chrom),
+                    );
+                }
+                if !block_contains(block, (chrom, start, end)) {
+                    self.problem(
+                        block.offset,
+                        format!("{} is outside of the bounds of its block", record),
+                    );
+                }
+                // For bigBeds, bases are counted once for each overlapping item
+                let is_bigwig = matches!(header.filetype, BBIFile::BigWig);
+                if is_bigwig && bases_covered > end.saturating_sub(start) {
+                    self.problem(
+                        block.offset,
+                        format!(
+                            "{} covers more bases ({}) than its length ({})",
+                            record,
+                            bases_covered,
+                            end.saturating_sub(start)
+                        ),
+                    );
+                }
+                if bases_covered > 0 && min_val > max_val {
+                    self.problem(
+                        block.offset,
+                        format!(
+                            "{} has a min ({}) greater than its max ({})",
+                            record, min_val, max_val
+                        ),
+                    );
+                }
+                if let Some((last_chrom, last_start, last_end)) = last_record {
+                    if (chrom, start) < (last_chrom, last_end) {
+                        self.problem(
+                            block.offset,
+                            format!(
+                                "{} ({}:{}-{}) overlaps or is before the previous record ({}:{}-{})",
+                                record, chrom, start, end, last_chrom, last_start, last_end
+                            ),
+                        );
+                    }
+                }
+                last_record = Some((chrom, start, end));
+            }
+        }
+        self.check_cir_tree_item_count(&tree, &cir_tree, records);
+    }
+
+    /// Checks that a decoded item is valid for its chromosome and block
+    fn check_item(
+        &mut self,
+        block: &LeafBlock,
+        item: &str,
+        (chrom, start, end): (u32, u32, u32),
+        chroms: &HashMap<u32, u32>,
+    ) {
+        if start > end {
+            self.problem(
+                block.offset,
+                format!("{} starts ({}) after it ends ({})", item, start, end),
+            );
+        }
+        match chroms.get(&chrom) {
+            None => self.problem(
+                block.offset,
+                format!("{} has an unknown chromosome id ({})", item, chrom),
+            ),
+            Some(length) if end > *length => self.problem(
+                block.offset,
+                format!(
+                    "{} ends ({}) past the end of its chromosome ({})",
+                    item, end, length
+                ),
+            ),
+            Some(_) => {}
+        }
+        if !block_contains(block, (chrom, start, end)) {
+            self.problem(
+                block.offset,
+                format!(
+                    "{} ({}:{}-{}) is outside of the bounds of its block",
+                    item, chrom, start, end
+                ),
+            );
+        }
+    }
+
+    /// Decodes the values of a bigWig block as (chrom, start, end, value)
+    fn decode_bigwig_block(&mut self, block: &LeafBlock, data: &[u8]) -> Vec<(u32, u32, u32, f32)> {
+        if data.len() < 24 {
+            self.problem(
+                block.offset,
+                format!(
+                    "Block is too small ({} bytes) for a section header",
+                    data.len()
+                ),
+            );
+            return vec![];
+        }
+        let mut fields = Fields::new(data, self.endianness);
+        let chrom = fields.u32();
+        let section_start = fields.u32();
+        let section_end = fields.u32();
+        let item_step = fields.u32();
+        let item_span = fields.u32();
+        let section_type = fields.u8();
+        fields.skip(1);
+        let item_count = usize::from(fields.u16());
+
+        let item_size = match section_type {
+            1 => 12,
+            2 => 8,
+            3 => 4,
+            _ => {
+                self.problem(
+                    block.offset,
+                    format!("Unknown section type ({})", section_type),
+                );
+                return vec![];
+            }
+        };
+        if data.len() - 24 != item_count * item_size {
+            self.problem(
+                block.offset,
+                format!(
+                    "Section has {} bytes of data, but {} items need {} bytes",
+                    data.len() - 24,
+                    item_count,
+                    item_count * item_size
+                ),
+            );
+            if data.len() - 24 < item_count * item_size {
+                return vec![];
+            }
+        }
+
+        let mut values = Vec::with_capacity(item_count);
+        for i in 0..item_count {
+            let (start, end, value) = match section_type {
+                1 => (fields.u32(), fields.u32(), fields.f32()),
+                2 => {
+                    let start = fields.u32();
+                    (start, start.saturating_add(item_span), fields.f32())
+                }
+                _ => {
+                    let start = section_start.saturating_add((i as u32).saturating_mul(item_step));
+                    (start, start.saturating_add(item_span), fields.f32())
+                }
+            };
+            if start < section_start || end > section_end {
+                self.problem(
+                    block.offset,
+                    format!(
+                        "Item {} in block ({}-{}) is outside of its section ({}-{})",
+                        i, start, end, section_start, section_end
+                    ),
+                );
+            }
+            values.push((chrom, start, end, value));
+        }
+        values
+    }
+
+    /// Decodes the entries of a bigBed block as (chrom, start, end, 1.0)
+    fn decode_bigbed_block(&mut self, block: &LeafBlock, data: &[u8]) -> Vec<(u32, u32, u32, f32)> {
+        let mut entries = vec![];
+        let mut bytes = data;
+        while !bytes.is_empty() {
+            let i = entries.len();
+            if bytes.len() < 12 {
+                self.problem(
+                    block.offset,
+                    format!("Block has {} trailing bytes after item {}", bytes.len(), i),
+                );
+                break;
+            }
+            let mut fields = Fields::new(bytes, self.endianness);
+            let chrom = fields.u32();
+            let start = fields.u32();
+            let end = fields.u32();
+            bytes = &bytes[12..];
+            let rest = match bytes.iter().position(|b| *b == 0) {
+                Some(nul) => {
+                    let rest = &bytes[..nul];
+                    bytes = &bytes[nul + 1..];
+                    rest
+                }
+                None => {
+                    self.problem(
+                        block.offset,
+                        format!("Item {} in block is not NUL-terminated", i),
+                    );
+                    let rest = bytes;
+                    bytes = &[];
+                    rest
+                }
+            };
+            if std::str::from_utf8(rest).is_err() {
+                self.problem(
+                    block.offset,
+                    format!("Item {} in block is not valid utf-8", i),
+                );
+            }
+            entries.push((chrom, start, end, 1.0));
+        }
+        entries
+    }
+}
+
+/// Whether a block's bounds (from the R-tree) contain an item
+fn block_contains(block: &LeafBlock, (chrom, start, end): (u32, u32, u32)) -> bool {
+    block.start <= (chrom, start) && (chrom, end) <= block.end
+}
+
+fn approx_eq(a: f64, b: f64) -> bool {
+    if a == b {
+        return true;
+    }
+    let scale = a.abs().max(b.abs()).max(1.0);
+    (a - b).abs() <= scale * 1e-6
+}
+
+/// Accumulates a `Summary` from runs of bases with the same value
+#[derive(Default)]
+struct SummaryBuilder {
+    total_items: u64,
+    summary: Option<Summary>,
+}
+
+impl SummaryBuilder {
+    fn add(&mut self, len: u32, value: f64) {
+        if len == 0 {
+            return;
+        }
+        let len_f = f64::from(len);
+        match &mut self.summary {
+            None => {
+                self.summary = Some(Summary {
+                    total_items: 0,
+                    bases_covered: u64::from(len),
+                    min_val: value,
+                    max_val: value,
+                    sum: len_f * value,
+                    sum_squares: len_f * value * value,
+                })
+            }
+            Some(summary) => {
+                summary.bases_covered += u64::from(len);
+                summary.min_val = summary.min_val.min(value);
+                summary.max_val = summary.max_val.max(value);
+                summary.sum += len_f * value;
+                summary.sum_squares += len_f * value * value;
+            }
+        }
+    }
+
+    fn finish(self) -> Option<Summary> {
+        self.summary.map(|summary| Summary {
+            total_items: self.total_items,
+            ..summary
+        })
+    }
+}
+
+/// Converts sorted bigBed entries into runs of bases with the same depth (the
+/// number of overlapping entries), which is what the bigBed summary is
+/// calculated from.
+#[derive(Default)]
+struct DepthSweep {
+    chrom: Option<u32>,
+    pos: u32,
+    ends: BinaryHeap<Reverse<u32>>,
+}
+
+impl DepthSweep {
+    fn add(&mut self, chrom: u32, start: u32, end: u32, summary: &mut SummaryBuilder) {
+        if self.chrom != Some(chrom) {
+            self.finish(summary);
+            self.chrom = Some(chrom);
+        }
+        self.advance(start, summary);
+        self.pos = self.pos.max(start);
+        self.ends.push(Reverse(end.max(start)));
+    }
+
+    /// Adds the runs up to `to` to the summary
+    fn advance(&mut self, to: u32, summary: &mut SummaryBuilder) {
+        while let Some(Reverse(next_end)) = self.ends.peek().copied() {
+            if next_end > to {
+                break;
+            }
+            let depth = self.ends.len();
+            summary.add(next_end.saturating_sub(self.pos), depth as f64);
+            self.pos = self.pos.max(next_end);
+            self.ends.pop();
+        }
+        if !self.ends.is_empty() {
+            summary.add(to.saturating_sub(self.pos), self.ends.len() as f64);
+        }
+        self.pos = self.pos.max(to);
+    }
+
+    fn finish(&mut self, summary: &mut SummaryBuilder) {
+        self.advance(u32::MAX, summary);
+        self.ends.clear();
+        self.chrom = None;
+        self.pos = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_depth_sweep() {
+        let mut summary = SummaryBuilder::default();
+        let mut depth = DepthSweep::default();
+        // 0-10 depth 1, 10-20 depth 2, 20-30 depth 1, gap, 40-50 depth 1
+        depth.add(0, 0, 20, &mut summary);
+        depth.add(0, 10, 30, &mut summary);
+        depth.add(0, 40, 50, &mut summary);
+        // A new chromosome flushes the last one
+        depth.add(1, 0, 5, &mut summary);
+        depth.add(1, 0, 5, &mut summary);
+        depth.finish(&mut summary);
+
+        let summary = summary.finish().unwrap();
+        assert_eq!(summary.bases_covered, 45);
+        assert_eq!(summary.min_val, 1.0);
+        assert_eq!(summary.max_val, 2.0);
+        assert_eq!(summary.sum, 10.0 + 20.0 + 10.0 + 10.0 + 10.0);
+        assert_eq!(summary.sum_squares, 10.0 + 40.0 + 10.0 + 10.0 + 20.0);
+    }
+}
@@ -2,6 +2,11 @@
 Utitilies for reading and writing the autosql section of a bigBed.
 */
 
+pub mod builder;
+pub mod presets;
+pub mod typed;
+pub mod validate;
+
 pub const BED3: &str = r#"
 table bed3
 "Simple bed"
@@ -56,6 +61,8 @@ table bed
 
 // Defined by https://github.com/ucscGenomeBrowser/kent/blob/c26640b68ba8ad219e7d79c3f8251ea20f9f57e0/src/hg/autoSql/autoSql.doc
 pub mod parse {
+    use std::fmt;
+
     mod parser {
         pub(super) struct Parser<'a> {
             pub(super) data: &'a str,
@@ -333,23 +340,24 @@ pub mod parse {
         InvalidFieldCommentSeparater(String),
         InvalidFieldValuesBrackets(String),
         InvalidIndexSizeBrackets(String),
+        InvalidFieldType(String),
     }
 
-    #[derive(Copy, Clone, Debug)]
+    #[derive(Copy, Clone, Debug, PartialEq)]
     pub enum DeclarationType {
         Simple,
         Object,
         Table,
     }
 
-    #[derive(Clone, Debug)]
+    #[derive(Clone, Debug, PartialEq)]
     pub enum IndexType {
         Primary,
         Index(Option<String>),
         Unique,
     }
 
-    #[derive(Clone, Debug)]
+    #[derive(Clone, Debug, PartialEq)]
     pub struct DeclareName {
         pub name: String,
         pub index_type: Option<IndexType>,
@@ -412,7 +420,7 @@ pub mod parse {
         }
     }
 
-    #[derive(Clone, Debug)]
+    #[derive(Clone, Debug, PartialEq)]
     pub struct Declaration {
         pub declaration_type: DeclarationType,
         pub name: DeclareName,
@@ -420,7 +428,7 @@ pub mod parse {
         pub fields: Vec<Field>,
     }
 
-    #[derive(Clone, Debug)]
+    #[derive(Clone, Debug, PartialEq)]
     pub enum FieldType {
         Int,
         Uint,
@@ -529,7 +537,7 @@ pub mod parse {
             return Ok(Some(field_type));
         }
 
-        /// This is currently incomplete when printing `simple`, `table`, and `object`.
+        /// The type as written in autoSql, for example `uint` or `enum(a, b)`
         pub fn to_string(&self) -> String {
             match self {
                 FieldType::Int => "int".to_string(),
@@ -544,33 +552,114 @@ pub mod parse {
                 FieldType::String => "string".to_string(),
                 FieldType::Lstring => "lstring".to_string(),
                 FieldType::Bigint => "bigint".to_string(),
-                FieldType::Enum(values) => {
-                    let mut e = "enum(".to_string();
-                    for v in values {
-                        e.push_str(v)
-                    }
-                    e.push(')');
-                    e
-                }
-                FieldType::Set(values) => {
-                    let mut e = "set(".to_string();
-                    for v in values {
-                        e.push_str(v)
-                    }
-                    e.push(')');
-                    e
-                }
-                FieldType::Declaration(decl_type, _decl_name) => match decl_type {
-                    DeclarationType::Simple => "simple ...",
-                    DeclarationType::Object => "object ...",
-                    DeclarationType::Table => "table ...",
+                FieldType::Enum(values) => format!("enum({})", values.join(", ")),
+                FieldType::Set(values) => format!("set({})", values.join(", ")),
+                FieldType::Declaration(decl_type, decl_name) => {
+                    format!("{} {}", decl_type, decl_name.name)
                 }
-                .to_string(),
             }
         }
     }
 
-    #[derive(Clone, Debug)]
+    impl std::str::FromStr for FieldType {
+        type Err = ParseError;
+
+        /// Parses a single field type, like `uint` or `enum(a, b)`. Array
+        /// sizes are not part of the type (see `Field::field_size`).
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let mut parser = parser::Parser::of(s);
+            match FieldType::try_parse(&mut parser)? {
+                Some(field_type) if parser.peek_one().is_empty() => Ok(field_type),
+                _ => Err(ParseError::InvalidFieldType(s.to_string())),
+            }
+        }
+    }
+
+    impl fmt::Display for DeclarationType {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(match self {
+                DeclarationType::Simple => "simple",
+                DeclarationType::Object => "object",
+                DeclarationType::Table => "table",
+            })
+        }
+    }
+
+    impl fmt::Display for IndexType {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                IndexType::Primary => f.write_str("primary"),
+                IndexType::Index(None) => f.write_str("index"),
+                IndexType::Index(Some(size)) => write!(f, "index[{}]", size),
+                IndexType::Unique => f.write_str("unique"),
+            }
+        }
+    }
+
+    impl fmt::Display for DeclareName {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(&self.name)?;
+            if let Some(index_type) = &self.index_type {
+                write!(f, " {}", index_type)?;
+            }
+            if self.auto {
+                f.write_str(" auto")?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Comments are stored as parsed, including their quotes
+    fn write_comment(f: &mut fmt::Formatter<'_>, comment: &str) -> fmt::Result {
+        if comment.len() >= 2 && comment.starts_with('"') && comment.ends_with('"') {
+            f.write_str(comment)
+        } else {
+            write!(f, "\"{}\"", comment)
+        }
+    }
+
+    impl fmt::Display for Field {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(&self.field_type.to_string())?;
+            if let Some(size) = &self.field_size {
+                write!(f, "[{}]", size)?;
+            }
+            write!(f, " {}", self.name)?;
+            if let Some(index_type) = &self.index_type {
+                write!(f, " {}", index_type)?;
+            }
+            if self.auto {
+                f.write_str(" auto")?;
+            }
+            f.write_str("; ")?;
+            write_comment(f, &self.comment)
+        }
+    }
+
+    /// Writes the declaration as autoSql, which can be read back with
+    /// `parse_autosql`.
+    impl fmt::Display for Declaration {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            writeln!(f, "{} {}", self.declaration_type, self.name)?;
+            write_comment(f, &self.comment)?;
+            f.write_str("\n(\n")?;
+            for field in &self.fields {
+                writeln!(f, "    {}", field)?;
+            }
+            f.write_str(")\n")
+        }
+    }
+
+    /// Writes several declarations as autoSql, separated by blank lines.
+    pub fn write_autosql(declarations: &[Declaration]) -> String {
+        declarations
+            .iter()
+            .map(|d| d.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
     pub struct Field {
         pub field_type: FieldType,
         pub field_size: Option<String>,
@@ -738,9 +827,32 @@ pub mod parse {
     }
 
     mod test {
+        /// Checks that writing and re-parsing `data` gives the same declarations
+        #[cfg(test)]
+        fn roundtrip(data: &str) {
+            let declarations = super::parse_autosql(data).unwrap();
+            let written = super::write_autosql(&declarations);
+            assert_eq!(super::parse_autosql(&written).unwrap(), declarations);
+        }
+
         #[test]
         fn test_bed3() {
             super::parse_autosql(super::super::BED3).unwrap();
+            roundtrip(super::super::BED3);
+            roundtrip(&super::super::bed_autosql(&["."; 15].join("\t")));
+        }
+
+        #[test]
+        fn test_field_type() {
+            use super::FieldType;
+
+            assert_eq!("uint".parse::<FieldType>().unwrap(), FieldType::Uint);
+            assert_eq!("Ubyte".parse::<FieldType>().unwrap(), FieldType::Ubyte);
+            let e = "enum(a, b)".parse::<FieldType>().unwrap();
+            assert_eq!(e, FieldType::Enum(vec!["a".to_string(), "b".to_string()]));
+            assert_eq!(e.to_string(), "enum(a, b)");
+            assert!("int[3]".parse::<FieldType>().is_err());
+            assert!("integer".parse::<FieldType>().is_err());
         }
 
         #[test]
@@ -800,6 +912,7 @@ pub mod parse {
             "#;
 
             super::parse_autosql(main_test).unwrap();
+            roundtrip(main_test);
         }
         #[test]
         fn test_hardtest() {
@@ -835,6 +948,7 @@ pub mod parse {
             "#;
 
             super::parse_autosql(hard_test).unwrap();
+            roundtrip(hard_test);
         }
 
         #[test]
@@ -861,6 +975,7 @@ pub mod parse {
             "#;
 
             super::parse_autosql(index_test).unwrap();
+            roundtrip(index_test);
         }
     }
 }
@@ -0,0 +1,279 @@
+//! A builder for autoSql declarations, for defining the schema of a bigBed in
+//! code rather than writing the autoSql by hand.
+//!
+//! ```
+//! use bigtools::bed::autosql::builder::DeclarationBuilder;
+//! use bigtools::bed::autosql::parse::FieldType;
+//!
+//! let declaration = DeclarationBuilder::bed("peaks", "Peaks with a signal")
+//!     .standard_fields(6)
+//!     .field(FieldType::Float, "signalValue", "Measurement of enrichment")
+//!     .field(FieldType::Uint, "summitCount", "Number of summits")
+//!     .array_field(FieldType::Int, "summitCount", "summits", "Summit offsets")
+//!     .build()
+//!     .unwrap();
+//! let autosql = declaration.to_string();
+//! assert!(autosql.starts_with("table peaks\n"));
+//! ```
+
+use thiserror::Error;
+
+use crate::bed::autosql::bed_autosql;
+use crate::bed::autosql::parse::{
+    parse_autosql, Declaration, DeclarationType, DeclareName, Field, FieldType, IndexType,
+};
+
+/// The most standard bed fields that can be added with `standard_fields`
+const MAX_STANDARD_FIELDS: usize = 12;
+
+/// An error building a `Declaration`
+#[derive(Debug, Error, PartialEq)]
+pub enum BuildError {
+    #[error("Invalid name `{0}`. Names must start with a letter.")]
+    InvalidName(String),
+    #[error("The field `{0}` is defined more than once.")]
+    DuplicateField(String),
+    #[error("The size of the array field `{field}` must be a number or an earlier field, but is `{size}`.")]
+    InvalidArraySize { field: String, size: String },
+    #[error("Comments cannot contain quotes: `{0}`")]
+    InvalidComment(String),
+}
+
+/// Builds a `Declaration`, which can be written as autoSql with `to_string`.
+#[derive(Clone, Debug)]
+pub struct DeclarationBuilder {
+    declaration: Declaration,
+}
+
+impl DeclarationBuilder {
+    pub fn new(
+        declaration_type: DeclarationType,
+        name: impl Into<String>,
+        comment: impl Into<String>,
+    ) -> Self {
+        DeclarationBuilder {
+            declaration: Declaration {
+                declaration_type,
+                name: DeclareName {
+                    name: name.into(),
+                    index_type: None,
+                    auto: false,
+                },
+                comment: quote(comment.into()),
+                fields: vec![],
+            },
+        }
+    }
+
+    pub fn table(name: impl Into<String>, comment: impl Into<String>) -> Self {
+        DeclarationBuilder::new(DeclarationType::Table, name, comment)
+    }
+
+    /// Starts a table with the `chrom`, `chromStart`, and `chromEnd` fields
+    /// that every bigBed starts with.
+    pub fn bed(name: impl Into<String>, comment: impl Into<String>) -> Self {
+        DeclarationBuilder::table(name, comment).standard_fields(3)
+    }
+
+    /// Adds the standard bed fields (`name`, `score`, `strand`, ...) up to a
+    /// total of `count` fields (at most 12), after any already added.
+    pub fn standard_fields(mut self, count: usize) -> Self {
+        let count = count.min(MAX_STANDARD_FIELDS);
+        let rest = vec!["."; count.saturating_sub(3)].join("\t");
+        let standard = parse_autosql(&bed_autosql(&rest))
+            .expect("Standard bed autosql is valid")
+            .swap_remove(0)
+            .fields;
+        let existing = self.declaration.fields.len();
+        self.declaration
+            .fields
+            .extend(standard.into_iter().take(count).skip(existing));
+        self
+    }
+
+    pub fn field(
+        self,
+        field_type: FieldType,
+        name: impl Into<String>,
+        comment: impl Into<String>,
+    ) -> Self {
+        self.push(field_type, None, name.into(), comment.into())
+    }
+
+    /// Adds an array field, where `size` is either a fixed size (like `3`)
+    /// or the name of an earlier field with the number of items.
+    pub fn array_field(
+        self,
+        field_type: FieldType,
+        size: impl Into<String>,
+        name: impl Into<String>,
+        comment: impl Into<String>,
+    ) -> Self {
+        self.push(field_type, Some(size.into()), name.into(), comment.into())
+    }
+
+    /// Sets the index type of the most recently added field.
+    pub fn index(mut self, index_type: IndexType) -> Self {
+        if let Some(field) = self.declaration.fields.last_mut() {
+            field.index_type = Some(index_type);
+        }
+        self
+    }
+
+    fn push(
+        mut self,
+        field_type: FieldType,
+        field_size: Option<String>,
+        name: String,
+        comment: String,
+    ) -> Self {
+        self.declaration.fields.push(Field {
+            field_type,
+            field_size,
+            name,
+            index_type: None,
+            auto: false,
+            comment: quote(comment),
+        });
+        self
+    }
+
+    /// Checks the names, array sizes, and comments, and returns the
+    /// `Declaration`.
+    pub fn build(self) -> Result<Declaration, BuildError> {
+        let declaration = self.declaration;
+        check_name(&declaration.name.name, false)?;
+        check_comment(&declaration.comment)?;
+        for (i, field) in declaration.fields.iter().enumerate() {
+            check_name(&field.name, true)?;
+            check_comment(&field.comment)?;
+            let previous = &declaration.fields[..i];
+            if previous.iter().any(|f| f.name == field.name) {
+                return Err(BuildError::DuplicateField(field.name.clone()));
+            }
+            if let Some(size) = &field.field_size {
+                let valid = size.parse::<usize>().is_ok()
+                    || previous
+                        .iter()
+                        .any(|f| &f.name == size && f.field_size.is_none());
+                if !valid {
+                    return Err(BuildError::InvalidArraySize {
+                        field: field.name.clone(),
+                        size: size.clone(),
+                    });
+                }
+            }
+        }
+        Ok(declaration)
+    }
+}
+
+/// Comments are stored with their quotes, as they are when parsed
+fn quote(comment: String) -> String {
+    format!("\"{}\"", comment)
+}
+
+/// Declaration names must be alphanumeric, but field names may also contain
+/// underscores
+fn check_name(name: &str, allow_underscore: bool) -> Result<(), BuildError> {
+    let valid = name.chars().next().is_some_and(|c| c.is_alphabetic())
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || (allow_underscore && c == '_'));
+    if !valid {
+        return Err(BuildError::InvalidName(name.to_string()));
+    }
+    Ok(())
+}
+
+fn check_comment(comment: &str) -> Result<(), BuildError> {
+    let inner = &comment[1..comment.len() - 1];
+    if inner.contains('"') {
+        return Err(BuildError::InvalidComment(inner.to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bed::autosql::typed::{BedSchema, FieldValue};
+
+    #[test]
+    fn test_build_roundtrip() {
+        let declaration = DeclarationBuilder::bed("peaks", "Peaks")
+            .standard_fields(6)
+            .field(FieldType::Float, "signalValue", "Signal")
+            .index(IndexType::Index(None))
+            .field(
+                FieldType::Enum(vec!["low".to_string(), "high".to_string()]),
+                "level",
+                "Level",
+            )
+            .field(FieldType::Uint, "count", "Count")
+            .array_field(FieldType::Int, "count", "offsets", "Offsets")
+            .array_field(FieldType::Char, "2", "code", "Code")
+            .build()
+            .unwrap();
+        let names: Vec<_> = declaration.fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "chrom",
+                "chromStart",
+                "chromEnd",
+                "name",
+                "score",
+                "strand",
+                "signalValue",
+                "level",
+                "count",
+                "offsets",
+                "code"
+            ]
+        );
+
+        let autosql = declaration.to_string();
+        let parsed = parse_autosql(&autosql).unwrap();
+        assert_eq!(parsed, vec![declaration]);
+
+        let schema = BedSchema::from_autosql(&autosql).unwrap();
+        let values = schema.decode("p1\t10\t+\t1.5\thigh\t2\t-5,5\tAB");
+        assert_eq!(values[5], Ok(FieldValue::Uint(2)));
+        assert_eq!(
+            values[6],
+            Ok(FieldValue::Array(vec![
+                FieldValue::Int(-5),
+                FieldValue::Int(5)
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_build_errors() {
+        let err = |builder: DeclarationBuilder| builder.build().unwrap_err();
+        assert_eq!(
+            err(DeclarationBuilder::bed("1bed", "")),
+            BuildError::InvalidName("1bed".to_string())
+        );
+        assert_eq!(
+            err(DeclarationBuilder::bed("bed", "").field(FieldType::Uint, "chrom", "")),
+            BuildError::DuplicateField("chrom".to_string())
+        );
+        assert!(matches!(
+            err(DeclarationBuilder::bed("bed", "").array_field(FieldType::Int, "n", "xs", "")),
+            BuildError::InvalidArraySize { .. }
+        ));
+        assert!(matches!(
+            err(DeclarationBuilder::bed("bed", "").field(FieldType::Int, "x", "a \"quote\"")),
+            BuildError::InvalidComment(_)
+        ));
+        // Standard fields are only added once
+        let declaration = DeclarationBuilder::bed("bed", "")
+            .standard_fields(5)
+            .standard_fields(5)
+            .build()
+            .unwrap();
+        assert_eq!(declaration.fields.len(), 5);
+    }
+}
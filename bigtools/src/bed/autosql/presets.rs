@@ -0,0 +1,614 @@
+//! The autoSql of common bigBed formats from UCSC (narrowPeak, broadPeak,
+//! bigGenePred, bigInteract, and bigLolly), and typed records for each.
+//!
+//! A `Preset` gives the autoSql and `BedType` of a format, for writing (see
+//! `bedtobigbed --as-preset`). The records (`NarrowPeak`, `BroadPeak`,
+//! `GenePred`, `Interact`, and `Lolly`) decode a `BedEntry` of that format,
+//! using the `BedSchema` of its autoSql.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
+
+use thiserror::Error;
+
+use crate::bbi::BedEntry;
+use crate::bed::autosql::typed::{BedSchema, FieldError, FieldValue, TypedBedEntry};
+use crate::bed::autosql::validate::BedType;
+
+pub const NARROW_PEAK: &str = r#"table bigNarrowPeak
+"BED6+4 Peaks of signal enrichment based on pooled, normalized (interpreted) data."
+(
+    string chrom;        "Reference sequence chromosome or scaffold"
+    uint   chromStart;   "Start position in chromosome"
+    uint   chromEnd;     "End position in chromosome"
+    string name;         "Name given to a region (preferably unique). Use . if no name is assigned"
+    uint   score;        "Indicates how dark the peak will be displayed in the browser (0-1000)"
+    char[1] strand;      "+ or - or . for unknown"
+    float  signalValue;  "Measurement of average enrichment for the region"
+    float  pValue;       "Statistical significance of signal value (-log10). Set to -1 if not used."
+    float  qValue;       "Statistical significance with multiple-test correction applied (FDR -log10). Set to -1 if not used."
+    int    peak;         "Point-source called for this peak; 0-based offset from chromStart. Set to -1 if no point-source called."
+)
+"#;
+
+pub const BROAD_PEAK: &str = r#"table broadPeak
+"BED6+3 Peaks of signal enrichment based on pooled, normalized (interpreted) data."
+(
+    string chrom;        "Reference sequence chromosome or scaffold"
+    uint   chromStart;   "Start position in chromosome"
+    uint   chromEnd;     "End position in chromosome"
+    string name;         "Name given to a region (preferably unique). Use . if no name is assigned."
+    uint   score;        "Indicates how dark the peak will be displayed in the browser (0-1000)"
+    char[1] strand;      "+ or - or . for unknown"
+    float  signalValue;  "Measurement of average enrichment for the region"
+    float  pValue;       "Statistical significance of signal value (-log10). Set to -1 if not used."
+    float  qValue;       "Statistical significance with multiple-test correction applied (FDR -log10). Set to -1 if not used."
+)
+"#;
+
+pub const BIG_GENE_PRED: &str = r#"table bigGenePred
+"bigGenePred gene models"
+(
+    string chrom;        "Reference sequence chromosome or scaffold"
+    uint   chromStart;   "Start position in chromosome"
+    uint   chromEnd;     "End position in chromosome"
+    string name;         "Name or ID of item, ideally both human readable and unique"
+    uint   score;        "Score (0-1000)"
+    char[1] strand;      "+ or - for strand"
+    uint   thickStart;   "Start of where display should be thick (start codon)"
+    uint   thickEnd;     "End of where display should be thick (stop codon)"
+    uint   reserved;     "RGB value (use R,G,B string in input file)"
+    int    blockCount;   "Number of blocks"
+    int[blockCount] blockSizes;  "Comma separated list of block sizes"
+    int[blockCount] chromStarts; "Start positions relative to chromStart"
+    string name2;        "Alternative/human readable name"
+    enum(none, unk, incmpl, cmpl) cdsStartStat; "Status of CDS start annotation (none, unknown, incomplete, or complete)"
+    enum(none, unk, incmpl, cmpl) cdsEndStat;   "Status of CDS end annotation (none, unknown, incomplete, or complete)"
+    int[blockCount] exonFrames; "Exon frame {0,1,2}, or -1 if no frame for exon"
+    string type;         "Transcript type"
+    string geneName;     "Primary identifier for gene"
+    string geneName2;    "Alternative/human readable gene name"
+    string geneType;     "Gene type"
+)
+"#;
+
+pub const BIG_INTERACT: &str = r#"table interact
+"Interaction between two regions"
+(
+    string chrom;        "Chromosome (or contig, scaffold, etc.). For interchromosomal, use 2 records"
+    uint   chromStart;   "Start position of lower region. For interchromosomal, set to chromStart of this region"
+    uint   chromEnd;     "End position of upper region. For interchromosomal, set to chromEnd of this region"
+    string name;         "Name of item, for display. Usually 'sourceName/targetName/exp' or empty"
+    uint   score;        "Score (0-1000)"
+    double value;        "Strength of interaction or other data value. Typically basis for score"
+    string exp;          "Experiment name (metadata for filtering). Use . if not applicable"
+    string color;        "Item color. Specified as r,g,b or hexadecimal #RRGGBB or html color name. Use 0 and spectrum setting to shade by score"
+    string sourceChrom;  "Chromosome of source region (directional) or lower region. For non-directional interchromosomal, chrom of this region."
+    uint   sourceStart;  "Start position in chromosome of source/lower/this region"
+    uint   sourceEnd;    "End position in chromosome of source/lower/this region"
+    string sourceName;   "Identifier of source/lower/this region"
+    string sourceStrand; "Orientation of source/lower/this region: + or -. Use . if not applicable"
+    string targetChrom;  "Chromosome of target region (directional) or upper region. For non-directional interchromosomal, chrom of other region"
+    uint   targetStart;  "Start position in chromosome of target/upper/this region"
+    uint   targetEnd;    "End position in chromosome of target/upper/this region"
+    string targetName;   "Identifier of target/upper/this region"
+    string targetStrand; "Orientation of target/upper/this region: + or -. Use . if not applicable"
+)
+"#;
+
+pub const BIG_LOLLY: &str = r#"table bigLolly
+"Lollipop items, drawn with a height based on score"
+(
+    string chrom;        "Reference sequence chromosome or scaffold"
+    uint   chromStart;   "Start position in chromosome"
+    uint   chromEnd;     "End position in chromosome"
+    string name;         "Name or ID of item"
+    uint   score;        "Score (0-1000)"
+    char[1] strand;      "+ or - for strand"
+    uint   thickStart;   "Start of where display should be thick (start codon)"
+    uint   thickEnd;     "End of where display should be thick (stop codon)"
+    uint   reserved;     "RGB value (use R,G,B string in input file)"
+    uint   lollySize;    "Size of lollipop"
+)
+"#;
+
+/// A bigBed format with a well-known autoSql
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Preset {
+    NarrowPeak,
+    BroadPeak,
+    BigGenePred,
+    BigInteract,
+    BigLolly,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 5] = [
+        Preset::NarrowPeak,
+        Preset::BroadPeak,
+        Preset::BigGenePred,
+        Preset::BigInteract,
+        Preset::BigLolly,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Preset::NarrowPeak => "narrowPeak",
+            Preset::BroadPeak => "broadPeak",
+            Preset::BigGenePred => "bigGenePred",
+            Preset::BigInteract => "bigInteract",
+            Preset::BigLolly => "bigLolly",
+        }
+    }
+
+    pub fn autosql(&self) -> &'static str {
+        match self {
+            Preset::NarrowPeak => NARROW_PEAK,
+            Preset::BroadPeak => BROAD_PEAK,
+            Preset::BigGenePred => BIG_GENE_PRED,
+            Preset::BigInteract => BIG_INTERACT,
+            Preset::BigLolly => BIG_LOLLY,
+        }
+    }
+
+    /// The schema of `autosql`, parsed once and then shared
+    pub fn schema(&self) -> Arc<BedSchema> {
+        static SCHEMAS: [OnceLock<Arc<BedSchema>>; 5] = [const { OnceLock::new() }; 5];
+        let index = Preset::ALL.iter().position(|p| p == self).unwrap();
+        SCHEMAS[index]
+            .get_or_init(|| {
+                let schema =
+                    BedSchema::from_autosql(self.autosql()).expect("Preset autosql is valid");
+                Arc::new(schema)
+            })
+            .clone()
+    }
+
+    pub fn bed_type(&self) -> BedType {
+        match self {
+            Preset::NarrowPeak => BedType::new(6, Some(4)),
+            Preset::BroadPeak => BedType::new(6, Some(3)),
+            Preset::BigGenePred => BedType::new(12, Some(8)),
+            Preset::BigInteract => BedType::new(5, Some(13)),
+            Preset::BigLolly => BedType::new(9, Some(1)),
+        }
+    }
+}
+
+impl FromStr for Preset {
+    type Err = String;
+
+    /// Parses a preset name, ignoring case. `bigNarrowPeak` and `interact`
+    /// are accepted as well.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "narrowpeak" | "bignarrowpeak" => Ok(Preset::NarrowPeak),
+            "broadpeak" | "bigbroadpeak" => Ok(Preset::BroadPeak),
+            "biggenepred" => Ok(Preset::BigGenePred),
+            "biginteract" | "interact" => Ok(Preset::BigInteract),
+            "biglolly" => Ok(Preset::BigLolly),
+            _ => Err(format!(
+                "Unknown autosql preset `{}`. Options are {}.",
+                s,
+                Preset::ALL
+                    .iter()
+                    .map(|p| format!("`{}`", p.name()))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// An error decoding a record from a `BedEntry`
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum RecordError {
+    #[error("Missing field `{0}`.")]
+    Missing(&'static str),
+    #[error("Invalid value for `{field}`: `{value}`")]
+    InvalidValue { field: &'static str, value: String },
+}
+
+/// The fields of a `BedEntry`, decoded with the schema of a preset. Only a
+/// missing column is `RecordError::Missing`; empty strings are allowed.
+struct Fields<'a> {
+    entry: TypedBedEntry,
+    columns: Vec<&'a str>,
+}
+
+impl<'a> Fields<'a> {
+    fn new(entry: &'a BedEntry, preset: Preset) -> Self {
+        Fields {
+            entry: TypedBedEntry::new(entry, preset.schema()),
+            columns: entry.rest.split('\t').collect(),
+        }
+    }
+
+    fn invalid(&self, field: &'static str) -> RecordError {
+        let value = self
+            .entry
+            .schema()
+            .rest_field_index(field)
+            .and_then(|i| self.columns.get(i))
+            .unwrap_or(&"");
+        RecordError::InvalidValue {
+            field,
+            value: value.to_string(),
+        }
+    }
+
+    fn value(&self, field: &'static str) -> Result<&FieldValue, RecordError> {
+        match self.entry.get(field) {
+            Some(Ok(value)) => Ok(value),
+            None | Some(Err(FieldError::Missing)) => Err(RecordError::Missing(field)),
+            Some(Err(_)) => Err(self.invalid(field)),
+        }
+    }
+
+    fn string(&self, field: &'static str) -> Result<String, RecordError> {
+        match self.value(field)? {
+            FieldValue::String(s) | FieldValue::Enum(s) => Ok(s.clone()),
+            _ => Err(self.invalid(field)),
+        }
+    }
+
+    fn uint<T: TryFrom<u64>>(&self, field: &'static str) -> Result<T, RecordError> {
+        match self.value(field)? {
+            FieldValue::Uint(v) => T::try_from(*v).map_err(|_| self.invalid(field)),
+            _ => Err(self.invalid(field)),
+        }
+    }
+
+    fn int<T: TryFrom<i64>>(&self, field: &'static str) -> Result<T, RecordError> {
+        match self.value(field)? {
+            FieldValue::Int(v) => T::try_from(*v).map_err(|_| self.invalid(field)),
+            _ => Err(self.invalid(field)),
+        }
+    }
+
+    fn float(&self, field: &'static str) -> Result<f64, RecordError> {
+        match self.value(field)? {
+            FieldValue::Float(v) => Ok(*v),
+            _ => Err(self.invalid(field)),
+        }
+    }
+
+    fn strand(&self, field: &'static str) -> Result<char, RecordError> {
+        match self.string(field)?.as_str() {
+            "+" => Ok('+'),
+            "-" => Ok('-'),
+            "." => Ok('.'),
+            _ => Err(self.invalid(field)),
+        }
+    }
+
+    /// An `int[N]` field
+    fn ints<T: TryFrom<i64>>(&self, field: &'static str) -> Result<Vec<T>, RecordError> {
+        let FieldValue::Array(values) = self.value(field)? else {
+            return Err(self.invalid(field));
+        };
+        values
+            .iter()
+            .map(|v| match v {
+                FieldValue::Int(v) => T::try_from(*v).ok(),
+                _ => None,
+            })
+            .collect::<Option<_>>()
+            .ok_or_else(|| self.invalid(field))
+    }
+}
+
+/// A narrowPeak (bed6+4) record
+#[derive(Clone, Debug, PartialEq)]
+pub struct NarrowPeak {
+    pub start: u32,
+    pub end: u32,
+    pub name: String,
+    pub score: u32,
+    pub strand: char,
+    pub signal_value: f32,
+    pub p_value: f32,
+    pub q_value: f32,
+    /// The offset of the peak from `start`, or `-1`
+    pub peak: i32,
+}
+
+impl TryFrom<&BedEntry> for NarrowPeak {
+    type Error = RecordError;
+
+    fn try_from(entry: &BedEntry) -> Result<Self, Self::Error> {
+        let fields = Fields::new(entry, Preset::NarrowPeak);
+        Ok(NarrowPeak {
+            start: entry.start,
+            end: entry.end,
+            name: fields.string("name")?,
+            score: fields.uint("score")?,
+            strand: fields.strand("strand")?,
+            signal_value: fields.float("signalValue")? as f32,
+            p_value: fields.float("pValue")? as f32,
+            q_value: fields.float("qValue")? as f32,
+            peak: fields.int("peak")?,
+        })
+    }
+}
+
+/// A broadPeak (bed6+3) record
+#[derive(Clone, Debug, PartialEq)]
+pub struct BroadPeak {
+    pub start: u32,
+    pub end: u32,
+    pub name: String,
+    pub score: u32,
+    pub strand: char,
+    pub signal_value: f32,
+    pub p_value: f32,
+    pub q_value: f32,
+}
+
+impl TryFrom<&BedEntry> for BroadPeak {
+    type Error = RecordError;
+
+    fn try_from(entry: &BedEntry) -> Result<Self, Self::Error> {
+        let fields = Fields::new(entry, Preset::BroadPeak);
+        Ok(BroadPeak {
+            start: entry.start,
+            end: entry.end,
+            name: fields.string("name")?,
+            score: fields.uint("score")?,
+            strand: fields.strand("strand")?,
+            signal_value: fields.float("signalValue")? as f32,
+            p_value: fields.float("pValue")? as f32,
+            q_value: fields.float("qValue")? as f32,
+        })
+    }
+}
+
+/// A bigGenePred (bed12+8) record
+#[derive(Clone, Debug, PartialEq)]
+pub struct GenePred {
+    pub start: u32,
+    pub end: u32,
+    pub name: String,
+    pub score: u32,
+    pub strand: char,
+    pub thick_start: u32,
+    pub thick_end: u32,
+    /// The color, as `0xRRGGBB`
+    pub item_rgb: u32,
+    pub block_sizes: Vec<u32>,
+    /// The block starts, relative to `start`
+    pub chrom_starts: Vec<u32>,
+    pub name2: String,
+    pub cds_start_stat: String,
+    pub cds_end_stat: String,
+    pub exon_frames: Vec<i32>,
+    pub transcript_type: String,
+    pub gene_name: String,
+    pub gene_name2: String,
+    pub gene_type: String,
+}
+
+impl TryFrom<&BedEntry> for GenePred {
+    type Error = RecordError;
+
+    fn try_from(entry: &BedEntry) -> Result<Self, Self::Error> {
+        let fields = Fields::new(entry, Preset::BigGenePred);
+        // The lengths of the lists are checked against `blockCount` when
+        // decoding
+        Ok(GenePred {
+            start: entry.start,
+            end: entry.end,
+            name: fields.string("name")?,
+            score: fields.uint("score")?,
+            strand: fields.strand("strand")?,
+            thick_start: fields.uint("thickStart")?,
+            thick_end: fields.uint("thickEnd")?,
+            item_rgb: fields.uint("reserved")?,
+            block_sizes: fields.ints("blockSizes")?,
+            chrom_starts: fields.ints("chromStarts")?,
+            name2: fields.string("name2")?,
+            cds_start_stat: fields.string("cdsStartStat")?,
+            cds_end_stat: fields.string("cdsEndStat")?,
+            exon_frames: fields.ints("exonFrames")?,
+            transcript_type: fields.string("type")?,
+            gene_name: fields.string("geneName")?,
+            gene_name2: fields.string("geneName2")?,
+            gene_type: fields.string("geneType")?,
+        })
+    }
+}
+
+/// A bigInteract (bed5+13) record
+#[derive(Clone, Debug, PartialEq)]
+pub struct Interact {
+    pub start: u32,
+    pub end: u32,
+    pub name: String,
+    pub score: u32,
+    pub value: f64,
+    pub exp: String,
+    /// The color as written (`r,g,b`, `#RRGGBB`, or a color name)
+    pub color: String,
+    pub source_chrom: String,
+    pub source_start: u32,
+    pub source_end: u32,
+    pub source_name: String,
+    pub source_strand: char,
+    pub target_chrom: String,
+    pub target_start: u32,
+    pub target_end: u32,
+    pub target_name: String,
+    pub target_strand: char,
+}
+
+impl TryFrom<&BedEntry> for Interact {
+    type Error = RecordError;
+
+    fn try_from(entry: &BedEntry) -> Result<Self, Self::Error> {
+        let fields = Fields::new(entry, Preset::BigInteract);
+        Ok(Interact {
+            start: entry.start,
+            end: entry.end,
+            name: fields.string("name")?,
+            score: fields.uint("score")?,
+            value: fields.float("value")?,
+            exp: fields.string("exp")?,
+            color: fields.string("color")?,
+            source_chrom: fields.string("sourceChrom")?,
+            source_start: fields.uint("sourceStart")?,
+            source_end: fields.uint("sourceEnd")?,
+            source_name: fields.string("sourceName")?,
+            source_strand: fields.strand("sourceStrand")?,
+            target_chrom: fields.string("targetChrom")?,
+            target_start: fields.uint("targetStart")?,
+            target_end: fields.uint("targetEnd")?,
+            target_name: fields.string("targetName")?,
+            target_strand: fields.strand("targetStrand")?,
+        })
+    }
+}
+
+/// A bigLolly (bed9+1) record
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lolly {
+    pub start: u32,
+    pub end: u32,
+    pub name: String,
+    pub score: u32,
+    pub strand: char,
+    pub thick_start: u32,
+    pub thick_end: u32,
+    /// The color, as `0xRRGGBB`
+    pub item_rgb: u32,
+    pub lolly_size: u32,
+}
+
+impl TryFrom<&BedEntry> for Lolly {
+    type Error = RecordError;
+
+    fn try_from(entry: &BedEntry) -> Result<Self, Self::Error> {
+        let fields = Fields::new(entry, Preset::BigLolly);
+        Ok(Lolly {
+            start: entry.start,
+            end: entry.end,
+            name: fields.string("name")?,
+            score: fields.uint("score")?,
+            strand: fields.strand("strand")?,
+            thick_start: fields.uint("thickStart")?,
+            thick_end: fields.uint("thickEnd")?,
+            item_rgb: fields.uint("reserved")?,
+            lolly_size: fields.uint("lollySize")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bed::autosql::typed::BedSchema;
+    use crate::bed::autosql::validate::BedValidator;
+
+    fn entry(start: u32, end: u32, rest: &str) -> BedEntry {
+        BedEntry {
+            start,
+            end,
+            rest: rest.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_presets() {
+        for preset in Preset::ALL {
+            assert_eq!(preset.name().parse::<Preset>(), Ok(preset));
+            let schema = BedSchema::from_autosql(preset.autosql()).unwrap();
+            // The autosql matches the bed type
+            BedValidator::new(schema, Some(preset.bed_type())).unwrap();
+        }
+        assert_eq!("NARROWPEAK".parse::<Preset>(), Ok(Preset::NarrowPeak));
+        assert!("gappedPeak".parse::<Preset>().is_err());
+    }
+
+    #[test]
+    fn test_records() {
+        let rest = "peak1\t500\t.\t12.5\t-1\t3.2\t150";
+        let validator = BedValidator::new(
+            BedSchema::from_autosql(NARROW_PEAK).unwrap(),
+            Some(Preset::NarrowPeak.bed_type()),
+        )
+        .unwrap();
+        assert_eq!(validator.validate(&entry(100, 400, rest)), Ok(()));
+        let peak = NarrowPeak::try_from(&entry(100, 400, rest)).unwrap();
+        assert_eq!(peak.name, "peak1");
+        assert_eq!(peak.strand, '.');
+        assert_eq!(peak.signal_value, 12.5);
+        assert_eq!(peak.p_value, -1.0);
+        assert_eq!(peak.peak, 150);
+        assert_eq!(
+            NarrowPeak::try_from(&entry(100, 400, "peak1\t500\t.\t12.5\t-1")),
+            Err(RecordError::Missing("qValue"))
+        );
+        // Empty strings are allowed, but not empty numbers
+        let peak = NarrowPeak::try_from(&entry(100, 400, "\t500\t.\t12.5\t-1\t3.2\t150")).unwrap();
+        assert_eq!(peak.name, "");
+        assert_eq!(
+            NarrowPeak::try_from(&entry(100, 400, "peak1\t500\t.\t\t-1\t3.2\t150")),
+            Err(RecordError::InvalidValue {
+                field: "signalValue",
+                value: "".to_string()
+            })
+        );
+        assert_eq!(
+            BroadPeak::try_from(&entry(100, 400, "p\tx\t+\t1\t2\t3")),
+            Err(RecordError::InvalidValue {
+                field: "score",
+                value: "x".to_string()
+            })
+        );
+
+        let rest = "tx1\t0\t+\t110\t380\t0,128,255\t2\t100,100,\t0,200,\tGENE1\tcmpl\tincmpl\t0,2,\tprotein_coding\tGENE1\tgene1\tcoding";
+        let validator = BedValidator::new(
+            BedSchema::from_autosql(BIG_GENE_PRED).unwrap(),
+            Some(Preset::BigGenePred.bed_type()),
+        )
+        .unwrap();
+        assert_eq!(validator.validate(&entry(100, 400, rest)), Ok(()));
+        let gene = GenePred::try_from(&entry(100, 400, rest)).unwrap();
+        assert_eq!(gene.item_rgb, 0x0080ff);
+        assert_eq!(gene.block_sizes, vec![100, 100]);
+        assert_eq!(gene.chrom_starts, vec![0, 200]);
+        assert_eq!(gene.cds_end_stat, "incmpl");
+        assert_eq!(gene.exon_frames, vec![0, 2]);
+        assert_eq!(gene.gene_type, "coding");
+        let rest = rest.replace("\t2\t100,100,", "\t3\t100,100,");
+        assert!(matches!(
+            GenePred::try_from(&entry(100, 400, &rest)),
+            Err(RecordError::InvalidValue {
+                field: "blockSizes",
+                ..
+            })
+        ));
+
+        let rest = "a/b\t800\t4.5\texp1\t255,0,0\tchr1\t100\t200\ta\t+\tchr1\t900\t1000\tb\t-";
+        let interact = Interact::try_from(&entry(100, 1000, rest)).unwrap();
+        assert_eq!(interact.value, 4.5);
+        assert_eq!(interact.source_strand, '+');
+        assert_eq!(interact.target_start, 900);
+        assert_eq!(interact.target_strand, '-');
+        let rest = "\t800\t4.5\t.\t0\tchr1\t100\t200\t\t.\tchr1\t900\t1000\t\t.";
+        let interact = Interact::try_from(&entry(100, 1000, rest)).unwrap();
+        assert_eq!(interact.name, "");
+        assert_eq!(interact.source_name, "");
+
+        let rest = "snp1\t900\t+\t100\t101\t255\t7";
+        let lolly = Lolly::try_from(&entry(100, 101, rest)).unwrap();
+        assert_eq!(lolly.item_rgb, 255);
+        assert_eq!(lolly.lolly_size, 7);
+    }
+}
@@ -0,0 +1,460 @@
+//! Typed decoding of the fields of bed entries, as described by an autoSql
+//! declaration.
+//!
+//! A `BedSchema` is built from the autoSql of a bigBed (see
+//! `BigBedRead::schema`), and decodes the tab-separated `rest` of a
+//! `BedEntry` into a `FieldValue` for each field. Fields are decoded
+//! independently, so that a single invalid field doesn't prevent the others
+//! from being read.
+
+use std::sync::Arc;
+
+use thiserror::Error;
+
+use crate::bbi::BedEntry;
+use crate::bed::autosql::bed_autosql;
+use crate::bed::autosql::parse::{parse_autosql, Declaration, Field, FieldType, ParseError};
+
+/// A decoded field value
+#[derive(Clone, Debug, PartialEq)]
+pub enum FieldValue {
+    /// A `byte`, `short`, `int`, or `bigint`
+    Int(i64),
+    /// A `ubyte`, `ushort`, or `uint`
+    Uint(u64),
+    /// A `float` or `double`
+    Float(f64),
+    /// A `char`, `char[N]`, `string`, or `lstring`
+    String(String),
+    Enum(String),
+    Set(Vec<String>),
+    /// A `type[N]` or `type[countField]` field (other than `char[N]`)
+    Array(Vec<FieldValue>),
+}
+
+/// An error decoding a single field
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum FieldError {
+    #[error("Missing value.")]
+    Missing,
+    #[error("Invalid {field_type} value: `{value}`")]
+    InvalidValue { field_type: String, value: String },
+    #[error("`{0}` is not one of the allowed values.")]
+    InvalidEnumValue(String),
+    #[error("Expected {expected} values, but found {found}.")]
+    ArrayLength { expected: usize, found: usize },
+    #[error("Invalid array size `{0}`.")]
+    InvalidArraySize(String),
+    #[error("Unsupported field type `{0}`.")]
+    UnsupportedType(String),
+}
+
+/// An error creating a `BedSchema`
+#[derive(Debug, Error)]
+pub enum SchemaError {
+    #[error("Invalid autosql: {0:?}")]
+    InvalidAutosql(ParseError),
+    #[error("The autosql has no declarations.")]
+    NoDeclaration,
+    #[error(
+        "The autosql must have at least 3 fields (chrom, chromStart, and chromEnd), but has {0}."
+    )]
+    TooFewFields(usize),
+}
+
+/// The fields of a bed file, from an autoSql declaration. The first three
+/// fields are always `chrom`, `chromStart`, and `chromEnd`; the remaining
+/// fields are the ones in `BedEntry::rest`.
+#[derive(Clone, Debug)]
+pub struct BedSchema {
+    name: String,
+    fields: Vec<Field>,
+}
+
+impl BedSchema {
+    /// Creates a `BedSchema` from the first declaration in `autosql`.
+    pub fn from_autosql(autosql: &str) -> Result<BedSchema, SchemaError> {
+        let mut declarations = parse_autosql(autosql).map_err(SchemaError::InvalidAutosql)?;
+        if declarations.is_empty() {
+            return Err(SchemaError::NoDeclaration);
+        }
+        BedSchema::from_declaration(declarations.swap_remove(0))
+    }
+
+    pub fn from_declaration(declaration: Declaration) -> Result<BedSchema, SchemaError> {
+        if declaration.fields.len() < 3 {
+            return Err(SchemaError::TooFewFields(declaration.fields.len()));
+        }
+        Ok(BedSchema {
+            name: declaration.name.name,
+            fields: declaration.fields,
+        })
+    }
+
+    /// The schema of standard bed with `extra_fields` fields after the first
+    /// three (like `bed_autosql`).
+    pub fn bed(extra_fields: usize) -> BedSchema {
+        let rest = vec!["."; extra_fields].join("\t");
+        BedSchema::from_autosql(&bed_autosql(&rest)).expect("Standard bed autosql is valid")
+    }
+
+    /// The name of the declaration
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// All the fields, including `chrom`, `chromStart`, and `chromEnd`
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    /// The fields after `chrom`, `chromStart`, and `chromEnd`
+    pub fn rest_fields(&self) -> &[Field] {
+        &self.fields[3..]
+    }
+
+    /// The index in `rest_fields` of the field named `name`
+    pub fn rest_field_index(&self, name: &str) -> Option<usize> {
+        self.rest_fields().iter().position(|f| f.name == name)
+    }
+
+    /// Decodes the tab-separated `rest` of a bed entry into a value for each
+    /// field in `rest_fields`. Any columns beyond those fields are ignored.
+    pub fn decode(&self, rest: &str) -> Vec<Result<FieldValue, FieldError>> {
+        let fields = self.rest_fields();
+        let mut columns = (!rest.is_empty())
+            .then(|| rest.split('\t'))
+            .into_iter()
+            .flatten();
+        let mut values: Vec<Result<FieldValue, FieldError>> = Vec::with_capacity(fields.len());
+        for field in fields {
+            let value = match columns.next() {
+                None => Err(FieldError::Missing),
+                Some(column) => self.decode_field(field, column, &values),
+            };
+            values.push(value);
+        }
+        values
+    }
+
+    /// Decodes a single field, given the values of the fields before it
+    /// (which may be the counts of array fields)
+    fn decode_field(
+        &self,
+        field: &Field,
+        column: &str,
+        previous: &[Result<FieldValue, FieldError>],
+    ) -> Result<FieldValue, FieldError> {
+        let size = match (&field.field_size, &field.field_type) {
+            (None, _) | (Some(_), FieldType::Char) => {
+                return decode_scalar(field, column);
+            }
+            (Some(size), _) => size,
+        };
+        let expected = match size.parse::<usize>() {
+            Ok(size) => size,
+            Err(_) => {
+                let count = self
+                    .rest_field_index(size)
+                    .and_then(|i| previous.get(i))
+                    .ok_or_else(|| FieldError::InvalidArraySize(size.clone()))?;
+                match count {
+                    Ok(FieldValue::Int(n)) if *n >= 0 => *n as usize,
+                    Ok(FieldValue::Uint(n)) => *n as usize,
+                    _ => return Err(FieldError::InvalidArraySize(size.clone())),
+                }
+            }
+        };
+        // Arrays are comma-separated, usually with a trailing comma
+        let column = column.strip_suffix(',').unwrap_or(column);
+        let elements: Vec<&str> = if column.is_empty() {
+            vec![]
+        } else {
+            column.split(',').collect()
+        };
+        if elements.len() != expected {
+            return Err(FieldError::ArrayLength {
+                expected,
+                found: elements.len(),
+            });
+        }
+        elements
+            .into_iter()
+            .map(|e| decode_scalar(field, e))
+            .collect::<Result<_, _>>()
+            .map(FieldValue::Array)
+    }
+}
+
+fn decode_scalar(field: &Field, value: &str) -> Result<FieldValue, FieldError> {
+    let invalid = || FieldError::InvalidValue {
+        field_type: field.field_type.to_string(),
+        value: value.to_string(),
+    };
+    Ok(match &field.field_type {
+        FieldType::Byte => FieldValue::Int(value.parse::<i8>().map_err(|_| invalid())?.into()),
+        FieldType::Short => FieldValue::Int(value.parse::<i16>().map_err(|_| invalid())?.into()),
+        FieldType::Int => FieldValue::Int(value.parse::<i32>().map_err(|_| invalid())?.into()),
+        FieldType::Bigint => FieldValue::Int(value.parse::<i64>().map_err(|_| invalid())?),
+        FieldType::Ubyte => FieldValue::Uint(value.parse::<u8>().map_err(|_| invalid())?.into()),
+        FieldType::Ushort => FieldValue::Uint(value.parse::<u16>().map_err(|_| invalid())?.into()),
+        FieldType::Uint => match value.parse::<u32>() {
+            Ok(v) => FieldValue::Uint(v.into()),
+            // `itemRgb` is a `uint` in the standard bed autosql, but is
+            // written as `r,g,b`
+            Err(_) if field.name == "reserved" || field.name == "itemRgb" => {
+                FieldValue::Uint(parse_rgb(value).ok_or_else(invalid)?.into())
+            }
+            Err(_) => return Err(invalid()),
+        },
+        FieldType::Float | FieldType::Double => {
+            FieldValue::Float(value.parse::<f64>().map_err(|_| invalid())?)
+        }
+        FieldType::Char | FieldType::String | FieldType::Lstring => {
+            FieldValue::String(value.to_string())
+        }
+        FieldType::Enum(values) => {
+            if !values.iter().any(|v| v == value) {
+                return Err(FieldError::InvalidEnumValue(value.to_string()));
+            }
+            FieldValue::Enum(value.to_string())
+        }
+        FieldType::Set(values) => {
+            let members: Vec<String> = value
+                .split(',')
+                .filter(|m| !m.is_empty())
+                .map(|m| m.to_string())
+                .collect();
+            if let Some(m) = members.iter().find(|m| !values.contains(m)) {
+                return Err(FieldError::InvalidEnumValue(m.clone()));
+            }
+            FieldValue::Set(members)
+        }
+        FieldType::Declaration(..) => {
+            return Err(FieldError::UnsupportedType(field.field_type.to_string()))
+        }
+    })
+}
+
+/// Parses an `r,g,b` color into `0xRRGGBB`
+fn parse_rgb(value: &str) -> Option<u32> {
+    let mut parts = value.split(',');
+    let mut rgb = 0u32;
+    for _ in 0..3 {
+        rgb = rgb << 8 | u32::from(parts.next()?.trim().parse::<u8>().ok()?);
+    }
+    parts.next().is_none().then_some(rgb)
+}
+
+/// A `BedEntry` with each of its fields decoded according to a `BedSchema`
+#[derive(Clone, Debug)]
+pub struct TypedBedEntry {
+    pub start: u32,
+    pub end: u32,
+    /// The value (or decoding error) of each field in
+    /// `BedSchema::rest_fields`
+    pub values: Vec<Result<FieldValue, FieldError>>,
+    schema: Arc<BedSchema>,
+}
+
+impl TypedBedEntry {
+    pub fn new(entry: &BedEntry, schema: Arc<BedSchema>) -> TypedBedEntry {
+        TypedBedEntry {
+            start: entry.start,
+            end: entry.end,
+            values: schema.decode(&entry.rest),
+            schema,
+        }
+    }
+
+    pub fn schema(&self) -> &BedSchema {
+        &self.schema
+    }
+
+    /// Gets the value of the field named `name`
+    pub fn get(&self, name: &str) -> Option<&Result<FieldValue, FieldError>> {
+        self.schema
+            .rest_field_index(name)
+            .and_then(|i| self.values.get(i))
+    }
+
+    /// The name and value of each field
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &Result<FieldValue, FieldError>)> {
+        self.schema
+            .rest_fields()
+            .iter()
+            .map(|f| f.name.as_str())
+            .zip(self.values.iter())
+    }
+
+    /// The name and error of each field that couldn't be decoded
+    pub fn errors(&self) -> impl Iterator<Item = (&str, &FieldError)> {
+        self.fields()
+            .filter_map(|(name, value)| value.as_ref().err().map(|e| (name, e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_bed12() {
+        assert_eq!(BedSchema::bed(0).fields().len(), 3);
+        assert_eq!(BedSchema::bed(1).fields().len(), 4);
+        let schema = BedSchema::bed(9);
+        assert_eq!(schema.name(), "bed");
+        assert_eq!(schema.rest_fields().len(), 9);
+
+        let values = schema.decode("gene1\t500\t+\t100\t900\t255,0,0\t2\t100,200,\t0,800,");
+        assert_eq!(
+            values,
+            vec![
+                Ok(FieldValue::String("gene1".to_string())),
+                Ok(FieldValue::Uint(500)),
+                Ok(FieldValue::String("+".to_string())),
+                Ok(FieldValue::Uint(100)),
+                Ok(FieldValue::Uint(900)),
+                Ok(FieldValue::Uint(0xff0000)),
+                Ok(FieldValue::Int(2)),
+                Ok(FieldValue::Array(vec![
+                    FieldValue::Int(100),
+                    FieldValue::Int(200)
+                ])),
+                Ok(FieldValue::Array(vec![
+                    FieldValue::Int(0),
+                    FieldValue::Int(800)
+                ])),
+            ]
+        );
+
+        // Errors are per field
+        let values = schema.decode("gene1\tabc\t+\t100\t900\t0\t3\t100,200,\t0,800,x,");
+        assert_eq!(values[0], Ok(FieldValue::String("gene1".to_string())));
+        assert_eq!(
+            values[1],
+            Err(FieldError::InvalidValue {
+                field_type: "uint".to_string(),
+                value: "abc".to_string()
+            })
+        );
+        assert_eq!(values[5], Ok(FieldValue::Uint(0)));
+        assert_eq!(
+            values[7],
+            Err(FieldError::ArrayLength {
+                expected: 3,
+                found: 2
+            })
+        );
+        assert_eq!(
+            values[8],
+            Err(FieldError::InvalidValue {
+                field_type: "int".to_string(),
+                value: "x".to_string()
+            })
+        );
+
+        let values = schema.decode("gene1\t500");
+        assert_eq!(values.len(), 9);
+        assert_eq!(values[2], Err(FieldError::Missing));
+    }
+
+    #[test]
+    fn test_decode_types() {
+        let autosql = r#"
+        table test
+        "Test"
+            (
+            string chrom; "Chromosome"
+            uint chromStart; "Start"
+            uint chromEnd; "End"
+            byte b; "byte"
+            ushort us; "ushort"
+            bigint big; "bigint"
+            double d; "double"
+            char[5] code; "code"
+            enum(male, female) sex; "enum"
+            set(cProg,javaProg,pythonProg) skills; "set"
+            int[3] xyz; "fixed size array"
+            uint count; "count"
+            string[count] names; "names"
+            simple pt point; "unsupported"
+            )
+        "#;
+        let schema = BedSchema::from_autosql(autosql).unwrap();
+        assert_eq!(schema.name(), "test");
+        let values = schema.decode(
+            "-5\t65535\t-9000000000\t1.5e-3\tABCDE\tfemale\tcProg,pythonProg\t1,2,3\t2\ta,b,\t1",
+        );
+        assert_eq!(
+            values,
+            vec![
+                Ok(FieldValue::Int(-5)),
+                Ok(FieldValue::Uint(65535)),
+                Ok(FieldValue::Int(-9000000000)),
+                Ok(FieldValue::Float(1.5e-3)),
+                Ok(FieldValue::String("ABCDE".to_string())),
+                Ok(FieldValue::Enum("female".to_string())),
+                Ok(FieldValue::Set(vec![
+                    "cProg".to_string(),
+                    "pythonProg".to_string()
+                ])),
+                Ok(FieldValue::Array(vec![
+                    FieldValue::Int(1),
+                    FieldValue::Int(2),
+                    FieldValue::Int(3)
+                ])),
+                Ok(FieldValue::Uint(2)),
+                Ok(FieldValue::Array(vec![
+                    FieldValue::String("a".to_string()),
+                    FieldValue::String("b".to_string())
+                ])),
+                Err(FieldError::UnsupportedType("simple pt".to_string())),
+            ]
+        );
+
+        let values = schema.decode("128\t65536\t0\tnan\tA\tother\trustProg\t1,2\tx\ta,b");
+        assert!(matches!(values[0], Err(FieldError::InvalidValue { .. })));
+        assert!(matches!(values[1], Err(FieldError::InvalidValue { .. })));
+        assert!(matches!(values[3], Ok(FieldValue::Float(f)) if f.is_nan()));
+        assert_eq!(
+            values[5],
+            Err(FieldError::InvalidEnumValue("other".to_string()))
+        );
+        assert_eq!(
+            values[6],
+            Err(FieldError::InvalidEnumValue("rustProg".to_string()))
+        );
+        assert_eq!(
+            values[7],
+            Err(FieldError::ArrayLength {
+                expected: 3,
+                found: 2
+            })
+        );
+        assert_eq!(
+            values[9],
+            Err(FieldError::InvalidArraySize("count".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_schema_errors() {
+        assert!(matches!(
+            BedSchema::from_autosql(""),
+            Err(SchemaError::NoDeclaration)
+        ));
+        let autosql = r#"
+        table short
+        "Too short"
+            (
+            string chrom; "Chromosome"
+            uint chromStart; "Start"
+            )
+        "#;
+        assert!(matches!(
+            BedSchema::from_autosql(autosql),
+            Err(SchemaError::TooFewFields(2))
+        ));
+    }
+}
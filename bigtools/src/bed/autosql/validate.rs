@@ -0,0 +1,435 @@
+//! Validation of bed entries against an autoSql declaration and a `bedN+M`
+//! type, as done by `bedToBigBed`.
+//!
+//! A `BedValidator` checks that each entry has the number of columns given by
+//! the type, that every field can be decoded as its autoSql type (including
+//! enum and set membership and the lengths of arrays), and that the standard
+//! bed columns (strand, thickStart/thickEnd, and blocks) are consistent.
+
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::bbi::BedEntry;
+use crate::bed::autosql::typed::{BedSchema, FieldError, FieldValue};
+
+/// The names of the standard bed columns, in order
+const STANDARD_FIELDS: &[&str] = &[
+    "chrom",
+    "chromStart",
+    "chromEnd",
+    "name",
+    "score",
+    "strand",
+    "thickStart",
+    "thickEnd",
+    "itemRgb",
+    "blockCount",
+    "blockSizes",
+    "chromStarts",
+];
+
+/// A bed type, written as `bedN`, `bedN+`, or `bedN+M`. The first `N`
+/// columns are standard bed columns, followed by `M` extra columns. `bedN+`
+/// allows any number of extra columns.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BedType {
+    pub standard_fields: u8,
+    pub extra_fields: Option<u8>,
+}
+
+impl BedType {
+    pub fn new(standard_fields: u8, extra_fields: Option<u8>) -> BedType {
+        BedType {
+            standard_fields,
+            extra_fields,
+        }
+    }
+}
+
+impl FromStr for BedType {
+    type Err = ValidatorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ValidatorError::InvalidType(s.to_string());
+        let rest = s.strip_prefix("bed").ok_or_else(invalid)?;
+        let (standard, extra) = match rest.split_once('+') {
+            None => (rest, Some("0")),
+            Some((standard, "")) => (standard, None),
+            Some((standard, extra)) => (standard, Some(extra)),
+        };
+        let standard_fields = standard.parse::<u8>().map_err(|_| invalid())?;
+        if !(3..=12).contains(&standard_fields) {
+            return Err(invalid());
+        }
+        let extra_fields = extra
+            .map(|e| e.parse::<u8>().map_err(|_| invalid()))
+            .transpose()?;
+        Ok(BedType {
+            standard_fields,
+            extra_fields,
+        })
+    }
+}
+
+impl fmt::Display for BedType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.extra_fields {
+            Some(0) => write!(f, "bed{}", self.standard_fields),
+            Some(extra) => write!(f, "bed{}+{}", self.standard_fields, extra),
+            None => write!(f, "bed{}+", self.standard_fields),
+        }
+    }
+}
+
+/// An error creating a `BedType` or `BedValidator`
+#[derive(Debug, Error)]
+pub enum ValidatorError {
+    #[error(
+        "Invalid bed type `{0}`. Expected `bedN`, `bedN+`, or `bedN+M`, with N between 3 and 12."
+    )]
+    InvalidType(String),
+    #[error("The bed type `{bed_type}` does not match the autosql, which has {fields} fields.")]
+    TypeMismatch { bed_type: BedType, fields: usize },
+}
+
+/// An error in a single bed entry
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum BedEntryError {
+    #[error("Expected {expected} columns, but found {found}.")]
+    ColumnCount { expected: String, found: usize },
+    #[error("Invalid `{field}`: {error}")]
+    Field { field: String, error: FieldError },
+    #[error("Invalid strand `{0}`. Must be `+`, `-`, or `.`.")]
+    Strand(String),
+    #[error("thickStart ({thick_start}) and thickEnd ({thick_end}) must be in order and within chromStart ({start}) and chromEnd ({end}).")]
+    ThickRange {
+        thick_start: i64,
+        thick_end: i64,
+        start: u32,
+        end: u32,
+    },
+    #[error("Invalid blocks: {0}")]
+    Blocks(String),
+}
+
+/// Validates bed entries against a `BedSchema` and `BedType`
+#[derive(Clone, Debug)]
+pub struct BedValidator {
+    schema: BedSchema,
+    bed_type: BedType,
+}
+
+impl BedValidator {
+    /// Creates a validator for entries of `schema`. If `bed_type` is `None`,
+    /// every field in the schema is required, and the leading fields with
+    /// standard bed names are checked as standard bed columns.
+    pub fn new(schema: BedSchema, bed_type: Option<BedType>) -> Result<Self, ValidatorError> {
+        let fields = schema.fields().len();
+        let bed_type = match bed_type {
+            Some(bed_type) => {
+                let standard = usize::from(bed_type.standard_fields);
+                let matches = match bed_type.extra_fields {
+                    Some(extra) => standard + usize::from(extra) == fields,
+                    None => standard <= fields,
+                };
+                if !matches {
+                    return Err(ValidatorError::TypeMismatch { bed_type, fields });
+                }
+                bed_type
+            }
+            None => {
+                let standard = schema
+                    .fields()
+                    .iter()
+                    .zip(STANDARD_FIELDS)
+                    .take_while(|(field, name)| {
+                        field.name == **name || (**name == "itemRgb" && field.name == "reserved")
+                    })
+                    .count()
+                    .max(3);
+                BedType::new(standard as u8, Some((fields - standard) as u8))
+            }
+        };
+        Ok(BedValidator { schema, bed_type })
+    }
+
+    pub fn schema(&self) -> &BedSchema {
+        &self.schema
+    }
+
+    pub fn bed_type(&self) -> BedType {
+        self.bed_type
+    }
+
+    /// Checks a single entry, returning the first problem found.
+    pub fn validate(&self, entry: &BedEntry) -> Result<(), BedEntryError> {
+        let columns = if entry.rest.is_empty() {
+            3
+        } else {
+            entry.rest.split('\t').count() + 3
+        };
+        let standard = usize::from(self.bed_type.standard_fields);
+        let fields = self.schema.fields().len();
+        let count_ok = match self.bed_type.extra_fields {
+            Some(_) => columns == fields,
+            None => (standard..=fields).contains(&columns),
+        };
+        if !count_ok {
+            let expected = match self.bed_type.extra_fields {
+                Some(_) => fields.to_string(),
+                None => format!("{} to {}", standard, fields),
+            };
+            return Err(BedEntryError::ColumnCount {
+                expected,
+                found: columns,
+            });
+        }
+
+        let values = self.schema.decode(&entry.rest);
+        let rest_fields = self.schema.rest_fields();
+        for (field, value) in rest_fields.iter().zip(values.iter()).take(columns - 3) {
+            match value {
+                Ok(_) | Err(FieldError::UnsupportedType(_)) => {}
+                Err(error) => {
+                    return Err(BedEntryError::Field {
+                        field: field.name.clone(),
+                        error: error.clone(),
+                    })
+                }
+            }
+        }
+
+        // Only the standard columns present in this entry are checked
+        let standard = standard.min(columns);
+        let value = |i: usize| values.get(i - 3).and_then(|v| v.as_ref().ok());
+        if standard >= 6 {
+            if let Some(FieldValue::String(strand) | FieldValue::Enum(strand)) = value(5) {
+                if !matches!(strand.as_str(), "+" | "-" | ".") {
+                    return Err(BedEntryError::Strand(strand.clone()));
+                }
+            }
+        }
+        if standard >= 8 {
+            if let (Some(thick_start), Some(thick_end)) =
+                (value(6).and_then(as_int), value(7).and_then(as_int))
+            {
+                // An empty thick region may be anywhere
+                let in_range = thick_start == thick_end
+                    || (i64::from(entry.start) <= thick_start
+                        && thick_start < thick_end
+                        && thick_end <= i64::from(entry.end));
+                if !in_range {
+                    return Err(BedEntryError::ThickRange {
+                        thick_start,
+                        thick_end,
+                        start: entry.start,
+                        end: entry.end,
+                    });
+                }
+            }
+        }
+        if standard >= 12 {
+            let count = value(9).and_then(as_int).unwrap_or(0);
+            let sizes = value(10).map(as_ints).unwrap_or_default();
+            let starts = value(11).map(as_ints).unwrap_or_default();
+            check_blocks(entry, count, &sizes, &starts).map_err(BedEntryError::Blocks)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn as_int(value: &FieldValue) -> Option<i64> {
+    match value {
+        FieldValue::Int(v) => Some(*v),
+        FieldValue::Uint(v) => i64::try_from(*v).ok(),
+        _ => None,
+    }
+}
+
+fn as_ints(value: &FieldValue) -> Vec<i64> {
+    match value {
+        FieldValue::Array(values) => values.iter().filter_map(as_int).collect(),
+        _ => vec![],
+    }
+}
+
+fn check_blocks(entry: &BedEntry, count: i64, sizes: &[i64], starts: &[i64]) -> Result<(), String> {
+    if count < 1 {
+        return Err(format!("blockCount must be at least 1, but is {}.", count));
+    }
+    if starts.first() != Some(&0) {
+        return Err("The first block must start at chromStart.".to_string());
+    }
+    let mut prev_end = 0;
+    for (i, (size, start)) in sizes.iter().zip(starts.iter()).enumerate() {
+        if *size < 0 {
+            return Err(format!("Block {} has a negative size ({}).", i + 1, size));
+        }
+        if *start < prev_end {
+            return Err(format!(
+                "Block {} starts at {}, which is before the end of the previous block ({}).",
+                i + 1,
+                start,
+                prev_end
+            ));
+        }
+        prev_end = start + size;
+    }
+    let length = i64::from(entry.end) - i64::from(entry.start);
+    if prev_end != length {
+        return Err(format!(
+            "The last block must end at chromEnd, but ends {} bases after chromStart instead of {}.",
+            prev_end, length
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(start: u32, end: u32, rest: &str) -> BedEntry {
+        BedEntry {
+            start,
+            end,
+            rest: rest.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_bed_type() {
+        assert_eq!("bed6".parse::<BedType>().unwrap(), BedType::new(6, Some(0)));
+        assert_eq!(
+            "bed6+4".parse::<BedType>().unwrap(),
+            BedType::new(6, Some(4))
+        );
+        assert_eq!("bed3+".parse::<BedType>().unwrap(), BedType::new(3, None));
+        assert_eq!(BedType::new(6, Some(4)).to_string(), "bed6+4");
+        assert_eq!(BedType::new(3, None).to_string(), "bed3+");
+        for invalid in ["bed", "bed2", "bed13", "bigBed6", "bed6+x"] {
+            assert!(matches!(
+                invalid.parse::<BedType>(),
+                Err(ValidatorError::InvalidType(_))
+            ));
+        }
+
+        let schema = BedSchema::bed(3);
+        assert!(BedValidator::new(schema.clone(), Some(BedType::new(6, Some(0)))).is_ok());
+        assert!(BedValidator::new(schema.clone(), Some(BedType::new(4, None))).is_ok());
+        assert!(matches!(
+            BedValidator::new(schema.clone(), Some(BedType::new(4, Some(0)))),
+            Err(ValidatorError::TypeMismatch { fields: 6, .. })
+        ));
+        let validator = BedValidator::new(schema, None).unwrap();
+        assert_eq!(validator.bed_type(), BedType::new(6, Some(0)));
+    }
+
+    #[test]
+    fn test_validate_bed12() {
+        let validator = BedValidator::new(BedSchema::bed(9), None).unwrap();
+        let valid = "gene1\t500\t+\t100\t900\t255,0,0\t2\t100,200,\t0,800,";
+        assert_eq!(validator.validate(&entry(0, 1000, valid)), Ok(()));
+        // Empty thick regions are allowed anywhere
+        let valid = "gene1\t500\t.\t0\t0\t0\t1\t1000,\t0,";
+        assert_eq!(validator.validate(&entry(0, 1000, valid)), Ok(()));
+
+        let err = |rest: &str| validator.validate(&entry(0, 1000, rest)).unwrap_err();
+        assert_eq!(
+            err("gene1\t500\t+"),
+            BedEntryError::ColumnCount {
+                expected: "12".to_string(),
+                found: 6
+            }
+        );
+        assert!(matches!(
+            err("gene1\tabc\t+\t100\t900\t0\t2\t100,200,\t0,800,"),
+            BedEntryError::Field { field, .. } if field == "score"
+        ));
+        assert_eq!(
+            err("gene1\t500\tx\t100\t900\t0\t2\t100,200,\t0,800,"),
+            BedEntryError::Strand("x".to_string())
+        );
+        assert!(matches!(
+            err("gene1\t500\t+\t900\t100\t0\t2\t100,200,\t0,800,"),
+            BedEntryError::ThickRange { .. }
+        ));
+        assert!(matches!(
+            err("gene1\t500\t+\t100\t1100\t0\t2\t100,200,\t0,800,"),
+            BedEntryError::ThickRange { .. }
+        ));
+        assert!(matches!(
+            err("gene1\t500\t+\t100\t900\t0\t3\t100,200,\t0,800,"),
+            BedEntryError::Field { field, error: FieldError::ArrayLength { expected: 3, found: 2 } }
+                if field == "blockSizes"
+        ));
+        assert!(matches!(
+            err("gene1\t500\t+\t100\t900\t0\t2\t100,200,\t10,800,"),
+            BedEntryError::Blocks(_)
+        ));
+        assert!(matches!(
+            err("gene1\t500\t+\t100\t900\t0\t2\t100,100,\t0,800,"),
+            BedEntryError::Blocks(_)
+        ));
+        assert!(matches!(
+            err("gene1\t500\t+\t100\t900\t0\t2\t500,200,\t0,400,"),
+            BedEntryError::Blocks(_)
+        ));
+    }
+
+    #[test]
+    fn test_validate_extra_fields() {
+        let autosql = r#"
+        table test
+        "Test"
+            (
+            string chrom; "Chromosome"
+            uint chromStart; "Start"
+            uint chromEnd; "End"
+            string name; "Name"
+            enum(low, high) level; "Level"
+            set(a, b, c) tags; "Tags"
+            uint count; "Count"
+            float[count] scores; "Scores"
+            )
+        "#;
+        let schema = BedSchema::from_autosql(autosql).unwrap();
+        let validator = BedValidator::new(schema.clone(), None).unwrap();
+        assert_eq!(validator.bed_type(), BedType::new(4, Some(4)));
+        assert_eq!(
+            validator.validate(&entry(0, 10, "x\thigh\ta,c\t2\t1.5,2")),
+            Ok(())
+        );
+        assert!(matches!(
+            validator.validate(&entry(0, 10, "x\tmedium\ta,c\t2\t1.5,2")),
+            Err(BedEntryError::Field { field, error: FieldError::InvalidEnumValue(_) })
+                if field == "level"
+        ));
+        assert!(matches!(
+            validator.validate(&entry(0, 10, "x\thigh\ta,d\t2\t1.5,2")),
+            Err(BedEntryError::Field { field, .. }) if field == "tags"
+        ));
+        assert!(matches!(
+            validator.validate(&entry(0, 10, "x\thigh\ta\t2\t1.5")),
+            Err(BedEntryError::Field { field, .. }) if field == "scores"
+        ));
+        assert!(matches!(
+            validator.validate(&entry(0, 10, "x\thigh\ta\t2\t1.5,2\textra")),
+            Err(BedEntryError::ColumnCount { found: 9, .. })
+        ));
+
+        // `bedN+` allows trailing extra fields to be left out
+        let validator = BedValidator::new(schema, Some(BedType::new(4, None))).unwrap();
+        assert_eq!(validator.validate(&entry(0, 10, "x\thigh")), Ok(()));
+        assert_eq!(
+            validator.validate(&entry(0, 10, "")),
+            Err(BedEntryError::ColumnCount {
+                expected: "4 to 8".to_string(),
+                found: 3
+            })
+        );
+    }
+}
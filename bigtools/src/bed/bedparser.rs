@@ -8,12 +8,19 @@
 //!
 //! The second layer of abstraction (`BedParser`) manages the state information for when values switch
 //! from one chromosome to another. The is important because bigwig/bigbed writing is "chunked" by chromosome.
+//!
+//! `WigFileStream` is a `StreamingBedValues` over WIG files, which (unlike the
+//! other formats here) need state carried between lines.
+//!
+//! `ValidatingBedStream` wraps another stream, checking each entry against an
+//! autoSql schema (see `BedValidator`).
 
 use std::io::{self, BufRead, BufReader, Read};
 
 use thiserror::Error;
 
 use crate::bbi::{BedEntry, Value};
+use crate::bed::autosql::validate::BedValidator;
 use crate::utils::streaming_linereader::StreamingLineReader;
 
 pub fn parse_bed<'a>(s: &'a str) -> Option<Result<(&'a str, BedEntry), BedValueError>> {
@@ -86,6 +93,14 @@ pub trait StreamingBedValues {
     fn next(&mut self) -> Option<Result<(&str, Self::Value), BedValueError>>;
 }
 
+impl<S: StreamingBedValues + ?Sized> StreamingBedValues for Box<S> {
+    type Value = S::Value;
+
+    fn next(&mut self) -> Option<Result<(&str, Self::Value), BedValueError>> {
+        (**self).next()
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum BedValueError {
     #[error("{}", .0)]
@@ -136,6 +151,240 @@ impl<V, B: BufRead> StreamingBedValues for BedFileStream<V, B> {
     }
 }
 
+/// The kind of data lines that follow the last declaration line of a WIG file
+#[derive(Copy, Clone, Debug)]
+enum WigSection {
+    BedGraph,
+    VariableStep {
+        span: u32,
+    },
+    FixedStep {
+        next_start: u32,
+        step: u32,
+        span: u32,
+    },
+}
+
+/// Parses a WIG file. Supports `variableStep` and `fixedStep` sections, as well
+/// as bedGraph lines (which are 0-based, unlike the step sections). `track`
+/// and `browser` lines and `#` comments are skipped.
+pub struct WigFileStream<B> {
+    pub bed: StreamingLineReader<B>,
+    chrom: String,
+    section: WigSection,
+}
+
+impl<R: Read> WigFileStream<BufReader<R>> {
+    pub fn from_wig_file(file: R) -> WigFileStream<BufReader<R>> {
+        WigFileStream::new(StreamingLineReader::new(BufReader::new(file)))
+    }
+}
+
+impl<B: BufRead> WigFileStream<B> {
+    pub fn new(bed: StreamingLineReader<B>) -> WigFileStream<B> {
+        WigFileStream {
+            bed,
+            chrom: String::new(),
+            section: WigSection::BedGraph,
+        }
+    }
+}
+
+fn parse_wig_u32(key: &str, value: Option<&str>, line: &str) -> Result<u32, BedValueError> {
+    let value =
+        value.ok_or_else(|| BedValueError::InvalidInput(format!("Missing {}: {}", key, line)))?;
+    value
+        .parse::<u32>()
+        .map_err(|_| BedValueError::InvalidInput(format!("Invalid {}: {}", key, line)))
+}
+
+fn parse_wig_value(value: Option<&str>, line: &str) -> Result<f32, BedValueError> {
+    let value =
+        value.ok_or_else(|| BedValueError::InvalidInput(format!("Missing value: {}", line)))?;
+    value
+        .parse::<f32>()
+        .map_err(|_| BedValueError::InvalidInput(format!("Invalid value: {}", line)))
+}
+
+/// The end of an item starting at `start` with `span` (which is non-zero)
+fn wig_end(start: u32, span: u32, line: &str) -> Result<u32, BedValueError> {
+    start
+        .checked_add(span)
+        .ok_or_else(|| BedValueError::InvalidInput(format!("Item end is out of range: {}", line)))
+}
+
+/// Parses a `variableStep` or `fixedStep` line, returning the chrom and section.
+fn parse_wig_declaration(line: &str) -> Result<(&str, WigSection), BedValueError> {
+    let mut split = line.split_whitespace();
+    let fixed = split.next() == Some("fixedStep");
+    let mut chrom = None;
+    let mut start = None;
+    let mut step = None;
+    let mut span = None;
+    for field in split {
+        let (key, value) = field.split_once('=').ok_or_else(|| {
+            BedValueError::InvalidInput(format!("Invalid declaration field `{}`: {}", field, line))
+        })?;
+        match key {
+            "chrom" => chrom = Some(value),
+            "start" => start = Some(value),
+            "step" => step = Some(value),
+            "span" => span = Some(value),
+            _ => {}
+        }
+    }
+    let chrom =
+        chrom.ok_or_else(|| BedValueError::InvalidInput(format!("Missing chrom: {}", line)))?;
+    let span = match span {
+        Some(_) => parse_wig_u32("span", span, line)?,
+        None => 1,
+    };
+    if span == 0 {
+        return Err(BedValueError::InvalidInput(format!(
+            "Invalid span: {}",
+            line
+        )));
+    }
+    if !fixed {
+        return Ok((chrom, WigSection::VariableStep { span }));
+    }
+    let start = parse_wig_u32("start", start, line)?;
+    let step = parse_wig_u32("step", step, line)?;
+    if start == 0 {
+        return Err(BedValueError::InvalidInput(format!(
+            "Invalid start (positions are 1-based): {}",
+            line
+        )));
+    }
+    Ok((
+        chrom,
+        WigSection::FixedStep {
+            next_start: start - 1,
+            step,
+            span,
+        },
+    ))
+}
+
+impl<B: BufRead> StreamingBedValues for WigFileStream<B> {
+    type Value = Value;
+
+    fn next(&mut self) -> Option<Result<(&str, Value), BedValueError>> {
+        let value = loop {
+            let line = match self.bed.read()? {
+                Ok(line) => line.trim(),
+                Err(e) => return Some(Err(e.into())),
+            };
+            if line.is_empty()
+                || line.starts_with('#')
+                || line.starts_with("browser")
+                || line.starts_with("track")
+            {
+                if line.starts_with("track") {
+                    self.section = WigSection::BedGraph;
+                }
+                continue;
+            }
+            if line.starts_with("variableStep") || line.starts_with("fixedStep") {
+                match parse_wig_declaration(line) {
+                    Ok((chrom, section)) => {
+                        self.chrom.clear();
+                        self.chrom.push_str(chrom);
+                        self.section = section;
+                    }
+                    Err(e) => return Some(Err(e)),
+                }
+                continue;
+            }
+
+            let mut split = line.split_whitespace();
+            let value = match &mut self.section {
+                WigSection::BedGraph => (|| {
+                    let chrom = split.next().unwrap();
+                    let start = parse_wig_u32("start", split.next(), line)?;
+                    let end = parse_wig_u32("end", split.next(), line)?;
+                    let value = parse_wig_value(split.next(), line)?;
+                    self.chrom.clear();
+                    self.chrom.push_str(chrom);
+                    Ok(Value { start, end, value })
+                })(),
+                WigSection::VariableStep { span } => (|| {
+                    let start = parse_wig_u32("start", split.next(), line)?;
+                    if start == 0 {
+                        return Err(BedValueError::InvalidInput(format!(
+                            "Invalid start (positions are 1-based): {}",
+                            line
+                        )));
+                    }
+                    let value = parse_wig_value(split.next(), line)?;
+                    let start = start - 1;
+                    let end = wig_end(start, *span, line)?;
+                    Ok(Value { start, end, value })
+                })(),
+                WigSection::FixedStep {
+                    next_start,
+                    step,
+                    span,
+                } => (|| {
+                    let value = parse_wig_value(split.next(), line)?;
+                    let start = *next_start;
+                    let end = wig_end(start, *span, line)?;
+                    // Saturating, so that any following item is out of range
+                    *next_start = start.saturating_add(*step);
+                    Ok(Value { start, end, value })
+                })(),
+            };
+            break value;
+        };
+        Some(value.map(|value| (self.chrom.as_str(), value)))
+    }
+}
+
+/// Checks each `BedEntry` of a stream with a `BedValidator`, failing on the
+/// first invalid entry. Entries are counted from 1, so for a
+/// `BedFileStream`, the reported line is the line in the file.
+pub struct ValidatingBedStream<S> {
+    inner: S,
+    validator: BedValidator,
+    line: usize,
+}
+
+impl<S: StreamingBedValues<Value = BedEntry>> ValidatingBedStream<S> {
+    pub fn new(inner: S, validator: BedValidator) -> Self {
+        ValidatingBedStream {
+            inner,
+            validator,
+            line: 0,
+        }
+    }
+}
+
+impl<S: StreamingBedValues<Value = BedEntry>> StreamingBedValues for ValidatingBedStream<S> {
+    type Value = BedEntry;
+
+    fn next(&mut self) -> Option<Result<(&str, BedEntry), BedValueError>> {
+        self.line += 1;
+        let line = self.line;
+        let (chrom, entry) = match self.inner.next()? {
+            Ok(v) => v,
+            Err(BedValueError::InvalidInput(e)) => {
+                return Some(Err(BedValueError::InvalidInput(format!(
+                    "Invalid bed at line {}: {}",
+                    line, e
+                ))))
+            }
+            Err(e) => return Some(Err(e)),
+        };
+        if let Err(e) = self.validator.validate(&entry) {
+            return Some(Err(BedValueError::InvalidInput(format!(
+                "Invalid bed at line {} ({}:{}-{}): {}",
+                line, chrom, entry.start, entry.end, e
+            ))));
+        }
+        Some(Ok((chrom, entry)))
+    }
+}
+
 // Wraps a bed-like Iterator
 pub struct BedIteratorStream<V, I> {
     pub(crate) iter: I,
@@ -194,3 +443,85 @@ impl<V: Clone, C: Into<String> + for<'a> PartialEq<&'a str>, I: Iterator<Item =
         self.curr.as_ref().map(|v| Ok((v.0.deref(), v.1.clone())))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wig() -> Result<(), BedValueError> {
+        let wig = "\
+track type=wiggle_0 name=test
+# a comment
+variableStep chrom=chr1 span=5
+11 1.5
+31\t2
+fixedStep chrom=chr2 start=101 step=10
+0.5
+0.25
+
+fixedStep chrom=chr2 start=201 step=20 span=15
+-1
+track type=bedGraph
+chr3 0 10 4.0
+chr3\t10\t15\t5.0
+";
+        let mut stream = WigFileStream::from_wig_file(wig.as_bytes());
+        let mut vals = vec![];
+        while let Some(val) = stream.next() {
+            let (chrom, val) = val?;
+            vals.push((chrom.to_string(), val.start, val.end, val.value));
+        }
+        let expected = [
+            ("chr1", 10, 15, 1.5),
+            ("chr1", 30, 35, 2.0),
+            ("chr2", 100, 101, 0.5),
+            ("chr2", 110, 111, 0.25),
+            ("chr2", 200, 215, -1.0),
+            ("chr3", 0, 10, 4.0),
+            ("chr3", 10, 15, 5.0),
+        ];
+        let expected: Vec<_> = expected
+            .into_iter()
+            .map(|(c, s, e, v)| (c.to_string(), s, e, v))
+            .collect();
+        assert_eq!(vals, expected);
+
+        let mut stream = WigFileStream::from_wig_file("fixedStep chrom=chr1 step=1\n".as_bytes());
+        assert!(matches!(
+            stream.next(),
+            Some(Err(BedValueError::InvalidInput(_)))
+        ));
+        let mut stream =
+            WigFileStream::from_wig_file("variableStep chrom=chr1\n0 1.0\n".as_bytes());
+        assert!(matches!(
+            stream.next(),
+            Some(Err(BedValueError::InvalidInput(_)))
+        ));
+
+        // Items that would end past `u32::MAX`
+        let wig = "variableStep chrom=chr1 span=4294967295\n2 1.0\n";
+        let mut stream = WigFileStream::from_wig_file(wig.as_bytes());
+        assert!(matches!(
+            stream.next(),
+            Some(Err(BedValueError::InvalidInput(e))) if e.ends_with("2 1.0")
+        ));
+        let wig = "fixedStep chrom=chr1 start=4294967290 step=4 span=3\n1.0\n2.0\n";
+        let mut stream = WigFileStream::from_wig_file(wig.as_bytes());
+        let (_, val) = stream.next().unwrap()?;
+        assert_eq!((val.start, val.end), (4294967289, 4294967292));
+        assert!(matches!(
+            stream.next(),
+            Some(Err(BedValueError::InvalidInput(e))) if e.ends_with("2.0")
+        ));
+        let wig = "fixedStep chrom=chr1 start=4294967290 step=4294967295\n1.0\n2.0\n";
+        let mut stream = WigFileStream::from_wig_file(wig.as_bytes());
+        assert!(stream.next().unwrap().is_ok());
+        assert!(matches!(
+            stream.next(),
+            Some(Err(BedValueError::InvalidInput(_)))
+        ));
+
+        Ok(())
+    }
+}
@@ -79,5 +79,10 @@ mod test {
         assert_args!(args, |args_comp| {
             args_comp.write_args.items_per_slot = 5;
         });
+
+        let args = "bedGraphToBigWig a b c -sizesIs2Bit";
+        assert_args!(args, |args_comp| {
+            args_comp.sizes_args.sizes_is_2bit = true;
+        });
     }
 }
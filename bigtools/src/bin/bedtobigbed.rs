@@ -82,23 +82,44 @@ mod test {
             args_comp.write_args.items_per_slot = 5;
         });
 
-        /*
-        let args = "bedToBigBed a b c -type=bed3+5";
-        assert_args!(args, |args_comp| {});
-
-        let args = "bedToBigBed a b c -tab";
-        assert_args!(args, |args_comp| {});
-
         let args = "bedToBigBed a b c -extraIndex=a,b,c";
-        assert_args!(args, |args_comp| {});
+        assert_args!(args, |args_comp| {
+            args_comp.extra_index = Some(vec!["a".to_string(), "b".to_string(), "c".to_string()]);
+        });
 
         let args = "bedToBigBed a b c -sizesIs2Bit";
-        assert_args!(args, |args_comp| {});
+        assert_args!(args, |args_comp| {
+            args_comp.sizes_args.sizes_is_2bit = true;
+        });
 
         let args = "bedToBigBed a b c -sizesIsChromAliasBb";
-        assert_args!(args, |args_comp| {});
+        assert_args!(args, |args_comp| {
+            args_comp.sizes_args.sizes_is_chrom_alias_bb = true;
+        });
 
         let args = "bedToBigBed a b c -sizesIsBb";
+        assert_args!(args, |args_comp| {
+            args_comp.sizes_args.sizes_is_bb = true;
+        });
+
+        let args = "bedToBigBed a b c --as-preset narrowPeak";
+        assert_args!(args, |args_comp| {
+            args_comp.as_preset = Some("narrowPeak".to_string());
+        });
+
+        let args = "bedToBigBed a b c -type=bed3+5";
+        assert_args!(args, |args_comp| {
+            args_comp.bed_type = Some("bed3+5".to_string());
+        });
+
+        let args = "bedToBigBed a b c --type bed6 --skip-validation";
+        assert_args!(args, |args_comp| {
+            args_comp.bed_type = Some("bed6".to_string());
+            args_comp.skip_validation = true;
+        });
+
+        /*
+        let args = "bedToBigBed a b c -tab";
         assert_args!(args, |args_comp| {});
 
         let args = "bedToBigBed a b c -udcDir";
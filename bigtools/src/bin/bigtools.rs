@@ -7,12 +7,19 @@ use bigtools::utils::cli::bedgraphtobigwig::{bedgraphtobigwig, BedGraphToBigWigA
 use bigtools::utils::cli::bedtobigbed::{bedtobigbed, BedToBigBedArgs};
 use bigtools::utils::cli::bigbedinfo::{bigbedinfo, BigBedInfoArgs};
 use bigtools::utils::cli::bigbedtobed::{bigbedtobed, BigBedToBedArgs};
+#[cfg(feature = "arrow")]
+use bigtools::utils::cli::bigbedtoparquet::{bigbedtoparquet, BigBedToParquetArgs};
 use bigtools::utils::cli::bigwigaverageoverbed::{bigwigaverageoverbed, BigWigAverageOverBedArgs};
 use bigtools::utils::cli::bigwiginfo::{bigwiginfo, BigWigInfoArgs};
 use bigtools::utils::cli::bigwigmerge::{bigwigmerge, BigWigMergeArgs};
+use bigtools::utils::cli::bigwigsummary::{bigwigsummary, BigWigSummaryArgs};
 use bigtools::utils::cli::bigwigtobedgraph::{bigwigtobedgraph, BigWigToBedGraphArgs};
+#[cfg(feature = "arrow")]
+use bigtools::utils::cli::bigwigtoparquet::{bigwigtoparquet, BigWigToParquetArgs};
 use bigtools::utils::cli::bigwigvaluesoverbed::{bigwigvaluesoverbed, BigWigValuesOverBedArgs};
 use bigtools::utils::cli::compat_args;
+use bigtools::utils::cli::validate::{validate, ValidateArgs};
+use bigtools::utils::cli::wigtobigwig::{wigtobigwig, WigToBigWigArgs};
 use bigtools::{BBIRead, GenericBBIRead};
 use clap::{Args, Parser, Subcommand};
 
@@ -73,6 +80,12 @@ enum SubCommands {
         #[command(flatten)]
         args: BigBedToBedArgs,
     },
+    #[cfg(feature = "arrow")]
+    #[command(name = "bigbedtoparquet", version)]
+    BigBedToParquet {
+        #[command(flatten)]
+        args: BigBedToParquetArgs,
+    },
     #[command(name = "bigwigaverageoverbed", version)]
     BigWigAverageOverBed {
         #[command(flatten)]
@@ -88,16 +101,37 @@ enum SubCommands {
         #[command(flatten)]
         args: BigWigMergeArgs,
     },
+    #[command(name = "bigwigsummary", version)]
+    BigWigSummary {
+        #[command(flatten)]
+        args: BigWigSummaryArgs,
+    },
     #[command(name = "bigwigtobedgraph", version)]
     BigWigToBedGraph {
         #[command(flatten)]
         args: BigWigToBedGraphArgs,
     },
+    #[cfg(feature = "arrow")]
+    #[command(name = "bigwigtoparquet", version)]
+    BigWigToParquet {
+        #[command(flatten)]
+        args: BigWigToParquetArgs,
+    },
     #[command(name = "bigwigvaluesoverbed", version)]
     BigWigValuesOverBed {
         #[command(flatten)]
         args: BigWigValuesOverBedArgs,
     },
+    #[command(name = "validate", version)]
+    Validate {
+        #[command(flatten)]
+        args: ValidateArgs,
+    },
+    #[command(name = "wigtobigwig", version)]
+    WigToBigWig {
+        #[command(flatten)]
+        args: WigToBigWigArgs,
+    },
 }
 
 #[derive(Debug, Parser)]
@@ -264,6 +298,8 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
         SubCommands::BigBedInfo { args } => bigbedinfo(args),
         SubCommands::BigBedToBed { args } => bigbedtobed(args),
+        #[cfg(feature = "arrow")]
+        SubCommands::BigBedToParquet { args } => bigbedtoparquet(args),
         SubCommands::BigWigAverageOverBed { args } => {
             match bigwigaverageoverbed(args) {
                 Ok(_) => {}
@@ -279,7 +315,12 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
         SubCommands::BigWigInfo { args } => bigwiginfo(args),
         SubCommands::BigWigMerge { args } => bigwigmerge(args),
+        SubCommands::BigWigSummary { args } => bigwigsummary(args),
         SubCommands::BigWigToBedGraph { args } => bigwigtobedgraph(args),
+        #[cfg(feature = "arrow")]
+        SubCommands::BigWigToParquet { args } => bigwigtoparquet(args),
         SubCommands::BigWigValuesOverBed { args } => bigwigvaluesoverbed(args),
+        SubCommands::Validate { args } => validate(args),
+        SubCommands::WigToBigWig { args } => wigtobigwig(args),
     }
 }
@@ -0,0 +1,108 @@
+include!("bigtools.rs");
+
+#[cfg(test)]
+mod test {
+    use bigtools::utils::cli::compat_args;
+    use bigtools::SummaryKind;
+    use clap::Parser;
+
+    use crate::{CliCommands, SubCommands};
+
+    #[test]
+    fn verify_cli_bigwigsummary() {
+        use clap::CommandFactory;
+        CliCommands::command().debug_assert();
+
+        let subcommand = |args: &str| {
+            let args = args.split_whitespace();
+            let cli = CliCommands::try_parse_from(compat_args(args.map(|a| a.into())))
+                .map_err(|e| e.print())
+                .unwrap();
+            match cli {
+                CliCommands::SubCommands(subcommand) => subcommand,
+                CliCommands::Bigtools { .. } => panic!("Expected subcommand, parsed applet."),
+            }
+        };
+        let applet = |args: &str| {
+            let args = args.split_whitespace();
+            let cli = CliCommands::try_parse_from(compat_args(args.map(|a| a.into()))).unwrap();
+            match cli {
+                CliCommands::Bigtools { command } => command,
+                CliCommands::SubCommands(..) => panic!("Expected applet, parsed subcommand."),
+            }
+        };
+
+        let args = "bigWigSummary in chr1 0 1000 10";
+        let cli = subcommand(args);
+        let args = match cli {
+            SubCommands::BigWigSummary { args } => {
+                assert_eq!(args.bigwig, "in");
+                assert_eq!(args.chrom, "chr1");
+                assert_eq!(args.start, 0);
+                assert_eq!(args.end, 1000);
+                assert_eq!(args.data_points, 10);
+                assert_eq!(args.summary_type, SummaryKind::Mean);
+                assert!(!args.exact);
+                assert_eq!(args.remote.cache_dir, None);
+                assert_eq!(args.remote.cache_max_size, None);
+
+                args
+            }
+            _ => panic!("Unexpected matched subcommand."),
+        };
+
+        let args_orig = args;
+
+        macro_rules! assert_args {
+            (inner; $cli: expr, $args_comp:ident; $inner:block) => {
+                let args_cli = match $cli {
+                    SubCommands::BigWigSummary { args } => args,
+                    _ => panic!("Unexpected matched subcommand."),
+                };
+                #[allow(unused_mut)]
+                let mut $args_comp = args_orig.clone();
+                $inner
+                assert_eq!(args_cli, $args_comp);
+            };
+            ($args:expr, |$args_comp:ident| $inner:block) => {
+                let cli = subcommand($args);
+                assert_args!(inner; cli, $args_comp; $inner);
+
+                let args = &format!("bigtools {}", $args);
+                let cli = applet(args);
+                assert_args!(inner; cli, $args_comp; $inner);
+            }
+        }
+
+        let args = "bigWigSummary in chr1 0 1000 10 -udcDir=/tmp/udc";
+        assert_args!(args, |args_comp| {
+            args_comp.remote.cache_dir = Some("/tmp/udc".to_string());
+        });
+
+        let args = "bigWigSummary in chr1 0 1000 10 --cache-dir /tmp/udc --cache-max-size 1000";
+        assert_args!(args, |args_comp| {
+            args_comp.remote.cache_dir = Some("/tmp/udc".to_string());
+            args_comp.remote.cache_max_size = Some(1000);
+        });
+
+        let args = "bigWigSummary in chr1 0 1000 10 -type=max";
+        assert_args!(args, |args_comp| {
+            args_comp.summary_type = SummaryKind::Max;
+        });
+
+        let args = "bigWigSummary -type=coverage in chr1 0 1000 10";
+        assert_args!(args, |args_comp| {
+            args_comp.summary_type = SummaryKind::Coverage;
+        });
+
+        let args = "bigWigSummary in chr1 0 1000 10 --type std";
+        assert_args!(args, |args_comp| {
+            args_comp.summary_type = SummaryKind::Std;
+        });
+
+        let args = "bigWigSummary in chr1 0 1000 10 --exact";
+        assert_args!(args, |args_comp| {
+            args_comp.exact = true;
+        });
+    }
+}
@@ -10,12 +10,24 @@ The entrypoint to reading bigWigs and bigBeds is [`BigWigRead::open`] and
 [`Read`][std::io::Read] and [`Seek`][std::io::Seek]. There are also
 [`BigWigRead::open_file`] and [`BigBedRead::open_file`], which take a `&str` and
 will open a `File`.
+For files that are already in memory, or that are read repeatedly,
+[`BigWigRead::from_bytes`] and `BigWigRead::open_mmap` (with the opt-in
+`mmap` feature, and the bigBed equivalents) decode the index and blocks
+directly from memory, without seeks, read calls, or copies of uncompressed
+block data.
+To query a file from many threads at once, [`SharedBigWigRead`] and
+[`SharedBigBedRead`] read with positional reads and take `&self`.
 
 Once a [`BigWigRead`] or [`BigBedRead`] have been constructed, they can be read
 in a number of ways. First, the info (in the form of [`BBIFileInfo`]) is available
 in `info` fields. However, to access the main data, the most common method to call
 is [`BigWigRead::get_interval`] or [`BigBedRead::get_interval`], which returns an
 `Iterator` of [`Value`]s or [`BedEntry`]s overlapping the provided region, respectively.
+For bigBeds, [`BigBedRead::get_interval_typed`] additionally decodes the `rest`
+fields of each entry according to the file's autoSql.
+With the `arrow` feature, `BigWigRead::record_batches` and
+`BigBedRead::record_batches` read a whole file (or a list of regions) as Arrow
+`RecordBatch`es.
 
 ## Writing
 
@@ -0,0 +1,250 @@
+//! Utilities for reading chromosome sizes, as needed when writing bigWigs and
+//! bigBeds.
+//!
+//! Sizes can come from a tab-separated chrom.sizes file, the header of a UCSC
+//! .2bit file, the chromosomes of an existing bigWig or bigBed, or a UCSC
+//! chromAlias bigBed.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+
+use byteordered::{ByteOrdered, Endianness};
+use thiserror::Error;
+
+use crate::{
+    BBIRead, BBIReadError, BigBedRead, BigBedReadOpenError, ChromAliases, GenericBBIFileOpenError,
+};
+
+const TWOBIT_SIGNATURE: u32 = 0x1A41_2743;
+
+/// The format of a file to read chromosome sizes from
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ChromSizesFormat {
+    /// A chrom.sizes file, where each line has a chromosome and its size
+    /// separated by whitespace
+    ChromSizes,
+    /// A UCSC .2bit file
+    TwoBit,
+    /// A bigWig or bigBed
+    Bbi,
+    /// A UCSC chromAlias bigBed. Each alias gets the size of the chromosome it
+    /// is an alias of.
+    ChromAliasBigBed,
+}
+
+#[derive(Error, Debug)]
+pub enum ChromSizesError {
+    #[error("Invalid chrom sizes on line {}: {}", .0, .1)]
+    InvalidChromSizes(usize, String),
+    #[error("Invalid 2bit file: {}", .0)]
+    InvalidTwoBit(String),
+    #[error("Unable to open bbi file: {}", .0)]
+    BBIOpenError(#[from] GenericBBIFileOpenError),
+    #[error("Unable to open chromAlias bigBed: {}", .0)]
+    BigBedOpenError(#[from] BigBedReadOpenError),
+    #[error("Unable to read chromAlias bigBed: {}", .0)]
+    BBIReadError(#[from] BBIReadError),
+    #[error("Error occurred: {}", .0)]
+    IoError(#[from] io::Error),
+}
+
+/// Reads the chromosome sizes from the file at `path`, which is in the given format.
+pub fn read_chrom_sizes(
+    path: &str,
+    format: ChromSizesFormat,
+) -> Result<HashMap<String, u32>, ChromSizesError> {
+    match format {
+        ChromSizesFormat::ChromSizes => chrom_sizes_from_file(BufReader::new(File::open(path)?)),
+        ChromSizesFormat::TwoBit => chrom_sizes_from_2bit(BufReader::new(File::open(path)?)),
+        ChromSizesFormat::Bbi => {
+            let bbi = crate::GenericBBIRead::open_file(path)?;
+            Ok(bbi
+                .chroms()
+                .iter()
+                .map(|c| (c.name.clone(), c.length))
+                .collect())
+        }
+        ChromSizesFormat::ChromAliasBigBed => {
+            chrom_sizes_from_chrom_alias(BigBedRead::open_file(path)?)
+        }
+    }
+}
+
+/// Reads chromosome sizes from chrom.sizes data. Empty lines are skipped.
+pub fn chrom_sizes_from_file<R: BufRead>(
+    chrom_sizes: R,
+) -> Result<HashMap<String, u32>, ChromSizesError> {
+    let mut sizes = HashMap::new();
+    for (i, line) in chrom_sizes.lines().enumerate() {
+        let line = line?;
+        let mut split = line.split_whitespace();
+        let Some(chrom) = split.next() else {
+            continue;
+        };
+        let size = split
+            .next()
+            .and_then(|s| s.parse::<u32>().ok())
+            .ok_or_else(|| ChromSizesError::InvalidChromSizes(i + 1, line.clone()))?;
+        sizes.insert(chrom.to_owned(), size);
+    }
+    Ok(sizes)
+}
+
+/// Reads chromosome sizes from the sequence index of a .2bit file. Both
+/// version 0 (32-bit offsets) and version 1 (64-bit offsets) files are
+/// supported, in either byte order.
+pub fn chrom_sizes_from_2bit<R: Read + Seek>(
+    mut twobit: R,
+) -> Result<HashMap<String, u32>, ChromSizesError> {
+    let mut header = [0u8; 16];
+    twobit.read_exact(&mut header)?;
+    let endianness = match u32::from_le_bytes(header[0..4].try_into().unwrap()) {
+        TWOBIT_SIGNATURE => Endianness::Little,
+        s if s.swap_bytes() == TWOBIT_SIGNATURE => Endianness::Big,
+        _ => return Err(ChromSizesError::InvalidTwoBit("Unknown signature.".into())),
+    };
+    let mut header = ByteOrdered::runtime(&header[4..], endianness);
+    let version = header.read_u32()?;
+    let seq_count = header.read_u32()?;
+    if version > 1 {
+        return Err(ChromSizesError::InvalidTwoBit(format!(
+            "Unknown version: {}",
+            version
+        )));
+    }
+
+    let mut index = Vec::with_capacity(seq_count as usize);
+    let mut file = ByteOrdered::runtime(&mut twobit, endianness);
+    for _ in 0..seq_count {
+        let name_size = file.read_u8()?;
+        let mut name = vec![0u8; name_size as usize];
+        file.read_exact(&mut name)?;
+        let name = String::from_utf8(name)
+            .map_err(|_| ChromSizesError::InvalidTwoBit("Invalid sequence name.".into()))?;
+        let offset = match version {
+            0 => file.read_u32()? as u64,
+            _ => file.read_u64()?,
+        };
+        index.push((name, offset));
+    }
+
+    let mut sizes = HashMap::with_capacity(index.len());
+    for (name, offset) in index {
+        file.seek(SeekFrom::Start(offset))?;
+        let size = file.read_u32()?;
+        sizes.insert(name, size);
+    }
+    Ok(sizes)
+}
+
+/// Reads chromosome sizes from a chromAlias bigBed. In addition to the
+/// chromosomes of the bigBed, every alias listed for a chromosome is included
+/// with the size of that chromosome.
+pub fn chrom_sizes_from_chrom_alias<R: crate::BBIFileRead>(
+    mut bigbed: BigBedRead<R>,
+) -> Result<HashMap<String, u32>, ChromSizesError> {
+    let aliases = ChromAliases::from_bigbed(&mut bigbed)?;
+    let mut sizes = HashMap::new();
+    for chrom in bigbed.chroms() {
+        for alias in aliases.aliases(&chrom.name).unwrap_or_default() {
+            sizes.insert(alias.clone(), chrom.length);
+        }
+        sizes.insert(chrom.name.clone(), chrom.length);
+    }
+    Ok(sizes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::path::PathBuf;
+
+    fn twobit(version: u32, big_endian: bool, seqs: &[(&str, u32)]) -> Vec<u8> {
+        let u32_bytes = |v: u32| {
+            if big_endian {
+                v.to_be_bytes()
+            } else {
+                v.to_le_bytes()
+            }
+        };
+        let mut bytes = vec![];
+        bytes.extend(u32_bytes(TWOBIT_SIGNATURE));
+        bytes.extend(u32_bytes(version));
+        bytes.extend(u32_bytes(seqs.len() as u32));
+        bytes.extend(u32_bytes(0));
+        let offset_size = if version == 0 { 4 } else { 8 };
+        let index_size: usize = seqs.iter().map(|s| 1 + s.0.len() + offset_size).sum();
+        let mut offset = (16 + index_size) as u64;
+        for (name, _) in seqs {
+            bytes.push(name.len() as u8);
+            bytes.extend(name.as_bytes());
+            match (version, big_endian) {
+                (0, _) => bytes.extend(u32_bytes(offset as u32)),
+                (_, true) => bytes.extend(offset.to_be_bytes()),
+                (_, false) => bytes.extend(offset.to_le_bytes()),
+            }
+            // The sequence record is the size, then (here) no blocks or dna
+            offset += 16;
+        }
+        for (_, size) in seqs {
+            bytes.extend(u32_bytes(*size));
+            bytes.extend([0u8; 12]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_2bit() -> Result<(), ChromSizesError> {
+        let seqs = [
+            ("chr1", 248956422),
+            ("chrM", 16569),
+            ("chrUn_KI270302v1", 2274),
+        ];
+        let expected: HashMap<String, u32> =
+            seqs.iter().map(|(c, s)| (c.to_string(), *s)).collect();
+        for (version, big_endian) in [(0, false), (0, true), (1, false), (1, true)] {
+            let bytes = twobit(version, big_endian, &seqs);
+            assert_eq!(chrom_sizes_from_2bit(Cursor::new(bytes))?, expected);
+        }
+
+        assert!(matches!(
+            chrom_sizes_from_2bit(Cursor::new(vec![0u8; 16])),
+            Err(ChromSizesError::InvalidTwoBit(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_chrom_sizes() -> Result<(), ChromSizesError> {
+        let sizes = chrom_sizes_from_file("chr1\t100\n\nchr2 200\n".as_bytes())?;
+        assert_eq!(sizes.len(), 2);
+        assert_eq!(sizes["chr1"], 100);
+        assert_eq!(sizes["chr2"], 200);
+
+        assert!(matches!(
+            chrom_sizes_from_file("chr1\t100\nchr2\n".as_bytes()),
+            Err(ChromSizesError::InvalidChromSizes(2, _))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bbi() -> Result<(), ChromSizesError> {
+        let mut dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("resources/test");
+        dir.push("valid.bigWig");
+
+        let sizes = read_chrom_sizes(dir.to_str().unwrap(), ChromSizesFormat::Bbi)?;
+        let bigwig = crate::BigWigRead::open_file(&dir).unwrap();
+        assert_eq!(sizes.len(), bigwig.chroms().len());
+        for chrom in bigwig.chroms() {
+            assert_eq!(sizes[&chrom.name], chrom.length);
+        }
+
+        Ok(())
+    }
+}
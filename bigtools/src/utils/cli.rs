@@ -3,6 +3,7 @@ use std::path::Path;
 use std::{ffi::OsString, str::FromStr};
 
 use crate::bbiwrite::{DEFAULT_BLOCK_SIZE, DEFAULT_ITEMS_PER_SLOT};
+use crate::utils::chromsizes::ChromSizesFormat;
 
 use clap::Args;
 use itertools::chain;
@@ -11,11 +12,18 @@ pub mod bedgraphtobigwig;
 pub mod bedtobigbed;
 pub mod bigbedinfo;
 pub mod bigbedtobed;
+#[cfg(feature = "arrow")]
+pub mod bigbedtoparquet;
 pub mod bigwigaverageoverbed;
 pub mod bigwiginfo;
 pub mod bigwigmerge;
+pub mod bigwigsummary;
 pub mod bigwigtobedgraph;
+#[cfg(feature = "arrow")]
+pub mod bigwigtoparquet;
 pub mod bigwigvaluesoverbed;
+pub mod validate;
+pub mod wigtobigwig;
 
 #[derive(Clone, Debug, PartialEq, Args)]
 pub struct BBIWriteArgs {
@@ -42,8 +50,8 @@ pub struct BBIWriteArgs {
     /// Sets whether the input is sorted. Can take `all`, `start`, or `none`.
     /// `all` means that the input bedGraph is sorted by chroms and start (`sort -k1,1 -k2,2n`).
     /// `start` means that the the chroms are out of order but the starts within a chrom is sorted.
-    /// `none` means that the file is not sorted at all.
-    /// `all` is default. `none` currently errors but may be supported in the future.
+    /// `none` means that the file is not sorted at all, and will be sorted (using temporary files) before conversion.
+    /// `all` is default. `none` always reads the input only once.
     /// Note that using a value other than `all` will not guarantee (though likely) support for third-party tools.
     #[arg(short = 's', long)]
     #[arg(default_value = "all")]
@@ -65,6 +73,159 @@ pub struct BBIWriteArgs {
     pub inmemory: bool,
 }
 
+#[derive(Clone, Debug, PartialEq, Args)]
+#[group(multiple = false)]
+pub struct ChromSizesArgs {
+    /// The chromosome sizes file is a 2bit file.
+    #[arg(long = "sizes-is-2bit")]
+    #[arg(default_value_t = false)]
+    pub sizes_is_2bit: bool,
+
+    /// The chromosome sizes file is a bigWig or bigBed.
+    #[arg(long)]
+    #[arg(default_value_t = false)]
+    pub sizes_is_bb: bool,
+
+    /// The chromosome sizes file is a chromAlias bigBed. Aliases are given the
+    /// size of the chromosome they are an alias of.
+    #[arg(long)]
+    #[arg(default_value_t = false)]
+    pub sizes_is_chrom_alias_bb: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Args)]
+pub struct RemoteArgs {
+    /// A directory to cache the data of remote (http) files in, so that it can
+    /// be reused by later runs. Cached data is only reused if the remote file
+    /// hasn't changed.
+    #[arg(long)]
+    pub cache_dir: Option<String>,
+
+    /// The maximum size (in bytes) of the data in the cache directory. When
+    /// the cache is larger than this, the data of the least-recently used
+    /// files is removed. Defaults to 1 GiB.
+    #[arg(long)]
+    pub cache_max_size: Option<u64>,
+}
+
+#[cfg(feature = "remote")]
+impl RemoteArgs {
+    /// Creates a `RemoteFile` for `url`, cached in `cache_dir` (if set)
+    pub fn remote_file(&self, url: &str) -> crate::utils::remote_file::RemoteFile {
+        use crate::utils::remote_cache::RemoteCache;
+        use crate::utils::remote_file::RemoteFile;
+
+        let file = RemoteFile::new(url);
+        match &self.cache_dir {
+            Some(cache_dir) => {
+                let cache = RemoteCache::new(cache_dir);
+                let cache = match self.cache_max_size {
+                    Some(max_size) => cache.with_max_size(max_size),
+                    None => cache,
+                };
+                file.with_cache(cache)
+            }
+            None => file,
+        }
+    }
+}
+
+#[cfg(feature = "arrow")]
+#[derive(Clone, Debug, PartialEq, Args)]
+pub struct ParquetArgs {
+    /// The number of rows to read and convert at a time.
+    #[arg(long)]
+    #[arg(default_value_t = crate::arrow::DEFAULT_BATCH_SIZE)]
+    pub batch_size: usize,
+
+    /// The maximum number of rows in each row group of the output.
+    #[arg(long)]
+    #[arg(default_value_t = parquet::file::properties::DEFAULT_MAX_ROW_GROUP_SIZE)]
+    pub row_group_size: usize,
+
+    /// The compression to use. Can take `none`, `snappy`, or `zstd`.
+    #[arg(long)]
+    #[arg(default_value = "zstd")]
+    pub compression: String,
+}
+
+#[cfg(feature = "arrow")]
+impl ParquetArgs {
+    pub fn compression(&self) -> Result<parquet::basic::Compression, String> {
+        use parquet::basic::{Compression, ZstdLevel};
+
+        match self.compression.as_str() {
+            "none" => Ok(Compression::UNCOMPRESSED),
+            "snappy" => Ok(Compression::SNAPPY),
+            "zstd" => Ok(Compression::ZSTD(ZstdLevel::default())),
+            c => Err(format!(
+                "Unexpected value for `compression`: \"{}\". Options are `none`, `snappy`, or `zstd`.",
+                c
+            )),
+        }
+    }
+}
+
+/// The region of `chrom` (which may be an alias) from `start` (or `0`) to
+/// `end` (or the end of the chromosome), if it's in `chroms`.
+#[cfg(feature = "arrow")]
+pub(crate) fn chrom_region(
+    chroms: &[crate::ChromInfo],
+    chrom_aliases: Option<&crate::ChromAliases>,
+    chrom: &str,
+    start: Option<u32>,
+    end: Option<u32>,
+) -> Option<(String, u32, u32)> {
+    let name = chrom_aliases
+        .and_then(|a| a.resolve(chroms, chrom))
+        .unwrap_or(chrom);
+    let chrom = chroms.iter().find(|c| c.name == name)?;
+    Some((
+        chrom.name.clone(),
+        start.unwrap_or(0),
+        end.unwrap_or(chrom.length),
+    ))
+}
+
+/// Writes `batches` to a Parquet file at `path`, with row groups of up to
+/// `args.row_group_size` rows
+#[cfg(feature = "arrow")]
+pub(crate) fn write_parquet(
+    schema: arrow_schema::SchemaRef,
+    batches: impl Iterator<Item = Result<arrow_array::RecordBatch, crate::BBIReadError>>,
+    path: &str,
+    args: &ParquetArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use parquet::arrow::ArrowWriter;
+    use parquet::file::properties::WriterProperties;
+
+    let props = WriterProperties::builder()
+        .set_compression(args.compression()?)
+        .set_max_row_group_size(args.row_group_size.max(1))
+        .build();
+    let out = std::fs::File::create(path)?;
+    let mut writer = ArrowWriter::try_new(out, schema, Some(props))?;
+    for batch in batches {
+        writer.write(&batch?)?;
+    }
+    writer.close()?;
+    Ok(())
+}
+
+impl ChromSizesArgs {
+    pub fn format(&self) -> ChromSizesFormat {
+        if self.sizes_is_2bit {
+            ChromSizesFormat::TwoBit
+        } else if self.sizes_is_bb {
+            ChromSizesFormat::Bbi
+        } else if self.sizes_is_chrom_alias_bb {
+            ChromSizesFormat::ChromAliasBigBed
+        } else {
+            ChromSizesFormat::ChromSizes
+        }
+    }
+}
+
 macro_rules! compat_replace_mut {
     (
         $a:expr;
@@ -110,10 +271,16 @@ fn compat_arg_mut(arg: &mut OsString) {
             "-chroms", "--chroms";
             "-clip", "--clip";
             "-end", "--end";
+            "-extraIndex", "--extra-index";
             "-itemsPerSlot", "--items-per-slot";
             "-minMax", "--minmax";
+            "-sizesIs2Bit", "--sizes-is-2bit";
+            "-sizesIsBb", "--sizes-is-bb";
+            "-sizesIsChromAliasBb", "--sizes-is-chrom-alias-bb";
             "-start", "--start";
             "-threshold", "--threshold";
+            "-type", "--type";
+            "-udcDir", "--cache-dir";
             "-unc", "--uncompressed";
             "-zooms", "--zooms"
         ignore:
@@ -122,21 +289,25 @@ fn compat_arg_mut(arg: &mut OsString) {
         unimplemented:
             "-allow1bOverlap";
             "-bedOut";
-            "-extraIndex";
             "-header";
             "-max";
             "-maxItems";
             "-minMax";
             "-sampleAroundCenter";
-            "-sizesIs2Bit";
-            "-sizesIsChromAliasBb";
-            "-sizesIsBb";
-            "-stats";
-            "-type";
-            "-udcDir"
+            "-stats"
     )
 }
 
+fn compat_bigwigsummary_arg_mut(arg: &mut OsString) {
+    compat_replace_mut!(arg;
+        replace:
+            "-type", "--type"
+        ignore:
+        unimplemented:
+    );
+    compat_arg_mut(arg);
+}
+
 pub fn compat_args(mut args: impl Iterator<Item = OsString>) -> impl Iterator<Item = OsString> {
     let first = args.next();
     let (command, args, start): (_, Vec<_>, Vec<_>) = if first
@@ -225,12 +396,19 @@ pub fn compat_args(mut args: impl Iterator<Item = OsString>) -> impl Iterator<It
             args_vec.iter_mut().for_each(compat_arg_mut);
             args_vec.into_iter()
         }
+        Some("bigwigsummary") => {
+            let mut args_vec = start;
+            args_vec.extend(args);
+            args_vec.iter_mut().for_each(compat_bigwigsummary_arg_mut);
+            args_vec.into_iter()
+        }
         Some("bedgraphtobigwig")
         | Some("bedtobigbed")
         | Some("bigbedtobed")
         | Some("bigwiginfo")
         | Some("bigwigaverageoverbed")
-        | Some("bigwigtobedgraph") => {
+        | Some("bigwigtobedgraph")
+        | Some("wigtobigwig") => {
             let mut args_vec = start;
             args_vec.extend(args);
             args_vec.iter_mut().for_each(compat_arg_mut);
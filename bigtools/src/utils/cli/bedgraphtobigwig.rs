@@ -1,15 +1,15 @@
-use std::io::{BufRead, BufReader};
-use std::{collections::HashMap, error::Error, fs::File, path::PathBuf};
+use std::{error::Error, fs::File, path::PathBuf};
 
 use clap::Parser;
 use tokio::runtime;
 
-use crate::bed::bedparser::parse_bedgraph;
+use crate::bed::bedparser::{parse_bedgraph, BedFileStream};
 use crate::bed::indexer::index_chroms;
 use crate::beddata::{BedParserParallelStreamingIterator, BedParserStreamingIterator};
+use crate::utils::chromsizes::read_chrom_sizes;
 use crate::{BigWigWrite, InputSortType};
 
-use super::BBIWriteArgs;
+use super::{BBIWriteArgs, ChromSizesArgs};
 
 #[derive(Clone, Debug, PartialEq, Parser)]
 #[command(
@@ -22,6 +22,7 @@ pub struct BedGraphToBigWigArgs {
     pub bedgraph: String,
 
     /// A chromosome sizes file. Each line should be have a chromosome and its size in bases, separated by whitespace.
+    /// Alternatively, a 2bit, bbi, or chromAlias bigBed file (see `--sizes-is-2bit`, `--sizes-is-bb`, and `--sizes-is-chrom-alias-bb`).
     pub chromsizes: String,
 
     /// The output bigwig path
@@ -39,6 +40,9 @@ pub struct BedGraphToBigWigArgs {
     #[arg(default_value_t = false)]
     pub single_pass: bool,
 
+    #[command(flatten)]
+    pub sizes_args: ChromSizesArgs,
+
     #[command(flatten)]
     pub write_args: BBIWriteArgs,
 }
@@ -51,10 +55,7 @@ pub fn bedgraphtobigwig(args: BedGraphToBigWigArgs) -> Result<(), Box<dyn Error>
     let input_sort_type = match args.write_args.sorted.as_ref() {
         "all" => InputSortType::ALL,
         "start" => InputSortType::START,
-        "none" => {
-            eprintln!("Using completely unsorted input is not implemented yet.");
-            return Ok(());
-        }
+        "none" => InputSortType::NONE,
         sorted => {
             eprintln!(
                 "Invalid option for `sorted`: `{}`. Options are `all`, `start`, or `none`.",
@@ -64,21 +65,7 @@ pub fn bedgraphtobigwig(args: BedGraphToBigWigArgs) -> Result<(), Box<dyn Error>
         }
     };
 
-    let chrom_map: HashMap<String, u32> = BufReader::new(File::open(chrom_map)?)
-        .lines()
-        .filter(|l| match l {
-            Ok(s) => !s.is_empty(),
-            _ => true,
-        })
-        .map(|l| {
-            let words = l.expect("Split error");
-            let mut split = words.split_whitespace();
-            (
-                split.next().expect("Missing chrom").to_owned(),
-                split.next().expect("Missing size").parse::<u32>().unwrap(),
-            )
-        })
-        .collect();
+    let chrom_map = read_chrom_sizes(&chrom_map, args.sizes_args.format())?;
 
     let mut outb = BigWigWrite::create_file(bigwigpath, chrom_map)?;
     outb.options.max_zooms = args.write_args.nzooms;
@@ -99,7 +86,25 @@ pub fn bedgraphtobigwig(args: BedGraphToBigWigArgs) -> Result<(), Box<dyn Error>
     };
 
     let allow_out_of_order_chroms = !matches!(outb.options.input_sort_type, InputSortType::ALL);
-    if bedgraphpath == "-" || bedgraphpath == "stdin" {
+    if matches!(input_sort_type, InputSortType::NONE) {
+        // Unsorted input has to be read in full to sort it, so only do that once
+        let inmemory = outb.options.inmemory;
+        if bedgraphpath == "-" || bedgraphpath == "stdin" {
+            let stdin = std::io::stdin().lock();
+            let vals = BedParserStreamingIterator::from_unsorted(
+                BedFileStream::from_bedgraph_file(stdin),
+                inmemory,
+            );
+            outb.write(vals, runtime)?;
+        } else {
+            let infile = File::open(&bedgraphpath)?;
+            let vals = BedParserStreamingIterator::from_unsorted(
+                BedFileStream::from_bedgraph_file(infile),
+                inmemory,
+            );
+            outb.write(vals, runtime)?;
+        }
+    } else if bedgraphpath == "-" || bedgraphpath == "stdin" {
         let stdin = std::io::stdin().lock();
         let vals = BedParserStreamingIterator::from_bedgraph_file(stdin, allow_out_of_order_chroms);
         outb.write(vals, runtime)?;
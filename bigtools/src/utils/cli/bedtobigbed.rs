@@ -1,18 +1,21 @@
-use std::collections::HashMap;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::Read;
 use std::path::PathBuf;
 
 use anyhow::Context;
 use clap::Parser;
 use tokio::runtime;
 
-use crate::bed::bedparser::{parse_bed, BedFileStream, StreamingBedValues};
+use crate::bed::autosql::presets::Preset;
+use crate::bed::autosql::typed::BedSchema;
+use crate::bed::autosql::validate::{BedType, BedValidator};
+use crate::bed::bedparser::{parse_bed, BedFileStream, StreamingBedValues, ValidatingBedStream};
 use crate::bed::indexer::index_chroms;
 use crate::beddata::BedParserParallelStreamingIterator;
-use crate::{beddata::BedParserStreamingIterator, BigBedWrite, InputSortType};
+use crate::utils::chromsizes::read_chrom_sizes;
+use crate::{beddata::BedParserStreamingIterator, BedEntry, BigBedWrite, InputSortType};
 
-use super::BBIWriteArgs;
+use super::{BBIWriteArgs, ChromSizesArgs};
 
 #[derive(Clone, Debug, PartialEq, Parser)]
 #[command(
@@ -25,6 +28,7 @@ pub struct BedToBigBedArgs {
     pub bed: String,
 
     /// A chromosome sizes file. Each line should be have a chromosome and its size in bases, separated by whitespace.
+    /// Alternatively, a 2bit, bbi, or chromAlias bigBed file (see `--sizes-is-2bit`, `--sizes-is-bb`, and `--sizes-is-chrom-alias-bb`).
     pub chromsizes: String,
 
     /// The output bigwig path
@@ -35,8 +39,33 @@ pub struct BedToBigBedArgs {
     #[arg(short = 'a', long)]
     pub autosql: Option<String>,
 
+    /// Use the autosql of a standard bigBed format instead of `--autosql`. One of `narrowPeak`,
+    /// `broadPeak`, `bigGenePred`, `bigInteract`, or `bigLolly`. Lines are checked against it, as
+    /// with `--autosql`, and `--type` defaults to the type of the format.
+    #[arg(long)]
+    #[arg(conflicts_with = "autosql")]
+    pub as_preset: Option<String>,
+
+    /// The type of bed, as `bedN`, `bedN+`, or `bedN+M`: `N` standard bed columns followed by
+    /// `M` extra columns (any number for `bedN+`). If either this or `--autosql` is given, each
+    /// line is checked against the autosql before writing (see `--skip-validation`).
+    #[arg(long = "type")]
+    pub bed_type: Option<String>,
+
+    /// If set, lines are not checked against the autosql or `--type`.
+    #[arg(long)]
+    #[arg(default_value_t = false)]
+    pub skip_validation: bool,
+
+    /// A comma-separated list of autosql fields (for example, `name`) to create extra indices for.
+    /// These indices allow searching the bigBed for items by the value of that field.
+    #[arg(long)]
+    #[arg(value_delimiter = ',', num_args = 1..)]
+    pub extra_index: Option<Vec<String>>,
+
     /// Set whether to read and convert the bedGraph in parallel. Requires that the bedGraph is sorted.
     /// Can take `auto` (default), `yes`, `no`. Ignored when input is stdin or when nthreads is `1`.
+    /// Errors from validating lines in parallel don't include the line number.
     #[arg(short = 'p', long)]
     #[arg(default_value = "auto")]
     pub parallel: String,
@@ -47,6 +76,9 @@ pub struct BedToBigBedArgs {
     #[arg(default_value_t = false)]
     pub single_pass: bool,
 
+    #[command(flatten)]
+    pub sizes_args: ChromSizesArgs,
+
     #[command(flatten)]
     pub write_args: BBIWriteArgs,
 }
@@ -59,10 +91,7 @@ pub fn bedtobigbed(args: BedToBigBedArgs) -> anyhow::Result<()> {
     let input_sort_type = match args.write_args.sorted.as_ref() {
         "all" => InputSortType::ALL,
         "start" => InputSortType::START,
-        "none" => {
-            eprintln!("Using completely unsorted input is not implemented yet.");
-            return Ok(());
-        }
+        "none" => InputSortType::NONE,
         sorted => {
             eprintln!(
                 "Invalid option for `sorted`: `{}`. Options are `all`, `start`, or `none`.",
@@ -72,23 +101,8 @@ pub fn bedtobigbed(args: BedToBigBedArgs) -> anyhow::Result<()> {
         }
     };
 
-    let chrom_map = File::open(&chrom_map)
-        .with_context(|| format!("Failed to open chrom sizes file `{}`", &chrom_map))?;
-    let chrom_map: HashMap<String, u32> = BufReader::new(chrom_map)
-        .lines()
-        .filter(|l| match l {
-            Ok(s) => !s.is_empty(),
-            _ => true,
-        })
-        .map(|l| {
-            let words = l.expect("Split error");
-            let mut split = words.split_whitespace();
-            (
-                split.next().expect("Missing chrom").to_owned(),
-                split.next().expect("Missing size").parse::<u32>().unwrap(),
-            )
-        })
-        .collect();
+    let chrom_map = read_chrom_sizes(&chrom_map, args.sizes_args.format())
+        .with_context(|| format!("Failed to read chrom sizes from `{}`", &chrom_map))?;
 
     let mut outb = BigBedWrite::create_file(bigwigpath, chrom_map)
         .with_context(|| format!("Failed to create bigBed file."))?;
@@ -97,6 +111,7 @@ pub fn bedtobigbed(args: BedToBigBedArgs) -> anyhow::Result<()> {
     outb.options.compress = !args.write_args.uncompressed;
     outb.options.input_sort_type = input_sort_type;
     outb.options.inmemory = args.write_args.inmemory;
+    outb.extra_indices = args.extra_index.unwrap_or_default();
     let runtime = if nthreads == 1 {
         outb.options.channel_size = 0;
         runtime::Builder::new_current_thread().build().unwrap()
@@ -107,17 +122,65 @@ pub fn bedtobigbed(args: BedToBigBedArgs) -> anyhow::Result<()> {
             .unwrap()
     };
 
+    let preset = args
+        .as_preset
+        .as_deref()
+        .map(|p| p.parse::<Preset>())
+        .transpose()
+        .map_err(anyhow::Error::msg)?;
+    let bed_type = args
+        .bed_type
+        .as_deref()
+        .map(|t| t.parse::<BedType>())
+        .transpose()?
+        .or(preset.map(|p| p.bed_type()));
+    let validate = !args.skip_validation && (args.autosql.is_some() || bed_type.is_some());
+    let type_autosql = match bed_type {
+        None => None,
+        Some(BedType {
+            standard_fields,
+            extra_fields: Some(0),
+        }) => Some(crate::bed::autosql::bed_autosql(
+            &vec!["."; usize::from(standard_fields) - 3].join("\t"),
+        )),
+        Some(bed_type) if args.autosql.is_none() && preset.is_none() => {
+            anyhow::bail!(
+                "The bed type `{}` has extra fields, so `--autosql` is required.",
+                bed_type
+            );
+        }
+        Some(_) => None,
+    };
+
     let allow_out_of_order_chroms = !matches!(outb.options.input_sort_type, InputSortType::ALL);
     if bedpath == "-" || bedpath == "stdin" {
         if let Some(file) = args.autosql.as_ref() {
             outb.autosql = Some(std::fs::read_to_string(file)?);
+        } else if let Some(preset) = preset {
+            outb.autosql = Some(preset.autosql().to_string());
+        } else {
+            outb.autosql = type_autosql;
         }
+        let validator = bed_validator(validate, outb.autosql.as_deref(), bed_type)?;
         let stdin = std::io::stdin().lock();
-        let data = BedParserStreamingIterator::from_bed_file(stdin, allow_out_of_order_chroms);
-        outb.write(data, runtime)
-            .with_context(|| format!("Failed to write bigBed."))?;
+        if matches!(input_sort_type, InputSortType::NONE) {
+            let inmemory = outb.options.inmemory;
+            let data =
+                BedParserStreamingIterator::from_unsorted(bed_stream(stdin, &validator), inmemory);
+            outb.write(data, runtime)
+                .with_context(|| "Failed to write bigBed.")?;
+        } else {
+            let data = BedParserStreamingIterator::new(
+                bed_stream(stdin, &validator),
+                allow_out_of_order_chroms,
+            );
+            outb.write(data, runtime)
+                .with_context(|| format!("Failed to write bigBed."))?;
+        }
     } else {
         let autosql = match args.autosql.as_ref() {
+            None if preset.is_some() => preset.map(|p| p.autosql().to_string()),
+            None if type_autosql.is_some() => type_autosql,
             None => {
                 let infile = File::open(&bedpath)
                     .with_context(|| format!("Failed to open bed file `{}`", &bedpath))?;
@@ -128,8 +191,21 @@ pub fn bedtobigbed(args: BedToBigBedArgs) -> anyhow::Result<()> {
             }
             Some(file) => Some(std::fs::read_to_string(file)?),
         };
+        let validator = bed_validator(validate, autosql.as_deref(), bed_type)?;
         outb.autosql = autosql;
 
+        if matches!(input_sort_type, InputSortType::NONE) {
+            // Unsorted input has to be read in full to sort it, so only do that once
+            let infile = File::open(&bedpath)
+                .with_context(|| format!("Failed to open bed file `{}`.", &bedpath))?;
+            let inmemory = outb.options.inmemory;
+            let data =
+                BedParserStreamingIterator::from_unsorted(bed_stream(infile, &validator), inmemory);
+            outb.write(data, runtime)
+                .with_context(|| "Failed to write bigBed.")?;
+            return Ok(());
+        }
+
         let infile = File::open(&bedpath)
             .with_context(|| format!("Failed to open bed file `{}`.", &bedpath))?;
         let (parallel, parallel_required) = match (nthreads, args.parallel.as_ref()) {
@@ -162,6 +238,9 @@ pub fn bedtobigbed(args: BedToBigBedArgs) -> anyhow::Result<()> {
             }
         };
         if let Some(chrom_indices) = chrom_indices {
+            // Lines are only numbered when read serially, so validate in the
+            // writer instead
+            outb.validator = validator;
             if args.single_pass {
                 let data = BedParserParallelStreamingIterator::new(
                     chrom_indices,
@@ -191,16 +270,18 @@ pub fn bedtobigbed(args: BedToBigBedArgs) -> anyhow::Result<()> {
             if args.single_pass {
                 let infile = File::open(&bedpath)
                     .with_context(|| format!("Failed to open bed file `{}`.", &bedpath))?;
-                let data =
-                    BedParserStreamingIterator::from_bed_file(infile, allow_out_of_order_chroms);
+                let data = BedParserStreamingIterator::new(
+                    bed_stream(infile, &validator),
+                    allow_out_of_order_chroms,
+                );
                 outb.write(data, runtime)
                     .with_context(|| format!("Failed to write bigBed."))?;
             } else {
                 outb.write_multipass(
                     || {
                         let infile = File::open(&bedpath)?;
-                        let data = BedParserStreamingIterator::from_bed_file(
-                            infile,
+                        let data = BedParserStreamingIterator::new(
+                            bed_stream(infile, &validator),
                             allow_out_of_order_chroms,
                         );
 
@@ -215,3 +296,31 @@ pub fn bedtobigbed(args: BedToBigBedArgs) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Creates the validator for the bed lines, if they should be validated
+fn bed_validator(
+    validate: bool,
+    autosql: Option<&str>,
+    bed_type: Option<BedType>,
+) -> anyhow::Result<Option<BedValidator>> {
+    if !validate {
+        return Ok(None);
+    }
+    let schema = match autosql {
+        Some(autosql) => BedSchema::from_autosql(autosql).context("Invalid autosql.")?,
+        None => BedSchema::bed(0),
+    };
+    Ok(Some(BedValidator::new(schema, bed_type)?))
+}
+
+/// Reads the bed entries of `file`, checking each with `validator`
+fn bed_stream<'a>(
+    file: impl Read + 'a,
+    validator: &Option<BedValidator>,
+) -> Box<dyn StreamingBedValues<Value = BedEntry> + 'a> {
+    let stream = BedFileStream::from_bed_file(file);
+    match validator {
+        Some(validator) => Box::new(ValidatingBedStream::new(stream, validator.clone())),
+        None => Box::new(stream),
+    }
+}
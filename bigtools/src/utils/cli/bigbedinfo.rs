@@ -3,6 +3,8 @@ use std::error::Error;
 use byteordered::Endianness;
 use clap::Parser;
 
+use crate::utils::cli::RemoteArgs;
+
 use crate::{BBIFileRead, BigBedRead};
 
 #[derive(Clone, Debug, Parser, PartialEq)]
@@ -34,6 +36,9 @@ pub struct BigBedInfoArgs {
     #[arg(long, hide(true))]
     #[arg(default_value_t = false)]
     pub debug: bool,
+
+    #[command(flatten)]
+    pub remote: RemoteArgs,
 }
 
 pub fn bigbedinfo(args: BigBedInfoArgs) -> Result<(), Box<dyn Error>> {
@@ -124,8 +129,7 @@ pub fn bigbedinfo(args: BigBedInfoArgs) -> Result<(), Box<dyn Error>> {
     #[cfg(feature = "remote")]
     {
         if bigbedpath.starts_with("http") {
-            use crate::utils::remote_file::RemoteFile;
-            let f = RemoteFile::new(bigbedpath);
+            let f = args.remote.remote_file(bigbedpath);
             let bigbed = BigBedRead::open(f)?;
             print_info(bigbed, &args)?;
             return Ok(());
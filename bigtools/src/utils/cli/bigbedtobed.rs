@@ -2,6 +2,7 @@ use std::error::Error;
 use std::fs::File;
 use std::io::{self, BufReader, Write};
 use std::path::Path;
+use std::sync::Arc;
 
 use clap::Parser;
 use futures::{SinkExt, StreamExt};
@@ -11,7 +12,7 @@ use ufmt::uwrite;
 use crate::utils::reopen::{Reopen, SeekableRead};
 use crate::utils::streaming_linereader::StreamingLineReader;
 use crate::utils::tempfilebuffer::{TempFileBuffer, TempFileBufferWriter};
-use crate::{BBIReadError, BigBedRead, ChromInfo};
+use crate::{BBIReadError, BigBedRead, ChromAliases, ChromInfo};
 
 #[derive(Clone, Debug, PartialEq, Parser)]
 #[command(
@@ -30,6 +31,11 @@ pub struct BigBedToBedArgs {
     #[arg(long)]
     pub chrom: Option<String>,
 
+    /// A chromAlias bigBed or chromAlias.txt file. If set, `--chrom` and the
+    /// chromosomes of `--overlap-bed` can be any alias of a chromosome.
+    #[arg(long)]
+    pub chrom_alias: Option<String>,
+
     /// If set, restrict output to regions greater than or equal to it
     #[arg(long)]
     pub start: Option<u32>,
@@ -64,7 +70,11 @@ pub fn bigbedtobed(args: BigBedToBedArgs) -> Result<(), Box<dyn Error>> {
 
     let nthreads = args.nthreads;
 
-    let bigbed = BigBedRead::open_file(&bigbedpath)?;
+    let mut bigbed = BigBedRead::open_file(&bigbedpath)?;
+    if let Some(chrom_alias) = args.chrom_alias {
+        let chrom_aliases = ChromAliases::open_file(chrom_alias)?;
+        bigbed = bigbed.with_chrom_aliases(Arc::new(chrom_aliases));
+    }
     let bed = File::create(bedpath)?;
 
     if args.start.is_some() || args.end.is_some() & args.chrom.is_none() {
@@ -87,9 +97,11 @@ pub fn bigbedtobed(args: BigBedToBedArgs) -> Result<(), Box<dyn Error>> {
             write_bed_from_bed(bigbed, bed, overlap_bed)?;
         }
         None => {
-            // Right now, we don't offload decompression to separate threads,
-            // so specifying `chrom` effectively means single-threaded
-            if nthreads == 1 || args.chrom.is_some() || args.zoom.is_some() {
+            // Chromosomes are read in parallel. With only one chromosome,
+            // data is read on one thread, but decompressed in parallel.
+            if nthreads > 1 && args.chrom.is_some() && args.zoom.is_none() {
+                write_bed_parallel(bigbed, bed, args.chrom, args.start, args.end, nthreads)?;
+            } else if nthreads == 1 || args.chrom.is_some() || args.zoom.is_some() {
                 write_bed_singlethreaded(bigbed, bed, args.chrom, args.start, args.end, args.zoom)?;
             } else {
                 write_bed(bigbed, bed, args.inmemory, nthreads)?;
@@ -101,18 +113,54 @@ pub fn bigbedtobed(args: BigBedToBedArgs) -> Result<(), Box<dyn Error>> {
 }
 
 pub fn write_bed_singlethreaded<R: Reopen + SeekableRead>(
+    bigbed: BigBedRead<R>,
+    out_file: File,
+    chrom: Option<String>,
+    start: Option<u32>,
+    end: Option<u32>,
+    zoom: Option<u32>,
+) -> Result<(), Box<dyn Error>> {
+    write_bed_chroms(bigbed, out_file, chrom, start, end, zoom, None)
+}
+
+/// Like `write_bed_singlethreaded` (without zoom levels), but while data is
+/// read on the current thread, blocks are decompressed and decoded in
+/// parallel on `nthreads` threads.
+pub fn write_bed_parallel<R: Reopen + SeekableRead>(
+    bigbed: BigBedRead<R>,
+    out_file: File,
+    chrom: Option<String>,
+    start: Option<u32>,
+    end: Option<u32>,
+    nthreads: usize,
+) -> Result<(), Box<dyn Error>> {
+    let runtime = runtime::Builder::new_multi_thread()
+        .worker_threads(nthreads)
+        .build()
+        .unwrap();
+    write_bed_chroms(bigbed, out_file, chrom, start, end, None, Some(&runtime))
+}
+
+/// Writes the bed (or zoom records, if `zoom` is set), decompressing blocks
+/// on `runtime` if it is set
+fn write_bed_chroms<R: Reopen + SeekableRead>(
     mut bigbed: BigBedRead<R>,
     out_file: File,
     chrom: Option<String>,
     start: Option<u32>,
     end: Option<u32>,
     zoom: Option<u32>,
+    runtime: Option<&runtime::Runtime>,
 ) -> Result<(), Box<dyn Error>> {
     let start = chrom.as_ref().and_then(|_| start);
     let end = chrom.as_ref().and_then(|_| end);
 
     let chroms: Vec<ChromInfo> = if let Some(arg_chrom) = chrom {
-        let chrom = bigbed.chroms().iter().find(|c| c.name == arg_chrom);
+        let name = bigbed
+            .chrom_aliases()
+            .and_then(|a| a.resolve(bigbed.chroms(), &arg_chrom))
+            .unwrap_or(&arg_chrom);
+        let chrom = bigbed.chroms().iter().find(|c| c.name == name);
         let Some(chrom) = chrom else {
             eprintln!("Error: {arg_chrom} not found in file.");
             return Ok(());
@@ -159,7 +207,13 @@ pub fn write_bed_singlethreaded<R: Reopen + SeekableRead>(
         for chrom in chroms {
             let start = start.unwrap_or(0);
             let end = end.unwrap_or(chrom.length);
-            for raw_val in bigbed.get_interval(&chrom.name, start, end)? {
+            let vals: Box<dyn Iterator<Item = _>> = match runtime {
+                Some(runtime) => {
+                    Box::new(bigbed.get_interval_parallel(&chrom.name, start, end, runtime)?)
+                }
+                None => Box::new(bigbed.get_interval(&chrom.name, start, end)?),
+            };
+            for raw_val in vals {
                 let val = raw_val?;
                 if !val.rest.is_empty() {
                     uwrite!(
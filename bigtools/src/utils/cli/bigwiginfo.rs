@@ -3,6 +3,8 @@ use std::error::Error;
 use byteordered::Endianness;
 use clap::Parser;
 
+use crate::utils::cli::RemoteArgs;
+
 use crate::{BBIFileRead, BigWigRead};
 
 #[derive(Clone, Debug, PartialEq, Parser)]
@@ -29,6 +31,9 @@ pub struct BigWigInfoArgs {
     #[arg(long)]
     #[arg(default_value_t = false)]
     pub minmax: bool,
+
+    #[command(flatten)]
+    pub remote: RemoteArgs,
 }
 
 pub fn bigwiginfo(args: BigWigInfoArgs) -> Result<(), Box<dyn Error>> {
@@ -100,8 +105,7 @@ pub fn bigwiginfo(args: BigWigInfoArgs) -> Result<(), Box<dyn Error>> {
     #[cfg(feature = "remote")]
     {
         if bigwigpath.starts_with("http") {
-            use crate::utils::remote_file::RemoteFile;
-            let f = RemoteFile::new(bigwigpath);
+            let f = args.remote.remote_file(bigwigpath);
             let bigwig = BigWigRead::open(f)?;
             print_info(bigwig, &args)?;
             return Ok(());
@@ -0,0 +1,132 @@
+use std::error::Error;
+
+use clap::Parser;
+
+use crate::utils::cli::RemoteArgs;
+
+use crate::{BBIFileRead, BigWigRead, SummaryKind};
+
+#[derive(Clone, Debug, PartialEq, Parser)]
+#[command(
+    name = "bigwigsummary",
+    about = "Gets summary data points for a region of a bigWig.",
+    long_about = None,
+)]
+pub struct BigWigSummaryArgs {
+    /// The bigwig to summarize.
+    pub bigwig: String,
+
+    /// The chromosome of the region to summarize.
+    pub chrom: String,
+
+    /// The start of the region to summarize.
+    pub start: u32,
+
+    /// The end of the region to summarize.
+    pub end: u32,
+
+    /// The number of equal-sized bins to divide the region into.
+    pub data_points: usize,
+
+    /// The summary statistic to output for each bin. Can be `mean`, `min`,
+    /// `max`, `std`, `coverage`, or `sum`.
+    #[arg(long = "type")]
+    #[arg(default_value = "mean")]
+    pub summary_type: SummaryKind,
+
+    /// If set, the summaries are calculated from the full data, instead of
+    /// being estimated from a zoom level.
+    #[arg(long)]
+    #[arg(default_value_t = false)]
+    pub exact: bool,
+
+    #[command(flatten)]
+    pub remote: RemoteArgs,
+}
+
+pub fn bigwigsummary(args: BigWigSummaryArgs) -> Result<(), Box<dyn Error>> {
+    let bigwigpath = &args.bigwig;
+
+    fn print_summary<R: BBIFileRead>(
+        mut bigwig: BigWigRead<R>,
+        args: &BigWigSummaryArgs,
+    ) -> Result<(), Box<dyn Error>> {
+        let summaries = bigwig.summarize(
+            &args.chrom,
+            args.start,
+            args.end,
+            args.data_points,
+            args.summary_type,
+            args.exact,
+        )?;
+        if summaries.iter().all(|s| s.is_none()) {
+            return Err(format!(
+                "no data in region {}:{}-{} in {}",
+                args.chrom, args.start, args.end, args.bigwig
+            )
+            .into());
+        }
+        let line = summaries
+            .into_iter()
+            .map(|s| s.map(format_g).unwrap_or_else(|| "n/a".to_string()))
+            .collect::<Vec<_>>()
+            .join("\t");
+        println!("{}", line);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "remote")]
+    {
+        if bigwigpath.starts_with("http") {
+            let f = args.remote.remote_file(bigwigpath);
+            let bigwig = BigWigRead::open(f)?;
+            print_summary(bigwig, &args)?;
+            return Ok(());
+        }
+    }
+
+    let bigwig = BigWigRead::open_file(bigwigpath)?;
+    print_summary(bigwig, &args)?;
+
+    Ok(())
+}
+
+/// Formats a value like C's `%g`, which is what UCSC's `bigWigSummary` uses.
+fn format_g(value: f64) -> String {
+    if value == 0.0 || !value.is_finite() {
+        return format!("{}", value);
+    }
+    // Round to 6 significant digits first, since that can change the exponent
+    let scientific = format!("{:.5e}", value);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+    let exponent: i32 = exponent.parse().unwrap();
+    let trim = |s: &str| {
+        if s.contains('.') {
+            s.trim_end_matches('0').trim_end_matches('.').to_string()
+        } else {
+            s.to_string()
+        }
+    };
+    if !(-4..6).contains(&exponent) {
+        let sign = if exponent < 0 { '-' } else { '+' };
+        format!("{}e{}{:02}", trim(mantissa), sign, exponent.abs())
+    } else {
+        let decimals = (5 - exponent) as usize;
+        trim(&format!("{:.*}", decimals, value))
+    }
+}
+
+#[test]
+fn test_format_g() {
+    assert_eq!("0", format_g(0.0));
+    assert_eq!("1", format_g(1.0));
+    assert_eq!("-2.5", format_g(-2.5));
+    assert_eq!("0.454263", format_g(0.4542629980980206));
+    assert_eq!("123457", format_g(123456.7));
+    assert_eq!("1e+06", format_g(999999.7));
+    assert_eq!("1.23457e+07", format_g(12345678.0));
+    assert_eq!("0.0001", format_g(0.0001));
+    assert_eq!("1.5e-05", format_g(0.000015));
+    assert_eq!("100", format_g(100.0));
+}
@@ -0,0 +1,78 @@
+use std::error::Error;
+use std::sync::Arc;
+
+use clap::Parser;
+
+use crate::utils::cli::{chrom_region, write_parquet, ParquetArgs};
+use crate::{BigWigRead, ChromAliases};
+
+#[derive(Clone, Debug, PartialEq, Parser)]
+#[command(
+    name = "bigwigtoparquet",
+    about = "Converts an input bigWig to a Parquet file.",
+    long_about = "Converts an input bigWig to a Parquet file, with `chrom`, `start`, `end`, and `value` columns."
+)]
+pub struct BigWigToParquetArgs {
+    /// the bigwig to convert to parquet
+    pub bigwig: String,
+
+    /// the path of the parquet file to output to
+    pub parquet: String,
+
+    /// If set, restrict output to given chromosome
+    #[arg(long)]
+    pub chrom: Option<String>,
+
+    /// A chromAlias bigBed or chromAlias.txt file. If set, `--chrom` can be
+    /// any alias of a chromosome.
+    #[arg(long)]
+    pub chrom_alias: Option<String>,
+
+    /// If set, restrict output to regions greater than or equal to it
+    #[arg(long)]
+    pub start: Option<u32>,
+
+    /// If set, restrict output to regions less than it
+    #[arg(long)]
+    pub end: Option<u32>,
+
+    #[command(flatten)]
+    pub parquet_args: ParquetArgs,
+}
+
+pub fn bigwigtoparquet(args: BigWigToParquetArgs) -> Result<(), Box<dyn Error>> {
+    let mut bigwig = BigWigRead::open_file(&args.bigwig)?;
+    if let Some(chrom_alias) = args.chrom_alias {
+        let chrom_aliases = ChromAliases::open_file(chrom_alias)?;
+        bigwig = bigwig.with_chrom_aliases(Arc::new(chrom_aliases));
+    }
+
+    if (args.start.is_some() || args.end.is_some()) && args.chrom.is_none() {
+        eprintln!("Cannot specify --start or --end without specifying --chrom.");
+        return Ok(());
+    }
+
+    let region = match args.chrom {
+        Some(chrom) => {
+            let region = chrom_region(
+                bigwig.chroms(),
+                bigwig.chrom_aliases(),
+                &chrom,
+                args.start,
+                args.end,
+            );
+            let Some(region) = region else {
+                eprintln!("Error: {chrom} not found in file.");
+                return Ok(());
+            };
+            Some(region)
+        }
+        None => None,
+    };
+
+    let mut batches = bigwig.record_batches(args.parquet_args.batch_size);
+    if let Some(region) = region {
+        batches = batches.with_regions(vec![region]);
+    }
+    write_parquet(batches.schema(), batches, &args.parquet, &args.parquet_args)
+}
@@ -5,6 +5,8 @@ use std::path::Path;
 
 use clap::Parser;
 
+use crate::utils::cli::RemoteArgs;
+
 use crate::utils::streaming_linereader::StreamingLineReader;
 use crate::BigWigRead;
 use crate::{BBIFileRead, BBIReadError};
@@ -33,6 +35,9 @@ pub struct BigWigValuesOverBedArgs {
     #[arg(short = 'd', long)]
     #[arg(default_value = "\t")]
     pub delimiter: String,
+
+    #[command(flatten)]
+    pub remote: RemoteArgs,
 }
 
 pub fn bigwigvaluesoverbed(args: BigWigValuesOverBedArgs) -> Result<(), Box<dyn Error>> {
@@ -61,8 +66,7 @@ pub fn bigwigvaluesoverbed(args: BigWigValuesOverBedArgs) -> Result<(), Box<dyn
     #[cfg(feature = "remote")]
     {
         if bigwigpath.starts_with("http") {
-            use crate::utils::remote_file::RemoteFile;
-            let f = RemoteFile::new(&bigwigpath);
+            let f = args.remote.remote_file(&bigwigpath);
             let inbigwig = BigWigRead::open(f)?;
             write(bedin, inbigwig, out, options)?;
         } else {
@@ -0,0 +1,48 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+
+use clap::Parser;
+
+use crate::validate::validate as validate_bbi;
+use crate::BBIFile;
+
+#[derive(Clone, Debug, PartialEq, Parser)]
+#[command(
+    name = "validate",
+    about = "Checks the integrity of a bigWig or bigBed, reporting every problem found.",
+    long_about = None,
+)]
+pub struct ValidateArgs {
+    /// The bigWig or bigBed to validate.
+    pub input: String,
+}
+
+pub fn validate(args: ValidateArgs) -> Result<(), Box<dyn Error>> {
+    let file = File::open(&args.input)?;
+    let report = validate_bbi(BufReader::new(file))?;
+
+    for problem in report.problems.iter() {
+        println!("{}", problem);
+    }
+    let filetype = match report.filetype {
+        Some(BBIFile::BigWig) => "bigWig",
+        Some(BBIFile::BigBed) => "bigBed",
+        None => "file",
+    };
+    if report.is_valid() {
+        println!(
+            "{} is a valid {} ({} blocks checked)",
+            args.input, filetype, report.blocks_checked
+        );
+        Ok(())
+    } else {
+        eprintln!(
+            "{} problem(s) found in {} ({} blocks checked)",
+            report.problems.len(),
+            args.input,
+            report.blocks_checked
+        );
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,125 @@
+use std::{error::Error, fs::File};
+
+use clap::Parser;
+use tokio::runtime;
+
+use crate::bed::bedparser::WigFileStream;
+use crate::beddata::BedParserStreamingIterator;
+use crate::utils::chromsizes::read_chrom_sizes;
+use crate::{BigWigWrite, InputSortType, SectionEncoding};
+
+use super::{BBIWriteArgs, ChromSizesArgs};
+
+#[derive(Clone, Debug, PartialEq, Parser)]
+#[command(
+    name = "wigtobigwig",
+    about = "Converts an input WIG to a bigWig.",
+    long_about = "Converts an input WIG (containing fixedStep, variableStep, or bedGraph data) to a bigWig. Note that ~11 temporary files are created/maintained."
+)]
+pub struct WigToBigWigArgs {
+    /// The WIG to convert to a bigwig. Can use `-` or `stdin` to read from stdin.
+    pub wig: String,
+
+    /// A chromosome sizes file. Each line should be have a chromosome and its size in bases, separated by whitespace.
+    /// Alternatively, a 2bit, bbi, or chromAlias bigBed file (see `--sizes-is-2bit`, `--sizes-is-bb`, and `--sizes-is-chrom-alias-bb`).
+    pub chromsizes: String,
+
+    /// The output bigwig path
+    pub output: String,
+
+    /// If set, indicates that only a single pass should be done on the input file. This is most useful
+    /// on large files in order to reduce total time. This automatically happens when the input is `stdin`.
+    #[arg(long)]
+    #[arg(default_value_t = false)]
+    pub single_pass: bool,
+
+    #[command(flatten)]
+    pub sizes_args: ChromSizesArgs,
+
+    #[command(flatten)]
+    pub write_args: BBIWriteArgs,
+}
+
+pub fn wigtobigwig(args: WigToBigWigArgs) -> Result<(), Box<dyn Error>> {
+    let wigpath = args.wig;
+    let chrom_map = args.chromsizes;
+    let bigwigpath = args.output;
+    let nthreads = args.write_args.nthreads;
+    let input_sort_type = match args.write_args.sorted.as_ref() {
+        "all" => InputSortType::ALL,
+        "start" => InputSortType::START,
+        "none" => InputSortType::NONE,
+        sorted => {
+            eprintln!(
+                "Invalid option for `sorted`: `{}`. Options are `all`, `start`, or `none`.",
+                sorted
+            );
+            return Ok(());
+        }
+    };
+
+    let chrom_map = read_chrom_sizes(&chrom_map, args.sizes_args.format())?;
+
+    let mut outb = BigWigWrite::create_file(bigwigpath, chrom_map)?;
+    outb.options.max_zooms = args.write_args.nzooms;
+    outb.options.manual_zoom_sizes = args.write_args.zooms;
+    outb.options.compress = !args.write_args.uncompressed;
+    outb.options.input_sort_type = input_sort_type;
+    outb.options.block_size = args.write_args.block_size;
+    outb.options.items_per_slot = args.write_args.items_per_slot;
+    outb.options.inmemory = args.write_args.inmemory;
+    // WIG data is usually fixedStep or variableStep, so keep it that way
+    outb.options.section_encoding = SectionEncoding::Auto;
+
+    let runtime = if nthreads == 1 {
+        outb.options.channel_size = 0;
+        runtime::Builder::new_current_thread().build().unwrap()
+    } else {
+        runtime::Builder::new_multi_thread()
+            .worker_threads(nthreads)
+            .build()
+            .unwrap()
+    };
+
+    let allow_out_of_order_chroms = !matches!(outb.options.input_sort_type, InputSortType::ALL);
+    if matches!(input_sort_type, InputSortType::NONE) {
+        // Unsorted input has to be read in full to sort it, so only do that once
+        let inmemory = outb.options.inmemory;
+        if wigpath == "-" || wigpath == "stdin" {
+            let stdin = std::io::stdin().lock();
+            let vals = BedParserStreamingIterator::from_unsorted(
+                WigFileStream::from_wig_file(stdin),
+                inmemory,
+            );
+            outb.write(vals, runtime)?;
+        } else {
+            let infile = File::open(&wigpath)?;
+            let vals = BedParserStreamingIterator::from_unsorted(
+                WigFileStream::from_wig_file(infile),
+                inmemory,
+            );
+            outb.write(vals, runtime)?;
+        }
+    } else if wigpath == "-" || wigpath == "stdin" {
+        let stdin = std::io::stdin().lock();
+        let vals = BedParserStreamingIterator::from_wig_file(stdin, allow_out_of_order_chroms);
+        outb.write(vals, runtime)?;
+    } else if args.single_pass {
+        let infile = File::open(&wigpath)?;
+        let vals = BedParserStreamingIterator::from_wig_file(infile, allow_out_of_order_chroms);
+        outb.write(vals, runtime)?;
+    } else {
+        outb.write_multipass(
+            || {
+                let infile = File::open(&wigpath)?;
+                Ok(BedParserStreamingIterator::from_wig_file(
+                    infile,
+                    allow_out_of_order_chroms,
+                ))
+            },
+            runtime,
+        )?;
+    }
+
+    Ok(())
+}
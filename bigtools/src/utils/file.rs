@@ -5,6 +5,10 @@ pub mod tell;
 #[cfg(feature = "write")]
 pub mod tempfilebuffer;
 
+#[cfg(feature = "remote")]
+pub mod range_fetcher;
+#[cfg(feature = "remote")]
+pub mod remote_cache;
 #[cfg(feature = "remote")]
 pub mod remote_file;
 
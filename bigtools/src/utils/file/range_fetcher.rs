@@ -0,0 +1,402 @@
+//! Ways of fetching byte ranges of a remote file, used by `RemoteFile`.
+//!
+//! `HttpFetcher` fetches ranges over http(s) (with optional extra headers,
+//! authentication, retries and timeouts), and `FileFetcher` fetches ranges of
+//! a `file://` url. Other sources can be supported by implementing
+//! `RangeFetcher`.
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use ureq::{Agent, AgentBuilder, Response};
+use url::Url;
+
+use crate::utils::file::remote_cache::RemoteFileValidators;
+
+/// Fetches byte ranges of a file.
+pub trait RangeFetcher {
+    /// The url of the file, used to identify it in a `RemoteCache`.
+    fn url(&self) -> &str;
+
+    /// Fetches `len` bytes starting at `start`. Fewer bytes are only returned
+    /// if the end of the file is reached.
+    fn fetch(&self, start: u64, len: u64) -> io::Result<Vec<u8>>;
+
+    /// Gets what identifies the current version of the file, or `None` if
+    /// that isn't known (in which case its data can't be cached between runs).
+    fn validators(&self) -> io::Result<Option<RemoteFileValidators>>;
+}
+
+/// The default number of times a failed request is retried
+pub const DEFAULT_RETRIES: u32 = 3;
+/// The default time to wait before the first retry. This doubles for each
+/// subsequent retry.
+pub const DEFAULT_BACKOFF: Duration = Duration::from_millis(500);
+/// The default time to wait for a connection or for data from the server
+pub const DEFAULT_IO_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_REDIRECTS: usize = 10;
+
+/// Fetches ranges of a file over http(s), using `Range` requests.
+///
+/// Requests that fail because of connection errors, server errors (`5xx`), or
+/// rate limiting (`429`) are retried with exponential backoff.
+///
+/// Requests are made with a `ureq::Agent`, which keeps connections alive and
+/// reuses them between requests (including by clones of this fetcher).
+/// Proxies can be configured through the `http_proxy` or `https_proxy`
+/// environment variables.
+///
+/// Redirects are followed, but the headers added with `with_header` (or
+/// `with_bearer_token`) are only sent to the origin (scheme, host, and port)
+/// of the original url. Once a redirect leaves that origin, they aren't sent
+/// for the rest of the request.
+#[derive(Clone, Debug)]
+pub struct HttpFetcher {
+    url: String,
+    headers: Vec<(String, String)>,
+    retries: u32,
+    backoff: Duration,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    keep_alive: bool,
+    agent: Agent,
+}
+
+impl HttpFetcher {
+    pub fn new(url: &str) -> HttpFetcher {
+        HttpFetcher {
+            url: url.to_string(),
+            headers: vec![],
+            retries: DEFAULT_RETRIES,
+            backoff: DEFAULT_BACKOFF,
+            timeout: None,
+            connect_timeout: None,
+            keep_alive: true,
+            agent: build_agent(None, true),
+        }
+    }
+
+    /// Sends the header `name: value` with every request. Invalid headers
+    /// cause requests to fail with `io::ErrorKind::InvalidInput`.
+    pub fn with_header(mut self, name: &str, value: &str) -> HttpFetcher {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Authenticates with `token` using the `Authorization: Bearer` header.
+    pub fn with_bearer_token(self, token: &str) -> HttpFetcher {
+        self.with_header("authorization", &format!("Bearer {}", token))
+    }
+
+    /// Retries failed requests up to `retries` times, waiting `backoff` before
+    /// the first retry and twice as long before each subsequent one.
+    pub fn with_retries(self, retries: u32, backoff: Duration) -> HttpFetcher {
+        HttpFetcher {
+            retries,
+            backoff,
+            ..self
+        }
+    }
+
+    /// Fails a request if it doesn't complete within `timeout`.
+    pub fn with_timeout(self, timeout: Duration) -> HttpFetcher {
+        HttpFetcher {
+            timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    /// Fails a request if a connection can't be made within `timeout`. This
+    /// replaces the agent (see `with_agent`).
+    pub fn with_connect_timeout(self, timeout: Duration) -> HttpFetcher {
+        HttpFetcher {
+            connect_timeout: Some(timeout),
+            agent: build_agent(Some(timeout), self.keep_alive),
+            ..self
+        }
+    }
+
+    /// Whether to keep connections alive between requests (the default). If
+    /// not, a new connection is made for every request. This replaces the
+    /// agent (see `with_agent`).
+    pub fn with_keep_alive(self, keep_alive: bool) -> HttpFetcher {
+        HttpFetcher {
+            keep_alive,
+            agent: build_agent(self.connect_timeout, keep_alive),
+            ..self
+        }
+    }
+
+    /// Makes requests with `agent`, sharing its connections with anything
+    /// else that uses it. The agent's own connect timeout, keep-alive, and
+    /// proxy settings are used. It should not follow redirects itself
+    /// (`AgentBuilder::redirects(0)`), since otherwise headers are sent to
+    /// every redirect target.
+    pub fn with_agent(self, agent: Agent) -> HttpFetcher {
+        HttpFetcher { agent, ..self }
+    }
+
+    /// Makes a request, following redirects. Responses with a server error
+    /// or `429` are transient errors.
+    fn send(&self, method: &str, extra_headers: &[(&str, &str)]) -> Result<Response, FetchError> {
+        let invalid = |e: &dyn std::fmt::Display| {
+            FetchError::Fatal(io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))
+        };
+        let original = Url::parse(&self.url).map_err(|e| invalid(&e))?;
+        let mut url = original.clone();
+        let mut same_origin = true;
+        for _ in 0..=MAX_REDIRECTS {
+            // Caller-supplied headers may be credentials, so they aren't
+            // sent to other origins (including https to http downgrades)
+            same_origin &= url.origin() == original.origin();
+            let mut request = self.agent.request_url(method, &url);
+            if same_origin {
+                for (name, value) in &self.headers {
+                    request = request.set(name, value);
+                }
+            }
+            for (name, value) in extra_headers {
+                request = request.set(name, value);
+            }
+            if let Some(timeout) = self.timeout {
+                request = request.timeout(timeout);
+            }
+            let resp = match request.call() {
+                Ok(resp) | Err(ureq::Error::Status(_, resp)) => resp,
+                Err(ureq::Error::Transport(e)) => return Err(FetchError::from(e)),
+            };
+            let status = resp.status();
+            match (status, resp.header("location")) {
+                (301 | 302 | 303 | 307 | 308, Some(location)) => {
+                    url = url.join(location).map_err(|e| invalid(&e))?;
+                }
+                (500..=599 | 429, _) => {
+                    return Err(FetchError::Transient(io::Error::other(format!(
+                        "Request to {} failed: {} {}",
+                        self.url,
+                        status,
+                        resp.status_text()
+                    ))));
+                }
+                _ => return Ok(resp),
+            }
+        }
+        Err(FetchError::Fatal(io::Error::other(format!(
+            "Too many redirects for {}",
+            self.url
+        ))))
+    }
+
+    /// Calls `f` until it succeeds, fails with a non-transient error, or
+    /// has been retried `self.retries` times.
+    fn retrying<T>(&self, mut f: impl FnMut() -> Result<T, FetchError>) -> io::Result<T> {
+        let mut backoff = self.backoff;
+        let mut attempt = 0;
+        loop {
+            match f() {
+                Ok(v) => return Ok(v),
+                Err(FetchError::Transient(_)) if attempt < self.retries => {
+                    thread::sleep(backoff);
+                    backoff = backoff.saturating_mul(2);
+                    attempt += 1;
+                }
+                Err(FetchError::Transient(e) | FetchError::Fatal(e)) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Builds an agent that doesn't follow redirects (they are followed by
+/// `HttpFetcher::send`)
+fn build_agent(connect_timeout: Option<Duration>, keep_alive: bool) -> Agent {
+    let mut builder = AgentBuilder::new()
+        .redirects(0)
+        .try_proxy_from_env(true)
+        .timeout_connect(connect_timeout.unwrap_or(DEFAULT_IO_TIMEOUT))
+        .timeout_read(DEFAULT_IO_TIMEOUT)
+        .timeout_write(DEFAULT_IO_TIMEOUT)
+        .user_agent("bigtools");
+    if !keep_alive {
+        builder = builder.max_idle_connections(0);
+    }
+    builder.build()
+}
+
+enum FetchError {
+    /// Errors that may not happen again if the request is retried
+    Transient(io::Error),
+    Fatal(io::Error),
+}
+
+impl From<ureq::Transport> for FetchError {
+    fn from(e: ureq::Transport) -> Self {
+        use ureq::ErrorKind;
+
+        match e.kind() {
+            ErrorKind::InvalidUrl
+            | ErrorKind::UnknownScheme
+            | ErrorKind::BadHeader
+            | ErrorKind::InvalidProxyUrl
+            | ErrorKind::InsecureRequestHttpsOnly => {
+                FetchError::Fatal(io::Error::new(io::ErrorKind::InvalidInput, e))
+            }
+            ErrorKind::ProxyUnauthorized | ErrorKind::TooManyRedirects | ErrorKind::HTTP => {
+                FetchError::Fatal(io::Error::other(e))
+            }
+            ErrorKind::Dns
+            | ErrorKind::ConnectionFailed
+            | ErrorKind::BadStatus
+            | ErrorKind::Io
+            | ErrorKind::ProxyConnect => FetchError::Transient(io::Error::other(e)),
+        }
+    }
+}
+
+impl RangeFetcher for HttpFetcher {
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn fetch(&self, start: u64, len: u64) -> io::Result<Vec<u8>> {
+        if len == 0 {
+            return Ok(vec![]);
+        }
+        let range = format!("bytes={}-{}", start, start + len - 1);
+        let (status, bytes) = self.retrying(|| {
+            let resp = self.send("GET", &[("range", &range)])?;
+            let status = resp.status();
+            // A server that ignores the range sends the whole file, of which
+            // only up to the end of the range is read
+            let limit = match status {
+                206 => len,
+                _ => start + len,
+            };
+            let mut bytes = vec![];
+            resp.into_reader()
+                .take(limit)
+                .read_to_end(&mut bytes)
+                .map_err(FetchError::Transient)?;
+            Ok((status, bytes))
+        })?;
+        if status == 416 {
+            // Past the end of the file
+            return Ok(vec![]);
+        }
+        if !(200..300).contains(&status) {
+            let kind = match status {
+                401 | 403 => io::ErrorKind::PermissionDenied,
+                404 => io::ErrorKind::NotFound,
+                _ => io::ErrorKind::Other,
+            };
+            return Err(io::Error::new(
+                kind,
+                format!("Request to {} failed: {}", self.url, status),
+            ));
+        }
+        let mut bytes = bytes;
+        if status != 206 {
+            // The server ignored the range and sent the whole file
+            let start = (start as usize).min(bytes.len());
+            bytes.drain(..start);
+        }
+        Ok(bytes)
+    }
+
+    fn validators(&self) -> io::Result<Option<RemoteFileValidators>> {
+        let resp = self.retrying(|| self.send("HEAD", &[]))?;
+        if !(200..300).contains(&resp.status()) {
+            return Ok(None);
+        }
+        let header = |name: &str| resp.header(name).map(|v| v.to_string());
+        let length = match header("content-length").and_then(|l| l.parse().ok()) {
+            Some(length) => length,
+            None => return Ok(None),
+        };
+        let validators = RemoteFileValidators {
+            length,
+            etag: header("etag"),
+            last_modified: header("last-modified"),
+        };
+        Ok(validators.is_usable().then_some(validators))
+    }
+}
+
+/// Fetches ranges of a local file given by a `file://` url.
+#[derive(Clone, Debug)]
+pub struct FileFetcher {
+    url: String,
+    path: PathBuf,
+}
+
+impl FileFetcher {
+    /// Creates a `FileFetcher` for `url`, which must start with `file://`.
+    pub fn new(url: &str) -> io::Result<FileFetcher> {
+        let path = url.strip_prefix("file://").ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Not a file:// url: {}", url),
+            )
+        })?;
+        Ok(FileFetcher {
+            url: url.to_string(),
+            path: PathBuf::from(path),
+        })
+    }
+}
+
+impl RangeFetcher for FileFetcher {
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn fetch(&self, start: u64, len: u64) -> io::Result<Vec<u8>> {
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(start))?;
+        let mut bytes = Vec::with_capacity(len as usize);
+        file.take(len).read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn validators(&self) -> io::Result<Option<RemoteFileValidators>> {
+        let metadata = std::fs::metadata(&self.path)?;
+        let last_modified = metadata
+            .modified()
+            .ok()
+            .and_then(|m| m.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .map(|d| format!("{}.{:09}", d.as_secs(), d.subsec_nanos()));
+        let validators = RemoteFileValidators {
+            length: metadata.len(),
+            etag: None,
+            last_modified,
+        };
+        Ok(validators.is_usable().then_some(validators))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_fetcher() -> io::Result<()> {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/test/small2.bed");
+        let data = std::fs::read(&path)?;
+
+        assert!(FileFetcher::new(path.to_str().unwrap()).is_err());
+        let fetcher = FileFetcher::new(&format!("file://{}", path.to_str().unwrap()))?;
+        assert_eq!(fetcher.fetch(0, 10)?, &data[0..10]);
+        assert_eq!(fetcher.fetch(100, 50)?, &data[100..150]);
+        let len = data.len() as u64;
+        assert_eq!(fetcher.fetch(len - 5, 10)?, &data[data.len() - 5..]);
+        assert_eq!(fetcher.fetch(len + 5, 10)?, Vec::<u8>::new());
+
+        let validators = fetcher.validators()?.unwrap();
+        assert_eq!(validators.length, len);
+        assert!(validators.last_modified.is_some());
+        Ok(())
+    }
+}
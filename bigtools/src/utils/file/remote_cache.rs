@@ -0,0 +1,402 @@
+//! A persistent, on-disk cache of the data of remote files (like the udc cache
+//! of the UCSC tools).
+//!
+//! Each url gets its own directory in the cache directory, named by a hash of
+//! the url, containing:
+//! - `url`: the url itself
+//! - `info`: the length of the file and the `ETag` and `Last-Modified`
+//!   headers used to check that the cached data is still current
+//! - `bitmap`: one bit for each block of the file, set once the block is cached
+//! - `data`: a sparse file with each cached block at its offset in the remote
+//!   file
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// The default maximum size (in bytes) of the data in a `RemoteCache`
+pub const DEFAULT_REMOTE_CACHE_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// A directory that caches the data of remote files between runs. See
+/// `RemoteFile::with_cache`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RemoteCache {
+    dir: PathBuf,
+    max_size: u64,
+}
+
+/// What identifies a specific version of a remote file. Cached data is only
+/// reused if these match.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RemoteFileValidators {
+    pub length: u64,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl RemoteFileValidators {
+    /// Whether these can identify a version of a file. Without an `ETag` or
+    /// `Last-Modified`, there's no way to tell if a file has changed.
+    pub fn is_usable(&self) -> bool {
+        self.etag.is_some() || self.last_modified.is_some()
+    }
+}
+
+/// Writes the `info` of a cache entry
+fn write_info(dir: &Path, validators: &RemoteFileValidators, block_size: u64) -> io::Result<()> {
+    let info = format!(
+        "length\t{}\netag\t{}\nlast-modified\t{}\nblock-size\t{}\n",
+        validators.length,
+        validators.etag.as_deref().unwrap_or(""),
+        validators.last_modified.as_deref().unwrap_or(""),
+        block_size,
+    );
+    fs::write(dir.join("info"), info)
+}
+
+/// Reads the `info` of a cache entry: its validators and block size
+fn read_info(dir: &Path) -> Option<(RemoteFileValidators, u64)> {
+    let info = fs::read_to_string(dir.join("info")).ok()?;
+    let mut validators = RemoteFileValidators::default();
+    let mut block_size = None;
+    for line in info.lines() {
+        let (key, value) = line.split_once('\t')?;
+        let value = (!value.is_empty()).then(|| value.to_string());
+        match key {
+            "length" => validators.length = value?.parse().ok()?,
+            "etag" => validators.etag = value,
+            "last-modified" => validators.last_modified = value,
+            "block-size" => block_size = Some(value?.parse().ok()?),
+            _ => return None,
+        }
+    }
+    Some((validators, block_size?))
+}
+
+impl RemoteCache {
+    /// Creates a cache in `dir`, holding up to `DEFAULT_REMOTE_CACHE_SIZE`
+    /// bytes of data. The directory is created when first used.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        RemoteCache {
+            dir: dir.into(),
+            max_size: DEFAULT_REMOTE_CACHE_SIZE,
+        }
+    }
+
+    /// Sets the maximum size (in bytes) of the data in this cache. When a
+    /// file is opened or data is added and the cache is larger than this, the
+    /// data of the least-recently opened files is removed. The data of a file
+    /// that is being read is never removed, so a single large file can still
+    /// exceed this.
+    pub fn with_max_size(self, max_size: u64) -> Self {
+        RemoteCache { max_size, ..self }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn max_size(&self) -> u64 {
+        self.max_size
+    }
+
+    /// The directory of the cached data for `url`
+    fn entry_dir(&self, url: &str) -> PathBuf {
+        // FNV-1a, since it needs to be stable between runs (and versions)
+        let hash = url.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, b| {
+            (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+        });
+        self.dir.join(format!("{:016x}", hash))
+    }
+
+    /// Opens the cached data for `url`, which is split into blocks of
+    /// `block_size` bytes. Any cached data is removed if it was for a
+    /// different version of the file (as identified by `validators`). Then,
+    /// least-recently opened data is evicted if the cache is too large (which
+    /// is checked again once blocks are cached past the maximum size).
+    pub(crate) fn open(
+        &self,
+        url: &str,
+        validators: &RemoteFileValidators,
+        block_size: u64,
+    ) -> io::Result<RemoteCacheEntry> {
+        let dir = self.entry_dir(url);
+        fs::create_dir_all(&dir)?;
+
+        let cached_url = fs::read_to_string(dir.join("url")).ok();
+        let cached_info = read_info(&dir);
+        let current = cached_url.as_deref() == Some(url)
+            && cached_info.as_ref() == Some(&(validators.clone(), block_size));
+        if !current {
+            for file in ["bitmap", "data"] {
+                match fs::remove_file(dir.join(file)) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                    _ => {}
+                }
+            }
+            fs::write(dir.join("url"), url)?;
+        }
+        // Always rewritten, so that its modification time is when the file
+        // was last opened
+        write_info(&dir, validators, block_size)?;
+
+        let mut bitmap_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(dir.join("bitmap"))?;
+        let num_blocks = validators.length.div_ceil(block_size);
+        let mut bitmap = vec![];
+        bitmap_file.read_to_end(&mut bitmap)?;
+        bitmap.resize(num_blocks.div_ceil(8) as usize, 0);
+        let data = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(dir.join("data"))?;
+
+        let other_size = self.evict(&dir)?;
+        let cached_blocks: u64 = bitmap.iter().map(|b| u64::from(b.count_ones())).sum();
+
+        Ok(RemoteCacheEntry {
+            cache: self.clone(),
+            dir,
+            size: cached_blocks * block_size,
+            other_size,
+            bitmap,
+            bitmap_file,
+            data,
+            length: validators.length,
+            block_size,
+        })
+    }
+
+    /// Removes the data of the least-recently opened files until the cache
+    /// is no larger than `max_size`. The data in `keep` is never removed.
+    /// Returns the size of the data left in the cache, other than `keep`.
+    fn evict(&self, keep: &Path) -> io::Result<u64> {
+        let mut entries = vec![];
+        let mut total_size = 0;
+        let mut keep_size = 0;
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if !path.is_dir() {
+                continue;
+            }
+            let size = match cached_size(&path) {
+                Ok(size) => size,
+                Err(_) => continue,
+            };
+            let opened = fs::metadata(path.join("info"))
+                .and_then(|m| m.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            total_size += size;
+            if path != keep {
+                entries.push((opened, size, path));
+            } else {
+                keep_size = size;
+            }
+        }
+        entries.sort();
+        for (_, size, path) in entries {
+            if total_size <= self.max_size {
+                break;
+            }
+            fs::remove_dir_all(&path)?;
+            total_size -= size;
+        }
+        Ok(total_size - keep_size)
+    }
+}
+
+/// The size of the cached data in an entry's directory. Since the data file
+/// is sparse, this is estimated from the blocks marked as cached.
+fn cached_size(dir: &Path) -> io::Result<u64> {
+    let (_, block_size) = read_info(dir)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid cache info"))?;
+    let bitmap = fs::read(dir.join("bitmap"))?;
+    let blocks: u64 = bitmap.iter().map(|b| u64::from(b.count_ones())).sum();
+    Ok(blocks * block_size)
+}
+
+/// The cached data of a single remote file
+pub(crate) struct RemoteCacheEntry {
+    cache: RemoteCache,
+    dir: PathBuf,
+    // The (estimated) size of the cached data of this file, and of the other
+    // files in the cache as of the last eviction
+    size: u64,
+    other_size: u64,
+    bitmap: Vec<u8>,
+    bitmap_file: File,
+    data: File,
+    length: u64,
+    block_size: u64,
+}
+
+impl RemoteCacheEntry {
+    /// The length (in bytes) of `block`, which is only less than the block
+    /// size for the last block
+    fn block_len(&self, block: u64) -> u64 {
+        let block_start = block * self.block_size;
+        self.block_size.min(self.length.saturating_sub(block_start))
+    }
+
+    /// Reads the byte of the bitmap for `block` from disk, since it may have
+    /// been written by another reader of the same file
+    fn read_bitmap_byte(&mut self, index: usize) -> io::Result<u8> {
+        let mut byte = [0u8];
+        self.bitmap_file.seek(SeekFrom::Start(index as u64))?;
+        match self.bitmap_file.read_exact(&mut byte) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {}
+            Err(e) => return Err(e),
+        }
+        self.bitmap[index] |= byte[0];
+        Ok(self.bitmap[index])
+    }
+
+    /// Whether the data of `block` is cached
+    pub(crate) fn contains(&mut self, block: u64) -> io::Result<bool> {
+        let index = (block / 8) as usize;
+        let bit = 1 << (block % 8);
+        if index >= self.bitmap.len() {
+            return Ok(false);
+        }
+        Ok(self.bitmap[index] & bit != 0 || self.read_bitmap_byte(index)? & bit != 0)
+    }
+
+    /// Gets the data of `block`, if it is cached
+    pub(crate) fn get(&mut self, block: u64) -> io::Result<Option<Vec<u8>>> {
+        if !self.contains(block)? {
+            return Ok(None);
+        }
+        let mut data = vec![0u8; self.block_len(block) as usize];
+        self.data.seek(SeekFrom::Start(block * self.block_size))?;
+        self.data.read_exact(&mut data)?;
+        Ok(Some(data))
+    }
+
+    /// Caches the data of `block`, then evicts the data of other files if the
+    /// cache is now too large. Only complete blocks (or the complete last
+    /// block) are cached.
+    ///
+    /// The size of the cache is tracked as blocks are cached, so the cache
+    /// directory is only scanned again once it may be too large and there is
+    /// data of other files left to evict.
+    pub(crate) fn put(&mut self, block: u64, data: &[u8]) -> io::Result<()> {
+        let index = (block / 8) as usize;
+        if index >= self.bitmap.len() || data.len() as u64 != self.block_len(block) {
+            return Ok(());
+        }
+        // The data is written before it is marked as cached, so that readers
+        // never see a block marked as cached without its data
+        self.data.seek(SeekFrom::Start(block * self.block_size))?;
+        self.data.write_all(data)?;
+        let bit = 1 << (block % 8);
+        let old_byte = self.read_bitmap_byte(index)?;
+        let byte = old_byte | bit;
+        self.bitmap[index] = byte;
+        self.bitmap_file.seek(SeekFrom::Start(index as u64))?;
+        self.bitmap_file.write_all(&[byte])?;
+        if old_byte & bit == 0 {
+            self.size += self.block_size;
+        }
+        if self.other_size > 0 && self.size + self.other_size > self.cache.max_size {
+            self.other_size = self.cache.evict(&self.dir)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validators(etag: &str) -> RemoteFileValidators {
+        RemoteFileValidators {
+            length: 25,
+            etag: Some(etag.to_string()),
+            last_modified: None,
+        }
+    }
+
+    #[test]
+    fn test_remote_cache() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = RemoteCache::new(dir.path());
+        let url = "http://example.com/a.bigWig";
+
+        let mut entry = cache.open(url, &validators("a"), 10)?;
+        assert_eq!(entry.get(0)?, None);
+        entry.put(0, &[1; 10])?;
+        // Only complete blocks are cached
+        entry.put(1, &[2; 5])?;
+        entry.put(2, &[3; 5])?;
+        assert_eq!(entry.get(0)?, Some(vec![1; 10]));
+        assert_eq!(entry.get(1)?, None);
+        assert_eq!(entry.get(2)?, Some(vec![3; 5]));
+        assert_eq!(entry.get(3)?, None);
+
+        // Another reader sees cached data, even after the entry is opened
+        let mut other = cache.open(url, &validators("a"), 10)?;
+        entry.put(1, &[2; 10])?;
+        assert_eq!(other.get(0)?, Some(vec![1; 10]));
+        assert_eq!(other.get(1)?, Some(vec![2; 10]));
+        drop((entry, other));
+
+        // Data is not reused if the file has changed
+        let mut entry = cache.open(url, &validators("b"), 10)?;
+        assert_eq!(entry.get(0)?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_remote_cache_eviction() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let block_size = 1024;
+        let cache = RemoteCache::new(dir.path()).with_max_size(3 * block_size);
+        let validators = RemoteFileValidators {
+            length: 2 * block_size,
+            etag: Some("a".to_string()),
+            last_modified: None,
+        };
+        let block = vec![0u8; block_size as usize];
+
+        let larger_cache = cache.clone().with_max_size(4 * block_size);
+        for url in ["a", "b"] {
+            let mut entry = larger_cache.open(url, &validators, block_size)?;
+            entry.put(0, &block)?;
+            entry.put(1, &block)?;
+            // Make sure the modification times of the entries differ
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        // Opening "c" evicts "a", the least-recently opened
+        let mut entry = cache.open("c", &validators, block_size)?;
+        assert!(!cache.entry_dir("a").exists());
+        assert!(cache.entry_dir("b").exists());
+        entry.put(0, &block)?;
+
+        // Caching a block again doesn't add to the size of the cache
+        entry.put(0, &block)?;
+        assert!(cache.entry_dir("b").exists());
+
+        // Caching more data evicts "b" once the cache is too large
+        entry.put(1, &block)?;
+        assert!(!cache.entry_dir("b").exists());
+        assert!(cache.entry_dir("c").exists());
+
+        // The data being opened is never evicted
+        let cache = cache.with_max_size(0);
+        let mut entry = cache.open("b", &validators, block_size)?;
+        assert!(!cache.entry_dir("c").exists());
+        entry.put(0, &block)?;
+        assert_eq!(entry.get(0)?, Some(block));
+
+        Ok(())
+    }
+}
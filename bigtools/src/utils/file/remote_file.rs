@@ -1,117 +1,240 @@
 use std::fs::File;
 use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
 
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use tempfile;
 
+use crate::utils::file::range_fetcher::{HttpFetcher, RangeFetcher};
+use crate::utils::file::remote_cache::{RemoteCache, RemoteCacheEntry};
 use crate::utils::file::reopen::Reopen;
 
-const READ_SIZE: usize = 10 * 1024; // 10 KB chunks
+/// The default size of the blocks a `RemoteFile` is read (and cached) in
+pub const READ_SIZE: usize = 10 * 1024; // 10 KB chunks
+/// The default maximum number of bytes read ahead of sequential reads
+pub const DEFAULT_MAX_READ_AHEAD: u64 = 4 * 1024 * 1024;
 
-// Remote file reads are cached to a temporary file. The size of each block
-// (with the exception of the last block) is `READ_SIZE + 1` bytes. The first
-// byte of a block is `0` if the data hasn't been written yet, or `1` if it
-// has (and has enough data to fill the block). A value of `2` signifies that
-// there wasn't enough data to fill the block, which only should happen for the
-// last block.
+// Without a `RemoteCache`, remote file reads are cached to a temporary file.
+// The size of each block (with the exception of the last block) is
+// `block_size + 1` bytes. The first byte of a block is `0` if the data hasn't
+// been written yet, or `1` if it has (and has enough data to fill the block).
+// A value of `2` signifies that there wasn't enough data to fill the block,
+// which only should happen for the last block.
 
-pub struct RemoteFile {
-    url: String,
+enum BlockCache {
+    Temp { file: File, block_size: u64 },
+    Persistent(RemoteCacheEntry),
+}
+
+impl BlockCache {
+    fn get(&mut self, block: u64) -> io::Result<Option<Vec<u8>>> {
+        let (cache, block_size) = match self {
+            BlockCache::Temp { file, block_size } => (file, *block_size),
+            BlockCache::Persistent(entry) => return entry.get(block),
+        };
+        cache.seek(SeekFrom::Start(block * (block_size + 1)))?;
+        let status = cache.read_u8().unwrap_or(0);
+        if status == 1 {
+            let mut bytes = vec![0u8; block_size as usize];
+            cache.read_exact(&mut bytes)?;
+            return Ok(Some(bytes));
+        } else if status == 2 {
+            let bytes_available = cache.read_u64::<BigEndian>()?;
+            let mut bytes = vec![0u8; bytes_available as usize];
+            cache.read_exact(&mut bytes)?;
+            return Ok(Some(bytes));
+        }
+        Ok(None)
+    }
+
+    fn contains(&mut self, block: u64) -> io::Result<bool> {
+        let (cache, block_size) = match self {
+            BlockCache::Temp { file, block_size } => (file, *block_size),
+            BlockCache::Persistent(entry) => return entry.contains(block),
+        };
+        cache.seek(SeekFrom::Start(block * (block_size + 1)))?;
+        Ok(cache.read_u8().unwrap_or(0) != 0)
+    }
+
+    fn put(&mut self, block: u64, block_data: &[u8]) -> io::Result<()> {
+        let (cache, block_size) = match self {
+            BlockCache::Temp { file, block_size } => (file, *block_size),
+            BlockCache::Persistent(entry) => return entry.put(block, block_data),
+        };
+        cache.seek(SeekFrom::Start(block * (block_size + 1)))?;
+        if block_data.len() as u64 == block_size {
+            cache.write_u8(1)?;
+        } else {
+            cache.write_u8(2)?;
+            cache.write_u64::<BigEndian>(block_data.len() as u64)?;
+        }
+        cache.write_all(block_data)
+    }
+}
+
+/// A file read over the network (or from any other source implementing
+/// `RangeFetcher`), in blocks (of `READ_SIZE` bytes by default) that are
+/// cached after they are first read.
+///
+/// When reads are sequential, increasingly more data is read ahead of the
+/// current position (up to `DEFAULT_MAX_READ_AHEAD` bytes by default), so
+/// that reading large parts of a file doesn't take a request per block.
+pub struct RemoteFile<F: RangeFetcher = HttpFetcher> {
+    fetcher: F,
+    block_size: u64,
+    max_read_ahead: u64,
+    /// The number of blocks currently read ahead
+    read_ahead: u64,
+    /// The block after the last block fetched, where the next fetch starts if
+    /// reads are sequential
+    next_block: Option<u64>,
     current_position: u64,
     current: Option<(u64, Cursor<Vec<u8>>)>,
-    cache: Option<File>,
+    remote_cache: Option<RemoteCache>,
+    cache: Option<BlockCache>,
 }
 
 impl RemoteFile {
+    /// Creates a `RemoteFile` for the http(s) `url`, using the default
+    /// `HttpFetcher` settings.
     pub fn new(url: &str) -> RemoteFile {
+        RemoteFile::with_fetcher(HttpFetcher::new(url))
+    }
+}
+
+impl<F: RangeFetcher> RemoteFile<F> {
+    /// Creates a `RemoteFile` that gets its data from `fetcher`.
+    pub fn with_fetcher(fetcher: F) -> RemoteFile<F> {
         RemoteFile {
-            url: url.to_string(),
+            fetcher,
+            block_size: READ_SIZE as u64,
+            max_read_ahead: DEFAULT_MAX_READ_AHEAD,
+            read_ahead: 0,
+            next_block: None,
             current_position: 0,
             current: None,
+            remote_cache: None,
             cache: None,
         }
     }
+
+    /// Caches the data of this file in `cache`, so that it can be reused
+    /// between runs. Before cached data is used, the `ETag` and
+    /// `Last-Modified` headers of the file are checked to make sure that it
+    /// hasn't changed. If the server doesn't provide either, data is only
+    /// cached temporarily, like without a `RemoteCache`.
+    pub fn with_cache(self, cache: RemoteCache) -> RemoteFile<F> {
+        RemoteFile {
+            remote_cache: Some(cache),
+            cache: None,
+            current: None,
+            ..self
+        }
+    }
+
+    /// Reads (and caches) this file in blocks of `block_size` bytes. Larger
+    /// blocks mean fewer requests, but more data read that may not be needed.
+    /// Data cached in a `RemoteCache` with a different block size is not
+    /// reused.
+    ///
+    /// Panics if `block_size` is `0`.
+    pub fn with_block_size(self, block_size: usize) -> RemoteFile<F> {
+        assert!(block_size > 0, "Block size must be greater than 0");
+        RemoteFile {
+            block_size: block_size as u64,
+            cache: None,
+            current: None,
+            ..self
+        }
+    }
+
+    /// Reads at most `max_read_ahead` bytes ahead of sequential reads. `0`
+    /// disables reading ahead.
+    pub fn with_max_read_ahead(self, max_read_ahead: u64) -> RemoteFile<F> {
+        RemoteFile {
+            max_read_ahead,
+            read_ahead: 0,
+            ..self
+        }
+    }
+
+    pub fn fetcher(&self) -> &F {
+        &self.fetcher
+    }
+
+    fn cache(&mut self) -> io::Result<&mut BlockCache> {
+        if self.cache.is_none() {
+            let entry = match &self.remote_cache {
+                Some(remote_cache) => match self.fetcher.validators()? {
+                    Some(validators) => {
+                        Some(remote_cache.open(self.fetcher.url(), &validators, self.block_size)?)
+                    }
+                    None => None,
+                },
+                None => None,
+            };
+            self.cache = Some(match entry {
+                Some(entry) => BlockCache::Persistent(entry),
+                None => BlockCache::Temp {
+                    file: tempfile::tempfile()?,
+                    block_size: self.block_size,
+                },
+            });
+        }
+        Ok(self.cache.as_mut().unwrap())
+    }
 }
 
-impl RemoteFile {
+impl<F: RangeFetcher> RemoteFile<F> {
     fn read_current_block(&mut self, read_size: u64) -> io::Result<u64> {
-        let block = self.current_position / READ_SIZE as u64;
-        let block_start = block * READ_SIZE as u64;
-        let cache_block_start = block * (READ_SIZE as u64 + 1);
-        let cache = match self.cache.as_mut() {
-            None => {
-                self.cache = Some(tempfile::tempfile()?);
-                self.cache.as_mut().unwrap()
-            }
-            Some(cache) => cache,
-        };
-        use byteorder::ReadBytesExt;
-        use byteorder::WriteBytesExt;
-        cache.seek(SeekFrom::Start(cache_block_start))?;
-        let status = cache.read_u8().unwrap_or(0);
-        if status == 1 {
-            let mut bytes = vec![0u8; READ_SIZE];
-            cache.read_exact(&mut bytes)?;
-            self.current = Some((block_start, Cursor::new(bytes.to_vec())));
-            return Ok(READ_SIZE as u64);
-        } else if status == 2 {
-            let bytes_available = cache.read_u64::<byteorder::BigEndian>()?;
-            let mut bytes = vec![0u8; bytes_available as usize];
-            cache.read_exact(&mut bytes)?;
-            self.current = Some((block_start, Cursor::new(bytes.to_vec())));
-            return Ok(bytes_available);
+        let block_size = self.block_size;
+        let block = self.current_position / block_size;
+        let block_start = block * block_size;
+        if let Some(bytes) = self.cache()?.get(block)? {
+            let len = bytes.len() as u64;
+            self.current = Some((block_start, Cursor::new(bytes)));
+            return Ok(len);
         }
 
-        let read_len = {
-            let cur_pos = self.current_position;
-            let block = cur_pos / (READ_SIZE as u64);
-            let block_start = block * (READ_SIZE as u64);
-            let blocks_to_read = (cur_pos - block_start + read_size - 1) / (READ_SIZE as u64) + 1;
-            blocks_to_read * (READ_SIZE as u64)
-        };
-
-        let resp = attohttpc::get(&self.url)
-            .header(
-                "range",
-                format!(
-                    "bytes={}-{}",
-                    block_start,
-                    block_start + read_len as u64 - 1
-                ),
-            )
-            .send()?;
-        let bytes = if resp.is_success() {
-            resp.bytes()?
+        let blocks_needed =
+            (self.current_position - block_start + read_size.max(1)).div_ceil(block_size);
+        // Grow the read ahead when this read starts where the last one ended,
+        // otherwise stop reading ahead
+        self.read_ahead = if self.next_block == Some(block) {
+            (self.read_ahead * 2)
+                .max(1)
+                .min(self.max_read_ahead / block_size)
         } else {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                "Unable to connect to server to receive file.".to_string(),
-            ));
+            0
         };
-        cache.seek(SeekFrom::Start(cache_block_start))?;
-        let blocks_to_write = if bytes.len() == read_len as usize {
-            bytes.len() / READ_SIZE
+        // Don't read ahead into blocks that are already cached
+        let max_blocks = blocks_needed + self.read_ahead;
+        let mut blocks_to_read = blocks_needed;
+        let cache = self.cache()?;
+        while blocks_to_read < max_blocks && !cache.contains(block + blocks_to_read)? {
+            blocks_to_read += 1;
+        }
+        let read_len = blocks_to_read * block_size;
+
+        let bytes = self.fetcher.fetch(block_start, read_len)?;
+        let blocks_to_write = if bytes.len() as u64 == read_len {
+            blocks_to_read
         } else {
-            (bytes.len() + READ_SIZE - 1) / READ_SIZE
+            (bytes.len() as u64).div_ceil(block_size)
         };
+        let cache = self.cache()?;
         for start in 0..blocks_to_write {
-            let begin = start * READ_SIZE;
-            let end = ((start + 1) * READ_SIZE).min(bytes.len());
-            let block_data = &bytes[begin..end];
-            if block_data.len() == READ_SIZE {
-                cache.write_u8(1)?;
-            } else {
-                cache.write_u8(2)?;
-                cache.write_u64::<byteorder::BigEndian>(block_data.len() as u64)?;
-            }
-            cache.write_all(block_data)?;
+            let begin = (start * block_size) as usize;
+            let end = (((start + 1) * block_size) as usize).min(bytes.len());
+            cache.put(block + start, &bytes[begin..end])?;
         }
+        self.next_block = Some(block + blocks_to_write);
         let len = bytes.len() as u64;
         self.current = Some((block_start, Cursor::new(bytes)));
         Ok(len)
     }
 }
 
-impl Read for RemoteFile {
+impl<F: RangeFetcher> Read for RemoteFile<F> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         let mut remaining_buf = buf;
         let mut total_read = 0;
@@ -125,7 +248,7 @@ impl Read for RemoteFile {
             // 4) Whatever is left in the current memory is leftover from a
             //    a previous read, and it's not enough.
             let reset_cursor = |this: &mut Self| -> io::Result<u64> {
-                let cursor_start = (this.current_position / READ_SIZE as u64) * READ_SIZE as u64;
+                let cursor_start = (this.current_position / this.block_size) * this.block_size;
                 let in_block = this.current_position - cursor_start;
                 // If we not at the start of the block, then the length that we need
                 // is longer than the length of the buf itself, since we have to
@@ -163,9 +286,9 @@ impl Read for RemoteFile {
             if read == 0 || read == remaining_buf.len() || read == bytes_available as usize {
                 break;
             }
-            let cursor_start = (self.current_position / READ_SIZE as u64) * READ_SIZE as u64;
+            let cursor_start = (self.current_position / self.block_size) * self.block_size;
             let in_block = self.current_position - cursor_start;
-            let remaining_in_block = READ_SIZE - in_block as usize;
+            let remaining_in_block = (self.block_size - in_block) as usize;
             // If we didn't read everything, we *must* have at least read until
             // the end of the block
             assert!(read >= remaining_in_block);
@@ -175,7 +298,7 @@ impl Read for RemoteFile {
     }
 }
 
-impl Seek for RemoteFile {
+impl<F: RangeFetcher> Seek for RemoteFile<F> {
     fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
         self.current_position = match pos {
             SeekFrom::Start(s) => s,
@@ -192,7 +315,7 @@ impl Seek for RemoteFile {
             }
         };
         if let Some((cursor_start, cursor)) = self.current.as_mut() {
-            let cursor_end = *cursor_start + READ_SIZE as u64;
+            let cursor_end = *cursor_start + cursor.get_ref().len() as u64;
             if *cursor_start <= self.current_position && self.current_position < cursor_end {
                 let new_position = self.current_position - *cursor_start;
                 cursor.set_position(new_position);
@@ -204,23 +327,33 @@ impl Seek for RemoteFile {
     }
 }
 
-impl Clone for RemoteFile {
+impl<F: RangeFetcher + Clone> Clone for RemoteFile<F> {
     fn clone(&self) -> Self {
         RemoteFile {
-            url: self.url.clone(),
+            fetcher: self.fetcher.clone(),
+            block_size: self.block_size,
+            max_read_ahead: self.max_read_ahead,
+            read_ahead: 0,
+            next_block: None,
             current_position: 0,
             current: None,
+            remote_cache: self.remote_cache.clone(),
             cache: None,
         }
     }
 }
 
-impl Reopen for RemoteFile {
-    fn reopen(&self) -> io::Result<RemoteFile> {
+impl<F: RangeFetcher + Clone> Reopen for RemoteFile<F> {
+    fn reopen(&self) -> io::Result<RemoteFile<F>> {
         Ok(RemoteFile {
-            url: self.url.clone(),
+            fetcher: self.fetcher.clone(),
+            block_size: self.block_size,
+            max_read_ahead: self.max_read_ahead,
+            read_ahead: 0,
+            next_block: None,
             current_position: 0,
             current: None,
+            remote_cache: self.remote_cache.clone(),
             cache: None,
         })
     }
@@ -1,5 +1,5 @@
 use std::fs::File;
-use std::io::{self, Seek, Write};
+use std::io::{self, Cursor, Read, Seek, Write};
 use std::marker::Send;
 use std::sync::{Arc, Condvar, Mutex};
 
@@ -120,6 +120,28 @@ impl<R: Write + Send + 'static> TempFileBuffer<R> {
         }
     }
 
+    /// Waits for the writer to be dropped, then returns a reader over all the
+    /// data written to it. Panics if `switch` was called.
+    pub fn into_reader(self) -> io::Result<Box<dyn Read + Send>> {
+        let (lock, cvar) = &*self.closed;
+        let mut closed = lock.lock().unwrap();
+
+        while closed.is_none() {
+            closed = cvar.wait(closed).unwrap();
+        }
+        let closed = closed.take().unwrap();
+
+        match closed {
+            BufferState::Temp(mut closed_file) => {
+                closed_file.seek(io::SeekFrom::Start(0))?;
+                Ok(Box::new(closed_file))
+            }
+            BufferState::InMemory(data) => Ok(Box::new(Cursor::new(data))),
+            BufferState::NotStarted => Ok(Box::new(io::empty())),
+            BufferState::Real(_) => panic!("Should not have switched to the real file."),
+        }
+    }
+
     pub fn expect_closed_write<O>(self, mut real: &mut O) -> io::Result<()>
     where
         O: Write,
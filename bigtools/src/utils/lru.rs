@@ -0,0 +1,130 @@
+//! A least-recently-used cache bounded by the total size (in bytes) of its
+//! values, rather than by the number of entries.
+
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+struct LruEntry<V> {
+    value: V,
+    size: usize,
+    last_used: u64,
+}
+
+pub(crate) struct Lru<K, V> {
+    capacity: usize,
+    size: usize,
+    tick: u64,
+    entries: HashMap<K, LruEntry<V>>,
+    // Maps the last time an entry was used to its key, so the least-recently
+    // used entry is always first.
+    order: BTreeMap<u64, K>,
+}
+
+impl<K: Hash + Eq + Clone, V> Lru<K, V> {
+    /// Creates an empty cache that holds at most `capacity` bytes.
+    pub(crate) fn new(capacity: usize) -> Self {
+        Lru {
+            capacity,
+            size: 0,
+            tick: 0,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+        }
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The total size of all values currently in the cache.
+    pub(crate) fn size(&self) -> usize {
+        self.size
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Gets the value for `key`, marking it as the most-recently used.
+    pub(crate) fn get(&mut self, key: &K) -> Option<&V> {
+        let entry = self.entries.get_mut(key)?;
+        self.tick += 1;
+        self.order.remove(&entry.last_used);
+        self.order.insert(self.tick, key.clone());
+        entry.last_used = self.tick;
+        Some(&entry.value)
+    }
+
+    /// Inserts `value` with the given `size`, evicting the least-recently used
+    /// entries until it fits. Values larger than the capacity are not cached.
+    pub(crate) fn insert(&mut self, key: K, value: V, size: usize) {
+        self.remove(&key);
+        if size > self.capacity {
+            return;
+        }
+        while self.size + size > self.capacity {
+            let Some((_, oldest)) = self.order.pop_first() else {
+                break;
+            };
+            if let Some(entry) = self.entries.remove(&oldest) {
+                self.size -= entry.size;
+            }
+        }
+        self.tick += 1;
+        self.order.insert(self.tick, key.clone());
+        self.size += size;
+        self.entries.insert(
+            key,
+            LruEntry {
+                value,
+                size,
+                last_used: self.tick,
+            },
+        );
+    }
+
+    fn remove(&mut self, key: &K) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.order.remove(&entry.last_used);
+            self.size -= entry.size;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lru() {
+        let mut lru = Lru::new(10);
+        lru.insert(1, "a", 4);
+        lru.insert(2, "b", 4);
+        assert_eq!(lru.size(), 8);
+
+        // 1 is now more recently used than 2, so 2 is evicted
+        assert_eq!(lru.get(&1), Some(&"a"));
+        lru.insert(3, "c", 4);
+        assert_eq!(lru.get(&2), None);
+        assert_eq!(lru.get(&1), Some(&"a"));
+        assert_eq!(lru.get(&3), Some(&"c"));
+        assert_eq!(lru.len(), 2);
+        assert_eq!(lru.size(), 8);
+
+        // Replacing a value updates the size
+        lru.insert(3, "d", 2);
+        assert_eq!(lru.size(), 6);
+        assert_eq!(lru.get(&3), Some(&"d"));
+
+        // Values larger than the capacity are never cached
+        lru.insert(4, "e", 11);
+        assert_eq!(lru.get(&4), None);
+        assert_eq!(lru.len(), 2);
+
+        // A large value can evict everything else
+        lru.insert(5, "f", 10);
+        assert_eq!(lru.len(), 1);
+        assert_eq!(lru.size(), 10);
+        assert_eq!(lru.get(&5), Some(&"f"));
+    }
+}
@@ -1,6 +1,10 @@
+#[cfg(feature = "read")]
+pub mod chromsizes;
 pub mod file;
 pub mod fill;
 pub mod idmap;
+#[cfg(feature = "read")]
+pub(crate) mod lru;
 pub mod merge;
 #[cfg(feature = "read")]
 pub mod misc;
@@ -70,6 +70,623 @@ fn bigbedwrite_test() -> Result<(), Box<dyn Error>> {
     assert_eq!(first.start, first_interval.start);
     assert_eq!(first.end, first_interval.end);
     assert_eq!(first.rest, first_interval.rest);
+    drop(intervals);
+
+    let expected: Vec<_> = bwread
+        .get_interval("chr17", 0, 83257441)?
+        .collect::<Result<_, _>>()?;
+    #[cfg(feature = "async")]
+    {
+        let runtime = runtime::Builder::new_current_thread()
+            .build()
+            .expect("Unable to create runtime.");
+        let entries: Vec<_> = runtime.block_on(async {
+            use futures::TryStreamExt;
+
+            let mut bbread = bigtools::AsyncBigBedRead::open_file(tempfile.path()).await?;
+            let entries = bbread
+                .get_interval("chr17", 0, 83257441)
+                .await?
+                .try_collect()
+                .await?;
+            Ok::<_, Box<dyn Error>>(entries)
+        })?;
+        assert_eq!(entries, expected);
+    }
+
+    let runtime = runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .build()
+        .expect("Unable to create runtime.");
+    let entries: Vec<_> = bwread
+        .get_interval_parallel("chr17", 0, 83257441, &runtime)?
+        .collect::<Result<_, _>>()?;
+    assert_eq!(entries, expected);
+
+    let regions = [("chr17", 0, 83257441), ("chr17", 0, first.end)];
+    let entries = bwread.get_intervals(regions)?;
+    assert_eq!(entries[0], expected);
+    assert_eq!(entries[1][0], first);
+
+    Ok(())
+}
+
+#[test]
+fn bigbedwrite_extra_index() -> Result<(), Box<dyn Error>> {
+    use std::collections::HashMap;
+
+    use bigtools::{BedEntry, BigBedRead, BigBedWrite};
+
+    let entries: Vec<(String, BedEntry)> = ["chr1", "chr2", "chr3"]
+        .into_iter()
+        .flat_map(|chrom| {
+            (0..500u32).map(move |i| {
+                let entry = BedEntry {
+                    start: i * 10 + 1,
+                    end: i * 10 + 15,
+                    rest: format!("gene{}\t{}", i % 150, i),
+                };
+                (chrom.to_string(), entry)
+            })
+        })
+        .collect();
+
+    let runtime = runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .build()
+        .expect("Unable to create runtime.");
+
+    let tempfile = tempfile::NamedTempFile::new()?;
+
+    let chrom_map = HashMap::from([
+        ("chr1".to_string(), 10_000),
+        ("chr2".to_string(), 10_000),
+        ("chr3".to_string(), 10_000),
+    ]);
+
+    let mut outb = BigBedWrite::create_file(tempfile.path(), chrom_map)?;
+    outb.autosql = Some(bigtools::bed::autosql::bed_autosql(&entries[0].1.rest));
+    outb.extra_indices = vec!["name".to_string()];
+    // Small blocks, so that both the data and the index are split
+    outb.options.items_per_slot = 16;
+    outb.options.block_size = 4;
+
+    let data = BedParserStreamingIterator::wrap_infallible_iter(entries.clone().into_iter(), false);
+    outb.write(data, runtime)?;
+
+    let mut bbread = BigBedRead::open_file(tempfile.path())?;
+    assert_eq!(bbread.extra_indices()?, vec!["name".to_string()]);
+
+    for name in ["gene0", "gene42", "gene149"] {
+        let mut found = bbread.search_extra_index("name", name)?;
+        found.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.start.cmp(&b.1.start)));
+        let expected: Vec<_> = entries
+            .iter()
+            .filter(|(_, e)| e.rest.split('\t').next() == Some(name))
+            .cloned()
+            .collect();
+        assert!(!expected.is_empty());
+        assert_eq!(found, expected);
+    }
+
+    assert!(bbread.search_extra_index("name", "gene150")?.is_empty());
+    assert_eq!(bbread.search_extra_index("name", "gene1")?.len(), 12);
+    assert!(bbread.search_extra_index("score", "gene1").is_err());
+
+    // The in-memory and memory-mapped readers read the same entries
+    let bytes = bytes::Bytes::from(std::fs::read(tempfile.path())?);
+    let mut memread = BigBedRead::from_bytes(bytes)?;
+    #[cfg(feature = "mmap")]
+    let mut mmapread = unsafe { BigBedRead::open_mmap(tempfile.path())? };
+    for chrom in ["chr1", "chr2", "chr3"] {
+        let expected: Vec<_> = bbread
+            .get_interval(chrom, 0, 10_000)?
+            .collect::<Result<_, _>>()?;
+        let mem: Vec<_> = memread
+            .get_interval(chrom, 0, 10_000)?
+            .collect::<Result<_, _>>()?;
+        assert_eq!(mem, expected);
+        #[cfg(feature = "mmap")]
+        {
+            let mmap: Vec<_> = mmapread
+                .get_interval(chrom, 0, 10_000)?
+                .collect::<Result<_, _>>()?;
+            assert_eq!(mmap, expected);
+        }
+    }
+    assert_eq!(memread.search_extra_index("name", "gene1")?.len(), 12);
+    #[cfg(feature = "mmap")]
+    assert_eq!(mmapread.search_extra_index("name", "gene1")?.len(), 12);
+
+    let shared = bigtools::SharedBigBedRead::open_file(tempfile.path())?;
+    for chrom in ["chr1", "chr2", "chr3"] {
+        let expected: Vec<_> = bbread
+            .get_interval(chrom, 2_000, 4_000)?
+            .collect::<Result<_, _>>()?;
+        let entries: Vec<_> = shared
+            .get_interval(chrom, 2_000, 4_000)?
+            .collect::<Result<_, _>>()?;
+        assert_eq!(entries, expected);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn bigbedwrite_typed() -> Result<(), Box<dyn Error>> {
+    use std::collections::HashMap;
+
+    use bigtools::bed::autosql::typed::FieldValue;
+    use bigtools::{BedEntry, BigBedRead, BigBedWrite};
+
+    let entries: Vec<(String, BedEntry)> = (0..100u32)
+        .map(|i| {
+            let start = i * 100;
+            let entry = BedEntry {
+                start,
+                end: start + 90,
+                rest: format!(
+                    "gene{}\t{}\t{}\t{}\t{}\t0,0,255\t2\t10,20,\t0,70,",
+                    i,
+                    i * 10,
+                    if i % 2 == 0 { "+" } else { "-" },
+                    start + 5,
+                    start + 85,
+                ),
+            };
+            ("chr1".to_string(), entry)
+        })
+        .collect();
+
+    let runtime = runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .build()
+        .expect("Unable to create runtime.");
+
+    let tempfile = tempfile::NamedTempFile::new()?;
+    let chrom_map = HashMap::from([("chr1".to_string(), 10_000)]);
+    let mut outb = BigBedWrite::create_file(tempfile.path(), chrom_map)?;
+    outb.autosql = Some(bigtools::bed::autosql::bed_autosql(&entries[0].1.rest));
+    let data = BedParserStreamingIterator::wrap_infallible_iter(entries.clone().into_iter(), false);
+    outb.write(data, runtime)?;
+
+    let mut bbread = BigBedRead::open_file(tempfile.path())?;
+    let schema = bbread.schema()?;
+    assert_eq!(schema.fields().len(), 12);
+    assert_eq!(schema.rest_fields()[0].name, "name");
+
+    let typed: Vec<_> = bbread
+        .get_interval_typed("chr1", 0, 10_000)?
+        .collect::<Result<_, _>>()?;
+    assert_eq!(typed.len(), entries.len());
+    for (entry, (_, expected)) in typed.iter().zip(entries.iter()) {
+        assert_eq!(entry.start, expected.start);
+        assert_eq!(entry.end, expected.end);
+        assert_eq!(entry.errors().count(), 0);
+    }
+    let third = &typed[3];
+    assert_eq!(
+        third.get("name"),
+        Some(&Ok(FieldValue::String("gene3".to_string())))
+    );
+    assert_eq!(third.get("score"), Some(&Ok(FieldValue::Uint(30))));
+    assert_eq!(
+        third.get("strand"),
+        Some(&Ok(FieldValue::String("-".to_string())))
+    );
+    assert_eq!(third.get("reserved"), Some(&Ok(FieldValue::Uint(255))));
+    assert_eq!(
+        third.get("blockSizes"),
+        Some(&Ok(FieldValue::Array(vec![
+            FieldValue::Int(10),
+            FieldValue::Int(20)
+        ])))
+    );
+    assert_eq!(third.get("missing"), None);
+
+    Ok(())
+}
+
+#[test]
+fn bigbedwrite_validated() -> Result<(), Box<dyn Error>> {
+    use std::collections::HashMap;
+
+    use bigtools::bed::autosql::typed::BedSchema;
+    use bigtools::bed::autosql::validate::{BedType, BedValidator};
+    use bigtools::bed::bedparser::ValidatingBedStream;
+    use bigtools::{BBIProcessError, BigBedRead, BigBedWrite};
+
+    let autosql = r#"
+    table peaks
+    "Peaks with a strand and a category"
+        (
+        string chrom; "Chromosome"
+        uint chromStart; "Start"
+        uint chromEnd; "End"
+        string name; "Name"
+        uint score; "Score"
+        char[1] strand; "Strand"
+        enum(promoter, enhancer) category; "Category"
+        )
+    "#;
+    let validator = BedValidator::new(
+        BedSchema::from_autosql(autosql)?,
+        Some("bed6+1".parse::<BedType>()?),
+    )?;
+    let chrom_map = HashMap::from([("chr1".to_string(), 10_000)]);
+
+    let write = |bed: &'static str| -> Result<tempfile::NamedTempFile, BBIProcessError<_>> {
+        let runtime = runtime::Builder::new_current_thread()
+            .build()
+            .expect("Unable to create runtime.");
+        let tempfile = tempfile::NamedTempFile::new()?;
+        let mut outb = BigBedWrite::create_file(tempfile.path(), chrom_map.clone())?;
+        outb.autosql = Some(autosql.to_string());
+        let stream = ValidatingBedStream::new(
+            BedFileStream::from_bed_file(bed.as_bytes()),
+            validator.clone(),
+        );
+        let data = BedParserStreamingIterator::new(stream, false);
+        outb.write(data, runtime)?;
+        Ok(tempfile)
+    };
+
+    let tempfile = write(
+        "chr1\t10\t20\tp1\t100\t+\tpromoter\n\
+         chr1\t30\t40\tp2\t200\t.\tenhancer\n",
+    )?;
+    let mut bbread = BigBedRead::open_file(tempfile.path())?;
+    assert_eq!(bbread.get_interval("chr1", 0, 100)?.count(), 2);
+
+    let err = |bed: &'static str| match write(bed) {
+        Err(BBIProcessError::SourceError(e)) => e.to_string(),
+        Err(e) => panic!("Unexpected error: {}", e),
+        Ok(_) => panic!("Expected an invalid bed."),
+    };
+    let msg = err("chr1\t10\t20\tp1\t100\t+\tpromoter\n\
+                   chr1\t30\t40\tp2\t200\t.\tsilencer\n");
+    assert!(
+        msg.starts_with("Invalid bed at line 2 (chr1:30-40):"),
+        "{}",
+        msg
+    );
+    assert!(msg.contains("category"), "{}", msg);
+    let msg = err("chr1\t10\t20\tp1\t100\t+\n");
+    assert!(msg.starts_with("Invalid bed at line 1"), "{}", msg);
+    let msg = err("chr1\t10\t20\tp1\t100\t+\tpromoter\n\
+                   chr1\t30\t40\tp2\t200\tx\tpromoter\n");
+    assert!(msg.contains("strand"), "{}", msg);
+
+    Ok(())
+}
+
+#[test]
+fn bigbedwrite_validator_option() -> Result<(), Box<dyn Error>> {
+    use std::collections::HashMap;
+
+    use bigtools::bed::autosql::typed::BedSchema;
+    use bigtools::bed::autosql::validate::{BedType, BedValidator};
+    use bigtools::{BBIProcessError, BedEntry, BigBedRead, BigBedWrite};
+
+    let autosql = bigtools::bed::autosql::bed_autosql("name\t0\t+");
+    let validator = BedValidator::new(
+        BedSchema::from_autosql(&autosql)?,
+        Some("bed6".parse::<BedType>()?),
+    )?;
+    let chrom_map = HashMap::from([("chr1".to_string(), 10_000), ("chr2".to_string(), 10_000)]);
+
+    // Validation happens in the writer, so any source of values can be used
+    let write = |strand: &str| -> Result<tempfile::NamedTempFile, BBIProcessError<_>> {
+        let entries: Vec<(String, BedEntry)> = ["chr1", "chr2"]
+            .into_iter()
+            .flat_map(|chrom| {
+                (0..10u32).map(move |i| {
+                    let bad = chrom == "chr1" && i == 7;
+                    let strand = if bad { strand } else { "+" };
+                    let entry = BedEntry {
+                        start: i * 100,
+                        end: i * 100 + 50,
+                        rest: format!("item{}\t{}\t{}", i, i, strand),
+                    };
+                    (chrom.to_string(), entry)
+                })
+            })
+            .collect();
+        let runtime = runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .build()
+            .expect("Unable to create runtime.");
+        let tempfile = tempfile::NamedTempFile::new()?;
+        let mut outb = BigBedWrite::create_file(tempfile.path(), chrom_map.clone())?;
+        outb.autosql = Some(autosql.clone());
+        outb.validator = Some(validator.clone());
+        let data = BedParserStreamingIterator::wrap_infallible_iter(entries.into_iter(), false);
+        outb.write(data, runtime)?;
+        Ok(tempfile)
+    };
+
+    let tempfile = write("-")?;
+    let mut bbread = BigBedRead::open_file(tempfile.path())?;
+    assert_eq!(bbread.get_interval("chr2", 0, 10_000)?.count(), 10);
+
+    let msg = match write("x") {
+        Err(BBIProcessError::InvalidInput(msg)) => msg,
+        Err(e) => panic!("Unexpected error: {}", e),
+        Ok(_) => panic!("Expected an invalid bed."),
+    };
+    assert!(msg.contains("Invalid bed (chr1:700-750)"), "{}", msg);
+    assert!(msg.contains("strand"), "{}", msg);
+
+    Ok(())
+}
+
+#[test]
+fn bigbedwrite_chrom_alias_sizes() -> Result<(), Box<dyn Error>> {
+    use std::collections::HashMap;
+
+    use bigtools::utils::chromsizes::{read_chrom_sizes, ChromSizesFormat};
+    use bigtools::{BedEntry, BigBedWrite};
+
+    // A chromAlias bigBed has one entry per chromosome, listing its aliases
+    let chrom_map = HashMap::from([("chr1".to_string(), 1_000), ("chrM".to_string(), 16_569)]);
+    let entries = vec![
+        (
+            "chr1",
+            BedEntry {
+                start: 0,
+                end: 1_000,
+                rest: "1\tCM000663.2".to_string(),
+            },
+        ),
+        (
+            "chrM",
+            BedEntry {
+                start: 0,
+                end: 16_569,
+                rest: "MT\t".to_string(),
+            },
+        ),
+    ];
+
+    let runtime = runtime::Builder::new_current_thread()
+        .build()
+        .expect("Unable to create runtime.");
+
+    let tempfile = tempfile::NamedTempFile::new()?;
+    let outb = BigBedWrite::create_file(tempfile.path(), chrom_map.clone())?;
+    let data = BedParserStreamingIterator::wrap_infallible_iter(entries.into_iter(), false);
+    outb.write(data, runtime)?;
+
+    let path = tempfile.path().to_str().unwrap();
+    assert_eq!(read_chrom_sizes(path, ChromSizesFormat::Bbi)?, chrom_map);
+
+    let sizes = read_chrom_sizes(path, ChromSizesFormat::ChromAliasBigBed)?;
+    let expected = HashMap::from([
+        ("chr1".to_string(), 1_000),
+        ("1".to_string(), 1_000),
+        ("CM000663.2".to_string(), 1_000),
+        ("chrM".to_string(), 16_569),
+        ("MT".to_string(), 16_569),
+    ]);
+    assert_eq!(sizes, expected);
+
+    Ok(())
+}
+
+#[test]
+fn bigbedwrite_overlapping() -> Result<(), Box<dyn Error>> {
+    use std::collections::HashMap;
+    use std::fs::File;
+
+    use bigtools::validate::validate;
+    use bigtools::{BedEntry, BigBedRead, BigBedWrite};
+
+    // The first section's first item ends after its last item, and the
+    // second item of each section extends past the first
+    let entries: Vec<(String, BedEntry)> = [(0, 100), (10, 20), (150, 160), (155, 200)]
+        .into_iter()
+        .map(|(start, end)| {
+            let entry = BedEntry {
+                start,
+                end,
+                rest: String::new(),
+            };
+            ("chr1".to_string(), entry)
+        })
+        .collect();
+
+    let runtime = runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .build()
+        .expect("Unable to create runtime.");
+
+    let tempfile = tempfile::NamedTempFile::new()?;
+    let chrom_map = HashMap::from([("chr1".to_string(), 10_000)]);
+    let mut outb = BigBedWrite::create_file(tempfile.path(), chrom_map)?;
+    outb.options.items_per_slot = 2;
+    outb.options.manual_zoom_sizes = Some(vec![10]);
+    let data = BedParserStreamingIterator::wrap_infallible_iter(entries.into_iter(), false);
+    outb.write(data, runtime)?;
+
+    let report = validate(File::open(tempfile.path())?)?;
+    assert!(report.is_valid(), "{:?}", report.problems);
+
+    // The section containing (0, 100) must be found by the index
+    let mut bbread = BigBedRead::open_file(tempfile.path())?;
+    let intervals: Vec<_> = bbread
+        .get_interval("chr1", 50, 60)?
+        .collect::<Result<_, _>>()?;
+    assert_eq!(intervals.len(), 1);
+    assert_eq!((intervals[0].start, intervals[0].end), (0, 100));
+
+    // Coverage is 1 for 0-10, 20-100, 150-155 and 160-200, and 2 for 10-20
+    // and 155-160
+    let summary = bbread.get_summary()?;
+    assert_eq!(summary.bases_covered, 150);
+    assert_eq!(summary.min_val, 1.0);
+    assert_eq!(summary.max_val, 2.0);
+    assert_eq!(summary.sum, 165.0);
+
+    let reduction_level = bbread.info().zoom_headers[0].reduction_level;
+    let zooms: Vec<_> = bbread
+        .get_zoom_interval("chr1", 0, 10_000, reduction_level)?
+        .collect::<Result<_, _>>()?;
+    let bases_covered: u64 = zooms.iter().map(|z| z.summary.bases_covered).sum();
+    let sum: f64 = zooms.iter().map(|z| z.summary.sum).sum();
+    assert_eq!(bases_covered, 150);
+    assert_eq!(sum, 165.0);
+
+    Ok(())
+}
+
+#[cfg(feature = "arrow")]
+#[test]
+fn bigbedwrite_record_batches() -> Result<(), Box<dyn Error>> {
+    use std::collections::HashMap;
+
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::{Float32Type, Int32Type, UInt32Type};
+    use arrow_array::RecordBatch;
+    use bigtools::bed::autosql::presets::Preset;
+    use bigtools::{BedEntry, BigBedRead, BigBedWrite};
+
+    /// The chrom, start, name, score, signalValue, and peak of each row
+    fn rows(batches: &[RecordBatch]) -> Vec<(String, u32, String, u32, f32, i32)> {
+        batches
+            .iter()
+            .flat_map(|b| {
+                (0..b.num_rows()).map(move |i| {
+                    (
+                        b.column(0).as_string::<i32>().value(i).to_string(),
+                        b.column(1).as_primitive::<UInt32Type>().value(i),
+                        b.column(3).as_string::<i32>().value(i).to_string(),
+                        b.column(4).as_primitive::<UInt32Type>().value(i),
+                        b.column(6).as_primitive::<Float32Type>().value(i),
+                        b.column(9).as_primitive::<Int32Type>().value(i),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    let entries: Vec<(String, BedEntry)> = ["chr1", "chr2"]
+        .into_iter()
+        .flat_map(|chrom| {
+            (0..25u32).map(move |i| {
+                let entry = BedEntry {
+                    start: i * 100,
+                    end: i * 100 + 50,
+                    rest: format!("peak{}\t{}\t.\t{}.5\t-1\t-1\t{}", i, i * 10, i, i),
+                };
+                (chrom.to_string(), entry)
+            })
+        })
+        .collect();
+
+    let runtime = runtime::Builder::new_current_thread()
+        .build()
+        .expect("Unable to create runtime.");
+    let tempfile = tempfile::NamedTempFile::new()?;
+    let chrom_map = HashMap::from([("chr1".to_string(), 10_000), ("chr2".to_string(), 10_000)]);
+    let mut outb = BigBedWrite::create_file(tempfile.path(), chrom_map)?;
+    outb.autosql = Some(Preset::NarrowPeak.autosql().to_string());
+    let data = BedParserStreamingIterator::wrap_infallible_iter(entries.clone().into_iter(), false);
+    outb.write(data, runtime)?;
+
+    let batches = BigBedRead::open_file(tempfile.path())?
+        .record_batches(7)?
+        .collect::<Result<Vec<_>, _>>()?;
+    assert!(batches.iter().all(|b| b.num_rows() <= 7));
+    let names: Vec<_> = batches[0]
+        .schema()
+        .fields()
+        .iter()
+        .map(|f| f.name().clone())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "chrom",
+            "chromStart",
+            "chromEnd",
+            "name",
+            "score",
+            "strand",
+            "signalValue",
+            "pValue",
+            "qValue",
+            "peak"
+        ]
+    );
+    let expected: Vec<_> = entries
+        .iter()
+        .map(|(chrom, entry)| {
+            let i = entry.start / 100;
+            let name = format!("peak{}", i);
+            (
+                chrom.clone(),
+                entry.start,
+                name,
+                i * 10,
+                i as f32 + 0.5,
+                i as i32,
+            )
+        })
+        .collect();
+    assert_eq!(rows(&batches), expected);
+
+    // Regions are read in the given order
+    let batches = BigBedRead::open_file(tempfile.path())?
+        .record_batches(2)?
+        .with_regions(vec![
+            ("chr2".to_string(), 1000, 1250),
+            ("chr1".to_string(), 0, 150),
+        ])
+        .collect::<Result<Vec<_>, _>>()?;
+    let starts: Vec<_> = rows(&batches)
+        .into_iter()
+        .map(|(chrom, start, ..)| (chrom, start))
+        .collect();
+    assert_eq!(
+        starts,
+        [
+            ("chr2".to_string(), 1000),
+            ("chr2".to_string(), 1100),
+            ("chr2".to_string(), 1200),
+            ("chr1".to_string(), 0),
+            ("chr1".to_string(), 100)
+        ]
+    );
+
+    // Round trip through Parquet, with a row group size separate from the
+    // batch size
+    #[cfg(feature = "cli")]
+    {
+        use bigtools::utils::cli::bigbedtoparquet::{bigbedtoparquet, BigBedToParquetArgs};
+        use bigtools::utils::cli::ParquetArgs;
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+        let parquet = tempfile::NamedTempFile::new()?;
+        bigbedtoparquet(BigBedToParquetArgs {
+            big_bed: tempfile.path().to_str().unwrap().to_string(),
+            parquet: parquet.path().to_str().unwrap().to_string(),
+            chrom: None,
+            chrom_alias: None,
+            start: None,
+            end: None,
+            parquet_args: ParquetArgs {
+                batch_size: 7,
+                row_group_size: 20,
+                compression: "zstd".to_string(),
+            },
+        })?;
+        let reader =
+            ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(parquet.path())?)?;
+        assert_eq!(reader.metadata().num_row_groups(), 3);
+        assert_eq!(reader.schema().fields().len(), names.len());
+        let batches = reader.build()?.collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(rows(&batches), expected);
+    }
 
     Ok(())
 }
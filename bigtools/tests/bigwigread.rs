@@ -77,3 +77,526 @@ fn test_reduction_values() -> Result<(), Box<dyn Error>> {
     assert_eq!(x.len(), 16);
     Ok(())
 }
+
+#[test]
+fn test_chrom_aliases() -> Result<(), Box<dyn Error>> {
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use bigtools::{BBIReadError, BigWigRead, ChromAliases};
+
+    let mut dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    dir.push("resources/test");
+
+    let mut valid_bigwig = dir.clone();
+    valid_bigwig.push("valid.bigWig");
+
+    let mut bwread = BigWigRead::open_file(&valid_bigwig).unwrap();
+    assert!(matches!(
+        bwread.get_interval("17", 0, 59899),
+        Err(BBIReadError::InvalidChromosome(_))
+    ));
+
+    let aliases = "# ucsc\tensembl\trefseq\nchr17\t17\tNC_000017.11\n";
+    let aliases = ChromAliases::from_txt(aliases.as_bytes())?;
+    let mut bwread = bwread.with_chrom_aliases(Arc::new(aliases));
+
+    let expected: Vec<_> = bwread
+        .get_interval("chr17", 0, 100000)?
+        .collect::<Result<_, _>>()?;
+    for chrom in ["17", "NC_000017.11"] {
+        let vals: Vec<_> = bwread
+            .get_interval(chrom, 0, 100000)?
+            .collect::<Result<_, _>>()?;
+        assert_eq!(vals, expected);
+        // Compare bits, since positions without data are NaN
+        let to_bits = |vals: Vec<f32>| vals.into_iter().map(f32::to_bits).collect::<Vec<_>>();
+        assert_eq!(
+            to_bits(bwread.values(chrom, 0, 59899)?),
+            to_bits(bwread.values("chr17", 0, 59899)?)
+        );
+    }
+    assert!(matches!(
+        bwread.get_interval("chr18", 0, 59899),
+        Err(BBIReadError::InvalidChromosome(_))
+    ));
+
+    Ok(())
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn test_async_read() -> Result<(), Box<dyn Error>> {
+    use std::path::PathBuf;
+
+    use futures::TryStreamExt;
+
+    use bigtools::{AsyncBigWigRead, BigWigRead};
+
+    let mut dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    dir.push("resources/test");
+
+    let mut valid_bigwig = dir.clone();
+    valid_bigwig.push("valid.bigWig");
+
+    let mut bwread = BigWigRead::open_file(&valid_bigwig).unwrap();
+    let expected: Vec<_> = bwread
+        .get_interval("chr17", 0, 83257441)?
+        .collect::<Result<_, _>>()?;
+    let expected_zoom: Vec<_> = bwread
+        .get_zoom_interval("chr17", 0, 36996442, 10240)?
+        .collect::<Result<_, _>>()?;
+    let expected_values = bwread.values("chr17", 0, 59899)?;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("Unable to create runtime.");
+    runtime.block_on(async {
+        let mut bwread = AsyncBigWigRead::open_file(&valid_bigwig).await?;
+        assert_eq!(bwread.chroms().len(), 1);
+        assert_eq!(bwread.chroms()[0].length, 83257441);
+
+        let vals: Vec<_> = bwread
+            .get_interval("chr17", 0, 83257441)
+            .await?
+            .try_collect()
+            .await?;
+        assert_eq!(vals, expected);
+
+        let zoom: Vec<_> = bwread
+            .get_zoom_interval("chr17", 0, 36996442, 10240)
+            .await?
+            .try_collect()
+            .await?;
+        assert_eq!(zoom.len(), expected_zoom.len());
+        for (a, b) in zoom.iter().zip(expected_zoom.iter()) {
+            assert_eq!((a.start, a.end), (b.start, b.end));
+            assert_eq!(a.summary.sum, b.summary.sum);
+        }
+
+        let values = bwread.values("chr17", 0, 59899).await?;
+        // Compare bits, since positions without data are NaN
+        let to_bits = |vals: &[f32]| vals.iter().map(|v| v.to_bits()).collect::<Vec<_>>();
+        assert_eq!(to_bits(&values), to_bits(&expected_values));
+
+        assert!(bwread.get_interval("chr18", 0, 100).await.is_err());
+
+        Ok(())
+    })
+}
+
+#[test]
+fn test_coalesced_reads() -> Result<(), Box<dyn Error>> {
+    use std::fs::File;
+    use std::io::{self, Read, Seek, SeekFrom};
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use bigtools::BigWigRead;
+
+    struct CountingRead {
+        file: File,
+        seeks: Arc<AtomicUsize>,
+    }
+    impl Read for CountingRead {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.file.read(buf)
+        }
+    }
+    impl Seek for CountingRead {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.seeks.fetch_add(1, Ordering::SeqCst);
+            self.file.seek(pos)
+        }
+    }
+
+    let mut dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    dir.push("resources/test");
+
+    let mut valid_bigwig = dir.clone();
+    valid_bigwig.push("valid.bigWig");
+
+    let mut interval_seeks = vec![];
+    let mut results = vec![];
+    for max_block_gap in [0, 1024 * 1024] {
+        let seeks = Arc::new(AtomicUsize::new(0));
+        let read = CountingRead {
+            file: File::open(&valid_bigwig)?,
+            seeks: seeks.clone(),
+        };
+        let mut bwread = BigWigRead::open(read)?.with_max_block_gap(max_block_gap);
+        // Read the index first, so only block reads are counted
+        bwread.get_interval("chr17", 0, 1)?.for_each(drop);
+        seeks.store(0, Ordering::SeqCst);
+        let vals: Vec<_> = bwread
+            .get_interval("chr17", 0, 83257441)?
+            .collect::<Result<_, _>>()?;
+        interval_seeks.push(seeks.load(Ordering::SeqCst));
+        results.push(vals);
+
+        let mut cached = bwread.cached();
+        let cached_vals: Vec<_> = cached
+            .get_interval("chr17", 0, 83257441)?
+            .collect::<Result<_, _>>()?;
+        assert_eq!(cached_vals, results[0]);
+    }
+    assert_eq!(results[0], results[1]);
+    assert!(interval_seeks[1] <= interval_seeks[0]);
+
+    Ok(())
+}
+
+#[test]
+fn test_parallel_read() -> Result<(), Box<dyn Error>> {
+    use std::path::PathBuf;
+
+    use bigtools::BigWigRead;
+    use tokio::runtime;
+
+    let mut dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    dir.push("resources/test");
+    dir.push("valid.bigWig");
+
+    let runtime = runtime::Builder::new_multi_thread()
+        .worker_threads(4)
+        .build()?;
+
+    let mut bwread = BigWigRead::open_file(&dir)?;
+    for (start, end) in [(0, 83257441), (59899, 1000000), (0, 10)] {
+        let expected = bwread
+            .get_interval("chr17", start, end)?
+            .collect::<Result<Vec<_>, _>>()?;
+        let parallel = bwread
+            .get_interval_parallel("chr17", start, end, &runtime)?
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(expected, parallel);
+    }
+
+    assert!(bwread
+        .get_interval_parallel("chrNone", 0, 10, &runtime)
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_cached_read() -> Result<(), Box<dyn Error>> {
+    use std::path::PathBuf;
+
+    use bigtools::utils::reopen::Reopen;
+    use bigtools::BigWigRead;
+
+    let mut dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    dir.push("resources/test");
+    dir.push("valid.bigWig");
+
+    let expected: Vec<_> = BigWigRead::open_file(&dir)?
+        .get_interval("chr17", 0, 83257441)?
+        .collect::<Result<_, _>>()?;
+
+    let mut bwread = BigWigRead::open_file(&dir)?.cached();
+    let vals: Vec<_> = bwread
+        .get_interval("chr17", 0, 83257441)?
+        .collect::<Result<_, _>>()?;
+    assert_eq!(vals, expected);
+    let stats = bwread.inner_read().cache_stats();
+    assert_eq!(stats.hits, 0);
+    assert!(stats.misses > 0);
+    assert!(stats.size > 0);
+
+    // A reopened reader shares the cache, so everything is a hit
+    let mut reopened = bwread.reopen()?;
+    let vals: Vec<_> = reopened
+        .get_interval("chr17", 0, 83257441)?
+        .collect::<Result<_, _>>()?;
+    assert_eq!(vals, expected);
+    let reopened_stats = reopened.inner_read().cache_stats();
+    assert_eq!(reopened_stats.misses, stats.misses);
+    assert_eq!(reopened_stats.hits, stats.misses);
+    assert_eq!(bwread.inner_read().cache_stats(), reopened_stats);
+
+    // The cache never grows beyond its capacity
+    let capacity = 16 * 1024;
+    let mut bwread = BigWigRead::open_file(&dir)?.cached_with_capacity(capacity);
+    for _ in 0..2 {
+        let vals: Vec<_> = bwread
+            .get_interval("chr17", 0, 83257441)?
+            .collect::<Result<_, _>>()?;
+        assert_eq!(vals, expected);
+        let stats = bwread.inner_read().cache_stats();
+        assert!(stats.size <= capacity);
+        assert_eq!(stats.capacity, capacity);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_in_memory_read() -> Result<(), Box<dyn Error>> {
+    use std::path::PathBuf;
+
+    use bigtools::utils::reopen::Reopen;
+    use bigtools::BigWigRead;
+
+    let mut dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    dir.push("resources/test");
+    dir.push("valid.bigWig");
+
+    let mut bwread = BigWigRead::open_file(&dir)?;
+    let expected: Vec<_> = bwread
+        .get_interval("chr17", 0, 83257441)?
+        .collect::<Result<_, _>>()?;
+    // Compare the bits of values, since regions without data are NaN
+    let values_bits = |values: Vec<f32>| values.into_iter().map(f32::to_bits).collect::<Vec<_>>();
+    let expected_values = values_bits(bwread.values("chr17", 0, 83257441)?);
+    let expected_zoom: Vec<_> = bwread
+        .get_zoom_interval("chr17", 0, 83257441, 10240)?
+        .collect::<Result<_, _>>()?;
+
+    let bytes = bytes::Bytes::from(std::fs::read(&dir)?);
+    let memread = BigWigRead::from_bytes(bytes)?;
+    assert_eq!(memread.chroms(), bwread.chroms());
+
+    for mut read in [memread.reopen()?, memread] {
+        let vals: Vec<_> = read
+            .get_interval("chr17", 0, 83257441)?
+            .collect::<Result<_, _>>()?;
+        assert_eq!(vals, expected);
+        assert!(values_bits(read.values("chr17", 0, 83257441)?) == expected_values);
+        let zoom: Vec<_> = read
+            .get_zoom_interval("chr17", 0, 83257441, 10240)?
+            .collect::<Result<_, _>>()?;
+        assert_eq!(zoom, expected_zoom);
+    }
+    #[cfg(feature = "mmap")]
+    {
+        let mmapread = unsafe { BigWigRead::open_mmap(&dir)? };
+        assert_eq!(mmapread.chroms(), bwread.chroms());
+        for mut read in [mmapread.reopen()?, mmapread] {
+            let vals: Vec<_> = read
+                .get_interval("chr17", 0, 83257441)?
+                .collect::<Result<_, _>>()?;
+            assert_eq!(vals, expected);
+            assert!(values_bits(read.values("chr17", 0, 83257441)?) == expected_values);
+        }
+    }
+
+    // Truncated data is an error, not a panic
+    let bytes = bytes::Bytes::from(std::fs::read(&dir)?);
+    let truncated = bytes.slice(..bytes.len() / 2);
+    let read_truncated = || -> Result<Vec<f32>, Box<dyn Error>> {
+        Ok(BigWigRead::from_bytes(truncated)?.values("chr17", 0, 83257441)?)
+    };
+    assert!(read_truncated().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_shared_read() -> Result<(), Box<dyn Error>> {
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use bigtools::{BigWigRead, SharedBigWigRead};
+
+    let mut dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    dir.push("resources/test");
+    dir.push("valid.bigWig");
+
+    let regions = [
+        (0, 20_000_000),
+        (20_000_000, 50_000_000),
+        (50_000_000, 83257441),
+    ];
+    let mut bwread = BigWigRead::open_file(&dir)?;
+    let mut expected = vec![];
+    for (start, end) in regions {
+        let vals: Vec<_> = bwread
+            .get_interval("chr17", start, end)?
+            .collect::<Result<_, _>>()?;
+        expected.push(vals);
+    }
+    let expected_zoom: Vec<_> = bwread
+        .get_zoom_interval("chr17", 0, 83257441, 10240)?
+        .collect::<Result<_, _>>()?;
+
+    let shared = Arc::new(SharedBigWigRead::open_file(&dir)?);
+    // Query each region from several threads at once
+    let handles: Vec<_> = (0..4)
+        .flat_map(|_| regions)
+        .map(|(start, end)| {
+            let shared = shared.clone();
+            std::thread::spawn(move || {
+                shared
+                    .get_interval("chr17", start, end)?
+                    .collect::<Result<Vec<_>, _>>()
+            })
+        })
+        .collect();
+    for (handle, expected) in handles.into_iter().zip(expected.iter().cycle()) {
+        assert_eq!(&handle.join().unwrap()?, expected);
+    }
+    let stats = shared.cache_stats();
+    assert!(stats.hits > 0);
+    assert!(stats.misses > 0);
+
+    let zoom: Vec<_> = shared
+        .get_zoom_interval("chr17", 0, 83257441, 10240)?
+        .collect::<Result<_, _>>()?;
+    assert_eq!(zoom, expected_zoom);
+    assert!(matches!(
+        shared.get_zoom_interval("chr17", 0, 83257441, 1),
+        Err(bigtools::ZoomIntervalError::ReductionLevelNotFound)
+    ));
+    assert!(shared.get_interval("chr1", 0, 100).is_err());
+
+    let values_bits = |values: Vec<f32>| values.into_iter().map(f32::to_bits).collect::<Vec<_>>();
+    let bytes = bytes::Bytes::from(std::fs::read(&dir)?);
+    let shared = SharedBigWigRead::open(bytes)?;
+    assert!(
+        values_bits(shared.values("chr17", 59_000, 1_059_000)?)
+            == values_bits(bwread.values("chr17", 59_000, 1_059_000)?)
+    );
+
+    assert!(matches!(
+        bigtools::SharedBigBedRead::open_file(&dir),
+        Err(bigtools::BigBedReadOpenError::NotABigBed)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_get_intervals() -> Result<(), Box<dyn Error>> {
+    use std::path::PathBuf;
+
+    use bigtools::BigWigRead;
+
+    let mut dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    dir.push("resources/test");
+    dir.push("valid.bigWig");
+
+    let mut bwread = BigWigRead::open_file(&dir)?;
+
+    // Unsorted, overlapping, duplicated, and empty regions
+    let regions = [
+        ("chr17", 59000, 60000),
+        ("chr17", 0, 83257441),
+        ("chr17", 59899, 59900),
+        ("chr17", 500000, 700000),
+        ("chr17", 59000, 60000),
+        ("chr17", 1, 2),
+        ("chr17", 650000, 2000000),
+    ];
+    let intervals = bwread.get_intervals(regions)?;
+    assert_eq!(intervals.len(), regions.len());
+    for ((chrom, start, end), vals) in regions.iter().zip(intervals) {
+        let expected: Vec<_> = bwread
+            .get_interval(chrom, *start, *end)?
+            .collect::<Result<_, _>>()?;
+        assert_eq!(vals, expected);
+    }
+
+    let reduction_level = bwread.info().zoom_headers[0].reduction_level;
+    let intervals = bwread.get_zoom_intervals(regions, reduction_level)?;
+    for ((chrom, start, end), vals) in regions.iter().zip(intervals) {
+        let expected: Vec<_> = bwread
+            .get_zoom_interval(chrom, *start, *end, reduction_level)?
+            .collect::<Result<_, _>>()?;
+        assert_eq!(vals, expected);
+    }
+
+    assert!(bwread
+        .get_intervals([("chr17", 0, 10), ("chrNone", 0, 10)])
+        .is_err());
+    assert!(bwread
+        .get_intervals(Vec::<(String, u32, u32)>::new())?
+        .is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_summarize() -> Result<(), Box<dyn Error>> {
+    use std::path::PathBuf;
+
+    use bigtools::{BigWigRead, SummaryKind};
+
+    let mut dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    dir.push("resources/test");
+    dir.push("valid.bigWig");
+
+    let mut bwread = BigWigRead::open_file(&dir)?;
+
+    let exact = bwread.summarize("chr17", 85525, 85730, 2, SummaryKind::Mean, true)?;
+    assert_eq!(exact, [Some(0.06770934917680595), Some(2.4864424403431347)]);
+    let estimate = bwread.summarize("chr17", 85525, 85730, 2, SummaryKind::Mean, false)?;
+    assert_eq!(
+        estimate,
+        [Some(0.15392776003070907), Some(2.728891665264241)]
+    );
+
+    // No data in the region
+    let empty = bwread.summarize("chr17", 0, 100, 2, SummaryKind::Max, true)?;
+    assert_eq!(empty, [None, None]);
+
+    let coverage = bwread.summarize("chr17", 59800, 60000, 1, SummaryKind::Coverage, true)?;
+    let coverage = coverage[0].unwrap();
+    assert!(coverage > 0.0 && coverage <= 1.0);
+
+    let min = bwread.summarize("chr17", 59800, 60000, 1, SummaryKind::Min, true)?;
+    let max = bwread.summarize("chr17", 59800, 60000, 1, SummaryKind::Max, true)?;
+    let std = bwread.summarize("chr17", 59800, 60000, 1, SummaryKind::Std, true)?;
+    assert!(min[0].unwrap() <= max[0].unwrap());
+    assert!(std[0].unwrap() >= 0.0);
+
+    Ok(())
+}
+
+#[cfg(feature = "arrow")]
+#[test]
+fn test_record_batches() -> Result<(), Box<dyn Error>> {
+    use std::path::PathBuf;
+
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::{Float32Type, UInt32Type};
+    use bigtools::BigWigRead;
+
+    let mut dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    dir.push("resources/test");
+    dir.push("valid.bigWig");
+
+    let mut bwread = BigWigRead::open_file(&dir)?;
+    let values = bwread
+        .get_interval("chr17", 0, 83257441)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let batches = BigWigRead::open_file(&dir)?
+        .record_batches(1000)
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(batches.len(), values.len().div_ceil(1000));
+    assert!(batches.iter().all(|b| b.num_rows() <= 1000));
+    let rows: usize = batches.iter().map(|b| b.num_rows()).sum();
+    assert_eq!(rows, values.len());
+
+    let first = &batches[0];
+    assert_eq!(first.column(0).as_string::<i32>().value(0), "chr17");
+    assert_eq!(
+        first.column(1).as_primitive::<UInt32Type>().value(0),
+        values[0].start
+    );
+    assert_eq!(
+        first.column(3).as_primitive::<Float32Type>().value(0),
+        values[0].value
+    );
+
+    let batches = BigWigRead::open_file(&dir)?
+        .record_batches(1000)
+        .with_regions(vec![("chr17".to_string(), 0, 59899)])
+        .collect::<Result<Vec<_>, _>>()?;
+    let starts = batches[0].column(1).as_primitive::<UInt32Type>();
+    assert!(starts.values().iter().all(|s| *s < 59899));
+
+    Ok(())
+}
@@ -1,14 +1,14 @@
 use std::collections::HashMap;
 use std::error::Error;
 use std::fs::File;
-use std::io::{self};
+use std::io::{self, Write};
 use std::path::PathBuf;
 
 use tempfile;
 
 use bigtools::bed::bedparser::{BedFileStream, StreamingBedValues};
 use bigtools::beddata::BedParserStreamingIterator;
-use bigtools::{BigWigRead, BigWigWrite, Value};
+use bigtools::{BigWigRead, BigWigWrite, SectionEncoding, Value};
 use tokio::runtime;
 
 #[test]
@@ -191,3 +191,143 @@ fn test_iter() {
         BigWigWrite::create_file(tempfile.path().to_string_lossy().to_string(), chrom_map).unwrap();
     outb.write(vals_iter, runtime).unwrap();
 }
+
+#[test]
+fn test_section_encodings() -> Result<(), Box<dyn Error>> {
+    // Three runs of 100 values, each suited to a different section type:
+    // evenly spaced with a fixed span, unevenly spaced with a fixed span, and
+    // varying spans.
+    let mut vals = vec![];
+    for i in 0..100u32 {
+        let start = 100 + i * 25;
+        vals.push(Value {
+            start,
+            end: start + 10,
+            value: i as f32,
+        });
+    }
+    let mut start = 5_000;
+    for i in 0..100u32 {
+        start += 10 + (i % 7);
+        vals.push(Value {
+            start,
+            end: start + 5,
+            value: -(i as f32),
+        });
+    }
+    let mut start = 10_000;
+    for i in 0..100u32 {
+        let end = start + 1 + (i % 13);
+        vals.push(Value {
+            start,
+            end,
+            value: 0.5 * i as f32,
+        });
+        start = end + 3;
+    }
+
+    let write = |encoding: SectionEncoding| -> Result<(u64, Vec<Value>), Box<dyn Error>> {
+        let runtime = runtime::Builder::new_current_thread()
+            .build()
+            .expect("Unable to create runtime.");
+        let tempfile = tempfile::NamedTempFile::new()?;
+        let chrom_map = HashMap::from([("chr1".to_string(), 100_000)]);
+        let mut outb = BigWigWrite::create_file(tempfile.path(), chrom_map)?;
+        outb.options.compress = false;
+        outb.options.items_per_slot = 50;
+        outb.options.section_encoding = encoding;
+        let iter = vals.clone().into_iter().map(|v| ("chr1", v));
+        let data = BedParserStreamingIterator::wrap_infallible_iter(iter, true);
+        outb.write(data, runtime).unwrap();
+
+        let mut bwread = BigWigRead::open_file(tempfile.path())?;
+        let read = bwread
+            .get_interval("chr1", 0, 100_000)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((tempfile.as_file().metadata()?.len(), read))
+    };
+
+    let (bedgraph_size, read) = write(SectionEncoding::BedGraph)?;
+    assert_eq!(read, vals);
+    let (fixedstep_size, read) = write(SectionEncoding::FixedStep)?;
+    assert_eq!(read, vals);
+    let (varstep_size, read) = write(SectionEncoding::VarStep)?;
+    assert_eq!(read, vals);
+    let (auto_size, read) = write(SectionEncoding::Auto)?;
+    assert_eq!(read, vals);
+
+    // Each forced encoding only applies to the sections it can represent, so
+    // auto, which picks per section, should beat each of them.
+    assert!(fixedstep_size < bedgraph_size);
+    assert!(varstep_size < bedgraph_size);
+    assert!(auto_size < fixedstep_size);
+    assert!(auto_size < varstep_size);
+
+    Ok(())
+}
+
+#[test]
+fn test_unsorted() -> Result<(), Box<dyn Error>> {
+    let mut dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    dir.push("resources/test");
+
+    let mut multi_chrom_bedgraph = dir.clone();
+    multi_chrom_bedgraph.push("multi_chrom.bedGraph");
+
+    let mut sorted = vec![];
+    let infile = File::open(&multi_chrom_bedgraph)?;
+    let mut vals_iter = BedFileStream::from_bedgraph_file(infile);
+    while let Some(val) = vals_iter.next() {
+        let (chrom, val) = val?;
+        sorted.push((chrom.to_string(), val));
+    }
+    // Deterministically shuffle the values
+    let mut unsorted = sorted.clone();
+    for i in 0..unsorted.len() {
+        unsorted.swap(i, (i * 7919) % sorted.len());
+    }
+    assert_ne!(unsorted, sorted);
+
+    let runtime = runtime::Builder::new_multi_thread()
+        .worker_threads(6)
+        .build()
+        .expect("Unable to create runtime.");
+
+    let tempfile = tempfile::NamedTempFile::new()?;
+
+    let chrom_map = HashMap::from([
+        ("chr1".to_string(), 248956422),
+        ("chr2".to_string(), 242193529),
+        ("chr3".to_string(), 198295559),
+        ("chr4".to_string(), 190214555),
+        ("chr5".to_string(), 181538259),
+        ("chr6".to_string(), 170805979),
+    ]);
+
+    let mut unsorted_bedgraph = tempfile::NamedTempFile::new()?;
+    for (chrom, val) in unsorted.iter() {
+        writeln!(
+            unsorted_bedgraph,
+            "{}\t{}\t{}\t{}",
+            chrom, val.start, val.end, val.value
+        )?;
+    }
+    unsorted_bedgraph.flush()?;
+
+    let outb = BigWigWrite::create_file(tempfile.path(), chrom_map.clone())?;
+    let infile = File::open(unsorted_bedgraph.path())?;
+    let data =
+        BedParserStreamingIterator::from_unsorted(BedFileStream::from_bedgraph_file(infile), false);
+    outb.write(data, runtime).unwrap();
+
+    let mut bwread = BigWigRead::open_file(tempfile.path())?;
+    let mut read = vec![];
+    for chrom in ["chr1", "chr2", "chr3", "chr4", "chr5", "chr6"] {
+        for val in bwread.get_interval(chrom, 0, chrom_map[chrom])? {
+            read.push((chrom.to_string(), val?));
+        }
+    }
+    assert_eq!(read, sorted);
+
+    Ok(())
+}
@@ -0,0 +1,310 @@
+//! Reads a corpus of corrupted bigWigs and bigBeds, generated from valid files
+//! by flipping bytes, overwriting integers, and truncating. Reading a
+//! corrupted file may fail, but must never panic.
+
+use std::error::Error;
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use bigtools::{
+    BBIReadError, BigBedRead, BigWigRead, BigWigReadOpenError, BlockError, SummaryKind,
+};
+
+/// The number of corrupted files generated from each valid file
+const CORPUS_SIZE: usize = 300;
+
+/// `values` allocates for every base, so is limited to the start of each
+/// chromosome
+const MAX_VALUES_LENGTH: u32 = 2_000_000;
+
+fn valid_bigwig() -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    dir.push("resources/test");
+    dir.push("valid.bigWig");
+    Ok(std::fs::read(dir)?)
+}
+
+/// An uncompressed bigWig with sections of every encoding, so that corrupted
+/// blocks are decoded rather than failing to decompress.
+fn uncompressed_bigwig() -> Result<Vec<u8>, Box<dyn Error>> {
+    use std::collections::HashMap;
+
+    use bigtools::beddata::BedParserStreamingIterator;
+    use bigtools::{BigWigWrite, SectionEncoding, Value};
+    use tokio::runtime;
+
+    let mut vals = vec![];
+    let mut start = 0;
+    for i in 0..3000u32 {
+        // Alternate between runs of evenly spaced items (fixed step), items
+        // of the same span (variable step), and anything else (bedGraph)
+        let (span, gap) = match (i / 100) % 3 {
+            0 => (10, 0),
+            1 => (5, i % 7),
+            _ => (i % 13 + 1, i % 3),
+        };
+        vals.push(Value {
+            start,
+            end: start + span,
+            value: i as f32,
+        });
+        start += span + gap;
+    }
+
+    let runtime = runtime::Builder::new_current_thread()
+        .build()
+        .expect("Unable to create runtime.");
+
+    let tempfile = tempfile::NamedTempFile::new()?;
+    let chrom_map = HashMap::from([("chr1".to_string(), 100_000)]);
+    let mut outb = BigWigWrite::create_file(tempfile.path(), chrom_map)?;
+    outb.options.compress = false;
+    outb.options.items_per_slot = 50;
+    outb.options.section_encoding = SectionEncoding::Auto;
+    let iter = vals.into_iter().map(|v| ("chr1", v));
+    let data = BedParserStreamingIterator::wrap_infallible_iter(iter, false);
+    outb.write(data, runtime)?;
+
+    Ok(std::fs::read(tempfile.path())?)
+}
+
+fn valid_bigbed(compress: bool) -> Result<Vec<u8>, Box<dyn Error>> {
+    use std::collections::HashMap;
+
+    use bigtools::beddata::BedParserStreamingIterator;
+    use bigtools::{BedEntry, BigBedWrite};
+    use tokio::runtime;
+
+    let entries: Vec<_> = ["chr1", "chr2"]
+        .into_iter()
+        .flat_map(|chrom| {
+            (0..500u32).map(move |i| {
+                let entry = BedEntry {
+                    start: i * 100,
+                    end: i * 100 + if i % 4 == 0 { 350 } else { 50 },
+                    rest: format!("item{}\t{}\t+", i, i % 1000),
+                };
+                (chrom.to_string(), entry)
+            })
+        })
+        .collect();
+
+    let runtime = runtime::Builder::new_current_thread()
+        .build()
+        .expect("Unable to create runtime.");
+
+    let tempfile = tempfile::NamedTempFile::new()?;
+    let chrom_map = HashMap::from([("chr1".to_string(), 100_000), ("chr2".to_string(), 100_000)]);
+    let mut outb = BigBedWrite::create_file(tempfile.path(), chrom_map)?;
+    outb.autosql = Some(bigtools::bed::autosql::bed_autosql(&entries[0].1.rest));
+    outb.extra_indices = vec!["name".to_string()];
+    // Small blocks, so that both the data and the index are split
+    outb.options.items_per_slot = 16;
+    outb.options.block_size = 4;
+    outb.options.compress = compress;
+    let data = BedParserStreamingIterator::wrap_infallible_iter(entries.into_iter(), false);
+    outb.write(data, runtime)?;
+
+    Ok(std::fs::read(tempfile.path())?)
+}
+
+/// Generates a corrupted copy of `bytes`
+fn corrupt(bytes: &[u8], rng: &mut StdRng) -> Vec<u8> {
+    let mut corrupted = bytes.to_vec();
+    // Most of the structure of a bbi file is at the start or end, so bias
+    // corruptions towards those.
+    let offset = match rng.gen_range(0..3) {
+        0 => rng.gen_range(0..corrupted.len().min(4096)),
+        1 => rng.gen_range(corrupted.len().saturating_sub(16384)..corrupted.len()),
+        _ => rng.gen_range(0..corrupted.len()),
+    };
+    match rng.gen_range(0..5) {
+        0 => corrupted[offset] ^= 1 << rng.gen_range(0..8),
+        1 => corrupted[offset] = rng.gen(),
+        2 => {
+            // A large (or zero) integer, like a count, size, or offset
+            let value: u32 = match rng.gen_range(0..3) {
+                0 => 0,
+                1 => u32::MAX,
+                _ => rng.gen(),
+            };
+            let end = (offset + 4).min(corrupted.len());
+            corrupted[offset..end].copy_from_slice(&value.to_le_bytes()[..end - offset]);
+        }
+        3 => {
+            let end = (offset + rng.gen_range(1..64)).min(corrupted.len());
+            corrupted[offset..end].fill(0);
+        }
+        _ => corrupted.truncate(offset),
+    }
+    corrupted
+}
+
+/// Reads everything from a possibly corrupted bigWig
+fn read_bigwig(bytes: &[u8]) -> Result<(), BBIReadError> {
+    let mut bwread = match BigWigRead::open(Cursor::new(bytes)) {
+        Ok(bwread) => bwread,
+        Err(_) => return Ok(()),
+    };
+    let _ = bwread.get_summary();
+    let chroms = bwread.chroms().to_vec();
+    let reduction_levels: Vec<_> = bwread
+        .info()
+        .zoom_headers
+        .iter()
+        .map(|z| z.reduction_level)
+        .collect();
+    for chrom in chroms {
+        let end = chrom.length;
+        for value in bwread.get_interval(&chrom.name, 0, end)? {
+            value?;
+        }
+        bwread.values(&chrom.name, 0, end.min(MAX_VALUES_LENGTH))?;
+        bwread.get_intervals([(&chrom.name, 0, end / 2), (&chrom.name, end / 4, end)])?;
+        bwread.summarize(&chrom.name, 0, end, 10, SummaryKind::Mean, false)?;
+        for &reduction_level in &reduction_levels {
+            let Ok(records) = bwread.get_zoom_interval(&chrom.name, 0, end, reduction_level) else {
+                continue;
+            };
+            for record in records {
+                record?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reads everything from a possibly corrupted bigBed
+fn read_bigbed(bytes: &[u8]) -> Result<(), BBIReadError> {
+    let mut bbread = match BigBedRead::open(Cursor::new(bytes)) {
+        Ok(bbread) => bbread,
+        Err(_) => return Ok(()),
+    };
+    let _ = bbread.get_summary();
+    let _ = bbread.autosql();
+    let _ = bbread.item_count();
+    let _ = bbread.search_extra_index("name", "item1");
+    let chroms = bbread.chroms().to_vec();
+    let reduction_levels: Vec<_> = bbread
+        .info()
+        .zoom_headers
+        .iter()
+        .map(|z| z.reduction_level)
+        .collect();
+    for chrom in chroms {
+        let end = chrom.length;
+        for entry in bbread.get_interval(&chrom.name, 0, end)? {
+            entry?;
+        }
+        bbread.get_intervals([(&chrom.name, 0, end / 2), (&chrom.name, end / 4, end)])?;
+        bbread.summarize(&chrom.name, 0, end, 10, SummaryKind::Coverage, true)?;
+        for &reduction_level in &reduction_levels {
+            let Ok(records) = bbread.get_zoom_interval(&chrom.name, 0, end, reduction_level) else {
+                continue;
+            };
+            for record in records {
+                record?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_corrupted_bigwigs() -> Result<(), Box<dyn Error>> {
+    for bytes in [valid_bigwig()?, uncompressed_bigwig()?] {
+        read_bigwig(&bytes)?;
+
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..CORPUS_SIZE {
+            let _ = read_bigwig(&corrupt(&bytes, &mut rng));
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_corrupted_bigbeds() -> Result<(), Box<dyn Error>> {
+    for bytes in [valid_bigbed(true)?, valid_bigbed(false)?] {
+        read_bigbed(&bytes)?;
+
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..CORPUS_SIZE {
+            let _ = read_bigbed(&corrupt(&bytes, &mut rng));
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_corrupted_block_offset() -> Result<(), Box<dyn Error>> {
+    let mut bytes = valid_bigwig()?;
+    let full_data_offset = u64::from_le_bytes(bytes[16..24].try_into().unwrap());
+    // The data count is 32 bits in this file, so the first block starts right after
+    let first_block = full_data_offset + 4;
+    bytes[first_block as usize..first_block as usize + 16].fill(0xff);
+
+    let mut bwread = BigWigRead::open(Cursor::new(&bytes))?;
+    let err = bwread
+        .get_interval("chr17", 0, 100_000)?
+        .find_map(Result::err)
+        .unwrap();
+    assert!(
+        matches!(
+            err,
+            BBIReadError::InvalidBlock(offset, BlockError::Decompression(_)) if offset == first_block
+        ),
+        "{:?}",
+        err
+    );
+    let err = bwread.values("chr17", 0, 100_000).unwrap_err();
+    assert!(matches!(err, BBIReadError::InvalidBlock(offset, _) if offset == first_block));
+
+    Ok(())
+}
+
+#[test]
+fn test_corrupted_uncompress_buf_size() -> Result<(), Box<dyn Error>> {
+    let mut bytes = valid_bigwig()?;
+    bytes[52..56].fill(0xff);
+
+    let mut bwread = BigWigRead::open(Cursor::new(&bytes))?;
+    let err = bwread
+        .get_interval("chr17", 0, 100_000)?
+        .find_map(Result::err)
+        .unwrap();
+    assert!(
+        matches!(
+            err,
+            BBIReadError::InvalidBlock(_, BlockError::UncompressBufTooLarge(u32::MAX))
+        ),
+        "{:?}",
+        err
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_truncated_chrom_tree() -> Result<(), Box<dyn Error>> {
+    let bytes = valid_bigwig()?;
+    let chrom_tree_offset = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+    // The chrom tree header is 32 bytes, so this is in the middle of the root
+    // node. Failing to read it is an I/O error, not invalid chromosomes.
+    let truncated = &bytes[..chrom_tree_offset as usize + 40];
+    let Err(err) = BigWigRead::open(Cursor::new(truncated)) else {
+        panic!("Opened a bigWig with a truncated chrom tree");
+    };
+    assert!(
+        matches!(&err, BigWigReadOpenError::IoError(e) if e.kind() == std::io::ErrorKind::UnexpectedEof),
+        "{:?}",
+        err
+    );
+
+    Ok(())
+}
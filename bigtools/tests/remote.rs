@@ -0,0 +1,654 @@
+#![cfg(feature = "remote")]
+
+use std::error::Error;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use bigtools::utils::file::range_fetcher::{FileFetcher, HttpFetcher, RangeFetcher};
+use bigtools::utils::file::remote_cache::RemoteCache;
+use bigtools::utils::file::remote_file::RemoteFile;
+use bigtools::{BBIFileRead, BigWigRead, Value};
+
+fn valid_bigwig_path() -> PathBuf {
+    let mut dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    dir.push("resources/test");
+    dir.push("valid.bigWig");
+    dir
+}
+
+#[derive(Clone, Default)]
+struct ServerOptions {
+    /// Requests without `Authorization: Bearer <token>` get a `401`
+    bearer_token: Option<&'static str>,
+    /// The first `failures` requests get a `503`
+    failures: usize,
+    /// How long to wait before responding
+    delay: Option<Duration>,
+    /// Whether to send an `ETag` header
+    etag: bool,
+    /// Whether to ignore `Range` headers and always send the whole file
+    ignore_range: bool,
+    /// Closes connections (without telling the client) after this many
+    /// requests
+    requests_per_connection: Option<usize>,
+    /// Requests for paths starting with `/redirect` get a `302` to this
+    /// location
+    redirect: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+struct Request {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// A minimal http server that serves `data`, with support for `Range`
+/// requests.
+struct TestServer {
+    addr: SocketAddr,
+    requests: Arc<Mutex<Vec<Request>>>,
+    connections: Arc<AtomicUsize>,
+}
+
+impl TestServer {
+    fn start(data: Vec<u8>, options: ServerOptions) -> TestServer {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        TestServer::start_on(listener, data, options)
+    }
+
+    fn start_on(listener: TcpListener, data: Vec<u8>, options: ServerOptions) -> TestServer {
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(Mutex::new(vec![]));
+        let data = Arc::new(data);
+        let count = Arc::new(AtomicUsize::new(0));
+        let connections = Arc::new(AtomicUsize::new(0));
+        let server_requests = requests.clone();
+        let server_connections = connections.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => break,
+                };
+                server_connections.fetch_add(1, Ordering::SeqCst);
+                let data = data.clone();
+                let options = options.clone();
+                let count = count.clone();
+                let requests = server_requests.clone();
+                thread::spawn(move || {
+                    let _ = handle_connection(stream, &data, &options, &count, &requests);
+                });
+            }
+        });
+        TestServer {
+            addr,
+            requests,
+            connections,
+        }
+    }
+
+    fn connections(&self) -> usize {
+        self.connections.load(Ordering::SeqCst)
+    }
+
+    fn url(&self) -> String {
+        format!("http://{}/valid.bigWig", self.addr)
+    }
+
+    fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    fn count(&self, method: &str) -> usize {
+        self.requests()
+            .iter()
+            .filter(|r| r.method == method)
+            .count()
+    }
+}
+
+fn handle_connection(
+    stream: TcpStream,
+    data: &[u8],
+    options: &ServerOptions,
+    count: &AtomicUsize,
+    requests: &Mutex<Vec<Request>>,
+) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut stream = stream;
+    let mut served = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        let mut parts = line.split(' ');
+        let method = parts.next().unwrap_or_default().to_string();
+        let path = parts.next().unwrap_or_default().to_string();
+        let mut headers = vec![];
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line)?;
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.push((name.trim().to_string(), value.trim().to_string()));
+            }
+        }
+        let request = Request {
+            method,
+            path,
+            headers,
+        };
+        requests.lock().unwrap().push(request.clone());
+        respond(&mut stream, &request, data, options, count)?;
+        served += 1;
+        let close = request
+            .header("connection")
+            .is_some_and(|c| c.eq_ignore_ascii_case("close"));
+        if close || options.requests_per_connection == Some(served) {
+            return Ok(());
+        }
+    }
+}
+
+fn respond(
+    stream: &mut TcpStream,
+    request: &Request,
+    data: &[u8],
+    options: &ServerOptions,
+    count: &AtomicUsize,
+) -> io::Result<()> {
+    if let Some(delay) = options.delay {
+        thread::sleep(delay);
+    }
+    let respond = |stream: &mut TcpStream, status: &str, headers: &[String], body: &[u8]| {
+        let mut response = format!("HTTP/1.1 {}\r\n", status);
+        for header in headers {
+            response.push_str(header);
+            response.push_str("\r\n");
+        }
+        response.push_str("\r\n");
+        let mut response = response.into_bytes();
+        response.extend_from_slice(body);
+        stream.write_all(&response)?;
+        stream.flush()
+    };
+
+    if count.fetch_add(1, Ordering::SeqCst) < options.failures {
+        return respond(
+            stream,
+            "503 Service Unavailable",
+            &["Content-Length: 0".to_string()],
+            &[],
+        );
+    }
+    if let Some(location) = &options.redirect {
+        if request.path.starts_with("/redirect") {
+            return respond(
+                stream,
+                "302 Found",
+                &[
+                    format!("Location: {}", location),
+                    "Content-Length: 0".to_string(),
+                ],
+                &[],
+            );
+        }
+    }
+    if let Some(token) = options.bearer_token {
+        if request.header("authorization") != Some(&format!("Bearer {}", token)) {
+            return respond(
+                stream,
+                "401 Unauthorized",
+                &["Content-Length: 0".to_string()],
+                &[],
+            );
+        }
+    }
+
+    let mut validators = vec![];
+    if options.etag {
+        validators.push("ETag: \"v1\"".to_string());
+    }
+    if request.method == "HEAD" {
+        let mut headers = vec![format!("Content-Length: {}", data.len())];
+        headers.extend(validators);
+        return respond(stream, "200 OK", &headers, &[]);
+    }
+
+    let range = request
+        .header("range")
+        .and_then(|r| r.strip_prefix("bytes="))
+        .and_then(|r| r.split_once('-'))
+        .and_then(|(start, end)| Some((start.parse::<usize>().ok()?, end.parse::<usize>().ok()?)));
+    match range {
+        Some((start, end)) if !options.ignore_range => {
+            if start >= data.len() {
+                return respond(
+                    stream,
+                    "416 Range Not Satisfiable",
+                    &["Content-Length: 0".to_string()],
+                    &[],
+                );
+            }
+            let end = end.min(data.len() - 1);
+            let body = &data[start..=end];
+            let mut headers = vec![
+                format!("Content-Length: {}", body.len()),
+                format!("Content-Range: bytes {}-{}/{}", start, end, data.len()),
+            ];
+            headers.extend(validators);
+            respond(stream, "206 Partial Content", &headers, body)
+        }
+        _ => {
+            let mut headers = vec![format!("Content-Length: {}", data.len())];
+            headers.extend(validators);
+            respond(stream, "200 OK", &headers, data)
+        }
+    }
+}
+
+fn read_all<R: Read>(mut read: R) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![];
+    read.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn all_values<R: BBIFileRead>(bigwig: &mut BigWigRead<R>) -> Result<Vec<Value>, Box<dyn Error>> {
+    let chroms = bigwig.chroms().to_vec();
+    let mut values = vec![];
+    for chrom in chroms {
+        for value in bigwig.get_interval(&chrom.name, 0, chrom.length)? {
+            values.push(value?);
+        }
+    }
+    Ok(values)
+}
+
+#[test]
+fn test_http_fetcher_ranges() -> Result<(), Box<dyn Error>> {
+    let data = std::fs::read(valid_bigwig_path())?;
+    let server = TestServer::start(data.clone(), ServerOptions::default());
+
+    let fetcher = HttpFetcher::new(&server.url());
+    assert_eq!(fetcher.fetch(0, 100)?, &data[0..100]);
+    assert_eq!(fetcher.fetch(1000, 24)?, &data[1000..1024]);
+    let len = data.len() as u64;
+    assert_eq!(fetcher.fetch(len - 10, 100)?, &data[data.len() - 10..]);
+    assert_eq!(fetcher.fetch(len + 10, 100)?, Vec::<u8>::new());
+    assert_eq!(server.requests()[0].header("range"), Some("bytes=0-99"),);
+    // Without an `ETag` or `Last-Modified`, the file can't be cached
+    assert_eq!(fetcher.validators()?, None);
+
+    // Servers that don't support ranges send the whole file
+    let server = TestServer::start(
+        data.clone(),
+        ServerOptions {
+            ignore_range: true,
+            ..Default::default()
+        },
+    );
+    let fetcher = HttpFetcher::new(&server.url());
+    assert_eq!(fetcher.fetch(1000, 24)?, &data[1000..1024]);
+
+    Ok(())
+}
+
+#[test]
+fn test_remote_file_read() -> Result<(), Box<dyn Error>> {
+    let data = std::fs::read(valid_bigwig_path())?;
+    let server = TestServer::start(data.clone(), ServerOptions::default());
+
+    let mut remote = RemoteFile::new(&server.url());
+    remote.seek(SeekFrom::Start(0))?;
+    assert_eq!(read_all(remote)?, data);
+
+    let mut local = BigWigRead::open_file(valid_bigwig_path().to_str().unwrap())?;
+    let expected = all_values(&mut local)?;
+    assert!(!expected.is_empty());
+    let mut remote = BigWigRead::open(RemoteFile::new(&server.url()))?;
+    assert_eq!(all_values(&mut remote)?, expected);
+
+    Ok(())
+}
+
+#[test]
+fn test_http_fetcher_auth() -> Result<(), Box<dyn Error>> {
+    let data = std::fs::read(valid_bigwig_path())?;
+    let server = TestServer::start(
+        data.clone(),
+        ServerOptions {
+            bearer_token: Some("secret"),
+            ..Default::default()
+        },
+    );
+
+    let err = HttpFetcher::new(&server.url()).fetch(0, 10).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    let err = HttpFetcher::new(&server.url())
+        .with_bearer_token("wrong")
+        .fetch(0, 10)
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+
+    let fetcher = HttpFetcher::new(&server.url())
+        .with_bearer_token("secret")
+        .with_header("x-custom", "value");
+    assert_eq!(fetcher.fetch(0, 10)?, &data[0..10]);
+    let request = server.requests().pop().unwrap();
+    assert_eq!(request.header("x-custom"), Some("value"));
+
+    let remote = RemoteFile::with_fetcher(fetcher);
+    let mut remote = BigWigRead::open(remote)?;
+    let mut local = BigWigRead::open_file(valid_bigwig_path().to_str().unwrap())?;
+    assert_eq!(all_values(&mut remote)?, all_values(&mut local)?);
+
+    let err = HttpFetcher::new(&server.url())
+        .with_header("bad header", "value")
+        .fetch(0, 10)
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    Ok(())
+}
+
+#[test]
+fn test_http_fetcher_retries() -> Result<(), Box<dyn Error>> {
+    let data = std::fs::read(valid_bigwig_path())?;
+    let options = ServerOptions {
+        failures: 2,
+        ..Default::default()
+    };
+
+    let server = TestServer::start(data.clone(), options.clone());
+    let fetcher = HttpFetcher::new(&server.url()).with_retries(1, Duration::from_millis(10));
+    assert!(fetcher.fetch(0, 10).is_err());
+    assert_eq!(server.count("GET"), 2);
+
+    let server = TestServer::start(data.clone(), options.clone());
+    let fetcher = HttpFetcher::new(&server.url()).with_retries(3, Duration::from_millis(10));
+    assert_eq!(fetcher.fetch(0, 10)?, &data[0..10]);
+    assert_eq!(server.count("GET"), 3);
+
+    // Client errors aren't retried
+    let server = TestServer::start(
+        data.clone(),
+        ServerOptions {
+            bearer_token: Some("secret"),
+            ..Default::default()
+        },
+    );
+    let fetcher = HttpFetcher::new(&server.url()).with_retries(3, Duration::from_millis(10));
+    assert!(fetcher.fetch(0, 10).is_err());
+    assert_eq!(server.count("GET"), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_http_fetcher_timeout() -> Result<(), Box<dyn Error>> {
+    let data = std::fs::read(valid_bigwig_path())?;
+    let server = TestServer::start(
+        data.clone(),
+        ServerOptions {
+            delay: Some(Duration::from_secs(2)),
+            ..Default::default()
+        },
+    );
+
+    let fetcher = HttpFetcher::new(&server.url())
+        .with_retries(0, Duration::ZERO)
+        .with_timeout(Duration::from_millis(200));
+    assert!(fetcher.fetch(0, 10).is_err());
+
+    let fetcher = HttpFetcher::new(&server.url()).with_timeout(Duration::from_secs(30));
+    assert_eq!(fetcher.fetch(0, 10)?, &data[0..10]);
+
+    Ok(())
+}
+
+#[test]
+fn test_remote_file_persistent_cache() -> Result<(), Box<dyn Error>> {
+    let data = std::fs::read(valid_bigwig_path())?;
+    let server = TestServer::start(
+        data.clone(),
+        ServerOptions {
+            etag: true,
+            ..Default::default()
+        },
+    );
+    let dir = tempfile::tempdir()?;
+    let cache = RemoteCache::new(dir.path());
+
+    let remote = RemoteFile::new(&server.url()).with_cache(cache.clone());
+    assert_eq!(read_all(remote)?, data);
+    let gets = server.count("GET");
+    assert!(gets > 0);
+    assert_eq!(server.count("HEAD"), 1);
+
+    // Everything is cached, so only the validators are checked
+    let remote = RemoteFile::new(&server.url()).with_cache(cache);
+    assert_eq!(read_all(remote)?, data);
+    assert_eq!(server.count("GET"), gets);
+    assert_eq!(server.count("HEAD"), 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_file_fetcher_remote_file() -> Result<(), Box<dyn Error>> {
+    let path = valid_bigwig_path();
+    let url = format!("file://{}", path.to_str().unwrap());
+    let data = std::fs::read(&path)?;
+
+    let remote = RemoteFile::with_fetcher(FileFetcher::new(&url)?);
+    assert_eq!(read_all(remote)?, data);
+
+    let dir = tempfile::tempdir()?;
+    let remote =
+        RemoteFile::with_fetcher(FileFetcher::new(&url)?).with_cache(RemoteCache::new(dir.path()));
+    let mut remote = BigWigRead::open(remote)?;
+    let mut local = BigWigRead::open_file(path.to_str().unwrap())?;
+    assert_eq!(all_values(&mut remote)?, all_values(&mut local)?);
+
+    Ok(())
+}
+
+#[test]
+fn test_http_fetcher_keep_alive() -> Result<(), Box<dyn Error>> {
+    let data = std::fs::read(valid_bigwig_path())?;
+
+    let server = TestServer::start(data.clone(), ServerOptions::default());
+    let fetcher = HttpFetcher::new(&server.url());
+    for i in 0..10 {
+        assert_eq!(
+            fetcher.fetch(i * 100, 100)?,
+            &data[i as usize * 100..][..100]
+        );
+    }
+    assert!(fetcher.validators()?.is_none());
+    assert_eq!(server.connections(), 1);
+
+    // Clones share their connections
+    let fetcher = HttpFetcher::new(&server.url());
+    fetcher.clone().fetch(0, 10)?;
+    fetcher.fetch(0, 10)?;
+    assert_eq!(server.connections(), 2);
+
+    let server = TestServer::start(data.clone(), ServerOptions::default());
+    let fetcher = HttpFetcher::new(&server.url()).with_keep_alive(false);
+    for i in 0..10 {
+        assert_eq!(
+            fetcher.fetch(i * 100, 100)?,
+            &data[i as usize * 100..][..100]
+        );
+    }
+    assert_eq!(server.connections(), 10);
+
+    // Connections closed by the server are replaced
+    let server = TestServer::start(
+        data.clone(),
+        ServerOptions {
+            requests_per_connection: Some(3),
+            ..Default::default()
+        },
+    );
+    let fetcher = HttpFetcher::new(&server.url()).with_retries(0, Duration::ZERO);
+    for i in 0..10 {
+        assert_eq!(
+            fetcher.fetch(i * 100, 100)?,
+            &data[i as usize * 100..][..100]
+        );
+    }
+    assert_eq!(server.connections(), 4);
+
+    Ok(())
+}
+
+#[test]
+fn test_http_fetcher_redirects() -> Result<(), Box<dyn Error>> {
+    let data = std::fs::read(valid_bigwig_path())?;
+
+    // Headers are kept for redirects within the same origin
+    let server = TestServer::start(
+        data.clone(),
+        ServerOptions {
+            bearer_token: Some("secret"),
+            redirect: Some("/valid.bigWig".to_string()),
+            ..Default::default()
+        },
+    );
+    let url = server.url().replace("/valid.bigWig", "/redirect");
+    let fetcher = HttpFetcher::new(&url).with_bearer_token("secret");
+    assert_eq!(fetcher.fetch(0, 10)?, &data[0..10]);
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[1].path, "/valid.bigWig");
+
+    // But not sent to other origins
+    let target = TestServer::start(data.clone(), ServerOptions::default());
+    let server = TestServer::start(
+        data.clone(),
+        ServerOptions {
+            redirect: Some(target.url()),
+            ..Default::default()
+        },
+    );
+    let url = server.url().replace("/valid.bigWig", "/redirect");
+    let fetcher = HttpFetcher::new(&url)
+        .with_bearer_token("secret")
+        .with_header("x-api-key", "key");
+    assert_eq!(fetcher.fetch(100, 10)?, &data[100..110]);
+    let request = server.requests().pop().unwrap();
+    assert_eq!(request.header("authorization"), Some("Bearer secret"));
+    assert_eq!(request.header("x-api-key"), Some("key"));
+    let request = target.requests().pop().unwrap();
+    assert_eq!(request.header("authorization"), None);
+    assert_eq!(request.header("x-api-key"), None);
+    assert_eq!(request.header("range"), Some("bytes=100-109"));
+
+    // Including after a redirect back to the original origin
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let original = format!("http://{}/valid.bigWig", listener.local_addr()?);
+    let target = TestServer::start(
+        data.clone(),
+        ServerOptions {
+            redirect: Some(original.clone()),
+            ..Default::default()
+        },
+    );
+    let server = TestServer::start_on(
+        listener,
+        data.clone(),
+        ServerOptions {
+            redirect: Some(target.url().replace("/valid.bigWig", "/redirect")),
+            ..Default::default()
+        },
+    );
+    let url = original.replace("/valid.bigWig", "/redirect");
+    let fetcher = HttpFetcher::new(&url).with_bearer_token("secret");
+    assert_eq!(fetcher.fetch(0, 10)?, &data[0..10]);
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].header("authorization"), Some("Bearer secret"));
+    assert_eq!(requests[1].path, "/valid.bigWig");
+    assert_eq!(requests[1].header("authorization"), None);
+
+    Ok(())
+}
+
+#[test]
+fn test_remote_file_read_ahead() -> Result<(), Box<dyn Error>> {
+    let data = std::fs::read(valid_bigwig_path())?;
+
+    fn read_sequentially<R: Read>(mut read: R) -> io::Result<Vec<u8>> {
+        let mut bytes = vec![];
+        let mut buf = [0u8; 256];
+        loop {
+            let read = read.read(&mut buf)?;
+            if read == 0 {
+                break Ok(bytes);
+            }
+            bytes.extend_from_slice(&buf[..read]);
+        }
+    }
+
+    let server = TestServer::start(data.clone(), ServerOptions::default());
+    let remote = RemoteFile::new(&server.url())
+        .with_block_size(1024)
+        .with_max_read_ahead(0);
+    assert_eq!(read_sequentially(remote)?, data);
+    assert_eq!(server.count("GET"), data.len().div_ceil(1024));
+
+    let server = TestServer::start(data.clone(), ServerOptions::default());
+    let remote = RemoteFile::new(&server.url())
+        .with_block_size(1024)
+        .with_max_read_ahead(64 * 1024);
+    assert_eq!(read_sequentially(remote)?, data);
+    let gets = server.count("GET");
+    assert!(gets < data.len() / (64 * 1024) + 10, "{} requests", gets);
+
+    // Random reads don't read ahead
+    let server = TestServer::start(data.clone(), ServerOptions::default());
+    let mut remote = RemoteFile::new(&server.url()).with_block_size(1024);
+    let mut buf = [0u8; 100];
+    for offset in [500_000u64, 10_000, 300_100, 50_000] {
+        remote.seek(SeekFrom::Start(offset))?;
+        remote.read_exact(&mut buf)?;
+        assert_eq!(&buf, &data[offset as usize..][..100]);
+    }
+    let ranges: Vec<_> = server
+        .requests()
+        .iter()
+        .filter_map(|r| r.header("range").map(|r| r.to_string()))
+        .collect();
+    assert_eq!(
+        ranges,
+        [
+            "bytes=499712-500735",
+            "bytes=9216-10239",
+            "bytes=300032-301055",
+            "bytes=49152-50175",
+        ]
+    );
+
+    Ok(())
+}
@@ -0,0 +1,140 @@
+use std::error::Error;
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use bigtools::validate::validate;
+use bigtools::BBIFile;
+
+fn valid_bigwig() -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    dir.push("resources/test");
+    dir.push("valid.bigWig");
+    Ok(std::fs::read(dir)?)
+}
+
+fn read_u64_le(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+#[test]
+fn test_validate_valid() -> Result<(), Box<dyn Error>> {
+    use bigtools::BigWigRead;
+
+    let bytes = valid_bigwig()?;
+    let report = validate(Cursor::new(&bytes))?;
+    assert!(report.is_valid(), "{:?}", report.problems);
+    assert!(matches!(report.filetype, Some(BBIFile::BigWig)));
+    assert!(report.blocks_checked > 0);
+
+    let mut bwread = BigWigRead::open(Cursor::new(&bytes))?;
+    let stored = bwread.get_summary()?;
+    let summary = report.summary.unwrap();
+    assert_eq!(summary.bases_covered, stored.bases_covered);
+    assert_eq!(summary.min_val, stored.min_val);
+    assert_eq!(summary.max_val, stored.max_val);
+
+    Ok(())
+}
+
+#[test]
+fn test_validate_overlapping_bigbed() -> Result<(), Box<dyn Error>> {
+    use std::collections::HashMap;
+
+    use bigtools::beddata::BedParserStreamingIterator;
+    use bigtools::{BedEntry, BigBedRead, BigBedWrite};
+    use tokio::runtime;
+
+    // Overlapping entries, where earlier entries can end after later ones
+    let entries: Vec<_> = (0..200u32)
+        .map(|i| {
+            let entry = BedEntry {
+                start: i * 10,
+                end: i * 10 + if i % 3 == 0 { 95 } else { 5 },
+                rest: format!("item{}", i),
+            };
+            ("chr1".to_string(), entry)
+        })
+        .collect();
+
+    let runtime = runtime::Builder::new_current_thread()
+        .build()
+        .expect("Unable to create runtime.");
+
+    let tempfile = tempfile::NamedTempFile::new()?;
+    let chrom_map = HashMap::from([("chr1".to_string(), 10_000)]);
+    let mut outb = BigBedWrite::create_file(tempfile.path(), chrom_map)?;
+    outb.options.items_per_slot = 16;
+    let data = BedParserStreamingIterator::wrap_infallible_iter(entries.into_iter(), false);
+    outb.write(data, runtime)?;
+
+    let report = validate(std::fs::File::open(tempfile.path())?)?;
+    assert!(report.is_valid(), "{:?}", report.problems);
+    assert!(matches!(report.filetype, Some(BBIFile::BigBed)));
+
+    let mut bbread = BigBedRead::open_file(tempfile.path())?;
+    let stored = bbread.get_summary()?;
+    let summary = report.summary.unwrap();
+    assert_eq!(summary.total_items, 200);
+    assert_eq!(summary.bases_covered, stored.bases_covered);
+    assert_eq!(summary.sum, stored.sum);
+    assert_eq!(summary.max_val, stored.max_val);
+
+    Ok(())
+}
+
+#[test]
+fn test_validate_corrupted() -> Result<(), Box<dyn Error>> {
+    let bytes = valid_bigwig()?;
+    let chrom_tree_offset = read_u64_le(&bytes, 8) as usize;
+    let full_data_offset = read_u64_le(&bytes, 16) as usize;
+    // The data count is 32 bits in this file, so the first block starts right after
+    let first_block = full_data_offset + 4;
+
+    // A corrupted block and chromosome tree are both reported
+    let mut corrupted = bytes.clone();
+    corrupted[first_block..first_block + 16].fill(0xff);
+    corrupted[chrom_tree_offset] ^= 0xff;
+    let report = validate(Cursor::new(&corrupted))?;
+    assert!(!report.is_valid());
+    assert!(report
+        .problems
+        .iter()
+        .any(|p| p.offset == first_block as u64 && p.message.contains("decompress")));
+    assert!(report
+        .problems
+        .iter()
+        .any(|p| p.offset == chrom_tree_offset as u64 && p.message.contains("magic")));
+
+    // A truncated file
+    let truncated = &bytes[..bytes.len() / 2];
+    let report = validate(Cursor::new(truncated))?;
+    assert!(!report.is_valid());
+    assert!(report
+        .problems
+        .iter()
+        .any(|p| p.message.contains("past the end of the file")));
+
+    // An absurd uncompress buffer size is reported rather than allocated
+    let mut corrupted = bytes.clone();
+    corrupted[52..56].copy_from_slice(&u32::MAX.to_le_bytes());
+    let report = validate(Cursor::new(&corrupted))?;
+    assert!(report
+        .problems
+        .iter()
+        .any(|p| p.offset == 52 && p.message.contains("Uncompress buffer size")));
+
+    // Not a bbi file at all
+    let report = validate(Cursor::new(b"not a bigwig"))?;
+    assert!(report.filetype.is_none());
+    assert_eq!(report.problems.len(), 1);
+    assert_eq!(report.problems[0].offset, 0);
+
+    // Corrupting bytes throughout the file never panics
+    for i in (0..bytes.len()).step_by(4099) {
+        let mut corrupted = bytes.clone();
+        corrupted[i] ^= 0x5a;
+        validate(Cursor::new(&corrupted))?;
+    }
+
+    Ok(())
+}
@@ -1,23 +1,27 @@
 #![allow(non_snake_case)]
 
-use std::collections::VecDeque;
 use std::fs::File;
 use std::io::{self, BufReader};
 use std::ops::IndexMut;
 use std::path::Path;
 
-use bigtools::bed::autosql::parse::parse_autosql;
+use bigtools::bed::autosql::builder::DeclarationBuilder;
+use bigtools::bed::autosql::parse::{parse_autosql, FieldType};
 use bigtools::beddata::BedParserStreamingIterator;
 #[cfg(feature = "remote")]
+use bigtools::utils::file::remote_cache::RemoteCache;
+#[cfg(feature = "remote")]
 use bigtools::utils::file::remote_file::RemoteFile;
 use bigtools::utils::file::reopen::ReopenableFile;
 use bigtools::utils::misc::{
     bigwig_average_over_bed, BigWigAverageOverBedEntry, BigWigAverageOverBedError, Name,
 };
 use bigtools::{
-    BBIFileRead, BBIReadError as _BBIReadError, BedEntry, BigBedRead as BigBedReadRaw,
-    BigBedWrite as BigBedWriteRaw, BigWigRead as BigWigReadRaw, BigWigWrite as BigWigWriteRaw,
-    CachedBBIFileRead, GenericBBIRead, Value, ZoomRecord,
+    summarize_entries, summarize_entry_zoom_records, summarize_values, summarize_zoom_records,
+    summary_zoom_level, BBIFileRead, BBIReadError as _BBIReadError, BedEntry,
+    BigBedRead as BigBedReadRaw, BigBedWrite as BigBedWriteRaw, BigWigRead as BigWigReadRaw,
+    BigWigWrite as BigWigWriteRaw, CachedBBIFileRead, GenericBBIRead, SummaryKind, Value,
+    ZoomRecord,
 };
 
 use bigtools::utils::reopen::Reopen;
@@ -141,13 +145,6 @@ enum BBIReadRaw {
     BigBedFileLike(BigBedReadRaw<CachedBBIFileRead<PyFileLikeObject>>),
 }
 
-#[derive(Copy, Clone, Debug)]
-enum Summary {
-    Mean,
-    Min,
-    Max,
-}
-
 trait ToPyErr {
     fn to_py_err(self) -> PyErr;
 }
@@ -186,24 +183,16 @@ fn intervals_to_array<R: BBIFileRead>(
     start: Option<i32>,
     end: Option<i32>,
     bins: Option<usize>,
-    summary: Summary,
+    summary: SummaryKind,
     exact: bool,
     missing: f64,
     oob: f64,
     arr: Option<PyObject>,
 ) -> PyResult<PyObject> {
     let (start, end, length) = bigwig_start_end_length(b, chrom, start, end)?;
-    let zoom = if let (Some(bins), false) = (bins, exact) {
-        let max_zoom_size = ((end - start) as f32 / (bins * 2) as f32) as u32;
-        let zoom = b
-            .info()
-            .zoom_headers
-            .iter()
-            .filter(|z| z.reduction_level <= max_zoom_size)
-            .min_by_key(|z| max_zoom_size - z.reduction_level);
-        zoom
-    } else {
-        None
+    let zoom = match (bins, exact) {
+        (Some(bins), false) => summary_zoom_level(b.info(), start as i64, end as i64, bins),
+        _ => None,
     };
     let arr = match (bins, arr) {
         (_, Some(arr)) => arr,
@@ -226,45 +215,28 @@ fn intervals_to_array<R: BBIFileRead>(
                 )));
             }
 
-            let mut array = v.readwrite();
-
-            match zoom {
-                Some(zoom) => {
+            let (start, end) = (start as i64, end as i64);
+            let summaries = match zoom {
+                Some(reduction_level) => {
                     let iter = b
-                        .get_zoom_interval(
-                            &chrom,
-                            intervals_start,
-                            intervals_end,
-                            zoom.reduction_level,
-                        )
+                        .get_zoom_interval(&chrom, intervals_start, intervals_end, reduction_level)
                         .convert_err()?;
-                    to_array_zoom(
-                        start,
-                        end,
-                        iter,
-                        summary,
-                        bins,
-                        missing,
-                        array.as_array_mut(),
-                    )
-                    .convert_err()?;
+                    summarize_zoom_records(iter, start, end, bins, summary)
                 }
                 None => {
                     let iter = b
                         .get_interval(&chrom, intervals_start, intervals_end)
                         .convert_err()?;
-                    to_array_bins(
-                        start,
-                        end,
-                        iter,
-                        summary,
-                        bins,
-                        missing,
-                        array.as_array_mut(),
-                    )
-                    .convert_err()?;
+                    summarize_values(iter, start, end, bins, summary)
                 }
-            };
+            }
+            .convert_err()?;
+
+            let mut array = v.readwrite();
+            let mut array = array.as_array_mut();
+            for (v, summary) in array.iter_mut().zip(summaries) {
+                *v = summary.unwrap_or(missing);
+            }
 
             (end - start) as f64 / bins as f64
         }
@@ -322,24 +294,16 @@ fn entries_to_array<R: BBIFileRead>(
     start: Option<i32>,
     end: Option<i32>,
     bins: Option<usize>,
-    summary: Summary,
+    summary: SummaryKind,
     exact: bool,
     missing: f64,
     oob: f64,
     arr: Option<PyObject>,
 ) -> PyResult<PyObject> {
     let (start, end, length) = bigbed_start_end_length(b, chrom, start, end)?;
-    let zoom = if let (Some(bins), false) = (bins, exact) {
-        let max_zoom_size = ((end - start) as f32 / (bins * 2) as f32) as u32;
-        let zoom = b
-            .info()
-            .zoom_headers
-            .iter()
-            .filter(|z| z.reduction_level <= max_zoom_size)
-            .min_by_key(|z| max_zoom_size - z.reduction_level);
-        zoom
-    } else {
-        None
+    let zoom = match (bins, exact) {
+        (Some(bins), false) => summary_zoom_level(b.info(), start as i64, end as i64, bins),
+        _ => None,
     };
     let arr = match (bins, arr) {
         (_, Some(arr)) => arr,
@@ -362,45 +326,28 @@ fn entries_to_array<R: BBIFileRead>(
                 )));
             }
 
-            let mut array = v.readwrite();
-
-            match zoom {
-                Some(zoom) => {
+            let (start, end) = (start as i64, end as i64);
+            let summaries = match zoom {
+                Some(reduction_level) => {
                     let iter = b
-                        .get_zoom_interval(
-                            &chrom,
-                            intervals_start,
-                            intervals_end,
-                            zoom.reduction_level,
-                        )
+                        .get_zoom_interval(&chrom, intervals_start, intervals_end, reduction_level)
                         .convert_err()?;
-                    to_entry_array_zoom(
-                        start,
-                        end,
-                        iter,
-                        summary,
-                        bins,
-                        missing,
-                        array.as_array_mut(),
-                    )
-                    .convert_err()?;
+                    summarize_entry_zoom_records(iter, start, end, bins, summary)
                 }
                 None => {
                     let iter = b
                         .get_interval(&chrom, intervals_start, intervals_end)
                         .convert_err()?;
-                    to_entry_array_bins(
-                        start,
-                        end,
-                        iter,
-                        summary,
-                        bins,
-                        missing,
-                        array.as_array_mut(),
-                    )
-                    .convert_err()?;
+                    summarize_entries(iter, start, end, bins, summary)
                 }
-            };
+            }
+            .convert_err()?;
+
+            let mut array = v.readwrite();
+            let mut array = array.as_array_mut();
+            for (v, summary) in array.iter_mut().zip(summaries) {
+                *v = summary.unwrap_or(missing);
+            }
 
             (end - start) as f64 / bins as f64
         }
@@ -471,239 +418,6 @@ fn to_array<I: Iterator<Item = Result<Value, _BBIReadError>>>(
     Ok(())
 }
 
-fn to_array_bins<I: Iterator<Item = Result<Value, _BBIReadError>>>(
-    start: i32,
-    end: i32,
-    iter: I,
-    summary: Summary,
-    bins: usize,
-    missing: f64,
-    mut v: ArrayViewMut<'_, f64, numpy::Ix1>,
-) -> Result<(), _BBIReadError> {
-    assert_eq!(v.len(), bins);
-    v.fill(missing);
-
-    let mut bin_data: VecDeque<(usize, i32, i32, Option<(i32, f64)>)> = VecDeque::new();
-    let bin_size = (end - start) as f64 / bins as f64;
-    for interval in iter {
-        let interval = interval?;
-        let interval_start = (interval.start as i32).max(start) - start;
-        let interval_end = (interval.end as i32).min(end) - start;
-        let bin_start = ((interval_start as f64) / bin_size) as usize;
-        let bin_end = (((interval_end - 1) as f64) / bin_size) as usize;
-
-        while let Some(front) = bin_data.front_mut() {
-            if front.0 < bin_start {
-                let front = bin_data.pop_front().unwrap();
-                let bin = front.0;
-
-                match summary {
-                    Summary::Min => {
-                        v[bin] = front.3.map(|v| v.1).unwrap_or(missing);
-                    }
-                    Summary::Max => {
-                        v[bin] = front.3.map(|v| v.1).unwrap_or(missing);
-                    }
-                    Summary::Mean => {
-                        v[bin] = front.3.map(|(c, v)| v / (c as f64)).unwrap_or(missing);
-                    }
-                }
-            } else {
-                break;
-            }
-        }
-        while let Some(bin) = bin_data
-            .back()
-            .map(|last_bin| {
-                if last_bin.0 < bin_end {
-                    Some(last_bin.0 + 1)
-                } else {
-                    None
-                }
-            })
-            .unwrap_or(Some(bin_start))
-        {
-            let bin_start = ((bin as f64) * bin_size) as i32;
-            let bin_end = (((bin + 1) as f64) * bin_size) as i32;
-
-            bin_data.push_back((bin, bin_start, bin_end, None));
-        }
-        for bin in bin_start..bin_end {
-            assert!(bin_data.iter().find(|b| b.0 == bin).is_some());
-        }
-        for (_bin, bin_start, bin_end, data) in bin_data.iter_mut() {
-            // Since bed files can have overlapping entries, a previous entry could have extended
-            // past the last bin for the current interval
-            // Not relevant here, but keeping for parity
-            if interval_end <= *bin_start {
-                break;
-            }
-            let (c, v) = data.get_or_insert_with(|| {
-                match summary {
-                    // min & max are defined for NAN and we are about to set it
-                    // can't use 0.0 because it may be either below or above the real value
-                    Summary::Min | Summary::Max => (0, f64::NAN),
-                    // addition is not defined for NAN
-                    Summary::Mean => (0, 0.0),
-                }
-            });
-            match summary {
-                Summary::Min => {
-                    *v = v.min(interval.value as f64);
-                }
-                Summary::Max => {
-                    *v = v.max(interval.value as f64);
-                }
-                Summary::Mean => {
-                    let overlap_start = (*bin_start).max(interval_start);
-                    let overlap_end = (*bin_end).min(interval_end);
-                    let overlap_size: i32 = overlap_end - overlap_start;
-                    *v += (overlap_size as f64) * interval.value as f64;
-                    *c += overlap_size;
-                }
-            }
-        }
-    }
-    while let Some(front) = bin_data.pop_front() {
-        let bin = front.0;
-
-        match summary {
-            Summary::Min => {
-                v[bin] = front.3.map(|v| v.1).unwrap_or(missing);
-            }
-            Summary::Max => {
-                v[bin] = front.3.map(|v| v.1).unwrap_or(missing);
-            }
-            Summary::Mean => {
-                v[bin] = front.3.map(|(c, v)| v / (c as f64)).unwrap_or(missing);
-            }
-        }
-    }
-    Ok(())
-}
-
-fn to_array_zoom<I: Iterator<Item = Result<ZoomRecord, _BBIReadError>>>(
-    start: i32,
-    end: i32,
-    iter: I,
-    summary: Summary,
-    bins: usize,
-    missing: f64,
-    mut v: ArrayViewMut<'_, f64, numpy::Ix1>,
-) -> Result<(), _BBIReadError> {
-    assert_eq!(v.len(), bins);
-    v.fill(missing);
-
-    // (bin, bin_start, bin_end, Option<(covered_bases, value)>)
-    let mut bin_data: VecDeque<(usize, i32, i32, Option<(i32, f64)>)> = VecDeque::new();
-    let bin_size = (end - start) as f64 / bins as f64;
-    for interval in iter {
-        let interval = interval?;
-        let interval_start = (interval.start as i32).max(start) - start;
-        let interval_end = (interval.end as i32).min(end) - start;
-        let bin_start = ((interval_start as f64) / bin_size) as usize;
-        let bin_end = (((interval_end - 1) as f64) / bin_size) as usize;
-
-        while let Some(front) = bin_data.front_mut() {
-            if front.0 < bin_start {
-                let front = bin_data.pop_front().unwrap();
-                let bin = front.0;
-
-                match summary {
-                    Summary::Min => {
-                        v[bin] = front.3.map(|v| v.1).unwrap_or(missing);
-                    }
-                    Summary::Max => {
-                        v[bin] = front.3.map(|v| v.1).unwrap_or(missing);
-                    }
-                    Summary::Mean => {
-                        v[bin] = front.3.map(|(c, v)| v / (c as f64)).unwrap_or(missing);
-                    }
-                }
-            } else {
-                break;
-            }
-        }
-        while let Some(bin) = bin_data
-            .back()
-            .map(|last_bin| {
-                if last_bin.0 < bin_end {
-                    Some(last_bin.0 + 1)
-                } else {
-                    None
-                }
-            })
-            .unwrap_or(Some(bin_start))
-        {
-            let bin_start = ((bin as f64) * bin_size) as i32;
-            let bin_end = (((bin + 1) as f64) * bin_size) as i32;
-
-            bin_data.push_back((bin, bin_start, bin_end, None));
-        }
-        for bin in bin_start..bin_end {
-            assert!(bin_data.iter().find(|b| b.0 == bin).is_some());
-        }
-        for (_bin, bin_start, bin_end, data) in bin_data.iter_mut() {
-            // Since bed files can have overlapping entries, a previous entry could have extended
-            // past the last bin for the current interval
-            // Not relevant here, but keeping for parity
-            if interval_end <= *bin_start {
-                break;
-            }
-            let overlap_start = (*bin_start).max(interval_start);
-            let overlap_end = (*bin_end).min(interval_end);
-            let overlap_size: i32 = overlap_end - overlap_start;
-            match data.as_mut() {
-                Some((c, v)) => match summary {
-                    Summary::Min => {
-                        *v = v.min(interval.summary.min_val as f64);
-                        *c += overlap_size;
-                    }
-                    Summary::Max => {
-                        *v = v.max(interval.summary.max_val as f64);
-                        *c += overlap_size;
-                    }
-                    Summary::Mean => {
-                        let zoom_mean =
-                            (interval.summary.sum as f64) / (interval.summary.bases_covered as f64);
-                        *v += (overlap_size as f64) * zoom_mean;
-                        *c += overlap_size;
-                    }
-                },
-                None => match summary {
-                    Summary::Min => {
-                        *data = Some((overlap_size, interval.summary.min_val as f64));
-                    }
-                    Summary::Max => {
-                        *data = Some((overlap_size, interval.summary.max_val as f64));
-                    }
-                    Summary::Mean => {
-                        let zoom_mean =
-                            (interval.summary.sum as f64) / (interval.summary.bases_covered as f64);
-                        *data = Some((overlap_size, (overlap_size as f64) * zoom_mean));
-                    }
-                },
-            }
-        }
-    }
-    while let Some(front) = bin_data.pop_front() {
-        let bin = front.0;
-
-        match summary {
-            Summary::Min => {
-                v[bin] = front.3.map(|v| v.1).unwrap_or(missing);
-            }
-            Summary::Max => {
-                v[bin] = front.3.map(|v| v.1).unwrap_or(missing);
-            }
-            Summary::Mean => {
-                v[bin] = front.3.map(|(c, v)| v / (c as f64)).unwrap_or(missing);
-            }
-        }
-    }
-    Ok(())
-}
-
 fn to_entry_array<I: Iterator<Item = Result<BedEntry, _BBIReadError>>>(
     start: i32,
     end: i32,
@@ -724,284 +438,6 @@ fn to_entry_array<I: Iterator<Item = Result<BedEntry, _BBIReadError>>>(
     Ok(())
 }
 
-fn to_entry_array_bins<I: Iterator<Item = Result<BedEntry, _BBIReadError>>>(
-    start: i32,
-    end: i32,
-    iter: I,
-    summary: Summary,
-    bins: usize,
-    missing: f64,
-    mut v: ArrayViewMut<'_, f64, numpy::Ix1>,
-) -> Result<(), _BBIReadError> {
-    assert_eq!(v.len(), bins);
-    v.fill(missing);
-
-    // (<bin>, <bin_start>, <bin_end>, <covered_bases>, <sum>)
-    // covered_bases = 0 if uncovered, 1 if covered
-    let mut bin_data: VecDeque<(usize, i32, i32, Vec<i32>, Vec<f64>)> = VecDeque::new();
-    let bin_size = (end - start) as f64 / bins as f64;
-    for interval in iter {
-        let interval = interval?;
-        let interval_start = (interval.start as i32).max(start) - start;
-        let interval_end = (interval.end as i32).min(end) - start;
-        let bin_start = ((interval_start as f64) / bin_size) as usize;
-        let bin_end = (((interval_end - 1) as f64) / bin_size) as usize;
-
-        while let Some(front) = bin_data.front_mut() {
-            if front.0 < bin_start {
-                let front = bin_data.pop_front().unwrap();
-                let bin = front.0;
-
-                match summary {
-                    Summary::Min => {
-                        v[bin] = front
-                            .4
-                            .into_iter()
-                            .reduce(|min, v| min.min(v))
-                            .unwrap_or(missing);
-                    }
-                    Summary::Max => {
-                        v[bin] = front
-                            .4
-                            .into_iter()
-                            .reduce(|max, v| max.max(v))
-                            .unwrap_or(missing);
-                    }
-                    Summary::Mean => {
-                        v[bin] = front
-                            .3
-                            .iter()
-                            .any(|v| *v > 0)
-                            .then(|| front.3.into_iter().sum::<i32>() as f64)
-                            // Map nan to 0 so the sum is non-nan
-                            .map(|c| front.4.into_iter().map(|c| c.max(0.0)).sum::<f64>() / c)
-                            .unwrap_or(missing);
-                    }
-                }
-            } else {
-                break;
-            }
-        }
-        while let Some(bin) = bin_data
-            .back()
-            .map(|last_bin| {
-                if last_bin.0 < bin_end {
-                    Some(last_bin.0 + 1)
-                } else {
-                    None
-                }
-            })
-            .unwrap_or(Some(bin_start))
-        {
-            let bin_start = ((bin as f64) * bin_size) as i32;
-            let bin_end = (((bin + 1) as f64) * bin_size) as i32;
-
-            bin_data.push_back((
-                bin,
-                bin_start,
-                bin_end,
-                vec![0; (bin_end - bin_start) as usize],
-                vec![missing; (bin_end - bin_start) as usize],
-            ));
-        }
-        for bin in bin_start..bin_end {
-            assert!(bin_data.iter().find(|b| b.0 == bin).is_some());
-        }
-        for (_bin, bin_start, bin_end, covered, data) in bin_data.iter_mut() {
-            // Since bed files can have overlapping entries, a previous entry could have extended
-            // past the last bin for the current interval
-            if interval_end <= *bin_start {
-                break;
-            }
-            let overlap_start = (*bin_start).max(interval_start);
-            let overlap_end = (*bin_end).min(interval_end);
-
-            let range =
-                ((overlap_start - *bin_start) as usize)..((overlap_end - *bin_start) as usize);
-            for i in &mut data[range.clone()] {
-                // If NAN, then 0.0 + 1.0, else i + 1.0
-                *i = (*i).max(0.0) + 1.0;
-            }
-            for i in &mut covered[range] {
-                *i = (*i).max(1);
-            }
-        }
-    }
-    while let Some(front) = bin_data.pop_front() {
-        let bin = front.0;
-
-        match summary {
-            Summary::Min => {
-                v[bin] = front
-                    .4
-                    .into_iter()
-                    .reduce(|min, v| min.min(v))
-                    .unwrap_or(missing);
-            }
-            Summary::Max => {
-                v[bin] = front
-                    .4
-                    .into_iter()
-                    .reduce(|max, v| max.max(v))
-                    .unwrap_or(missing);
-            }
-            Summary::Mean => {
-                v[bin] = front
-                    .3
-                    .iter()
-                    .any(|v| *v > 0)
-                    .then(|| front.3.into_iter().sum::<i32>() as f64)
-                    // Map nan to 0 so the sum is non-nan
-                    .map(|c| front.4.into_iter().map(|c| c.max(0.0)).sum::<f64>() / c)
-                    .unwrap_or(missing);
-            }
-        }
-    }
-    Ok(())
-}
-
-fn to_entry_array_zoom<I: Iterator<Item = Result<ZoomRecord, _BBIReadError>>>(
-    start: i32,
-    end: i32,
-    iter: I,
-    summary: Summary,
-    bins: usize,
-    missing: f64,
-    mut v: ArrayViewMut<'_, f64, numpy::Ix1>,
-) -> Result<(), _BBIReadError> {
-    assert_eq!(v.len(), bins);
-    v.fill(missing);
-
-    // (<bin>, <bin_start>, <bin_end>, <covered_bases>, <sum>)
-    // covered_bases = 0 if uncovered, 1 if covered
-    let mut bin_data: VecDeque<(usize, i32, i32, Vec<i32>, Vec<f64>)> = VecDeque::new();
-    let bin_size = (end - start) as f64 / bins as f64;
-    for interval in iter {
-        let interval = interval?;
-        let interval_start = (interval.start as i32).max(start) - start;
-        let interval_end = (interval.end as i32).min(end) - start;
-        let bin_start = ((interval_start as f64) / bin_size) as usize;
-        let bin_end = (((interval_end - 1) as f64) / bin_size) as usize;
-
-        while let Some(front) = bin_data.front_mut() {
-            if front.0 < bin_start {
-                let front = bin_data.pop_front().unwrap();
-                let bin = front.0;
-
-                match summary {
-                    Summary::Min => {
-                        v[bin] = front
-                            .4
-                            .into_iter()
-                            .reduce(|min, v| min.min(v))
-                            .unwrap_or(missing);
-                    }
-                    Summary::Max => {
-                        v[bin] = front
-                            .4
-                            .into_iter()
-                            .reduce(|max, v| max.max(v))
-                            .unwrap_or(missing);
-                    }
-                    Summary::Mean => {
-                        v[bin] = front
-                            .3
-                            .iter()
-                            .any(|v| *v > 0)
-                            .then(|| front.3.into_iter().sum::<i32>() as f64)
-                            // Map nan to 0 so the sum is non-nan
-                            .map(|c| front.4.into_iter().map(|c| c.max(0.0)).sum::<f64>() / c)
-                            .unwrap_or(missing);
-                    }
-                }
-            } else {
-                break;
-            }
-        }
-        while let Some(bin) = bin_data
-            .back()
-            .map(|last_bin| {
-                if last_bin.0 < bin_end {
-                    Some(last_bin.0 + 1)
-                } else {
-                    None
-                }
-            })
-            .unwrap_or(Some(bin_start))
-        {
-            let bin_start = ((bin as f64) * bin_size) as i32;
-            let bin_end = (((bin + 1) as f64) * bin_size) as i32;
-
-            bin_data.push_back((
-                bin,
-                bin_start,
-                bin_end,
-                vec![0; (bin_end - bin_start) as usize],
-                vec![missing; (bin_end - bin_start) as usize],
-            ));
-        }
-        for bin in bin_start..bin_end {
-            assert!(bin_data.iter().find(|b| b.0 == bin).is_some());
-        }
-        for (_bin, bin_start, bin_end, covered, data) in bin_data.iter_mut() {
-            // Since bed files can have overlapping entries, a previous entry could have extended
-            // past the last bin for the current interval
-            if interval_end <= *bin_start {
-                break;
-            }
-            let overlap_start = (*bin_start).max(interval_start);
-            let overlap_end = (*bin_end).min(interval_end);
-
-            let mean = interval.summary.sum / (interval.summary.bases_covered) as f64;
-            let range =
-                ((overlap_start - *bin_start) as usize)..((overlap_end - *bin_start) as usize);
-            for i in &mut data[range.clone()] {
-                *i = i.max(0.0);
-                match summary {
-                    Summary::Mean => *i += mean,
-                    Summary::Min => *i = i.min(interval.summary.min_val),
-                    Summary::Max => *i = i.max(interval.summary.max_val),
-                }
-            }
-
-            for i in &mut covered[range] {
-                *i = (*i).max(1);
-            }
-        }
-    }
-    while let Some(front) = bin_data.pop_front() {
-        let bin = front.0;
-
-        match summary {
-            Summary::Min => {
-                v[bin] = front
-                    .4
-                    .into_iter()
-                    .reduce(|min, v| min.min(v))
-                    .unwrap_or(missing);
-            }
-            Summary::Max => {
-                v[bin] = front
-                    .4
-                    .into_iter()
-                    .reduce(|max, v| max.max(v))
-                    .unwrap_or(missing);
-            }
-            Summary::Mean => {
-                v[bin] = front
-                    .3
-                    .iter()
-                    .any(|v| *v > 0)
-                    .then(|| front.3.into_iter().sum::<i32>() as f64)
-                    // Map nan to 0 so the sum is non-nan
-                    .map(|c| front.4.into_iter().map(|c| c.max(0.0)).sum::<f64>() / c)
-                    .unwrap_or(missing);
-            }
-        }
-    }
-    Ok(())
-}
-
 /// Interface for reading a BigWig or BigBed file.
 #[pyclass(module = "pybigtools")]
 struct BBIRead {
@@ -1437,9 +873,10 @@ impl BBIRead {
     ///     If provided, the query interval will be divided into equally spaced
     ///     bins and the values in each bin will be interpolated or summarized.
     ///     If not provided, the values will be returned for each base.
-    /// summary : Literal["mean", "min", "max"], optional [default: "mean"]
+    /// summary : Literal["mean", "min", "max", "sum", "coverage", "std"], optional [default: "mean"]
     ///     The summary statistic to use. Currently supported statistics are
-    ///     ``mean``, ``min``, and ``max``.
+    ///     ``mean``, ``min``, ``max``, ``sum``, ``coverage`` (the fraction of
+    ///     bases covered), and ``std`` (the standard deviation).
     /// exact : bool, optional [default: False]
     ///     If True and ``bins`` is specified, return exact summary statistic
     ///     values instead of interpolating from the optimal zoom level.
@@ -1494,16 +931,9 @@ impl BBIRead {
         oob: f64,
         arr: Option<PyObject>,
     ) -> PyResult<PyObject> {
-        let summary = match summary.as_ref() {
-            "mean" => Summary::Mean,
-            "min" => Summary::Min,
-            "max" => Summary::Max,
-            _ => {
-                return Err(PyErr::new::<exceptions::PyValueError, _>(format!(
-                    "Unrecognized summary. Only `mean`, `min`, and `max` are allowed."
-                )));
-            }
-        };
+        let summary = summary
+            .parse::<SummaryKind>()
+            .map_err(|e| PyErr::new::<exceptions::PyValueError, _>(e.to_string()))?;
         match &mut self.bbi {
             BBIReadRaw::Closed => return Err(BBIFileClosed::new_err("File is closed.")),
             BBIReadRaw::BigWigFile(b) => intervals_to_array(
@@ -2085,12 +1515,27 @@ impl BigBedWrite {
     ///     An iterable with values that represents each value to write in the
     ///     format (chromosome, start, end, rest). The ``rest`` string should
     ///     consist of tab-delimited fields.  
+    /// autosql : str or list[tuple[str, str] or tuple[str, str, str]], optional
+    ///     The autoSql describing the fields. Either the autoSql text, or a
+    ///     list of (type, name) or (type, name, comment) tuples for the fields
+    ///     in ``rest``, such as ``("float", "signalValue")`` or
+    ///     ``("int[blockCount]", "blockSizes")``. The ``chrom``,
+    ///     ``chromStart``, and ``chromEnd`` fields are added automatically.
     ///
     /// Notes
     /// -----
     /// The underlying file will be closed automatically when the function
     /// completes, and no other operations will be able to be performed.
-    fn write(&mut self, py: Python, chroms: &PyDict, vals: Py<PyAny>) -> PyResult<()> {
+    #[pyo3(signature = (chroms, vals, autosql = None))]
+    fn write(
+        &mut self,
+        py: Python,
+        chroms: &PyDict,
+        vals: Py<PyAny>,
+        autosql: Option<&PyAny>,
+    ) -> PyResult<()> {
+        let autosql = autosql.map(autosql_from_py).transpose()?;
+
         let runtime = runtime::Builder::new_multi_thread()
             .worker_threads(
                 std::thread::available_parallelism()
@@ -2113,12 +1558,13 @@ impl BigBedWrite {
             .bigbed
             .take()
             .ok_or_else(|| PyErr::new::<BBIFileClosed, _>("File already closed."))?;
-        let bigbed = BigBedWriteRaw::create_file(bigbed, chrom_map).map_err(|e| {
+        let mut bigbed = BigBedWriteRaw::create_file(bigbed, chrom_map).map_err(|e| {
             PyErr::new::<exceptions::PyException, _>(format!(
                 "Error occured when creating file: {}",
                 e
             ))
         })?;
+        bigbed.autosql = autosql;
 
         struct IterError(String);
         struct Iter {
@@ -2225,6 +1671,50 @@ impl BigBedWrite {
     }
 }
 
+/// Converts the `autosql` argument of `BigBedWrite.write`, which is either
+/// autoSql text or a list of `(type, name[, comment])` field tuples.
+fn autosql_from_py(autosql: &PyAny) -> PyResult<String> {
+    if let Ok(autosql) = autosql.downcast::<PyString>() {
+        return Ok(autosql.to_str()?.to_owned());
+    }
+    let invalid = |msg: String| PyErr::new::<exceptions::PyValueError, _>(msg);
+    let mut builder = DeclarationBuilder::bed("bed", "Browser Extensible Data");
+    for field in autosql.iter()? {
+        let field = field?;
+        let (field_type, name, comment) = match field.extract::<(String, String, String)>() {
+            Ok(field) => field,
+            Err(_) => {
+                let (field_type, name) = field.extract::<(String, String)>().map_err(|_| {
+                    invalid(format!(
+                        "Invalid autosql field `{}`. Must be a tuple of (type, name) or (type, name, comment).",
+                        field
+                    ))
+                })?;
+                (field_type, name, String::new())
+            }
+        };
+        let (base_type, size) = match field_type.split_once('[') {
+            Some((base_type, size)) => {
+                let size = size
+                    .strip_suffix(']')
+                    .ok_or_else(|| invalid(format!("Invalid field type `{}`.", field_type)))?;
+                (base_type, Some(size.to_string()))
+            }
+            None => (field_type.as_str(), None),
+        };
+        let base_type = base_type
+            .trim()
+            .parse::<FieldType>()
+            .map_err(|_| invalid(format!("Invalid field type `{}`.", field_type)))?;
+        builder = match size {
+            Some(size) => builder.array_field(base_type, size, name, comment),
+            None => builder.field(base_type, name, comment),
+        };
+    }
+    let declaration = builder.build().map_err(|e| invalid(e.to_string()))?;
+    Ok(declaration.to_string())
+}
+
 enum BigWigAverageOverBedStatistics {
     Size,
     Bases,
@@ -2337,6 +1827,14 @@ impl BigWigAverageOverBedEntriesIterator {
 ///     The mode to open the file in. If not provided, it will default to read.
 ///     "r" will open a bigWig/bigBed for reading but will not allow writing.
 ///     "w" will open a bigWig/bigBed for writing but will not allow reading.
+/// cache_dir : str, optional
+///     A directory to cache the data of remote files in, so that it can be
+///     reused later (including by other processes). Cached data is only
+///     reused if the remote file hasn't changed. Ignored for local files.
+/// cache_max_size : int, optional [default: 1 GiB]
+///     The maximum size (in bytes) of the data in ``cache_dir``. When the
+///     cache is larger than this, the data of the least-recently used files
+///     is removed. Ignored if ``cache_dir`` is not set.
 ///
 /// Returns
 /// -------
@@ -2350,7 +1848,13 @@ impl BigWigAverageOverBedEntriesIterator {
 /// If passing a file-like object, concurrent reading of different intervals
 /// is not supported and may result in incorrect behavior.
 #[pyfunction]
-fn open(py: Python, path_url_or_file_like: PyObject, mode: Option<String>) -> PyResult<PyObject> {
+fn open(
+    py: Python,
+    path_url_or_file_like: PyObject,
+    mode: Option<String>,
+    cache_dir: Option<String>,
+    cache_max_size: Option<u64>,
+) -> PyResult<PyObject> {
     let iswrite = match &mode {
         Some(mode) if mode == "w" => true,
         Some(mode) if mode == "r" => false,
@@ -2365,14 +1869,20 @@ fn open(py: Python, path_url_or_file_like: PyObject, mode: Option<String>) -> Py
 
     // If string, might be path or url like
     if let Ok(string_ref) = path_url_or_file_like.downcast::<PyString>(py) {
-        return open_path_or_url(py, string_ref.to_str().unwrap().to_owned(), iswrite);
+        return open_path_or_url(
+            py,
+            string_ref.to_str().unwrap().to_owned(),
+            iswrite,
+            cache_dir,
+            cache_max_size,
+        );
     }
 
     // If pathlib.Path, convert to string and try to open
     let path_class = py.import("pathlib")?.getattr("Path")?;
     if path_url_or_file_like.as_ref(py).is_instance(path_class)? {
         let path_str = path_url_or_file_like.as_ref(py).str()?.to_str()?;
-        return open_path_or_url(py, path_str.to_owned(), iswrite);
+        return open_path_or_url(py, path_str.to_owned(), iswrite, cache_dir, cache_max_size);
     }
 
     if iswrite {
@@ -2408,7 +1918,26 @@ fn open_path_or_url(
     py: Python,
     path_url_or_file_like: String,
     iswrite: bool,
+    cache_dir: Option<String>,
+    cache_max_size: Option<u64>,
 ) -> PyResult<PyObject> {
+    #[cfg(feature = "remote")]
+    let remote_file = |url: &str| {
+        let file = RemoteFile::new(url);
+        match &cache_dir {
+            Some(cache_dir) => {
+                let cache = RemoteCache::new(cache_dir);
+                let cache = match cache_max_size {
+                    Some(max_size) => cache.with_max_size(max_size),
+                    None => cache,
+                };
+                file.with_cache(cache)
+            }
+            None => file,
+        }
+    };
+    #[cfg(not(feature = "remote"))]
+    let _ = (cache_dir, cache_max_size);
     let extension = match &Path::new(&path_url_or_file_like)
         .extension()
         .map(|e| e.to_string_lossy())
@@ -2468,7 +1997,7 @@ fn open_path_or_url(
                     }
                 } else {
                     #[cfg(feature = "remote")]
-                    match BigWigReadRaw::open(RemoteFile::new(&path_url_or_file_like)) {
+                    match BigWigReadRaw::open(remote_file(&path_url_or_file_like)) {
                         Ok(bwr) => BBIRead {
                             bbi: BBIReadRaw::BigWigRemote(bwr.cached()),
                         }
@@ -2503,7 +2032,7 @@ fn open_path_or_url(
                     }
                 } else {
                     #[cfg(feature = "remote")]
-                    match BigBedReadRaw::open(RemoteFile::new(&path_url_or_file_like)) {
+                    match BigBedReadRaw::open(remote_file(&path_url_or_file_like)) {
                         Ok(bwr) => BBIRead {
                             bbi: BBIReadRaw::BigBedRemote(bwr.cached()),
                         }
@@ -2561,206 +2090,3 @@ fn pybigtools(py: Python, m: &PyModule) -> PyResult<()> {
 
     Ok(())
 }
-
-#[cfg(test)]
-mod test {
-    use bigtools::BedEntry;
-    use numpy::ndarray::Array;
-
-    use crate::to_entry_array_bins;
-
-    #[test]
-    fn test_to_entry_array_bins() {
-        fn eq_with_nan_eq(a: f64, b: f64) -> bool {
-            (a.is_nan() && b.is_nan()) || (a == b)
-        }
-
-        #[track_caller]
-        fn assert_equal(found: Vec<f64>, expected: Vec<f64>) {
-            let equal = (found.len() == expected.len()) &&  // zip stops at the shortest
-            found.iter()
-               .zip(expected.iter())
-               .all(|(a,b)| eq_with_nan_eq(*a, *b));
-            if !equal {
-                panic!("Vecs not equal: expected {:?}, found {:?}", expected, found);
-            }
-        }
-
-        let entries = [];
-        let mut arr = Array::from(vec![f64::NAN]);
-        to_entry_array_bins(
-            10,
-            20,
-            entries.into_iter().map(|v| Ok(v)),
-            crate::Summary::Mean,
-            1,
-            f64::NAN,
-            arr.view_mut(),
-        )
-        .unwrap();
-        let res = arr.to_vec();
-        assert_equal(res, [f64::NAN].into_iter().collect::<Vec<_>>());
-
-        let entries = [];
-        let mut arr = Array::from(vec![f64::NAN]);
-        to_entry_array_bins(
-            10,
-            20,
-            entries.into_iter().map(|v| Ok(v)),
-            crate::Summary::Mean,
-            1,
-            0.0,
-            arr.view_mut(),
-        )
-        .unwrap();
-        let res = arr.to_vec();
-        assert_equal(res, [0.0].into_iter().collect::<Vec<_>>());
-
-        let entries = [BedEntry {
-            start: 10,
-            end: 20,
-            rest: "".to_string(),
-        }];
-        let mut arr = Array::from(vec![f64::NAN]);
-        to_entry_array_bins(
-            10,
-            20,
-            entries.into_iter().map(|v| Ok(v)),
-            crate::Summary::Mean,
-            1,
-            f64::NAN,
-            arr.view_mut(),
-        )
-        .unwrap();
-        let res = arr.to_vec();
-        assert_equal(res, [1.0].into_iter().collect::<Vec<_>>());
-
-        let entries = [BedEntry {
-            start: 10,
-            end: 20,
-            rest: "".to_string(),
-        }];
-        let mut arr = Array::from(vec![f64::NAN, f64::NAN]);
-        to_entry_array_bins(
-            10,
-            20,
-            entries.into_iter().map(|v| Ok(v)),
-            crate::Summary::Mean,
-            2,
-            f64::NAN,
-            arr.view_mut(),
-        )
-        .unwrap();
-        let res = arr.to_vec();
-        assert_equal(res, [1.0, 1.0].into_iter().collect::<Vec<_>>());
-
-        let entries = [
-            BedEntry {
-                start: 10,
-                end: 20,
-                rest: "".to_string(),
-            },
-            BedEntry {
-                start: 15,
-                end: 20,
-                rest: "".to_string(),
-            },
-        ];
-        let mut arr = Array::from(vec![f64::NAN, f64::NAN]);
-        to_entry_array_bins(
-            10,
-            20,
-            entries.clone().into_iter().map(|v| Ok(v)),
-            crate::Summary::Mean,
-            2,
-            f64::NAN,
-            arr.view_mut(),
-        )
-        .unwrap();
-        let res = arr.to_vec();
-        assert_equal(res, [1.0, 2.0].into_iter().collect::<Vec<_>>());
-        let mut arr = Array::from(vec![f64::NAN]);
-        to_entry_array_bins(
-            10,
-            20,
-            entries.clone().into_iter().map(|v| Ok(v)),
-            crate::Summary::Min,
-            1,
-            f64::NAN,
-            arr.view_mut(),
-        )
-        .unwrap();
-        let res = arr.to_vec();
-        assert_equal(res, [1.0].into_iter().collect::<Vec<_>>());
-        let mut arr = Array::from(vec![f64::NAN]);
-        to_entry_array_bins(
-            10,
-            20,
-            entries.clone().into_iter().map(|v| Ok(v)),
-            crate::Summary::Max,
-            1,
-            f64::NAN,
-            arr.view_mut(),
-        )
-        .unwrap();
-        let res = arr.to_vec();
-        assert_equal(res, [2.0].into_iter().collect::<Vec<_>>());
-
-        let entries = [
-            BedEntry {
-                start: 10,
-                end: 20,
-                rest: "".to_string(),
-            },
-            BedEntry {
-                start: 15,
-                end: 20,
-                rest: "".to_string(),
-            },
-            BedEntry {
-                start: 15,
-                end: 25,
-                rest: "".to_string(),
-            },
-        ];
-        let mut arr = Array::from(vec![f64::NAN, f64::NAN]);
-        to_entry_array_bins(
-            10,
-            20,
-            entries.clone().into_iter().map(|v| Ok(v)),
-            crate::Summary::Mean,
-            2,
-            f64::NAN,
-            arr.view_mut(),
-        )
-        .unwrap();
-        let res = arr.to_vec();
-        assert_equal(res, [1.0, 3.0].into_iter().collect::<Vec<_>>());
-        let mut arr = Array::from(vec![f64::NAN]);
-        to_entry_array_bins(
-            10,
-            20,
-            entries.clone().into_iter().map(|v| Ok(v)),
-            crate::Summary::Min,
-            1,
-            f64::NAN,
-            arr.view_mut(),
-        )
-        .unwrap();
-        let res = arr.to_vec();
-        assert_equal(res, [1.0].into_iter().collect::<Vec<_>>());
-        let mut arr = Array::from(vec![f64::NAN]);
-        to_entry_array_bins(
-            10,
-            20,
-            entries.clone().into_iter().map(|v| Ok(v)),
-            crate::Summary::Max,
-            1,
-            f64::NAN,
-            arr.view_mut(),
-        )
-        .unwrap();
-        let res = arr.to_vec();
-        assert_equal(res, [3.0].into_iter().collect::<Vec<_>>());
-    }
-}
@@ -88,5 +88,31 @@ def test_bigbed_write(tmpdir):
     records = list(f.records("chr2"))
     assert records[0][2] == 'xxx'
 
+def test_bigbed_write_autosql(tmpdir):
+    f = pybigtools.open(os.path.join(tmpdir, "test.bigBed"), "w")
+    f.write(
+        {"chr1": 1000},
+        [
+            ("chr1", 0, 100, "foo\t2\t1,2,"),
+            ("chr1", 100, 200, "bar\t1\t3,"),
+        ],
+        autosql=[
+            ("string", "name", "Name of item"),
+            ("uint", "count"),
+            ("int[count]", "values", "Values"),
+        ],
+    )
+    f.close()
+
+    f = pybigtools.open(os.path.join(tmpdir, "test.bigBed"))
+    sql = f.sql(True)
+    assert sql["name"] == "bed"
+    assert [field[0] for field in sql["fields"]] == [
+        "chrom", "chromStart", "chromEnd", "name", "count", "values"
+    ]
+    assert sql["fields"][5][1] == "int"
+    records = list(f.records("chr1"))
+    assert records[1][2] == 'bar'
+
 # TODO: bigWigAverageOverBed
 # TODO: bigWigMerge